@@ -7,50 +7,49 @@ use egui::{
 };
 use image::{ImageBuffer, Rgb};
 use rvlib::{
+    annotations::Visibility,
     domain::{PtF, PtI},
     orig_2_view, orig_pos_2_view_pos, project_on_bb, scale_coord, view_pos_2_orig_pos, Annotation,
-    GeoFig, ImageU8, KeyCode, MainEventLoop, UpdateAnnos, UpdateImage, UpdateZoomBox, BB,
+    BboxAnnotation, GeoFig, ImageU8, KeyCode, KeypointsAnnotation, MainEventLoop, UpdateAnnos,
+    UpdateImage, UpdateZoomBox, BB,
 };
 
-fn map_key(egui_key: egui::Key) -> Option<rvlib::KeyCode> {
-    match egui_key {
-        egui::Key::A => Some(rvlib::KeyCode::A),
-        egui::Key::B => Some(rvlib::KeyCode::B),
-        egui::Key::C => Some(rvlib::KeyCode::C),
-        egui::Key::D => Some(rvlib::KeyCode::D),
-        egui::Key::L => Some(rvlib::KeyCode::L),
-        egui::Key::H => Some(rvlib::KeyCode::H),
-        egui::Key::M => Some(rvlib::KeyCode::M),
-        egui::Key::Q => Some(rvlib::KeyCode::Q),
-        egui::Key::R => Some(rvlib::KeyCode::R),
-        egui::Key::T => Some(rvlib::KeyCode::T),
-        egui::Key::V => Some(rvlib::KeyCode::V),
-        egui::Key::Y => Some(rvlib::KeyCode::Y),
-        egui::Key::Z => Some(rvlib::KeyCode::Z),
-        egui::Key::Num0 => Some(rvlib::KeyCode::Key0),
-        egui::Key::Num1 => Some(rvlib::KeyCode::Key1),
-        egui::Key::Num2 => Some(rvlib::KeyCode::Key2),
-        egui::Key::Num3 => Some(rvlib::KeyCode::Key3),
-        egui::Key::Num4 => Some(rvlib::KeyCode::Key4),
-        egui::Key::Num5 => Some(rvlib::KeyCode::Key5),
-        egui::Key::Num6 => Some(rvlib::KeyCode::Key6),
-        egui::Key::Num7 => Some(rvlib::KeyCode::Key7),
-        egui::Key::Num8 => Some(rvlib::KeyCode::Key8),
-        egui::Key::Num9 => Some(rvlib::KeyCode::Key9),
-        egui::Key::PlusEquals => Some(rvlib::KeyCode::PlusEquals),
-        egui::Key::Minus => Some(rvlib::KeyCode::Minus),
-        egui::Key::Delete => Some(rvlib::KeyCode::Delete),
-        egui::Key::Backspace => Some(rvlib::KeyCode::Back),
-        egui::Key::ArrowLeft => Some(rvlib::KeyCode::Left),
-        egui::Key::ArrowRight => Some(rvlib::KeyCode::Right),
-        egui::Key::ArrowUp => Some(rvlib::KeyCode::Up),
-        egui::Key::ArrowDown => Some(rvlib::KeyCode::Down),
-        egui::Key::F5 => Some(rvlib::KeyCode::F5),
-        egui::Key::PageDown => Some(rvlib::KeyCode::PageDown),
-        egui::Key::PageUp => Some(rvlib::KeyCode::PageUp),
-        egui::Key::Escape => Some(rvlib::KeyCode::Escape),
-        _ => None,
-    }
+macro_rules! map_key {
+    ($egui_key:expr, [$($same:ident),*], [$(($egui:ident, $rv:ident)),*]) => {
+        match $egui_key {
+            $(egui::Key::$same => rvlib::KeyCode::$same,)*
+            $(egui::Key::$egui => rvlib::KeyCode::$rv,)*
+        }
+    };
+}
+
+fn map_key(egui_key: egui::Key) -> rvlib::KeyCode {
+    map_key!(
+        egui_key,
+        [
+            A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, F1, F2,
+            F3, F4, F5, F6, F7, F8, F9, F10, F11, F12, F13, F14, F15, F16, F17, F18, F19, F20,
+            PlusEquals, Minus, Delete, Tab, Enter, Space, Insert, Home, End, PageDown, PageUp,
+            Escape
+        ],
+        [
+            (Num0, Key0),
+            (Num1, Key1),
+            (Num2, Key2),
+            (Num3, Key3),
+            (Num4, Key4),
+            (Num5, Key5),
+            (Num6, Key6),
+            (Num7, Key7),
+            (Num8, Key8),
+            (Num9, Key9),
+            (Backspace, Back),
+            (ArrowLeft, Left),
+            (ArrowRight, Right),
+            (ArrowUp, Up),
+            (ArrowDown, Down)
+        ]
+    )
 }
 
 #[derive(Default)]
@@ -106,15 +105,14 @@ fn map_key_events(ui: &mut Ui) -> Vec<rvlib::Event> {
                 modifiers,
             } = e
             {
-                if let Some(k) = map_key(*key) {
-                    if !pressed {
-                        events.push(rvlib::Event::Released(k));
-                    } else if !repeat {
-                        events.push(rvlib::Event::Pressed(k));
-                        events.push(rvlib::Event::Held(k));
-                    } else {
-                        events.push(rvlib::Event::Held(k));
-                    }
+                let k = map_key(*key);
+                if !pressed {
+                    events.push(rvlib::Event::Released(k));
+                } else if !repeat {
+                    events.push(rvlib::Event::Pressed(k));
+                    events.push(rvlib::Event::Held(k));
+                } else {
+                    events.push(rvlib::Event::Held(k));
                 }
                 let modifier_events = map_modifiers(modifiers);
                 if let Some(mut me) = modifier_events {
@@ -257,56 +255,108 @@ impl RvImageApp {
         rvlib::Shape::from_im(&self.im_view)
     }
 
+    fn orig_pos_2_egui_rect(&self, p: PtI, image_rect: &Rect) -> Pos2 {
+        orig_pos_2_egui_rect(
+            p,
+            image_rect.min,
+            self.shape_orig(),
+            self.shape_view(),
+            image_rect.size(),
+            &self.zoom_box,
+        )
+    }
+
+    fn bbox_anno_shape(&self, anno: &BboxAnnotation, image_rect: &Rect) -> Shape {
+        let bb = match &anno.geofig {
+            GeoFig::BB(bb) => *bb,
+            // TODO: draw actual polygon
+            GeoFig::Poly(poly) => poly.enclosing_bb(),
+        };
+        let (fill_alpha, outline_thickness) = if let Some(is_selected) = anno.is_selected {
+            if is_selected {
+                (
+                    anno.fill_alpha.saturating_add(60),
+                    anno.outline.thickness + 2.0,
+                )
+            } else {
+                (anno.fill_alpha, anno.outline.thickness)
+            }
+        } else {
+            (anno.fill_alpha, anno.outline.thickness)
+        };
+        let fill_rgb = rgb_2_clr(anno.fill_color, fill_alpha);
+
+        let bb_min_rect = self.orig_pos_2_egui_rect(bb.min(), image_rect);
+        let bb_max_rect = self.orig_pos_2_egui_rect(bb.max(), image_rect);
+        let stroke = Stroke::new(
+            outline_thickness,
+            rgb_2_clr(Some(anno.outline.color), anno.outline_alpha),
+        );
+        let rect = Rect::from_min_max(bb_min_rect, bb_max_rect);
+        if anno.outline.is_dashed {
+            let corners = [
+                rect.left_top(),
+                rect.right_top(),
+                rect.right_bottom(),
+                rect.left_bottom(),
+                rect.left_top(),
+            ];
+            let mut shapes = vec![Shape::Rect(RectShape::filled(
+                rect,
+                Rounding::ZERO,
+                fill_rgb,
+            ))];
+            shapes.extend(Shape::dashed_line(&corners, stroke, 6.0, 4.0));
+            Shape::Vec(shapes)
+        } else {
+            Shape::Rect(RectShape::new(rect, Rounding::ZERO, fill_rgb, stroke))
+        }
+    }
+
+    fn keypoints_anno_shapes(&self, anno: &KeypointsAnnotation, image_rect: &Rect) -> Vec<Shape> {
+        let thickness = if anno.is_selected { 3.0 } else { 1.5 };
+        let color = rgb_2_clr(Some(anno.color), 255);
+        let stroke = Stroke::new(thickness, color);
+        let positions = anno
+            .keypoints
+            .iter()
+            .map(|kp| {
+                if kp.visibility == Visibility::NotLabeled {
+                    None
+                } else {
+                    Some(self.orig_pos_2_egui_rect(kp.pos, image_rect))
+                }
+            })
+            .collect::<Vec<_>>();
+        let edges = anno.edges.iter().filter_map(|(from, to)| {
+            match (positions.get(*from), positions.get(*to)) {
+                (Some(Some(from)), Some(Some(to))) => {
+                    Some(Shape::line_segment([*from, *to], stroke))
+                }
+                _ => None,
+            }
+        });
+        // occluded keypoints are drawn as rings
+        let points = anno
+            .keypoints
+            .iter()
+            .zip(positions.iter())
+            .filter_map(|(kp, pos)| {
+                pos.map(|pos| match kp.visibility {
+                    Visibility::Occluded => Shape::circle_stroke(pos, 4.0, stroke),
+                    _ => Shape::circle_filled(pos, 4.0, color),
+                })
+            });
+        edges.chain(points).collect()
+    }
+
     fn draw_annos(&self, ui: &mut Ui, image_rect: &Rect) {
         let shapes = self
             .annos
             .iter()
-            .flat_map(|anno| {
-                let bb = match &anno.geofig {
-                    GeoFig::BB(bb) => *bb,
-                    // TODO: draw actual polygon
-                    GeoFig::Poly(poly) => poly.enclosing_bb(),
-                };
-                let (fill_alpha, outline_thickness) = if let Some(is_selected) = anno.is_selected {
-                    if is_selected {
-                        (
-                            anno.fill_alpha.saturating_add(60),
-                            anno.outline.thickness + 2.0,
-                        )
-                    } else {
-                        (anno.fill_alpha, anno.outline.thickness)
-                    }
-                } else {
-                    (anno.fill_alpha, anno.outline.thickness)
-                };
-                let fill_rgb = rgb_2_clr(anno.fill_color, fill_alpha);
-
-                let bb_min_rect = orig_pos_2_egui_rect(
-                    bb.min(),
-                    image_rect.min,
-                    self.shape_orig(),
-                    self.shape_view(),
-                    image_rect.size(),
-                    &self.zoom_box,
-                );
-                let bb_max_rect = orig_pos_2_egui_rect(
-                    bb.max(),
-                    image_rect.min,
-                    self.shape_orig(),
-                    self.shape_view(),
-                    image_rect.size(),
-                    &self.zoom_box,
-                );
-                let stroke = Stroke::new(
-                    outline_thickness,
-                    rgb_2_clr(Some(anno.outline.color), anno.outline_alpha),
-                );
-                Some(Shape::Rect(RectShape::new(
-                    Rect::from_min_max(bb_min_rect, bb_max_rect),
-                    Rounding::ZERO,
-                    fill_rgb,
-                    stroke,
-                )))
+            .flat_map(|anno| match anno {
+                Annotation::Bbox(anno) => vec![self.bbox_anno_shape(anno, image_rect)],
+                Annotation::Keypoints(anno) => self.keypoints_anno_shapes(anno, image_rect),
             })
             .collect::<Vec<Shape>>();
         ui.painter().add(Shape::Vec(shapes));
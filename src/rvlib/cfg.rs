@@ -1,6 +1,7 @@
 use crate::{
     cache::FileCacheCfgArgs,
     file_util::{self, DEFAULT_HOMEDIR, DEFAULT_TMPDIR},
+    keymap::Keymap,
     result::{to_rv, RvError, RvResult},
 };
 use serde::{Deserialize, Serialize};
@@ -125,16 +126,72 @@ pub struct PyHttpReaderCfg {
     pub server_address: String,
 }
 
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct CollabCfg {
+    pub user: String,
+    claim_timeout_secs: Option<u64>,
+}
+impl CollabCfg {
+    /// claims of other users older than this are considered stale and can be taken over
+    pub fn claim_timeout_secs(&self) -> u64 {
+        let default = 1800;
+        self.claim_timeout_secs.unwrap_or(default)
+    }
+}
+
+/// Http endpoint of a model that pre-annotates images, see [`crate::inference`]
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct InferenceCfg {
+    pub url: String,
+    timeout_secs: Option<u64>,
+    n_threads: Option<usize>,
+}
+impl InferenceCfg {
+    pub fn timeout_secs(&self) -> u64 {
+        let default = 60;
+        self.timeout_secs.unwrap_or(default)
+    }
+    /// number of images that are sent to the endpoint in parallel
+    pub fn n_threads(&self) -> usize {
+        let default = 2;
+        self.n_threads.unwrap_or(default).max(1)
+    }
+}
+
+/// Video files are only listed with the Cargo-feature `ffmpeg`
+#[derive(Deserialize, Serialize, Debug, Default, Clone, PartialEq, Eq)]
+pub struct VideoCfg {
+    frame_stride: Option<usize>,
+}
+impl VideoCfg {
+    /// only every `frame_stride`-th frame of a video is listed
+    pub fn frame_stride(&self) -> usize {
+        let default = 1;
+        self.frame_stride.unwrap_or(default).max(1)
+    }
+}
+
 #[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq, Eq)]
 pub enum CocoFileConnection {
     Ssh,
     #[default]
     Local,
 }
+
+/// Replaces the beginning `from` of image paths in imported Coco files by `to`
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct PrefixMapping {
+    pub from: String,
+    pub to: String,
+}
+
 #[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq, Eq)]
 pub struct CocoFile {
     pub path: PathBuf,
     pub conn: CocoFileConnection,
+    /// applied to absolute image paths on import, e.g., to move from a local drive to a server
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub prefix_map: Vec<PrefixMapping>,
 }
 
 #[derive(Deserialize, Serialize, Debug, Clone, Default, PartialEq, Eq)]
@@ -142,6 +199,7 @@ pub struct Cfg {
     pub connection: Connection,
     pub cache: Cache,
     http_address: Option<String>,
+    http_token: Option<String>,
     tmpdir: Option<String>,
     pub current_prj_name: String,
     pub file_cache_args: Option<FileCacheCfgArgs>,
@@ -149,6 +207,11 @@ pub struct Cfg {
     pub export_folder: Option<String>,
     pub py_http_reader_cfg: Option<PyHttpReaderCfg>,
     pub coco_file: Option<CocoFile>,
+    pub collab_cfg: Option<CollabCfg>,
+    pub inference_cfg: Option<InferenceCfg>,
+    pub video_cfg: Option<VideoCfg>,
+    #[serde(default)]
+    pub keymap: Keymap,
     #[cfg(feature = "azure_blob")]
     pub azure_blob_cfg: Option<AzureBlobCfg>,
 }
@@ -179,6 +242,11 @@ impl Cfg {
             None => "127.0.0.1:5432",
         }
     }
+
+    /// requests to the http server need the header `Authorization: Bearer <token>` if set
+    pub fn http_token(&self) -> Option<&str> {
+        self.http_token.as_deref()
+    }
 }
 
 #[test]
@@ -0,0 +1,560 @@
+//! Several annotators can work on the same folder if the export folder is on a shared file
+//! system. Images are claimed by the annotator that currently looks at them and project files are
+//! merged per image on save.
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations::BboxAnnotations,
+    domain::Shape,
+    file_util,
+    result::{to_rv, RvResult},
+    rverr,
+    tools::{BBOX_NAME, CLASSIFY_NAME, KEYPOINTS_NAME},
+    tools_data::{
+        annotations::KeypointAnnotations, bbox_data::AnnotationsMap, classify_data::TagsMap,
+        keypoint_data::KeypointAnnotationsMap, BboxSpecificData, ClassifyToolData,
+        KeypointToolData,
+    },
+    world::ToolsDataMap,
+};
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct Claim {
+    pub user: String,
+    pub timestamp: u64,
+}
+
+/// file path -> claim
+pub type Claims = HashMap<String, Claim>;
+
+/// An image that has been changed by us and by somebody else since our last load or save.
+#[derive(Clone, Debug)]
+pub struct Conflict {
+    pub file_path: String,
+    pub theirs: BboxAnnotations,
+    pub shape: Shape,
+}
+
+/// Per-image data as of our last load or save of the project, the base of the three-way merges.
+#[derive(Clone, Debug, Default)]
+pub struct Base {
+    bbox: Option<AnnotationsMap>,
+    tags: Option<TagsMap>,
+    keypoints: Option<KeypointAnnotationsMap>,
+}
+impl Base {
+    pub fn new(tools_data_map: &ToolsDataMap) -> Self {
+        Self {
+            bbox: tools_data_map
+                .get(BBOX_NAME)
+                .map(|td| td.specifics.bbox().annotations_map().clone()),
+            tags: tools_data_map
+                .get(CLASSIFY_NAME)
+                .map(|td| td.specifics.classify().tags_map().clone()),
+            keypoints: tools_data_map
+                .get(KEYPOINTS_NAME)
+                .map(|td| td.specifics.keypoint().annotations_map().clone()),
+        }
+    }
+    pub fn bbox(&self) -> Option<&AnnotationsMap> {
+        self.bbox.as_ref()
+    }
+    pub fn tags(&self) -> Option<&TagsMap> {
+        self.tags.as_ref()
+    }
+    pub fn keypoints(&self) -> Option<&KeypointAnnotationsMap> {
+        self.keypoints.as_ref()
+    }
+}
+
+/// The claims are stored next to the project file.
+pub fn claims_path(prj_path: &Path) -> PathBuf {
+    prj_path.with_extension("claims")
+}
+
+pub fn now_secs() -> RvResult<u64> {
+    Ok(SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_err(to_rv)?
+        .as_secs())
+}
+
+const LOCK_ATTEMPTS: u32 = 100;
+const LOCK_RETRY_MILLIS: u64 = 20;
+/// Locks older than this have been left behind by a crashed annotator.
+const LOCK_STALE_SECS: u64 = 10;
+
+/// Exclusive lock on the claims file, released on drop.
+struct ClaimsLock {
+    path: PathBuf,
+}
+
+impl ClaimsLock {
+    fn acquire(claims_path: &Path) -> RvResult<Self> {
+        let path = claims_path.with_extension("claims.lock");
+        for _ in 0..LOCK_ATTEMPTS {
+            match fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+            {
+                Ok(_) => return Ok(Self { path }),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => {
+                    let is_stale = fs::metadata(&path)
+                        .and_then(|m| m.modified())
+                        .ok()
+                        .and_then(|modified| modified.elapsed().ok())
+                        .is_some_and(|age| age.as_secs() >= LOCK_STALE_SECS);
+                    if is_stale {
+                        fs::remove_file(&path).ok();
+                    } else {
+                        thread::sleep(Duration::from_millis(LOCK_RETRY_MILLIS));
+                    }
+                }
+                Err(e) => return Err(rverr!("could not lock {:?} due to {:?}", path, e)),
+            }
+        }
+        Err(rverr!("{:?} is locked by another annotator", path))
+    }
+}
+
+impl Drop for ClaimsLock {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).ok();
+    }
+}
+
+fn read_claims(claims_path: &Path) -> RvResult<Claims> {
+    if claims_path.exists() {
+        let s = file_util::read_to_string(claims_path)?;
+        serde_json::from_str(&s).map_err(to_rv)
+    } else {
+        Ok(Claims::new())
+    }
+}
+
+/// Claims `file_path` for `user` and releases all other claims of `user`. If the file is already
+/// claimed by another user and this claim is not stale, the claim of the other user is returned
+/// and nothing is written. Reading and writing the claims happens under an exclusive lock such
+/// that concurrent claims of the same file cannot both succeed.
+pub fn claim(
+    claims_path: &Path,
+    file_path: &str,
+    user: &str,
+    timeout_secs: u64,
+    now: u64,
+) -> RvResult<Option<Claim>> {
+    let is_stale = |c: &Claim| now.saturating_sub(c.timestamp) >= timeout_secs;
+    if let Some(parent) = claims_path.parent() {
+        fs::create_dir_all(parent).map_err(to_rv)?;
+    }
+    let _lock = ClaimsLock::acquire(claims_path)?;
+    let mut claims = read_claims(claims_path)?;
+    if let Some(c) = claims.get(file_path) {
+        if c.user != user && !is_stale(c) {
+            return Ok(Some(c.clone()));
+        }
+    }
+    claims.retain(|_, c| c.user != user && !is_stale(c));
+    claims.insert(
+        file_path.to_string(),
+        Claim {
+            user: user.to_string(),
+            timestamp: now,
+        },
+    );
+    let claims_str = serde_json::to_string(&claims).map_err(to_rv)?;
+    file_util::write(claims_path, claims_str).map(|_| None)
+}
+
+/// Returns the claim of `file_path` if it is claimed by another user than `user` and the claim is
+/// not stale. Nothing is written.
+pub fn claim_of_other(
+    claims_path: &Path,
+    file_path: &str,
+    user: &str,
+    timeout_secs: u64,
+    now: u64,
+) -> RvResult<Option<Claim>> {
+    let claims = read_claims(claims_path)?;
+    Ok(claims
+        .get(file_path)
+        .filter(|c| c.user != user && now.saturating_sub(c.timestamp) < timeout_secs)
+        .cloned())
+}
+
+fn is_same(annos1: Option<&BboxAnnotations>, annos2: Option<&BboxAnnotations>) -> bool {
+    let empty = BboxAnnotations::new();
+    annos1
+        .unwrap_or(&empty)
+        .has_same_content(annos2.unwrap_or(&empty))
+}
+
+/// Three-way merge per image. `base` is the state of the project file at our last load or save
+/// and `theirs` is the state of the project file on disk. Images that have only been changed on
+/// one side are taken from that side. Images that have been changed on both sides stay as they
+/// are in `ours` and are returned as conflicts.
+pub fn merge(
+    base: Option<&AnnotationsMap>,
+    ours: &mut BboxSpecificData,
+    theirs: BboxSpecificData,
+) -> RvResult<Vec<Conflict>> {
+    let idx_map = ours.unify_labels(theirs.labels(), theirs.colors(), theirs.cat_ids())?;
+    ours.unify_attribute_defs(theirs.attribute_defs());
+    for (file_path, review) in theirs.image_reviews() {
+        if ours.image_review(file_path).is_none() {
+            ours.set_image_review(file_path, review.clone());
+        }
+    }
+    let base_annos = |file_path: &str| base.and_then(|b| b.get(file_path)).map(|(a, _)| a);
+    let theirs = theirs
+        .anno_intoiter()
+        .map(|(file_path, (mut annos, shape))| {
+            annos.remap_cat_idxs(&idx_map);
+            (file_path, (annos, shape))
+        })
+        .collect::<AnnotationsMap>();
+
+    // images that have been cleared out of their project file
+    let removed_by_them = ours
+        .anno_iter()
+        .filter(|(file_path, (annos, _))| {
+            !theirs.contains_key(*file_path)
+                && base_annos(file_path).is_some()
+                && is_same(Some(annos), base_annos(file_path))
+        })
+        .map(|(file_path, (_, shape))| (file_path.clone(), *shape))
+        .collect::<Vec<_>>();
+    for (file_path, shape) in removed_by_them {
+        ours.get_annos_mut(&file_path, shape).clear();
+    }
+
+    let mut conflicts = vec![];
+    for (file_path, (theirs_annos, shape)) in theirs {
+        let ours_annos = ours.get_annos(&file_path);
+        if is_same(ours_annos, Some(&theirs_annos)) {
+            continue;
+        }
+        if is_same(ours_annos, base_annos(&file_path)) {
+            *ours.get_annos_mut(&file_path, shape) = theirs_annos;
+        } else if !is_same(Some(&theirs_annos), base_annos(&file_path)) {
+            conflicts.push(Conflict {
+                file_path,
+                theirs: theirs_annos,
+                shape,
+            });
+        }
+    }
+    Ok(conflicts)
+}
+
+/// Three-way merge of per-image data, see [`merge`]. Images that have been changed on both sides
+/// stay as they are in `ours` and their file paths are returned.
+fn merge_per_image<V>(
+    base: Option<&HashMap<String, V>>,
+    ours: &mut HashMap<String, V>,
+    theirs: HashMap<String, V>,
+    is_same: impl Fn(Option<&V>, Option<&V>) -> bool,
+) -> Vec<String> {
+    let base_value = |file_path: &str| base.and_then(|b| b.get(file_path));
+    // images that have been cleared out of their project file
+    ours.retain(|file_path, v| {
+        theirs.contains_key(file_path) || !is_same(Some(v), base_value(file_path))
+    });
+    let mut conflicts = vec![];
+    for (file_path, theirs_value) in theirs {
+        let ours_value = ours.get(&file_path);
+        if is_same(ours_value, Some(&theirs_value)) {
+            continue;
+        }
+        if is_same(ours_value, base_value(&file_path)) {
+            ours.insert(file_path, theirs_value);
+        } else if !is_same(Some(&theirs_value), base_value(&file_path)) {
+            conflicts.push(file_path);
+        }
+    }
+    conflicts.sort();
+    conflicts
+}
+
+/// Three-way merge of the tags per image, see [`merge`]. Tags of images that have been changed on
+/// both sides stay as they are in `ours` and the file paths of these images are returned.
+pub fn merge_tags(
+    base: Option<&TagsMap>,
+    ours: &mut ClassifyToolData,
+    theirs: ClassifyToolData,
+) -> Vec<String> {
+    ours.unify_tags(theirs.tags());
+    merge_per_image(
+        base,
+        ours.tags_map_mut(),
+        theirs.tags_map().clone(),
+        |t1, t2| t1 == t2,
+    )
+}
+
+fn is_same_keypoints(
+    annos1: Option<&(KeypointAnnotations, Shape)>,
+    annos2: Option<&(KeypointAnnotations, Shape)>,
+) -> bool {
+    // the selection is not part of the annotations
+    annos1.map(|(annos, shape)| (annos.instances(), shape))
+        == annos2.map(|(annos, shape)| (annos.instances(), shape))
+}
+
+/// Three-way merge of the keypoint annotations per image, see [`merge`]. Annotations of images
+/// that have been changed on both sides stay as they are in `ours` and the file paths of these
+/// images are returned. If the skeletons differ, all annotations stay as they are in `ours` and
+/// all images that differ are returned.
+pub fn merge_keypoints(
+    base: Option<&KeypointAnnotationsMap>,
+    ours: &mut KeypointToolData,
+    theirs: KeypointToolData,
+) -> Vec<String> {
+    if ours.skeleton() != theirs.skeleton() {
+        let mut conflicts = theirs
+            .anno_iter()
+            .filter(|(file_path, annos)| {
+                !is_same_keypoints(ours.annotations_map().get(*file_path), Some(annos))
+            })
+            .map(|(file_path, _)| file_path.clone())
+            .collect::<Vec<_>>();
+        conflicts.sort();
+        conflicts
+    } else {
+        merge_per_image(
+            base,
+            ours.annotations_map_mut(),
+            theirs.anno_intoiter().collect(),
+            is_same_keypoints,
+        )
+    }
+}
+
+#[cfg(test)]
+use crate::{
+    annotations::{Keypoint, Visibility},
+    defer_file_removal,
+    domain::{PtI, BB},
+    file_util::DEFAULT_TMPDIR,
+};
+
+#[test]
+fn test_claim() -> RvResult<()> {
+    let claims_path = DEFAULT_TMPDIR.join("rvprj_test_claim.claims");
+    defer_file_removal!(&claims_path);
+    let timeout = 10;
+    assert_eq!(claim(&claims_path, "a.png", "me", timeout, 100)?, None);
+    assert_eq!(claim(&claims_path, "b.png", "me", timeout, 101)?, None);
+    // my claim on a.png has been released when I claimed b.png
+    assert_eq!(claim(&claims_path, "a.png", "you", timeout, 102)?, None);
+    let mine = Claim {
+        user: "me".to_string(),
+        timestamp: 101,
+    };
+    assert_eq!(
+        claim(&claims_path, "b.png", "you", timeout, 105)?,
+        Some(mine)
+    );
+    assert_eq!(
+        claim_of_other(&claims_path, "b.png", "you", timeout, 105)?.map(|c| c.user),
+        Some("me".to_string())
+    );
+    assert_eq!(
+        claim_of_other(&claims_path, "b.png", "me", timeout, 105)?,
+        None
+    );
+    // claiming again refreshes the timestamp
+    assert_eq!(claim(&claims_path, "b.png", "me", timeout, 108)?, None);
+    assert_eq!(
+        claim_of_other(&claims_path, "b.png", "you", timeout, 111)?.map(|c| c.timestamp),
+        Some(108)
+    );
+    // stale claims can be taken over
+    assert_eq!(
+        claim_of_other(&claims_path, "b.png", "you", timeout, 118)?,
+        None
+    );
+    assert_eq!(claim(&claims_path, "b.png", "you", timeout, 118)?, None);
+    assert_eq!(read_claims(&claims_path)?.len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_claim_concurrent() -> RvResult<()> {
+    let claims_path = DEFAULT_TMPDIR.join("rvprj_test_claim_concurrent.claims");
+    defer_file_removal!(&claims_path);
+    let handles = (0..8)
+        .map(|i| {
+            let claims_path = claims_path.clone();
+            thread::spawn(move || claim(&claims_path, "a.png", &format!("user{i}"), 10, 100))
+        })
+        .collect::<Vec<_>>();
+    let n_granted = handles
+        .into_iter()
+        .map(|h| h.join().unwrap())
+        .collect::<RvResult<Vec<_>>>()?
+        .iter()
+        .filter(|c| c.is_none())
+        .count();
+    assert_eq!(n_granted, 1);
+    assert!(!claims_path.with_extension("claims.lock").exists());
+    Ok(())
+}
+
+#[test]
+fn test_merge() -> RvResult<()> {
+    let shape = Shape::new(100, 100);
+    let bb1 = BB::from_arr(&[1, 1, 5, 5]);
+    let bb2 = BB::from_arr(&[20, 20, 5, 5]);
+    let bb3 = BB::from_arr(&[40, 40, 5, 5]);
+
+    let mut base = BboxSpecificData::new();
+    base.get_annos_mut("unchanged.png", shape).add_bb(bb1, 0);
+    base.get_annos_mut("changed_by_them.png", shape)
+        .add_bb(bb1, 0);
+    base.get_annos_mut("changed_by_us.png", shape)
+        .add_bb(bb1, 0);
+    base.get_annos_mut("changed_by_both.png", shape)
+        .add_bb(bb1, 0);
+    let base_map = base.annotations_map().clone();
+
+    let mut ours = base.clone();
+    ours.get_annos_mut("changed_by_us.png", shape)
+        .add_bb(bb2, 0);
+    ours.get_annos_mut("changed_by_both.png", shape)
+        .add_bb(bb2, 0);
+
+    let mut theirs = base.clone();
+    theirs.push("their label".to_string(), None, None)?;
+    theirs
+        .get_annos_mut("changed_by_them.png", shape)
+        .add_bb(bb3, 1);
+    theirs
+        .get_annos_mut("changed_by_both.png", shape)
+        .add_bb(bb3, 0);
+    theirs
+        .get_annos_mut("new_by_them.png", shape)
+        .add_bb(bb3, 1);
+
+    let conflicts = merge(Some(&base_map), &mut ours, theirs)?;
+    assert_eq!(ours.labels(), &vec!["foreground", "their label"]);
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].file_path, "changed_by_both.png");
+    assert_eq!(
+        ours.get_annos("changed_by_them.png").unwrap().cat_idxs(),
+        &vec![0, 1]
+    );
+    assert_eq!(ours.get_annos("changed_by_us.png").unwrap().geos().len(), 2);
+    assert_eq!(
+        ours.get_annos("new_by_them.png").unwrap().cat_idxs(),
+        &vec![1]
+    );
+    assert_eq!(ours.get_annos("unchanged.png").unwrap().geos().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_merge_tags() -> RvResult<()> {
+    let mut base = ClassifyToolData::default();
+    base.push("day".to_string())?;
+    base.apply_tag("unchanged.png", 0);
+    base.apply_tag("removed_by_them.png", 0);
+    base.apply_tag("changed_by_both.png", 0);
+    let base_map = base.tags_map().clone();
+
+    let mut ours = base.clone();
+    ours.push("night".to_string())?;
+    ours.apply_tag("changed_by_both.png", 1);
+    ours.apply_tag("new_by_us.png", 1);
+
+    let mut theirs = base.clone();
+    theirs.push("blurry".to_string())?;
+    theirs.options.is_multi_tag = true;
+    theirs.apply_tag("changed_by_both.png", 1);
+    theirs.apply_tag("new_by_them.png", 1);
+    theirs.apply_tag("removed_by_them.png", 0);
+
+    let conflicts = merge_tags(Some(&base_map), &mut ours, theirs);
+    assert_eq!(conflicts, vec!["changed_by_both.png".to_string()]);
+    assert_eq!(ours.tags(), &vec!["day", "night", "blurry"]);
+    assert_eq!(
+        ours.get_tags("changed_by_both.png"),
+        Some(&vec!["night".to_string()])
+    );
+    assert_eq!(
+        ours.get_tags("new_by_us.png"),
+        Some(&vec!["night".to_string()])
+    );
+    assert_eq!(
+        ours.get_tags("new_by_them.png"),
+        Some(&vec!["blurry".to_string()])
+    );
+    assert_eq!(
+        ours.get_tags("unchanged.png"),
+        Some(&vec!["day".to_string()])
+    );
+    assert!(ours.get_tags("removed_by_them.png").is_none());
+    Ok(())
+}
+
+#[test]
+fn test_merge_keypoints() -> RvResult<()> {
+    let shape = Shape::new(10, 10);
+    let kp = |x| Keypoint {
+        pos: PtI { x, y: 1 },
+        visibility: Visibility::Visible,
+    };
+    let add_instance = |data: &mut KeypointToolData, file_path: &str, x: u32| {
+        let annos = data.get_annos_mut(file_path, shape);
+        let inst_idx = annos.add_instance(1);
+        annos.set_keypoint(inst_idx, 0, kp(x));
+    };
+    let mut base = KeypointToolData::default();
+    base.push_keypoint("nose".to_string())?;
+    add_instance(&mut base, "changed_by_both.png", 1);
+    let base_map = base.annotations_map().clone();
+
+    let mut ours = base.clone();
+    ours.get_annos_mut("changed_by_both.png", shape)
+        .set_keypoint(0, 0, kp(2));
+    add_instance(&mut ours, "new_by_us.png", 2);
+    // selecting is not changing
+    ours.get_annos_mut("changed_by_both.png", shape).select(0);
+
+    let mut theirs = base.clone();
+    theirs
+        .get_annos_mut("changed_by_both.png", shape)
+        .set_keypoint(0, 0, kp(3));
+    add_instance(&mut theirs, "new_by_them.png", 3);
+
+    // annotations of different skeletons are not merged
+    let mut ours_other_skeleton = ours.clone();
+    ours_other_skeleton.push_keypoint("tail".to_string())?;
+    let conflicts = merge_keypoints(Some(&base_map), &mut ours_other_skeleton, theirs.clone());
+    assert_eq!(conflicts, vec!["changed_by_both.png", "new_by_them.png"]);
+    assert!(ours_other_skeleton.get_annos("new_by_them.png").is_none());
+
+    let conflicts = merge_keypoints(Some(&base_map), &mut ours, theirs);
+    assert_eq!(conflicts, vec!["changed_by_both.png"]);
+    assert_eq!(
+        ours.get_annos("changed_by_both.png")
+            .unwrap()
+            .keypoint(0, 0),
+        &kp(2)
+    );
+    assert!(ours.get_annos("new_by_us.png").is_some());
+    assert_eq!(
+        ours.get_annos("new_by_them.png").unwrap().keypoint(0, 0),
+        &kp(3)
+    );
+    Ok(())
+}
@@ -3,14 +3,29 @@ use std::str::FromStr;
 use exmex::prelude::*;
 use exmex::{ops_factory, BinOp, ExError, MakeOperators, MatchLiteral, Operator};
 
-use crate::result::{RvError, RvResult};
+use crate::annotations::{AnnoSource, Provenance, ReviewStatus};
+use crate::result::{to_rv, RvError, RvResult};
+use crate::rverr;
 use crate::tools;
+use crate::tools_data::image_review::ImageStatus;
 use crate::world::ToolsDataMap;
 
 #[derive(Clone, Debug, Default)]
 pub enum FilterPredicate {
     FilterStr(String),
     Label(Box<FilterPredicate>),
+    /// `attr(occluded)` for truthy attributes or `attr(track_id=3)` for specific values
+    Attr(Box<FilterPredicate>),
+    /// images with a tag of the classification tool
+    Tag(Box<FilterPredicate>),
+    /// `source(predicted)` for images with annotations from `manual`, `imported`, or `predicted`
+    Source(Box<FilterPredicate>),
+    /// `review(unreviewed)` for images with `unreviewed`, `accepted`, or `rejected` annotations
+    Review(Box<FilterPredicate>),
+    /// `score(<0.5)` or `score(>=0.9)` for images with annotations of matching scores
+    Score(Box<FilterPredicate>),
+    /// `status(needs_fix)` for images with review status `todo`, `in_progress`, `done`, or `needs_fix`
+    Status(Box<FilterPredicate>),
     Nolabel,
     And(Box<FilterPredicate>, Box<FilterPredicate>),
     Or(Box<FilterPredicate>, Box<FilterPredicate>),
@@ -49,6 +64,98 @@ impl FilterPredicate {
                     true
                 }
             }
+            FilterPredicate::Attr(attr) => {
+                let attr = match &(**attr) {
+                    FilterPredicate::FilterStr(attr) => attr,
+                    _ => Err(RvError::new("Attribute must be a string"))?,
+                };
+                let (name, value) = match attr.split_once('=') {
+                    Some((name, value)) => (name.trim(), Some(value.trim())),
+                    None => (attr.trim(), None),
+                };
+                let tdm = tdm.unwrap();
+                if let Some(bbox_data) = tdm.get(tools::BBOX_NAME) {
+                    let bbox_data = bbox_data.specifics.bbox();
+                    if let Some(annos) = bbox_data.get_annos(path) {
+                        annos.cat_idxs().iter().zip(annos.attributes().iter()).any(
+                            |(cat_idx, attributes)| {
+                                let attributes =
+                                    bbox_data.effective_attributes(*cat_idx, attributes);
+                                match (attributes.get(name), value) {
+                                    (Some(v), Some(value)) => v.to_string() == value,
+                                    (Some(v), None) => v.is_truthy(),
+                                    (None, _) => false,
+                                }
+                            },
+                        )
+                    } else {
+                        false
+                    }
+                } else {
+                    true
+                }
+            }
+            FilterPredicate::Tag(tag) => {
+                let tag = match &(**tag) {
+                    FilterPredicate::FilterStr(tag) => tag.trim(),
+                    _ => Err(RvError::new("Tag must be a string"))?,
+                };
+                let tdm = tdm.unwrap();
+                if let Some(classify_data) = tdm.get(tools::CLASSIFY_NAME) {
+                    classify_data
+                        .specifics
+                        .classify()
+                        .get_tags(path)
+                        .map(|tags| tags.iter().any(|t| t == tag))
+                        .unwrap_or(false)
+                } else {
+                    true
+                }
+            }
+            FilterPredicate::Source(source) => {
+                let source = match &(**source) {
+                    FilterPredicate::FilterStr(source) => source.parse::<AnnoSource>()?,
+                    _ => Err(RvError::new("Source must be a string"))?,
+                };
+                any_provenance(path, tdm, |prov| prov.source == source)
+            }
+            FilterPredicate::Review(review) => {
+                let review = match &(**review) {
+                    FilterPredicate::FilterStr(review) => review.parse::<ReviewStatus>()?,
+                    _ => Err(RvError::new("Review status must be a string"))?,
+                };
+                any_provenance(path, tdm, |prov| prov.review == review)
+            }
+            FilterPredicate::Score(cmp) => {
+                let cmp = match &(**cmp) {
+                    FilterPredicate::FilterStr(cmp) => cmp.trim(),
+                    _ => Err(RvError::new("Score comparison must be a string"))?,
+                };
+                let (op, value) = ["<=", ">=", "<", ">"]
+                    .iter()
+                    .find_map(|op| cmp.strip_prefix(op).map(|value| (*op, value)))
+                    .ok_or_else(|| rverr!("score comparison '{}' needs <, <=, >, or >=", cmp))?;
+                let value = value.trim().parse::<f32>().map_err(to_rv)?;
+                any_provenance(path, tdm, |prov| match (prov.score, op) {
+                    (Some(score), "<=") => score <= value,
+                    (Some(score), ">=") => score >= value,
+                    (Some(score), "<") => score < value,
+                    (Some(score), ">") => score > value,
+                    _ => false,
+                })
+            }
+            FilterPredicate::Status(status) => {
+                let status = match &(**status) {
+                    FilterPredicate::FilterStr(status) => status.parse::<ImageStatus>()?,
+                    _ => Err(RvError::new("Status must be a string"))?,
+                };
+                let tdm = tdm.unwrap();
+                let image_status = tdm
+                    .get(tools::BBOX_NAME)
+                    .map(|bbox_data| bbox_data.specifics.bbox().image_status(path))
+                    .unwrap_or_default();
+                image_status == status
+            }
             FilterPredicate::Nolabel => {
                 if let Some(tdm) = tdm {
                     let bb_tool = tdm.get(tools::BBOX_NAME);
@@ -69,6 +176,25 @@ impl FilterPredicate {
         })
     }
 }
+/// Whether any annotation of the image has a matching provenance, true without bbox data
+fn any_provenance(
+    path: &str,
+    tdm: Option<&ToolsDataMap>,
+    pred: impl Fn(&Provenance) -> bool,
+) -> bool {
+    let tdm = tdm.unwrap();
+    if let Some(bbox_data) = tdm.get(tools::BBOX_NAME) {
+        bbox_data
+            .specifics
+            .bbox()
+            .get_annos(path)
+            .map(|annos| annos.provenances().iter().any(pred))
+            .unwrap_or(false)
+    } else {
+        true
+    }
+}
+
 impl FromStr for FilterPredicate {
     type Err = ExError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -103,6 +229,24 @@ ops_factory!(
     Operator::make_unary("label", |a: FilterPredicate| FilterPredicate::Label(
         Box::new(a)
     )),
+    Operator::make_unary("attr", |a: FilterPredicate| FilterPredicate::Attr(
+        Box::new(a)
+    )),
+    Operator::make_unary("tag", |a: FilterPredicate| FilterPredicate::Tag(Box::new(
+        a
+    ))),
+    Operator::make_unary("source", |a: FilterPredicate| FilterPredicate::Source(
+        Box::new(a)
+    )),
+    Operator::make_unary("review", |a: FilterPredicate| FilterPredicate::Review(
+        Box::new(a)
+    )),
+    Operator::make_unary("score", |a: FilterPredicate| FilterPredicate::Score(
+        Box::new(a)
+    )),
+    Operator::make_unary("status", |a: FilterPredicate| FilterPredicate::Status(
+        Box::new(a)
+    )),
     Operator::make_constant("nolabel", FilterPredicate::Nolabel)
 );
 
@@ -111,11 +255,19 @@ pub struct PathMatcher;
 impl MatchLiteral for PathMatcher {
     fn is_literal(text: &str) -> Option<&str> {
         let trimmed = text.trim();
-        if trimmed.starts_with("label") || trimmed.starts_with("nolabel") {
+        if trimmed.starts_with("label")
+            || trimmed.starts_with("nolabel")
+            || trimmed.starts_with("attr")
+            || trimmed.starts_with("tag")
+            || trimmed.starts_with("source")
+            || trimmed.starts_with("review")
+            || trimmed.starts_with("score")
+            || trimmed.starts_with("status")
+        {
             None
         } else {
             exmex::lazy_static::lazy_static! {
-                static ref RE_VAR_NAME_EXACT: exmex::regex::Regex = exmex::regex::Regex::new(r"^[a-zA-z0-9\\/\-]+").unwrap();
+                static ref RE_VAR_NAME_EXACT: exmex::regex::Regex = exmex::regex::Regex::new(r"^[a-zA-z0-9\\/\-=.<>]+").unwrap();
             }
             RE_VAR_NAME_EXACT.find(text).map(|m| m.as_str())
         }
@@ -140,3 +292,125 @@ fn test_filter_exmex() {
         assert_eq!(pred.apply(path, None).unwrap(), *expected);
     }
 }
+
+#[cfg(test)]
+use crate::{
+    annotations::{AttrDef, AttrVal},
+    domain::{Shape, BB},
+    tools_data::{BboxSpecificData, ClassifyToolData, ToolSpecifics, ToolsData},
+    GeoFig,
+};
+
+#[test]
+fn test_filter_attr() {
+    let mut bbox_data = BboxSpecificData::new();
+    let def = AttrDef {
+        name: "occluded".to_string(),
+        default: AttrVal::Bool(false),
+    };
+    bbox_data.add_attribute_def(0, def).unwrap();
+    let shape = Shape::new(10, 10);
+    let annos = bbox_data.get_annos_mut("occluded.png", shape);
+    annos.add_bb(BB::from_arr(&[0, 0, 5, 5]), 0);
+    annos.select(0);
+    annos.set_attribute_selected(0, "occluded", &AttrVal::Bool(true));
+    bbox_data
+        .get_annos_mut("visible.png", shape)
+        .add_bb(BB::from_arr(&[0, 0, 5, 5]), 0);
+    let tdm = ToolsDataMap::from([(
+        tools::BBOX_NAME,
+        ToolsData::new(ToolSpecifics::Bbox(bbox_data)),
+    )]);
+    let test = |s: &str, path: &str, expected: bool| {
+        let expr = FilterExpr::parse(s).unwrap();
+        let pred = expr.eval(&[]).unwrap();
+        assert_eq!(pred.apply(path, Some(&tdm)).unwrap(), expected);
+    };
+    test("attr(occluded)", "occluded.png", true);
+    test("attr(occluded)", "visible.png", false);
+    test("attr(occluded=false)", "visible.png", true);
+    test("!attr(occluded=true)", "occluded.png", false);
+}
+
+#[test]
+fn test_filter_tag() {
+    let mut classify_data = ClassifyToolData::default();
+    classify_data.push("day".to_string()).unwrap();
+    classify_data.push("night".to_string()).unwrap();
+    classify_data.apply_tag("a.png", 0);
+    classify_data.apply_tag("b.png", 1);
+    let tdm = ToolsDataMap::from([(
+        tools::CLASSIFY_NAME,
+        ToolsData::new(ToolSpecifics::Classify(classify_data)),
+    )]);
+    let test = |s: &str, path: &str, expected: bool| {
+        let expr = FilterExpr::parse(s).unwrap();
+        let pred = expr.eval(&[]).unwrap();
+        assert_eq!(pred.apply(path, Some(&tdm)).unwrap(), expected);
+    };
+    test("tag(day)", "a.png", true);
+    test("tag(day)", "b.png", false);
+    test("tag(day) || tag(night)", "b.png", true);
+    test("!tag(night)", "c.png", true);
+}
+
+#[test]
+fn test_filter_provenance() {
+    let mut bbox_data = BboxSpecificData::new();
+    let shape = Shape::new(10, 10);
+    let annos = bbox_data.get_annos_mut("predicted.png", shape);
+    annos.add_geo_with_provenance(
+        GeoFig::BB(BB::from_arr(&[0, 0, 5, 5])),
+        0,
+        Provenance {
+            score: Some(0.3),
+            source: AnnoSource::Predicted,
+            review: ReviewStatus::Unreviewed,
+        },
+    );
+    bbox_data
+        .get_annos_mut("manual.png", shape)
+        .add_bb(BB::from_arr(&[0, 0, 5, 5]), 0);
+    let tdm = ToolsDataMap::from([(
+        tools::BBOX_NAME,
+        ToolsData::new(ToolSpecifics::Bbox(bbox_data)),
+    )]);
+    let test = |s: &str, path: &str, expected: bool| {
+        let expr = FilterExpr::parse(s).unwrap();
+        let pred = expr.eval(&[]).unwrap();
+        assert_eq!(pred.apply(path, Some(&tdm)).unwrap(), expected);
+    };
+    test("source(predicted)", "predicted.png", true);
+    test("source(predicted)", "manual.png", false);
+    test("source(manual)", "manual.png", true);
+    test("review(unreviewed)", "manual.png", true);
+    test("review(accepted)", "predicted.png", false);
+    test("score(<0.5)", "predicted.png", true);
+    test("score(>=0.5)", "predicted.png", false);
+    test("score(<0.5)", "manual.png", false);
+    test("source(predicted) && score(<0.5)", "predicted.png", true);
+    let expr = FilterExpr::parse("review(maybe)").unwrap();
+    let pred = expr.eval(&[]).unwrap();
+    assert!(pred.apply("manual.png", Some(&tdm)).is_err());
+}
+
+#[test]
+fn test_filter_status() {
+    let mut bbox_data = BboxSpecificData::new();
+    bbox_data.set_image_status("fix.png", ImageStatus::NeedsFix);
+    bbox_data.set_image_status("done.png", ImageStatus::Done);
+    let tdm = ToolsDataMap::from([(
+        tools::BBOX_NAME,
+        ToolsData::new(ToolSpecifics::Bbox(bbox_data)),
+    )]);
+    let test = |s: &str, path: &str, expected: bool| {
+        let expr = FilterExpr::parse(s).unwrap();
+        let pred = expr.eval(&[]).unwrap();
+        assert_eq!(pred.apply(path, Some(&tdm)).unwrap(), expected);
+    };
+    test("status(needs_fix)", "fix.png", true);
+    test("status(needs_fix)", "done.png", false);
+    test("status(todo)", "other.png", true);
+    test("!status(done)", "done.png", false);
+    test("status(done) || status(needs_fix)", "fix.png", true);
+}
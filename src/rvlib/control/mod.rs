@@ -1,5 +1,5 @@
 use std::fmt::Debug;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::Duration;
 
@@ -7,17 +7,23 @@ use crate::cfg::{self, Connection};
 use crate::file_util::{filename_to_prjname, ConnectionData, MetaData};
 use crate::history::{History, Record};
 use crate::result::RvError;
+use crate::tools::{BBOX_NAME, CLASSIFY_NAME, KEYPOINTS_NAME};
+use crate::tools_data::image_review::ImageStatus;
+use crate::tools_data::merge::{self, ImageDiff, MergeMode};
+use crate::tools_data::{BboxExportData, BboxSpecificData, ToolSpecifics, ToolsData};
 use crate::world::{DataRaw, ToolsDataMap, World};
 use crate::{
     cfg::Cfg, image_reader::ReaderFromCfg, result::RvResult, threadpool::ThreadPool,
     types::AsyncResultImage,
 };
+pub mod collab;
 mod filter;
 pub mod paths_navigator;
 use crate::image_reader::LoadImageForGui;
+use crate::paths_selector::PathsSelector;
 use paths_navigator::PathsNavigator;
 
-mod detail {
+pub(crate) mod detail {
     use std::{
         fs,
         path::{Path, PathBuf},
@@ -31,7 +37,7 @@ mod detail {
         file_util::{self, make_prjcfg_path, ExportData},
         result::{to_rv, RvResult},
         rverr,
-        tools::BBOX_NAME,
+        tools::{BBOX_NAME, CLASSIFY_NAME, KEYPOINTS_NAME},
         tools_data::{BboxExportData, BboxSpecificData, ToolSpecifics, ToolsData},
         world::ToolsDataMap,
     };
@@ -40,25 +46,56 @@ mod detail {
         export_folder: &str,
         file_name: &str,
     ) -> RvResult<(ToolsDataMap, Option<String>, Cfg)> {
-        let file_path = Path::new(export_folder).join(file_name);
+        read(&Path::new(export_folder).join(file_name))
+    }
+
+    pub(crate) fn read(file_path: &Path) -> RvResult<(ToolsDataMap, Option<String>, Cfg)> {
         let s = file_util::read_to_string(file_path)?;
         let read: ExportData = serde_json::from_str(s.as_str()).map_err(to_rv)?;
 
-        let tools_data_map = if let Some(bbox_data) = read.bbox_data {
+        let mut tools_data_map = if let Some(bbox_data) = read.bbox_data {
             let bbox_data = BboxSpecificData::from_bbox_export_data(bbox_data)?;
             ToolsDataMap::from([(BBOX_NAME, ToolsData::new(ToolSpecifics::Bbox(bbox_data)))])
         } else {
             ToolsDataMap::new()
         };
+        if let Some(classify_data) = read.classify_data {
+            tools_data_map.insert(
+                CLASSIFY_NAME,
+                ToolsData::new(ToolSpecifics::Classify(classify_data)),
+            );
+        }
+        if let Some(keypoint_data) = read.keypoint_data {
+            tools_data_map.insert(
+                KEYPOINTS_NAME,
+                ToolsData::new(ToolSpecifics::Keypoint(keypoint_data)),
+            );
+        }
         Ok((tools_data_map, read.opened_folder, read.cfg))
     }
 
-    pub fn save(
+    pub(super) fn prj_path(
         opened_folder: Option<&String>,
-        tools_data_map: &ToolsDataMap,
         export_folder: &str,
         cfg: &Cfg,
-    ) -> RvResult<PathBuf> {
+    ) -> PathBuf {
+        let prj_name = if "default" != &cfg.current_prj_name {
+            &cfg.current_prj_name
+        } else if let Some(of) = opened_folder {
+            of.as_str()
+        } else {
+            "default"
+        };
+        make_prjcfg_path(Path::new(export_folder), prj_name)
+    }
+
+    /// Writes the project file to `file_path`.
+    pub(crate) fn write(
+        file_path: &Path,
+        opened_folder: Option<&String>,
+        tools_data_map: &ToolsDataMap,
+        cfg: &Cfg,
+    ) -> RvResult<()> {
         let bbox_data = tools_data_map.get(BBOX_NAME);
 
         let data = ExportData {
@@ -66,23 +103,31 @@ mod detail {
             bbox_data: bbox_data.map(|bbox_data| {
                 BboxExportData::from_bbox_data(bbox_data.specifics.bbox().clone())
             }),
+            classify_data: tools_data_map
+                .get(CLASSIFY_NAME)
+                .map(|classify_data| classify_data.specifics.classify().clone()),
+            keypoint_data: tools_data_map
+                .get(KEYPOINTS_NAME)
+                .map(|keypoint_data| keypoint_data.specifics.keypoint().clone()),
             cfg: cfg.clone(),
         };
-        let prj_name = if "default" != &cfg.current_prj_name {
-            &cfg.current_prj_name
-        } else if let Some(of) = opened_folder {
-            of.as_str()
-        } else {
-            "default"
-        };
+        let data_str = serde_json::to_string(&data).map_err(to_rv)?;
+        file_util::write(file_path, data_str)
+    }
+
+    pub fn save(
+        opened_folder: Option<&String>,
+        tools_data_map: &ToolsDataMap,
+        export_folder: &str,
+        cfg: &Cfg,
+    ) -> RvResult<PathBuf> {
         let ef_path = Path::new(export_folder);
-        let path = make_prjcfg_path(ef_path, prj_name);
+        let path = prj_path(opened_folder, export_folder, cfg);
         match fs::create_dir_all(ef_path) {
             Ok(_) => Ok(()),
             Err(e) => Err(rverr!("could not create {:?} due to {:?}", ef_path, e)),
         }?;
-        let data_str = serde_json::to_string(&data).map_err(to_rv)?;
-        file_util::write(&path, data_str)?;
+        write(&path, opened_folder, tools_data_map, cfg)?;
         println!("saved to {path:?}");
         Ok(path)
     }
@@ -147,7 +192,9 @@ pub struct Control {
     pub info: Info,
     pub paths_navigator: PathsNavigator,
     pub opened_folder: Option<String>,
-    tp: ThreadPool<RvResult<ReaderFromCfg>>,
+    /// connects and lists the opened folder in the background since listing might be slow, e.g.,
+    /// for remote folders or videos whose frames need to be probed
+    tp: ThreadPool<RvResult<(ReaderFromCfg, PathsSelector)>>,
     last_open_folder_job_id: Option<u128>,
     pub cfg: Cfg,
     pub file_loaded: Option<usize>,
@@ -155,6 +202,15 @@ pub struct Control {
     pub file_info_selected: Option<String>,
     flags: ControlFlags,
     pub loading_screen_animation_counter: u128,
+    /// images that have been changed by us and by another annotator, see [`collab::merge`]
+    pub conflicts: Vec<collab::Conflict>,
+    /// images whose tags or keypoints have been changed by us and by another annotator, we keep
+    /// ours
+    pub kept_conflicts: Vec<String>,
+    /// annotations as of the last load or save of the project
+    collab_base: collab::Base,
+    /// path of the most recent save that has not been announced to the event stream yet
+    pub saved_prj_path: Option<PathBuf>,
 }
 
 impl Control {
@@ -189,6 +245,9 @@ impl Control {
             self.open_folder(of)?;
         }
         self.cfg = read_cfg;
+        self.collab_base = collab::Base::new(&tools_data_map);
+        self.conflicts.clear();
+        self.kept_conflicts.clear();
 
         // update prj name in cfg
         let mut cfg_global = cfg::get_cfg()?;
@@ -214,11 +273,178 @@ impl Control {
         }
     }
 
-    pub fn save(&self, tools_data_map: &ToolsDataMap) -> RvResult<PathBuf> {
-        let opened_folder = self.opened_folder();
+    pub fn save(&mut self, tools_data_map: &mut ToolsDataMap) -> RvResult<PathBuf> {
+        let export_folder = self.cfg.export_folder()?.to_string();
+        if self.cfg.collab_cfg.is_some() {
+            self.merge_with_saved(tools_data_map, &export_folder)?;
+        }
+        let path = detail::save(
+            self.opened_folder(),
+            tools_data_map,
+            &export_folder,
+            &self.cfg,
+        )?;
+        self.collab_base = collab::Base::new(tools_data_map);
+        self.saved_prj_path = Some(path.clone());
+        // saving counts as activity on the shown image and keeps our claim from going stale
+        if let Some(file_path) = self.meta_data(self.file_selected_idx, None).file_path {
+            self.claim_image(&file_path)?;
+        }
+        Ok(path)
+    }
+
+    /// Merges the project file on disk that might have been saved by another annotator into our
+    /// data. Images with conflicting bounding boxes are collected in `self.conflicts`, images with
+    /// conflicting data of other tools in `self.kept_conflicts`.
+    fn merge_with_saved(
+        &mut self,
+        tools_data_map: &mut ToolsDataMap,
+        export_folder: &str,
+    ) -> RvResult<()> {
+        let path = detail::prj_path(self.opened_folder(), export_folder, &self.cfg);
+        if !path.exists() {
+            return Ok(());
+        }
+        let (mut saved, _, _) = detail::read(&path)?;
+        if let Some(theirs) = saved.remove(BBOX_NAME) {
+            if let Some(ours) = tools_data_map.get_mut(BBOX_NAME) {
+                let ours = ours.specifics.bbox_mut();
+                let theirs = theirs.specifics.bbox().clone();
+                self.conflicts = collab::merge(self.collab_base.bbox(), ours, theirs)?;
+                ours.options.is_redraw_annos_triggered = true;
+            } else {
+                tools_data_map.insert(BBOX_NAME, theirs);
+            }
+        }
+        self.kept_conflicts.clear();
+        if let Some(theirs) = saved.remove(CLASSIFY_NAME) {
+            if let Some(ours) = tools_data_map.get_mut(CLASSIFY_NAME) {
+                let ours = ours.specifics.classify_mut();
+                let theirs = theirs.specifics.classify().clone();
+                self.kept_conflicts = collab::merge_tags(self.collab_base.tags(), ours, theirs);
+            } else {
+                tools_data_map.insert(CLASSIFY_NAME, theirs);
+            }
+        }
+        if let Some(theirs) = saved.remove(KEYPOINTS_NAME) {
+            if let Some(ours) = tools_data_map.get_mut(KEYPOINTS_NAME) {
+                let ours = ours.specifics.keypoint_mut();
+                let theirs = theirs.specifics.keypoint().clone();
+                self.kept_conflicts.extend(collab::merge_keypoints(
+                    self.collab_base.keypoints(),
+                    ours,
+                    theirs,
+                ));
+                ours.options.is_redraw_annos_triggered = true;
+            } else {
+                tools_data_map.insert(KEYPOINTS_NAME, theirs);
+            }
+        }
+        Ok(())
+    }
+
+    fn load_bbox_export_data(&self, file_name: &str) -> RvResult<Option<BboxExportData>> {
         let export_folder = self.cfg.export_folder()?;
+        let (mut tools_data_map, _, _) = detail::load(export_folder, file_name)?;
+        Ok(tools_data_map
+            .remove(BBOX_NAME)
+            .map(|td| BboxExportData::from_bbox_data(td.specifics.bbox().clone())))
+    }
 
-        detail::save(opened_folder, tools_data_map, export_folder, &self.cfg)
+    /// Merges the bounding box annotations of another project in the export folder into ours.
+    /// Returns the images where both projects disagree.
+    pub fn merge_project(
+        &self,
+        tools_data_map: &mut ToolsDataMap,
+        file_name: &str,
+        mode: MergeMode,
+        iou_threshold: f64,
+    ) -> RvResult<Vec<ImageDiff>> {
+        let theirs = match self.load_bbox_export_data(file_name)? {
+            Some(theirs) => theirs,
+            None => return Ok(vec![]),
+        };
+        let ours = tools_data_map
+            .entry(BBOX_NAME)
+            .or_insert_with(|| ToolsData::new(ToolSpecifics::Bbox(BboxSpecificData::new())))
+            .specifics
+            .bbox_mut();
+        let options = ours.options;
+        let (merged, diffs) = merge::merge(
+            BboxExportData::from_bbox_data(ours.clone()),
+            theirs,
+            mode,
+            iou_threshold,
+        )?;
+        *ours = BboxSpecificData::from_bbox_export_data(merged)?;
+        ours.options = options;
+        ours.options.is_redraw_annos_triggered = true;
+        Ok(diffs)
+    }
+
+    /// Images where our bounding box annotations and the ones of another project disagree.
+    pub fn diff_project(
+        &self,
+        tools_data_map: &ToolsDataMap,
+        file_name: &str,
+        iou_threshold: f64,
+    ) -> RvResult<Vec<ImageDiff>> {
+        let empty = || BboxExportData::from_bbox_data(BboxSpecificData::new());
+        let ours = tools_data_map
+            .get(BBOX_NAME)
+            .map(|td| BboxExportData::from_bbox_data(td.specifics.bbox().clone()))
+            .unwrap_or_else(empty);
+        let theirs = self.load_bbox_export_data(file_name)?.unwrap_or_else(empty);
+        merge::diff(ours, theirs, iou_threshold)
+    }
+
+    /// Selects the next image with the review status, returns false if there is none.
+    pub fn select_next_with_status(
+        &mut self,
+        status: ImageStatus,
+        tools_data_map: &ToolsDataMap,
+    ) -> bool {
+        let bbox_data = tools_data_map.get(BBOX_NAME).map(|td| td.specifics.bbox());
+        self.paths_navigator.next_matching(|path| {
+            bbox_data
+                .map(|bbox_data| bbox_data.image_status(path))
+                .unwrap_or_default()
+                == status
+        })
+    }
+
+    /// Claims the image for the configured user if collaboration is configured. Returns the claim
+    /// of another user if the image is already taken. Claiming an image that we have already
+    /// claimed refreshes the timestamp of the claim.
+    pub fn claim_image(&self, file_path: &str) -> RvResult<Option<collab::Claim>> {
+        self.with_claims(|claims_path, user, timeout_secs, now| {
+            collab::claim(claims_path, file_path, user, timeout_secs, now)
+        })
+    }
+
+    /// Returns the claim of another user on the image without claiming it ourselves.
+    pub fn claim_of_other(&self, file_path: &str) -> RvResult<Option<collab::Claim>> {
+        self.with_claims(|claims_path, user, timeout_secs, now| {
+            collab::claim_of_other(claims_path, file_path, user, timeout_secs, now)
+        })
+    }
+
+    fn with_claims(
+        &self,
+        f: impl FnOnce(&Path, &str, u64, u64) -> RvResult<Option<collab::Claim>>,
+    ) -> RvResult<Option<collab::Claim>> {
+        if let Some(collab_cfg) = &self.cfg.collab_cfg {
+            let export_folder = self.cfg.export_folder()?;
+            let prj_path = detail::prj_path(self.opened_folder(), export_folder, &self.cfg);
+            f(
+                &collab::claims_path(&prj_path),
+                &collab_cfg.user,
+                collab_cfg.claim_timeout_secs(),
+                collab::now_secs()?,
+            )
+        } else {
+            Ok(None)
+        }
     }
 
     pub fn new(cfg: Cfg) -> Self {
@@ -245,18 +471,19 @@ impl Control {
         }
     }
 
-    fn make_reader(&mut self, cfg: Cfg) -> RvResult<()> {
+    fn make_reader(&mut self, cfg: Cfg, folder: String) -> RvResult<()> {
         self.paths_navigator = PathsNavigator::new(None, SortType::default())?;
-        self.last_open_folder_job_id = Some(
-            self.tp
-                .apply(Box::new(move || ReaderFromCfg::from_cfg(cfg)))?,
-        );
+        self.last_open_folder_job_id = Some(self.tp.apply(Box::new(move || {
+            let reader = ReaderFromCfg::from_cfg(cfg)?;
+            let selector = reader.open_folder(&folder)?;
+            Ok((reader, selector))
+        }))?);
         Ok(())
     }
 
     pub fn open_folder(&mut self, new_folder: String) -> RvResult<()> {
         println!("new opened folder {new_folder}");
-        self.make_reader(self.cfg.clone())?;
+        self.make_reader(self.cfg.clone(), new_folder.clone())?;
         self.opened_folder = Some(new_folder);
         Ok(())
     }
@@ -274,9 +501,9 @@ impl Control {
             let tp_res = self.tp.result(job_id);
             if let Some(res) = tp_res {
                 self.last_open_folder_job_id = None;
-                res.and_then(|reader| {
+                res.and_then(|(reader, selector)| {
                     self.reader = Some(reader);
-                    self.load_opened_folder_content(sort_type)?;
+                    self.paths_navigator = PathsNavigator::new(Some(selector), sort_type)?;
                     Ok(true)
                 })
             } else {
@@ -444,13 +671,13 @@ impl Control {
 #[cfg(test)]
 use {
     crate::{
+        cfg::CollabCfg,
         defer_file_removal,
         domain::{make_test_bbs, Shape},
         file_util::{make_prjcfg_filename, DEFAULT_TMPDIR},
-        tools::BBOX_NAME,
-        tools_data::{BboxSpecificData, ToolSpecifics, ToolsData},
+        tools_data::{ClassifyToolData, KeypointToolData},
     },
-    std::{collections::HashMap, fs, path::Path, str::FromStr},
+    std::{collections::HashMap, fs, str::FromStr},
 };
 #[cfg(test)]
 pub fn make_data(image_file: &Path) -> ToolsDataMap {
@@ -487,7 +714,14 @@ pub fn make_data(image_file: &Path) -> ToolsDataMap {
 
 #[test]
 fn test_save_load() {
-    let tdm = make_data(&PathBuf::from_str("dummyfile").unwrap());
+    let mut tdm = make_data(&PathBuf::from_str("dummyfile").unwrap());
+    let mut classify_data = ClassifyToolData::default();
+    classify_data.push("day".to_string()).unwrap();
+    classify_data.apply_tag("dummyfile", 0);
+    tdm.insert(
+        CLASSIFY_NAME,
+        ToolsData::new(ToolSpecifics::Classify(classify_data)),
+    );
     let cfg = cfg::get_default_cfg();
     let opened_folder_name = "dummy_opened_folder";
     let export_folder = cfg.tmpdir().unwrap();
@@ -504,3 +738,76 @@ fn test_save_load() {
     assert_eq!(tdm, tdm_imported);
     assert_eq!(cfg, cfg_imported);
 }
+
+#[test]
+fn test_collab_save() -> RvResult<()> {
+    let export_folder = DEFAULT_TMPDIR.join("rvprj_test_collab_save");
+    let make_ctrl = |user: &str| {
+        let mut cfg = cfg::get_default_cfg();
+        cfg.export_folder = Some(export_folder.to_str().unwrap().to_string());
+        cfg.current_prj_name = "collab".to_string();
+        let collab_cfg: CollabCfg =
+            serde_json::from_str(&format!(r#"{{"user": "{user}"}}"#)).unwrap();
+        cfg.collab_cfg = Some(collab_cfg);
+        Control::new(cfg)
+    };
+    let make_tdm = || {
+        let mut classify_data = ClassifyToolData::default();
+        classify_data.push("day".to_string()).unwrap();
+        let mut keypoint_data = KeypointToolData::default();
+        keypoint_data.push_keypoint("nose".to_string()).unwrap();
+        ToolsDataMap::from([
+            (
+                CLASSIFY_NAME,
+                ToolsData::new(ToolSpecifics::Classify(classify_data)),
+            ),
+            (
+                KEYPOINTS_NAME,
+                ToolsData::new(ToolSpecifics::Keypoint(keypoint_data)),
+            ),
+        ])
+    };
+    let add_instance = |tdm: &mut ToolsDataMap, file_path: &str| {
+        tdm.get_mut(KEYPOINTS_NAME)
+            .unwrap()
+            .specifics
+            .keypoint_mut()
+            .get_annos_mut(file_path, Shape::new(10, 10))
+            .add_instance(1);
+    };
+    let mut ctrl_me = make_ctrl("me");
+    let mut ctrl_you = make_ctrl("you");
+    let mut tdm_me = make_tdm();
+    let mut tdm_you = make_tdm();
+    let path = ctrl_me.save(&mut tdm_me)?;
+    defer_file_removal!(&path);
+    ctrl_you.save(&mut tdm_you)?;
+
+    // both annotators work at the same time on different images
+    tdm_me
+        .get_mut(CLASSIFY_NAME)
+        .unwrap()
+        .specifics
+        .classify_mut()
+        .apply_tag("mine.png", 0);
+    tdm_you
+        .get_mut(CLASSIFY_NAME)
+        .unwrap()
+        .specifics
+        .classify_mut()
+        .apply_tag("yours.png", 0);
+    add_instance(&mut tdm_me, "mine.png");
+    add_instance(&mut tdm_you, "yours.png");
+    ctrl_me.save(&mut tdm_me)?;
+    ctrl_you.save(&mut tdm_you)?;
+    assert!(ctrl_you.kept_conflicts.is_empty());
+
+    let (saved, _, _) = detail::read(&path)?;
+    let classify_data = saved[CLASSIFY_NAME].specifics.classify();
+    assert!(classify_data.has_tag("mine.png", 0));
+    assert!(classify_data.has_tag("yours.png", 0));
+    let keypoint_data = saved[KEYPOINTS_NAME].specifics.keypoint();
+    assert!(keypoint_data.get_annos("mine.png").is_some());
+    assert!(keypoint_data.get_annos("yours.png").is_some());
+    Ok(())
+}
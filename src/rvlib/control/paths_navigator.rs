@@ -19,6 +19,18 @@ fn prev(file_selected_idx: usize, files_len: usize) -> usize {
         0
     }
 }
+/// Index of the first path after `selected_idx` that matches, wraps around at the end.
+fn next_matching(
+    selected_idx: Option<usize>,
+    paths: &[&str],
+    pred: impl Fn(&str) -> bool,
+) -> Option<usize> {
+    let start = selected_idx.map(|idx| idx + 1).unwrap_or(0);
+    (0..paths.len())
+        .map(|offset| (start + offset) % paths.len())
+        .find(|idx| Some(*idx) != selected_idx && pred(paths[*idx]))
+}
+
 #[derive(Default)]
 pub struct PathsNavigator {
     file_label_selected_idx: Option<usize>,
@@ -53,6 +65,22 @@ impl PathsNavigator {
     pub fn prev(&mut self) {
         self.pn(prev);
     }
+    /// Selects the next filtered image whose path matches, returns false if there is none.
+    pub fn next_matching(&mut self, pred: impl Fn(&str) -> bool) -> bool {
+        if let Some(ps) = &self.paths_selector {
+            let idx = next_matching(
+                self.file_label_selected_idx,
+                &ps.filtered_file_paths(),
+                pred,
+            );
+            if idx.is_some() {
+                self.file_label_selected_idx = idx;
+                self.scroll_to_selected_label = true;
+                return true;
+            }
+        }
+        false
+    }
     pub fn file_label_selected_idx(&self) -> Option<usize> {
         self.file_label_selected_idx
     }
@@ -215,3 +243,16 @@ fn test_prev_next() {
     assert_eq!(prev(4, 3), 2);
     assert_eq!(prev(9, 3), 2);
 }
+
+#[test]
+fn test_next_matching() {
+    let paths = ["a.png", "b.jpg", "c.png", "d.jpg"];
+    let is_png = |p: &str| p.ends_with("png");
+    assert_eq!(next_matching(Some(0), &paths, is_png), Some(2));
+    // wraps around
+    assert_eq!(next_matching(Some(2), &paths, is_png), Some(0));
+    assert_eq!(next_matching(None, &paths, is_png), Some(0));
+    // the selected image itself is not the next one
+    assert_eq!(next_matching(Some(1), &paths, |p| p == "b.jpg"), None);
+    assert_eq!(next_matching(None, &[], is_png), None);
+}
@@ -379,14 +379,36 @@ impl Polygon {
     pub fn min_enclosing_bb(&self) -> PtI {
         self.enclosing_bb.min()
     }
+    /// In resize mode, points that leave the image are moved to the image border.
     pub fn translate(
         &self,
-        _x: i32,
-        _y: i32,
-        _shape: Shape,
-        _oob_mode: OutOfBoundsMode,
+        x: i32,
+        y: i32,
+        shape: Shape,
+        oob_mode: OutOfBoundsMode,
     ) -> Option<Self> {
-        panic!("not implemented");
+        let shifted = self.points.iter().map(|p| (p.x as i32 + x, p.y as i32 + y));
+        let points = match oob_mode {
+            OutOfBoundsMode::Deny => shifted
+                .map(|(x, y)| {
+                    if x < 0 || y < 0 || x >= shape.w as i32 || y >= shape.h as i32 {
+                        None
+                    } else {
+                        Some(PtI {
+                            x: x as u32,
+                            y: y as u32,
+                        })
+                    }
+                })
+                .collect::<Option<Vec<_>>>()?,
+            OutOfBoundsMode::Resize(_) => shifted
+                .map(|(x, y)| PtI {
+                    x: x.clamp(0, shape.w as i32 - 1) as u32,
+                    y: y.clamp(0, shape.h as i32 - 1) as u32,
+                })
+                .collect(),
+        };
+        Self::from_vec(points, self.is_open).ok()
     }
     pub fn max_squaredist(&self, other: impl Iterator<Item = PtI> + Clone) -> (PtI, PtI, i64) {
         max_squaredist(self.points_iter(), other)
@@ -484,8 +506,18 @@ impl Polygon {
     pub fn points(&self) -> &Vec<PtI> {
         &self.points
     }
-    /// We will need this as soon as we support polygons
-    fn from_vec(points: Vec<PtI>, is_open: bool) -> RvResult<Self> {
+    /// Area enclosed by the vertices computed with the shoelace formula
+    pub fn area(&self) -> f64 {
+        let n = self.points.len();
+        let twice_area = (0..n)
+            .map(|i| {
+                let (p, q) = (self.points[i], self.points[(i + 1) % n]);
+                p.x as f64 * q.y as f64 - q.x as f64 * p.y as f64
+            })
+            .sum::<f64>();
+        twice_area.abs() / 2.0
+    }
+    pub fn from_vec(points: Vec<PtI>, is_open: bool) -> RvResult<Self> {
         let enclosing_bb = BB::from_vec(&points)?;
         Ok(Self {
             points,
@@ -510,6 +542,62 @@ pub enum OutOfBoundsMode {
     Resize(Shape), // minimal area the box needs to keep
 }
 
+/// Box with sub-pixel precision as used by exchange formats like Coco
+#[derive(Clone, Copy, Debug, PartialEq, Default, Serialize, Deserialize)]
+pub struct BbF {
+    pub x: f64,
+    pub y: f64,
+    pub w: f64,
+    pub h: f64,
+}
+/// Coordinates are validated to be finite when read.
+impl Eq for BbF {}
+impl BbF {
+    pub fn from_arr(a: &[f64; 4]) -> Self {
+        BbF {
+            x: a[0],
+            y: a[1],
+            w: a[2],
+            h: a[3],
+        }
+    }
+    pub fn to_arr(&self) -> [f64; 4] {
+        [self.x, self.y, self.w, self.h]
+    }
+    pub fn scale(&self, x_factor: f64, y_factor: f64) -> Self {
+        BbF {
+            x: self.x * x_factor,
+            y: self.y * y_factor,
+            w: self.w * x_factor,
+            h: self.h * y_factor,
+        }
+    }
+    /// Pixel box between the rounded corners such that rounding errors of position and size do
+    /// not add up
+    pub fn round(&self) -> BB {
+        let x_min = self.x.round().max(0.0);
+        let y_min = self.y.round().max(0.0);
+        let x_max = (self.x + self.w).round().max(x_min);
+        let y_max = (self.y + self.h).round().max(y_min);
+        BB::from_arr(&[
+            x_min as u32,
+            y_min as u32,
+            (x_max - x_min) as u32,
+            (y_max - y_min) as u32,
+        ])
+    }
+}
+impl From<BB> for BbF {
+    fn from(bb: BB) -> Self {
+        BbF {
+            x: bb.x as f64,
+            y: bb.y as f64,
+            w: bb.w as f64,
+            h: bb.h as f64,
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, Default)]
 pub struct BB {
     pub x: u32,
@@ -594,6 +682,22 @@ impl BB {
         )
     }
 
+    /// Intersection over union, 0 for disjoint boxes
+    pub fn iou(&self, other: &BB) -> f64 {
+        let x_min = self.x.max(other.x);
+        let y_min = self.y.max(other.y);
+        let x_max = self.x_max().min(other.x_max());
+        let y_max = self.y_max().min(other.y_max());
+        if x_min >= x_max || y_min >= y_max {
+            0.0
+        } else {
+            let intersection = (x_max - x_min) as f64 * (y_max - y_min) as f64;
+            let union =
+                self.w as f64 * self.h as f64 + other.w as f64 * other.h as f64 - intersection;
+            intersection / union
+        }
+    }
+
     pub fn intersect_or_self(&self, other: Option<BB>) -> BB {
         if let Some(other) = other {
             self.intersect(other)
@@ -1020,6 +1124,17 @@ fn test_polygon() {
     assert_eq!(poly.enclosing_bb(), ebb);
 }
 
+#[test]
+fn test_iou() {
+    let bb = BB::from_arr(&[0, 0, 10, 10]);
+    assert!((bb.iou(&bb) - 1.0).abs() < 1e-12);
+    assert!((bb.iou(&BB::from_arr(&[5, 0, 10, 10])) - 50.0 / 150.0).abs() < 1e-12);
+    assert_eq!(bb.iou(&BB::from_arr(&[10, 0, 10, 10])), 0.0);
+    let large = BB::from_arr(&[0, 0, 100_000, 100_000]);
+    let half = BB::from_arr(&[0, 0, 50_000, 100_000]);
+    assert!((large.iou(&half) - 0.5).abs() < 1e-12);
+}
+
 #[test]
 fn test_zb() {
     fn test(zb: Option<BB>, y_delta: f32, reference_coords: &[u32; 4]) {
@@ -1189,6 +1304,42 @@ fn test_poly_triangle() {
     assert!(poly.contains(PtF::from((6.0, 6.01))));
 }
 #[test]
+fn test_poly_translate() {
+    let poly =
+        Polygon::from_vec(vec![(5, 5).into(), (10, 10).into(), (5, 10).into()], false).unwrap();
+    let shape = Shape::new(12, 12);
+    let moved = poly.translate(1, -2, shape, OutOfBoundsMode::Deny).unwrap();
+    assert_eq!(moved.points()[0], PtI::from((6, 3)));
+    assert_eq!(moved.enclosing_bb(), BB::from_arr(&[6, 3, 5, 5]));
+    assert!(poly.translate(2, 0, shape, OutOfBoundsMode::Deny).is_none());
+    let moved = poly
+        .translate(2, 0, shape, OutOfBoundsMode::Resize(Shape::new(1, 1)))
+        .unwrap();
+    assert_eq!(moved.points()[1], PtI::from((11, 10)));
+}
+#[test]
+fn test_bbf() {
+    let bbf = BbF::from_arr(&[0.4, 1.6, 2.2, 2.2]);
+    // the box covers 0.4 to 2.6 and 1.6 to 3.8
+    assert_eq!(bbf.round(), BB::from_arr(&[0, 2, 3, 2]));
+    let bbf = BbF::from_arr(&[0.91, 0.87, 0.15, 0.15]).scale(100.0, 1000.0);
+    assert_eq!(bbf.round(), BB::from_arr(&[91, 870, 15, 150]));
+    let bb = BB::from_arr(&[3, 4, 5, 6]);
+    assert_eq!(BbF::from(bb).round(), bb);
+}
+#[test]
+fn test_poly_area() {
+    let poly =
+        Polygon::from_vec(vec![(5, 5).into(), (15, 5).into(), (5, 10).into()], false).unwrap();
+    assert_eq!(poly.area(), 25.0);
+    let poly = Polygon::from_vec(
+        vec![(0, 0).into(), (0, 4).into(), (4, 4).into(), (4, 0).into()],
+        false,
+    )
+    .unwrap();
+    assert_eq!(poly.area(), 16.0);
+}
+#[test]
 fn test_poly_intersect() {
     let poly =
         Polygon::from_vec(vec![(5, 5).into(), (10, 10).into(), (5, 10).into()], false).unwrap();
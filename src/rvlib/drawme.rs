@@ -1,10 +1,12 @@
-use crate::{domain::BB, types::ViewImage, GeoFig};
+use crate::{annotations::KeypointInstance, domain::BB, types::ViewImage, GeoFig};
 use std::default::Default;
 
 #[derive(Clone, Debug)]
 pub struct Stroke {
     pub thickness: f32,
     pub color: [u8; 3],
+    /// e.g., for predictions that have not been reviewed
+    pub is_dashed: bool,
 }
 
 impl Stroke {
@@ -12,12 +14,13 @@ impl Stroke {
         Stroke {
             thickness: 2.0,
             color,
+            is_dashed: false,
         }
     }
 }
 
 #[derive(Clone, Debug)]
-pub struct Annotation {
+pub struct BboxAnnotation {
     pub geofig: GeoFig,
     pub fill_color: Option<[u8; 3]>,
     pub fill_alpha: u8,
@@ -27,6 +30,21 @@ pub struct Annotation {
     pub is_selected: Option<bool>,
 }
 
+/// Keypoints of one instance connected by the edges of the skeleton
+#[derive(Clone, Debug)]
+pub struct KeypointsAnnotation {
+    pub keypoints: KeypointInstance,
+    pub edges: Vec<(usize, usize)>,
+    pub color: [u8; 3],
+    pub is_selected: bool,
+}
+
+#[derive(Clone, Debug)]
+pub enum Annotation {
+    Bbox(BboxAnnotation),
+    Keypoints(KeypointsAnnotation),
+}
+
 #[derive(Clone, Debug, Default)]
 pub enum Update<T> {
     Yes(T),
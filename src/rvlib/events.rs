@@ -1,4 +1,11 @@
-use crate::domain::PtF;
+use std::str::FromStr;
+
+use crate::{
+    domain::PtF,
+    keymap::{KeyAction, KeyChord, Keymap},
+    result::{RvError, RvResult},
+    rverr,
+};
 
 macro_rules! action_keycode {
     ($name:ident, $action:ident, $key_code:ident) => {
@@ -31,6 +38,8 @@ macro_rules! action {
 #[derive(Debug, Clone, Default)]
 pub struct Events {
     events: Vec<Event>,
+    actions: Vec<KeyAction>,
+    released_actions: Vec<KeyAction>,
     pub mouse_pos: Option<PtF>,
 }
 
@@ -43,6 +52,53 @@ impl Events {
         self.events.append(&mut events);
         self
     }
+    /// Resolves the actions whose key chords have been triggered
+    pub fn actions(mut self, keymap: &Keymap) -> Self {
+        self.actions = keymap.triggered_actions(&self);
+        self.released_actions = keymap.released_actions(&self);
+        self
+    }
+    pub fn triggered(&self, action: KeyAction) -> bool {
+        self.actions.contains(&action)
+    }
+    pub fn triggered_actions(&self) -> &[KeyAction] {
+        &self.actions
+    }
+    /// The key of a repeated action has been released
+    pub fn released_action(&self, action: KeyAction) -> bool {
+        self.released_actions.contains(&action)
+    }
+    pub fn released_actions(&self) -> &[KeyAction] {
+        &self.released_actions
+    }
+    /// Index of the label whose key chord has been triggered
+    pub fn triggered_label(&self) -> Option<usize> {
+        self.actions.iter().find_map(|action| match action {
+            KeyAction::Label(idx) => Some(*idx),
+            _ => None,
+        })
+    }
+    /// The key has been pressed and exactly the modifiers of the chord are held.
+    pub fn pressed_chord(&self, chord: &KeyChord) -> bool {
+        self.pressed(chord.key)
+            && self.held_ctrl() == chord.ctrl
+            && self.held_alt() == chord.alt
+            && self.held_shift() == chord.shift
+    }
+    /// The key is held and exactly the modifiers of the chord are held.
+    pub fn held_chord(&self, chord: &KeyChord) -> bool {
+        self.held(chord.key)
+            && self.held_ctrl() == chord.ctrl
+            && self.held_alt() == chord.alt
+            && self.held_shift() == chord.shift
+    }
+    /// The key is released and exactly the modifiers of the chord are held.
+    pub fn released_chord(&self, chord: &KeyChord) -> bool {
+        self.released(chord.key)
+            && self.held_ctrl() == chord.ctrl
+            && self.held_alt() == chord.alt
+            && self.held_shift() == chord.shift
+    }
     action_keycode!(held_alt, Held, Alt);
     action_keycode!(held_shift, Held, Shift);
     action_keycode!(held_ctrl, Held, Ctrl);
@@ -51,51 +107,39 @@ impl Events {
     action!(released, Released);
 }
 
-#[derive(PartialEq, Debug, Clone, Copy)]
-pub enum KeyCode {
-    A,
-    B,
-    C,
-    D,
-    L,
-    H,
-    M,
-    Q,
-    R,
-    T,
-    V,
-    Y,
-    Z,
-    Key0,
-    Key1,
-    Key2,
-    Key3,
-    Key4,
-    Key5,
-    Key6,
-    Key7,
-    Key8,
-    Key9,
-    PlusEquals,
-    Minus,
-    Delete,
-    Back,
-    Left,
-    Right,
-    Up,
-    Down,
-    F5,
-    PageDown,
-    PageUp,
-    Alt,
-    Ctrl,
-    Shift,
-    Escape,
-    MouseLeft,
-    MouseRight,
-    DontCare,
+macro_rules! key_codes {
+    ($($key:ident),*) => {
+        #[derive(PartialEq, Eq, Hash, Debug, Clone, Copy)]
+        pub enum KeyCode {
+            $($key,)*
+        }
+        impl KeyCode {
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(Self::$key => stringify!($key),)*
+                }
+            }
+        }
+        impl FromStr for KeyCode {
+            type Err = RvError;
+            fn from_str(s: &str) -> RvResult<Self> {
+                match s {
+                    $(stringify!($key) => Ok(Self::$key),)*
+                    _ => Err(rverr!("unknown key {}", s)),
+                }
+            }
+        }
+    };
 }
 
+key_codes!(
+    A, B, C, D, E, F, G, H, I, J, K, L, M, N, O, P, Q, R, S, T, U, V, W, X, Y, Z, Key0, Key1, Key2,
+    Key3, Key4, Key5, Key6, Key7, Key8, Key9, F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    F13, F14, F15, F16, F17, F18, F19, F20, PlusEquals, Minus, Delete, Back, Tab, Enter, Space,
+    Insert, Home, End, Left, Right, Up, Down, PageDown, PageUp, Alt, Ctrl, Shift, Escape,
+    MouseLeft, MouseRight, DontCare
+);
+
 #[derive(Debug, Clone, Copy)]
 pub enum Event {
     Pressed(KeyCode),
@@ -1,4 +1,5 @@
 use std::{
+    collections::HashSet,
     ffi::OsStr,
     fmt::Debug,
     fs, io,
@@ -13,7 +14,7 @@ use crate::{
 };
 use crate::{
     result::{to_rv, RvResult},
-    tools_data::BboxExportData,
+    tools_data::{BboxExportData, ClassifyToolData, KeypointToolData},
 };
 use lazy_static::lazy_static;
 use serde::{Deserialize, Serialize};
@@ -139,6 +140,10 @@ pub fn filename_to_prjname(filename: &str) -> RvResult<&str> {
 pub struct ExportData {
     pub opened_folder: Option<String>,
     pub bbox_data: Option<BboxExportData>,
+    #[serde(default)]
+    pub classify_data: Option<ClassifyToolData>,
+    #[serde(default)]
+    pub keypoint_data: Option<KeypointToolData>,
     pub cfg: Cfg,
 }
 
@@ -300,6 +305,43 @@ pub fn get_last_part_of_path(path: &str) -> Option<LastPartOfPath> {
     }
 }
 
+fn path_parts(path: &str) -> Vec<&str> {
+    path.split(['/', '\\']).filter(|p| !p.is_empty()).collect()
+}
+
+/// Names of the paths relative to the folder they have in common with separators and dots
+/// replaced by `_`, e.g., `a_im_png` and `b_im_jpg` for `x/a/im.png` and `x/b/im.jpg`. In contrast
+/// to file stems, the names do not collide when files from different folders are collected in one
+/// folder. The last part of each path is always kept. Names that still collide, e.g., for
+/// `x/a/im.png` and `x/a_im.png`, get a numeric suffix in the order of the paths.
+pub fn flat_names(paths: &[&str]) -> Vec<String> {
+    let parts = paths.iter().map(|p| path_parts(p)).collect::<Vec<_>>();
+    let n_common = match parts.split_first() {
+        Some((first, rest)) => {
+            let n_min = parts.iter().map(|p| p.len()).min().unwrap_or(0);
+            (0..n_min.saturating_sub(1))
+                .take_while(|i| rest.iter().all(|p| p[*i] == first[*i]))
+                .count()
+        }
+        None => 0,
+    };
+    let mut used = HashSet::new();
+    parts
+        .iter()
+        .map(|p| {
+            let name = p[n_common..].join("_").replace(['.', ':'], "_");
+            let mut unique = name.clone();
+            let mut suffix = 2;
+            while used.contains(&unique) {
+                unique = format!("{name}_{suffix}");
+                suffix += 1;
+            }
+            used.insert(unique.clone());
+            unique
+        })
+        .collect()
+}
+
 pub fn local_file_info<P>(p: P) -> String
 where
     P: AsRef<Path>,
@@ -318,6 +360,24 @@ where
         .unwrap_or_else(|_| "".to_string())
 }
 
+#[test]
+fn test_flat_names() {
+    assert_eq!(
+        flat_names(&["x/a/im.png", "x/b/im.png", "x/b/im.jpg"]),
+        vec!["a_im_png", "b_im_png", "b_im_jpg"]
+    );
+    assert_eq!(flat_names(&["/x/a/im.png"]), vec!["im_png"]);
+    assert_eq!(
+        flat_names(&["C:\\x\\im.png", "C:\\im.png"]),
+        vec!["x_im_png", "im_png"]
+    );
+    assert!(flat_names(&[]).is_empty());
+    assert_eq!(
+        flat_names(&["x/a/im.png", "x/a_im.png", "x/a/im_png", "x/a_im_png_2"]),
+        vec!["a_im_png", "a_im_png_2", "a_im_png_3", "a_im_png_2_2"]
+    );
+}
+
 #[test]
 fn get_last_part() {
     let path = "http://localhost:8000/a/21%20%20b/Beg.png";
@@ -28,6 +28,7 @@ impl Record {
 pub struct History {
     records: Vec<Record>,
     current_idx: Option<usize>,
+    change_actors: Vec<&'static str>,
 }
 
 impl History {
@@ -51,8 +52,15 @@ impl History {
         self.current_idx.map(|idx| self.records[idx].clone())
     }
 
+    /// Actors of the pushes, undos, and redos since the last call. An undo or redo is attributed
+    /// to the actor of the change that is undone or redone.
+    pub fn take_change_actors(&mut self) -> Vec<&'static str> {
+        std::mem::take(&mut self.change_actors)
+    }
+
     pub fn push(&mut self, record: Record) {
         self.clear_on_folder_change(&record.folder_label);
+        self.change_actors.push(record.actor);
         match self.current_idx {
             None => {
                 self.current_idx = Some(0);
@@ -85,6 +93,9 @@ impl History {
         match self.current_idx {
             Some(idx) if pred(idx) => {
                 self.current_idx = Some(idx_change(idx));
+                // the later of both records contains the change
+                let idx_changed = idx.max(idx_change(idx));
+                self.change_actors.push(self.records[idx_changed].actor);
                 Some(
                     self.records[idx_change(idx)]
                         .clone()
@@ -164,6 +175,10 @@ fn test_history() -> RvResult<()> {
     assert_eq!(hist.records[0].data.shape().w, 64);
     assert_eq!(hist.records[1].data.shape().w, 32);
     hist.prev_world(&None);
+    assert_eq!(hist.take_change_actors(), vec!["", "", ""]);
+    // nothing to undo
+    hist.prev_world(&None);
+    assert!(hist.take_change_actors().is_empty());
     let world = World::from_real_im(
         DynamicImage::ImageRgb8(ViewImage::new(16, 16)),
         HashMap::new(),
@@ -213,3 +228,21 @@ fn test_history() -> RvResult<()> {
 
     Ok(())
 }
+
+#[test]
+fn test_change_actors() {
+    let im = ViewImage::new(8, 8);
+    let world = World::from_real_im(DynamicImage::ImageRgb8(im), HashMap::new(), "".to_string());
+    let mut hist = History::default();
+    hist.push(Record::new(world.data.clone(), "load"));
+    hist.push(Record::new(world.data.clone(), "bbox"));
+    assert_eq!(hist.take_change_actors(), vec!["load", "bbox"]);
+    assert!(hist.take_change_actors().is_empty());
+    // undo and redo are attributed to the change of the bbox tool
+    hist.prev_world(&None);
+    hist.next_world(&None);
+    assert_eq!(hist.take_change_actors(), vec!["bbox", "bbox"]);
+    // nothing to redo
+    hist.next_world(&None);
+    assert!(hist.take_change_actors().is_empty());
+}
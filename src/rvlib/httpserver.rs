@@ -1,164 +1,646 @@
-use httparse::{Request, EMPTY_HEADER};
+use httparse::{Request, Status, EMPTY_HEADER};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
 use std::{
-    fmt::Debug,
-    io::{prelude::*, Read},
-    net::TcpListener,
-    str,
-    sync::mpsc::{self, Receiver, Sender},
+    fmt::{Debug, Display},
+    io::{prelude::*, ErrorKind, Read},
+    net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream},
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        Arc, Mutex,
+    },
     thread::{self, JoinHandle},
+    time::Duration,
 };
 
 use crate::{
+    domain::Shape,
+    inference::RawPrediction,
     result::{to_rv, RvResult},
     rverr,
+    tools_data::BboxSpecificData,
+    GeoFig,
 };
 
+/// Time the server waits for the main loop to answer an api request
+const RESPONSE_TIMEOUT: Duration = Duration::from_secs(5);
+/// Connections without new requests for this time are closed
+const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_HEADER_BYTES: usize = 64 * 1024;
+const MAX_BODY_BYTES: usize = 64 * 1024 * 1024;
+/// Event streams without events get a comment in this interval
+const EVENT_PING_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Annotation of the api, boxes are `[x, y, w, h]` and polygons are lists of `[x, y]` in pixels
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct ApiAnnotation {
+    pub label: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bbox: Option<[f64; 4]>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub polygon: Option<Vec<[f64; 2]>>,
+}
+impl ApiAnnotation {
+    fn from_geo(geo: &GeoFig, label: &str) -> Self {
+        let (bbox, polygon) = match geo {
+            GeoFig::BB(bb) => (
+                Some([bb.x as f64, bb.y as f64, bb.w as f64, bb.h as f64]),
+                None,
+            ),
+            GeoFig::Poly(poly) => (
+                None,
+                Some(
+                    poly.points_iter()
+                        .map(|p| [p.x as f64, p.y as f64])
+                        .collect(),
+                ),
+            ),
+        };
+        Self {
+            label: label.to_string(),
+            bbox,
+            polygon,
+        }
+    }
+    fn to_geo(&self, shape: Shape) -> Option<GeoFig> {
+        RawPrediction {
+            label: self.label.clone(),
+            score: 1.0,
+            bbox: self.bbox,
+            polygon: self.polygon.clone(),
+        }
+        .to_geo(shape)
+    }
+}
+
+/// Annotations of one image, the shape is needed to set annotations of images that have not
+/// been annotated or shown before
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq)]
+pub struct ApiAnnotations {
+    pub annotations: Vec<ApiAnnotation>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+#[derive(Deserialize)]
+struct LabelBody {
+    label: String,
+}
+
+pub fn get_annotations(bbox_data: &BboxSpecificData, file_path: &str) -> ApiAnnotations {
+    match bbox_data.annotations_map().get(file_path) {
+        Some((annos, shape)) => ApiAnnotations {
+            annotations: annos
+                .geos()
+                .iter()
+                .zip(annos.cat_idxs().iter())
+                .map(|(geo, cat_idx)| ApiAnnotation::from_geo(geo, &bbox_data.labels()[*cat_idx]))
+                .collect(),
+            width: Some(shape.w),
+            height: Some(shape.h),
+        },
+        None => ApiAnnotations::default(),
+    }
+}
+
+/// Replaces the annotations of the image. Unknown labels are added unless the labels are locked.
+/// Nothing is changed if one of the annotations is invalid. Returns the number of annotations.
+pub fn set_annotations(
+    bbox_data: &mut BboxSpecificData,
+    file_path: &str,
+    shape: Shape,
+    annotations: &[ApiAnnotation],
+) -> RvResult<usize> {
+    let mut geos = vec![];
+    for (idx, anno) in annotations.iter().enumerate() {
+        let geo = anno
+            .to_geo(shape)
+            .ok_or_else(|| rverr!("annotation {} has no valid bbox or polygon", idx))?;
+        if bbox_data.is_locked() && !bbox_data.labels().contains(&anno.label) {
+            return Err(rverr!("labels are locked, cannot add '{}'", anno.label));
+        }
+        geos.push(geo);
+    }
+    let mut cat_idxs = vec![];
+    for anno in annotations {
+        let cat_idx = match bbox_data.labels().iter().position(|l| *l == anno.label) {
+            Some(cat_idx) => cat_idx,
+            None => {
+                bbox_data.push(anno.label.clone(), None, None)?;
+                bbox_data.labels().len() - 1
+            }
+        };
+        cat_idxs.push(cat_idx);
+    }
+    let annos = bbox_data.get_annos_mut(file_path, shape);
+    annos.clear();
+    for (geo, cat_idx) in geos.into_iter().zip(cat_idxs) {
+        annos.add_geo(geo, cat_idx);
+    }
+    Ok(annotations.len())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ApiRequest {
+    /// `GET /<file-label>`, selects the file
+    SelectFile(String),
+    /// `GET /api/files`
+    ListFiles,
+    /// `GET /api/current`, index and path of the selected image
+    CurrentImage,
+    /// `GET /api/annotations?path=<path>`, the selected image if no path is passed
+    GetAnnotations(Option<String>),
+    /// `PUT /api/annotations?path=<path>` with [`ApiAnnotations`] as body
+    SetAnnotations {
+        path: Option<String>,
+        annotations: ApiAnnotations,
+    },
+    /// `PUT /api/label` with `{"label": <label>}` as body
+    SetLabel(String),
+    /// `POST /api/save`, saves the project
+    Save,
+    /// `POST /api/export`, exports the annotations in the Coco format
+    ExportCoco,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ApiResponse {
+    pub status: u16,
+    pub body: Value,
+}
+impl ApiResponse {
+    pub fn ok(body: Value) -> Self {
+        Self { status: 200, body }
+    }
+    pub fn error(status: u16, msg: impl Display) -> Self {
+        Self {
+            status,
+            body: json!({ "error": msg.to_string() }),
+        }
+    }
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            408 => "Request Timeout",
+            413 => "Payload Too Large",
+            431 => "Request Header Fields Too Large",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            503 => "Service Unavailable",
+            _ => "Unknown",
+        }
+    }
+    fn to_http(&self, keep_alive: bool) -> String {
+        let body = self.body.to_string();
+        let authenticate = if self.status == 401 {
+            "WWW-Authenticate: Bearer\r\n"
+        } else {
+            ""
+        };
+        format!(
+            "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: {}\r\n{authenticate}\r\n{}",
+            self.status,
+            self.reason(),
+            body.len(),
+            if keep_alive { "keep-alive" } else { "close" },
+            body
+        )
+    }
+}
+
+/// Request that is forwarded to the main loop, the server waits for the response
+#[derive(Debug)]
+pub struct ApiCall {
+    pub request: ApiRequest,
+    tx_response: Sender<ApiResponse>,
+}
+impl ApiCall {
+    pub fn respond(self, response: ApiResponse) {
+        // the server has stopped waiting if sending fails
+        self.tx_response.send(response).ok();
+    }
+}
+
+/// Event of the stream `GET /api/events`, sent as server-sent event with the name of the variant
+/// in snake case as event name and the fields as JSON data
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum ServerEvent {
+    ImageSelected {
+        index: Option<usize>,
+        path: Option<String>,
+    },
+    AnnotationsChanged {
+        tool: String,
+        path: Option<String>,
+    },
+    ProjectSaved {
+        path: PathBuf,
+    },
+}
+impl ServerEvent {
+    fn name(&self) -> &'static str {
+        match self {
+            Self::ImageSelected { .. } => "image_selected",
+            Self::AnnotationsChanged { .. } => "annotations_changed",
+            Self::ProjectSaved { .. } => "project_saved",
+        }
+    }
+    fn to_sse(&self) -> String {
+        let data = serde_json::to_string(self).unwrap_or_else(|_| "{}".to_string());
+        format!("event: {}\ndata: {data}\n\n", self.name())
+    }
+}
+
+/// Distributes events to the connections of the event stream
+#[derive(Clone, Debug, Default)]
+pub struct EventHub {
+    subscribers: Arc<Mutex<Vec<Sender<ServerEvent>>>>,
+}
+impl EventHub {
+    fn subscribe(&self) -> Receiver<ServerEvent> {
+        let (tx, rx) = mpsc::channel();
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.push(tx);
+        }
+        rx
+    }
+    /// Subscribers of closed connections are removed.
+    pub fn publish(&self, event: ServerEvent) {
+        if let Ok(mut subscribers) = self.subscribers.lock() {
+            subscribers.retain(|tx| tx.send(event.clone()).is_ok());
+        }
+    }
+    pub fn n_subscribers(&self) -> usize {
+        self.subscribers.lock().map(|s| s.len()).unwrap_or(0)
+    }
+}
+
 #[derive(Debug, PartialEq)]
 enum HandleResult {
-    Path(String),
+    Api(ApiRequest),
+    /// `GET /api/events`, stream of [`ServerEvent`]s
+    Events,
     Terminate,
 }
 
-fn handle_connection(buffer: &[u8]) -> RvResult<HandleResult> {
-    let mut headers = [EMPTY_HEADER; 64];
+fn decode(s: &str) -> Result<String, ApiResponse> {
+    percent_encoding::percent_decode_str(s)
+        .decode_utf8()
+        .map(|s| s.to_string())
+        .map_err(|e| ApiResponse::error(400, format!("invalid encoding of {s}, {e:?}")))
+}
+
+fn query_param(query: Option<&str>, key: &str) -> Result<Option<String>, ApiResponse> {
+    query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .filter_map(|kv| kv.split_once('='))
+        .find(|(k, _)| *k == key)
+        .map(|(_, v)| decode(v))
+        .transpose()
+}
+
+fn parse_body<'a, T: Deserialize<'a>>(body: &'a [u8]) -> Result<T, ApiResponse> {
+    serde_json::from_slice(body).map_err(|e| ApiResponse::error(400, format!("invalid body, {e}")))
+}
+
+fn route(method: &str, path: &str, body: &[u8]) -> Result<HandleResult, ApiResponse> {
+    let (path, query) = match path.split_once('?') {
+        Some((path, query)) => (path, Some(query)),
+        None => (path, None),
+    };
+    let request = match (method, path) {
+        (_, "/TERMINATE") => return Ok(HandleResult::Terminate),
+        ("GET", "/api/files") => ApiRequest::ListFiles,
+        ("GET", "/api/current") => ApiRequest::CurrentImage,
+        ("GET", "/api/annotations") => ApiRequest::GetAnnotations(query_param(query, "path")?),
+        ("PUT", "/api/annotations") => ApiRequest::SetAnnotations {
+            path: query_param(query, "path")?,
+            annotations: parse_body(body)?,
+        },
+        ("PUT", "/api/label") => ApiRequest::SetLabel(parse_body::<LabelBody>(body)?.label),
+        ("POST", "/api/save") => ApiRequest::Save,
+        ("POST", "/api/export") => ApiRequest::ExportCoco,
+        ("GET", "/api/events") => return Ok(HandleResult::Events),
+        (
+            _,
+            "/api/files" | "/api/current" | "/api/annotations" | "/api/label" | "/api/save"
+            | "/api/export" | "/api/events",
+        ) => {
+            return Err(ApiResponse::error(
+                405,
+                format!("method {method} not allowed for {path}"),
+            ))
+        }
+        (_, p) if p.starts_with("/api/") => {
+            return Err(ApiResponse::error(404, format!("unknown endpoint {p}")))
+        }
+        ("GET", p) => ApiRequest::SelectFile(decode(&p[1..])?),
+        (m, p) => {
+            return Err(ApiResponse::error(
+                405,
+                format!("method {m} not allowed for {p}"),
+            ))
+        }
+    };
+    Ok(HandleResult::Api(request))
+}
 
+/// Request as read from the connection
+#[derive(Debug, PartialEq)]
+struct HttpRequest {
+    method: String,
+    path: String,
+    authorization: Option<String>,
+    keep_alive: bool,
+    body: Vec<u8>,
+}
+
+/// Parses the first request in the buffer. Returns `None` if the request is not complete yet and
+/// otherwise the request and the number of bytes it occupies in the buffer.
+fn parse_request(buffer: &[u8]) -> Result<Option<(HttpRequest, usize)>, ApiResponse> {
+    let mut headers = [EMPTY_HEADER; 64];
     let mut req = Request::new(&mut headers);
-    let res = req.parse(buffer);
-    res.map_err(to_rv)?;
-    match req.path {
-        Some(p) => Ok(if p == "/TERMINATE" {
-            HandleResult::Terminate
-        } else {
-            HandleResult::Path(
-                percent_encoding::percent_decode_str(&p[1..])
-                    .decode_utf8()
-                    .map_err(to_rv)?
-                    .to_string(),
-            )
-        }),
-        None => {
-            let msg = "could not find path in stream";
-            match str::from_utf8(buffer) {
-                Ok(b) => Err(rverr!("{} '{}'", msg, b)),
-                Err(e) => Err(rverr!("{}, {:?}", msg, e)),
-            }
+    let header_len = match req
+        .parse(buffer)
+        .map_err(|e| ApiResponse::error(400, format!("invalid request, {e:?}")))?
+    {
+        Status::Complete(header_len) => header_len,
+        Status::Partial if buffer.len() > MAX_HEADER_BYTES => {
+            return Err(ApiResponse::error(431, "request header too large"))
         }
+        Status::Partial => return Ok(None),
+    };
+    let header = |name: &str| {
+        req.headers
+            .iter()
+            .find(|h| h.name.eq_ignore_ascii_case(name))
+            .map(|h| String::from_utf8_lossy(h.value).trim().to_string())
+    };
+    if header("transfer-encoding").is_some() {
+        return Err(ApiResponse::error(
+            501,
+            "transfer encodings are not supported, pass a Content-Length",
+        ));
+    }
+    let content_length = match header("content-length") {
+        Some(cl) => cl
+            .parse::<usize>()
+            .map_err(|_| ApiResponse::error(400, format!("invalid Content-Length {cl}")))?,
+        None => 0,
+    };
+    if content_length > MAX_BODY_BYTES {
+        return Err(ApiResponse::error(
+            413,
+            format!("body larger than {MAX_BODY_BYTES} bytes"),
+        ));
+    }
+    let request_len = header_len + content_length;
+    if buffer.len() < request_len {
+        return Ok(None);
     }
+    let connection = header("connection").map(|c| c.to_ascii_lowercase());
+    // HTTP/1.1 keeps connections alive by default, HTTP/1.0 only on request
+    let keep_alive = match req.version {
+        Some(1) => connection.as_deref() != Some("close"),
+        _ => connection.as_deref() == Some("keep-alive"),
+    };
+    let request = HttpRequest {
+        method: req.method.unwrap_or_default().to_string(),
+        path: req.path.unwrap_or_default().to_string(),
+        authorization: header("authorization"),
+        keep_alive,
+        body: buffer[header_len..request_len].to_vec(),
+    };
+    Ok(Some((request, request_len)))
 }
 
-fn to_rv_or_send<T, E>(tx: &Sender<RvResult<String>>, x: Result<T, E>) -> RvResult<T>
-where
-    E: Debug,
-{
-    match x {
-        Ok(r) => Ok(r),
-        Err(e) => {
-            let error_str = format!("{e:?}");
-            match tx.send(Err(to_rv(e))) {
-                Ok(()) => Err(rverr!("error in http server, {}", error_str)),
-                Err(e) => Err(rverr!("error {}, send error {:?}", error_str, e)),
+/// Reads until the buffer contains a complete request. Bytes after the request stay in the
+/// buffer for the next call. Returns `None` if the client closed the connection or was idle too
+/// long between requests.
+fn read_request(
+    stream: &mut impl Read,
+    buffer: &mut Vec<u8>,
+) -> Result<Option<HttpRequest>, ApiResponse> {
+    let mut chunk = [0; 4096];
+    loop {
+        if let Some((request, request_len)) = parse_request(buffer)? {
+            buffer.drain(..request_len);
+            return Ok(Some(request));
+        }
+        match stream.read(&mut chunk) {
+            Ok(0) if buffer.is_empty() => return Ok(None),
+            Ok(0) => {
+                return Err(ApiResponse::error(
+                    400,
+                    "connection closed before the request was complete",
+                ))
             }
+            Ok(n_read) => buffer.extend_from_slice(&chunk[..n_read]),
+            Err(e) if e.kind() == ErrorKind::Interrupted => (),
+            Err(_) if buffer.is_empty() => return Ok(None),
+            Err(e) => return Err(ApiResponse::error(408, format!("incomplete request, {e}"))),
         }
     }
 }
-pub type LaunchResultType = RvResult<(JoinHandle<RvResult<()>>, Receiver<RvResult<String>>)>;
-pub fn launch(address: String) -> LaunchResultType {
-    println!("spawning httpserver at {address}");
-    let (tx_from_server, rx_from_server) = mpsc::channel();
-    let handle = thread::spawn(move || -> RvResult<()> {
-        let bind_result = TcpListener::bind(address);
-        let listener = to_rv_or_send(&tx_from_server, bind_result)?;
-        let mut buffer = vec![0; 4096];
-        // since the requests will only change the shown image, they will be handled sequentially
-        for stream in listener.incoming() {
-            let stream_processing_result: RvResult<HandleResult> = {
-                let mut stream = stream.map_err(to_rv)?;
-                stream.read(&mut buffer).map_err(to_rv)?;
-                // we don't care about headers and bodies and strip everything after the first CRLF
-                let buffer_slice = if let Some(idx) =
-                    (0..(buffer.len() - 1)).find(|idx| &buffer[*idx..*idx + 2] == b"\r\n")
-                {
-                    &buffer[..idx + 2]
+
+fn is_authorized(request: &HttpRequest, token: Option<&str>) -> bool {
+    match token {
+        Some(token) => request
+            .authorization
+            .as_deref()
+            .and_then(|auth| auth.strip_prefix("Bearer "))
+            .map(|t| t.trim() == token)
+            .unwrap_or(false),
+        None => true,
+    }
+}
+
+/// Forwards the request to the main loop and waits for its answer.
+fn forward(tx: &Sender<ApiCall>, request: ApiRequest) -> ApiResponse {
+    let (tx_response, rx_response) = mpsc::channel();
+    let call = ApiCall {
+        request,
+        tx_response,
+    };
+    if let Err(e) = tx.send(call) {
+        return ApiResponse::error(503, format!("main loop gone, {e:?}"));
+    }
+    rx_response
+        .recv_timeout(RESPONSE_TIMEOUT)
+        .unwrap_or_else(|e| ApiResponse::error(503, format!("no answer from main loop, {e:?}")))
+}
+
+/// The accept loop blocks until the next connection, hence, we connect to wake it up after a
+/// termination request.
+fn wake_up(mut address: SocketAddr) {
+    if address.ip().is_unspecified() {
+        address.set_ip(match address {
+            SocketAddr::V4(_) => Ipv4Addr::LOCALHOST.into(),
+            SocketAddr::V6(_) => Ipv6Addr::LOCALHOST.into(),
+        });
+    }
+    TcpStream::connect(address).ok();
+}
+
+/// Writes the events to the stream until the client disconnects or the server terminates.
+/// Comments are sent in between to notice disconnected clients.
+fn stream_events(stream: &mut TcpStream, events: &EventHub, is_terminated: &AtomicBool) {
+    let rx = events.subscribe();
+    let header = "HTTP/1.1 200 OK\r\nContent-Type: text/event-stream\r\nCache-Control: no-cache\r\nConnection: keep-alive\r\n\r\n";
+    let mut write = |s: &str| stream.write_all(s.as_bytes()).and_then(|_| stream.flush());
+    if write(header).is_err() {
+        return;
+    }
+    while !is_terminated.load(Ordering::SeqCst) {
+        let write_result = match rx.recv_timeout(EVENT_PING_INTERVAL) {
+            Ok(event) => write(&event.to_sse()),
+            Err(RecvTimeoutError::Timeout) => write(": ping\n\n"),
+            Err(RecvTimeoutError::Disconnected) => return,
+        };
+        if write_result.is_err() {
+            return;
+        }
+    }
+}
+
+/// Answers the requests of one connection until the client closes it, an error occurs, or the
+/// connection is idle for longer than [`KEEP_ALIVE_TIMEOUT`]
+fn serve_connection(
+    mut stream: TcpStream,
+    tx: &Sender<ApiCall>,
+    token: Option<&str>,
+    events: &EventHub,
+    is_terminated: &AtomicBool,
+) {
+    if let Err(e) = stream.set_read_timeout(Some(KEEP_ALIVE_TIMEOUT)) {
+        println!("could not set timeout of http connection, {e:?}");
+    }
+    let mut buffer = vec![];
+    loop {
+        let (response, keep_alive) = match read_request(&mut stream, &mut buffer) {
+            Ok(None) => return,
+            // we cannot know where the next request starts after a broken one
+            Err(response) => (response, false),
+            Ok(Some(request)) => {
+                let handle_result = if is_authorized(&request, token) {
+                    route(&request.method, &request.path, &request.body)
                 } else {
-                    &buffer
+                    Err(ApiResponse::error(401, "missing or invalid bearer token"))
                 };
-                let response = "HTTP/1.1 200 OK\r\n\r\n";
-                stream.write(response.as_bytes()).map_err(to_rv)?;
-                stream.flush().map_err(to_rv)?;
-                let res = handle_connection(buffer_slice);
-                let path = res?;
-                Ok(path)
-            };
-            // send recieved path
-            if let Ok(p) = to_rv_or_send(&tx_from_server, stream_processing_result) {
-                match p {
-                    HandleResult::Terminate => {
-                        println!("terminating httpserver");
-                        return Ok(());
+                let response = match handle_result {
+                    Ok(HandleResult::Events) => {
+                        stream_events(&mut stream, events, is_terminated);
+                        return;
                     }
-                    HandleResult::Path(p_) => {
-                        println!("tcp listener sending result...");
-                        let send_result = tx_from_server.send(Ok(p_));
-                        println!("done. {send_result:?}");
-                        to_rv_or_send(&tx_from_server, send_result)?;
+                    Ok(HandleResult::Terminate) => {
+                        is_terminated.store(true, Ordering::SeqCst);
+                        ApiResponse::ok(json!({}))
                     }
+                    Ok(HandleResult::Api(api_request)) => forward(tx, api_request),
+                    Err(response) => response,
+                };
+                (response, request.keep_alive)
+            }
+        };
+        let keep_alive = keep_alive && !is_terminated.load(Ordering::SeqCst);
+        let write_result = stream
+            .write_all(response.to_http(keep_alive).as_bytes())
+            .and_then(|_| stream.flush());
+        if let Err(e) = write_result {
+            // the client might have closed the connection, not our problem
+            println!("could not write http response, {e:?}");
+            return;
+        }
+        if !keep_alive {
+            return;
+        }
+    }
+}
+
+pub type LaunchResultType = RvResult<(JoinHandle<()>, Receiver<ApiCall>)>;
+/// Binds to exactly the passed address and serves each connection in its own thread. If a token
+/// is passed, requests need the header `Authorization: Bearer <token>`. Events published to the
+/// hub are sent to the clients of the event stream.
+pub fn launch(address: &str, token: Option<String>, events: EventHub) -> LaunchResultType {
+    println!("spawning httpserver at {address}");
+    let listener = TcpListener::bind(address)
+        .map_err(|e| rverr!("could not start http server at {}, {:?}", address, e))?;
+    let local_address = listener.local_addr().map_err(to_rv)?;
+    let (tx_from_server, rx_from_server) = mpsc::channel();
+    let handle = thread::spawn(move || {
+        let token = Arc::new(token);
+        let is_terminated = Arc::new(AtomicBool::new(false));
+        for stream in listener.incoming() {
+            if is_terminated.load(Ordering::SeqCst) {
+                println!("terminating httpserver");
+                return;
+            }
+            match stream {
+                Ok(stream) => {
+                    let tx = tx_from_server.clone();
+                    let token = token.clone();
+                    let events = events.clone();
+                    let is_terminated = is_terminated.clone();
+                    thread::spawn(move || {
+                        serve_connection(stream, &tx, token.as_deref(), &events, &is_terminated);
+                        if is_terminated.load(Ordering::SeqCst) {
+                            wake_up(local_address);
+                        }
+                    });
                 }
+                Err(e) => println!("could not accept http connection, {e:?}"),
             }
-            println!("tcp listener waiting for new input");
         }
-        Ok(())
     });
     println!("...done");
     Ok((handle, rx_from_server))
 }
-
-fn increase_port(address: &str) -> RvResult<String> {
-    let address_wo_port = address.split(':').next();
-    let port = address.split(':').last();
-    if let Some(port) = port {
-        if let Some(address_wo_port) = address_wo_port {
-            Ok(format!(
-                "{}:{}",
-                address_wo_port,
-                (port.parse::<usize>().map_err(to_rv)? + 1)
-            ))
-        } else {
-            Err(rverr!("is address of {} missing?", address))
-        }
-    } else {
-        Err(rverr!("is port of address {} missing?", address))
+#[cfg(test)]
+fn handle_connection(buffer: &[u8]) -> Result<HandleResult, ApiResponse> {
+    match parse_request(buffer)? {
+        Some((request, _)) => route(&request.method, &request.path, &request.body),
+        None => Err(ApiResponse::error(400, "incomplete request")),
     }
 }
-
-pub fn restart_with_increased_port(
-    http_addr: &str,
-) -> RvResult<(String, Option<Receiver<RvResult<String>>>)> {
-    let http_addr = increase_port(http_addr)?;
-
-    println!("restarting http server with increased port");
-    Ok(if let Ok((_, rx)) = launch(http_addr.clone()) {
-        (http_addr, Some(rx))
-    } else {
-        (http_addr, None)
-    })
-}
 #[test]
 fn test_handler() -> RvResult<()> {
     let buffer = b"garbage";
     assert!(handle_connection(&buffer.as_slice()).is_err());
+    assert!(handle_connection(b"garbage\r\n\r\n").is_err());
 
+    // partial requests are completed by further reads
     let buffer = b"GET /index.html HTTP/1.1\r\nHost:";
+    assert_eq!(parse_request(buffer), Ok(None));
+    let buffer = b"GET /index.html HTTP/1.1\r\nHost: x\r\n\r\n";
     assert_eq!(
         handle_connection(&buffer.as_slice()),
-        Ok(HandleResult::Path("index.html".to_string()))
+        Ok(HandleResult::Api(ApiRequest::SelectFile(
+            "index.html".to_string()
+        )))
     );
 
-    let buffer = b"GET /folder%20name/file%20name.png HTTP/1.1\r\nHost:";
+    let buffer = b"GET /folder%20name/file%20name.png HTTP/1.1\r\nHost: x\r\n\r\n";
     assert_eq!(
         handle_connection(&buffer.as_slice()),
-        Ok(HandleResult::Path("folder name/file name.png".to_string()))
+        Ok(HandleResult::Api(ApiRequest::SelectFile(
+            "folder name/file name.png".to_string()
+        )))
+    );
+    assert_eq!(
+        handle_connection(b"GET /api/events HTTP/1.1\r\n\r\n"),
+        Ok(HandleResult::Events)
     );
-    let buffer = b"GET /TERMINATE HTTP/1.1\r\nHost:";
+    let buffer = b"GET /TERMINATE HTTP/1.1\r\nHost: x\r\n\r\n";
     assert_eq!(
         handle_connection(&buffer.as_slice()),
         Ok(HandleResult::Terminate)
@@ -166,48 +648,345 @@ fn test_handler() -> RvResult<()> {
 
     Ok(())
 }
+#[test]
+fn test_parse_request() {
+    let buffer =
+        b"PUT /api/label HTTP/1.1\r\nContent-Length: 5\r\nAuthorization: Bearer abc\r\n\r\nab";
+    assert_eq!(parse_request(buffer), Ok(None));
+    let buffer = b"PUT /api/label HTTP/1.1\r\nContent-Length: 5\r\nAuthorization: Bearer abc\r\n\r\nabcdeGET /";
+    let (request, request_len) = parse_request(buffer).unwrap().unwrap();
+    assert_eq!(&buffer[request_len..], b"GET /");
+    assert_eq!(request.body, b"abcde");
+    assert!(request.keep_alive);
+    assert!(is_authorized(&request, Some("abc")));
+    assert!(is_authorized(&request, None));
+    assert!(!is_authorized(&request, Some("abd")));
+    let (request, _) = parse_request(b"GET / HTTP/1.1\r\nConnection: close\r\n\r\n")
+        .unwrap()
+        .unwrap();
+    assert!(!request.keep_alive);
+    assert!(!is_authorized(&request, Some("abc")));
+    let (request, _) = parse_request(b"GET / HTTP/1.0\r\n\r\n").unwrap().unwrap();
+    assert!(!request.keep_alive);
+    let status = |buffer: &[u8]| parse_request(buffer).map_err(|r| r.status).map(|_| ());
+    assert_eq!(
+        status(b"PUT / HTTP/1.1\r\nContent-Length: 999999999999\r\n\r\n"),
+        Err(413)
+    );
+    assert_eq!(
+        status(b"PUT / HTTP/1.1\r\nTransfer-Encoding: chunked\r\n\r\n"),
+        Err(501)
+    );
+    assert_eq!(
+        status(b"PUT / HTTP/1.1\r\nContent-Length: x\r\n\r\n"),
+        Err(400)
+    );
+}
+#[test]
+fn test_api_routes() {
+    let api = |req: &[u8]| match handle_connection(req) {
+        Ok(HandleResult::Api(request)) => Ok(request),
+        Ok(other) => panic!("unexpected {other:?}"),
+        Err(response) => Err(response.status),
+    };
+    assert_eq!(
+        api(b"GET /api/files HTTP/1.1\r\n\r\n"),
+        Ok(ApiRequest::ListFiles)
+    );
+    assert_eq!(
+        api(b"GET /api/current HTTP/1.1\r\n\r\n"),
+        Ok(ApiRequest::CurrentImage)
+    );
+    assert_eq!(
+        api(b"GET /api/annotations?path=a%20b.png HTTP/1.1\r\n\r\n"),
+        Ok(ApiRequest::GetAnnotations(Some("a b.png".to_string())))
+    );
+    let body =
+        r#"{"annotations": [{"label": "car", "bbox": [1, 2, 3, 4]}], "width": 10, "height": 20}"#;
+    let req = format!(
+        "PUT /api/annotations HTTP/1.1\r\nContent-Length: {}\r\n\r\n{body}",
+        body.len()
+    );
+    assert_eq!(
+        api(req.as_bytes()),
+        Ok(ApiRequest::SetAnnotations {
+            path: None,
+            annotations: ApiAnnotations {
+                annotations: vec![ApiAnnotation {
+                    label: "car".to_string(),
+                    bbox: Some([1.0, 2.0, 3.0, 4.0]),
+                    polygon: None
+                }],
+                width: Some(10),
+                height: Some(20)
+            }
+        })
+    );
+    let req = b"PUT /api/label HTTP/1.1\r\nContent-Length: 14\r\n\r\n{\"label\": \"a\"}";
+    assert_eq!(api(req), Ok(ApiRequest::SetLabel("a".to_string())));
+    assert_eq!(
+        api(b"POST /api/save HTTP/1.1\r\n\r\n"),
+        Ok(ApiRequest::Save)
+    );
+    assert_eq!(
+        api(b"POST /api/export HTTP/1.1\r\n\r\n"),
+        Ok(ApiRequest::ExportCoco)
+    );
+    assert_eq!(api(b"POST /api/files HTTP/1.1\r\n\r\n"), Err(405));
+    assert_eq!(api(b"GET /api/nothing HTTP/1.1\r\n\r\n"), Err(404));
+    assert_eq!(
+        api(b"PUT /api/label HTTP/1.1\r\nContent-Length: 3\r\n\r\n{\"l"),
+        Err(400)
+    );
+}
+#[cfg(test)]
+use crate::{Polygon, BB};
+#[test]
+fn test_annotations() -> RvResult<()> {
+    let mut bbox_data = BboxSpecificData::new();
+    bbox_data.set_locked(true);
+    let shape = Shape::new(100, 50);
+    let annos = vec![
+        ApiAnnotation {
+            label: bbox_data.labels()[0].clone(),
+            bbox: Some([10.0, 5.0, 20.0, 10.0]),
+            polygon: None,
+        },
+        ApiAnnotation {
+            label: "car".to_string(),
+            bbox: None,
+            polygon: Some(vec![[0.0, 0.0], [10.0, 0.0], [5.0, 8.0]]),
+        },
+    ];
+    assert!(set_annotations(&mut bbox_data, "a.png", shape, &annos).is_err());
+    assert!(bbox_data.get_annos("a.png").is_none());
+    bbox_data.set_locked(false);
+    assert_eq!(set_annotations(&mut bbox_data, "a.png", shape, &annos)?, 2);
+    let annos_set = bbox_data.get_annos("a.png").unwrap();
+    assert_eq!(annos_set.cat_idxs(), &vec![0, 1]);
+    assert_eq!(
+        annos_set.geos()[0],
+        GeoFig::BB(BB::from_arr(&[10, 5, 20, 10]))
+    );
+    let poly = Polygon::from_vec(vec![(0, 0).into(), (10, 0).into(), (5, 8).into()], false)?;
+    assert_eq!(annos_set.geos()[1], GeoFig::Poly(poly));
+    let annos_back = get_annotations(&bbox_data, "a.png");
+    assert_eq!(annos_back.annotations, annos);
+    assert_eq!((annos_back.width, annos_back.height), (Some(100), Some(50)));
+    // existing annotations are replaced
+    set_annotations(&mut bbox_data, "a.png", shape, &annos[1..])?;
+    assert_eq!(bbox_data.get_annos("a.png").unwrap().geos().len(), 1);
+    let invalid = ApiAnnotation {
+        label: "car".to_string(),
+        bbox: None,
+        polygon: None,
+    };
+    assert!(set_annotations(&mut bbox_data, "a.png", shape, &[invalid]).is_err());
+    assert_eq!(
+        get_annotations(&bbox_data, "b.png"),
+        ApiAnnotations::default()
+    );
+    Ok(())
+}
+#[cfg(test)]
+fn read_response(stream: &mut TcpStream) -> RvResult<(u16, String)> {
+    let mut response = vec![];
+    let mut byte = [0u8];
+    while !response.ends_with(b"\r\n\r\n") {
+        if stream.read(&mut byte).map_err(to_rv)? == 0 {
+            return Err(rverr!("connection closed, got {:?}", response));
+        }
+        response.push(byte[0]);
+    }
+    let header = String::from_utf8_lossy(&response).to_string();
+    let status = header[9..12].parse::<u16>().map_err(to_rv)?;
+    let content_length = header
+        .lines()
+        .find_map(|l| l.strip_prefix("Content-Length: "))
+        .ok_or_else(|| rverr!("no content length in {}", header))?
+        .parse::<usize>()
+        .map_err(to_rv)?;
+    let mut body = vec![0; content_length];
+    stream.read_exact(&mut body).map_err(to_rv)?;
+    Ok((status, String::from_utf8_lossy(&body).to_string()))
+}
 #[cfg(test)]
-use std::{net::TcpStream, time::Duration};
+fn launch_with_echo(address: &str, token: Option<String>) -> RvResult<JoinHandle<()>> {
+    let (handle, rx) = launch(address, token, EventHub::default())?;
+    // the main loop is replaced by a thread that answers with the received request
+    thread::spawn(move || {
+        for call in rx {
+            let response = ApiResponse::ok(json!({ "request": format!("{:?}", call.request) }));
+            call.respond(response);
+        }
+    });
+    Ok(handle)
+}
 #[test]
 fn test_launch() -> RvResult<()> {
     let address = "127.0.0.1:7942";
     println!("launching server...");
-    let (handle, rx) = launch(address.to_string())?;
+    let handle = launch_with_echo(address, None)?;
     thread::sleep(Duration::from_millis(10));
     assert!(!handle.is_finished());
+    // the address is taken
+    assert!(launch(address, None, EventHub::default()).is_err());
     println!("...done");
 
-    let send_request = |req| {
-        let mut stream = TcpStream::connect(address).map_err(to_rv)?;
-        stream.write(req).map_err(to_rv)?;
-        stream.flush().map_err(to_rv)?;
-        Ok(())
+    let connect = || TcpStream::connect(address).map_err(to_rv);
+    let send = |stream: &mut TcpStream, req: &[u8]| -> RvResult<()> {
+        stream.write_all(req).map_err(to_rv)?;
+        stream.flush().map_err(to_rv)
     };
-    println!("writing to stream...");
-    let input_stream = b"GET /some_path.png HTTP/1.1\r\nHost:";
-    send_request(&input_stream.as_slice())?;
+    println!("request in pieces...");
+    let mut stream = connect()?;
+    send(&mut stream, b"GET /some_pa")?;
+    thread::sleep(Duration::from_millis(50));
+    send(&mut stream, b"th.png HTTP/1.1\r\nHost: x\r\n\r\n")?;
+    // selecting files is answered by the main loop, too
+    let (status, body) = read_response(&mut stream)?;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#"SelectFile(\"some_path.png\")"#));
     println!("...done");
-    println!("writing to stream...");
-    let input_stream = b"GET /some_other_path.png HTTP/1.1\r\nHost:";
-    send_request(&input_stream.as_slice())?;
+    println!("keep-alive with body in pieces...");
+    send(
+        &mut stream,
+        b"PUT /api/label HTTP/1.1\r\nContent-Length: 14\r\n\r\n{\"label\"",
+    )?;
+    thread::sleep(Duration::from_millis(50));
+    send(&mut stream, b": \"a\"}")?;
+    let (status, body) = read_response(&mut stream)?;
+    assert_eq!(status, 200);
+    assert!(body.contains(r#"SetLabel(\"a\")"#));
     println!("...done");
-    thread::sleep(Duration::from_millis(1500));
-    println!("checking results...");
-    let result1 = rx.recv().map_err(to_rv)?;
-    let result2 = rx.recv().map_err(to_rv)?;
-    assert_eq!(result1, Ok("some_path.png".to_string()));
-    assert_eq!(result2, Ok("some_other_path.png".to_string()));
+    println!("pipelined requests...");
+    send(
+        &mut stream,
+        b"GET /api/current HTTP/1.1\r\n\r\nGET /api/files HTTP/1.1\r\n\r\n",
+    )?;
+    assert!(read_response(&mut stream)?.1.contains("CurrentImage"));
+    assert!(read_response(&mut stream)?.1.contains("ListFiles"));
+    println!("...done");
+    println!("errors...");
+    send(&mut stream, b"GET /api/nothing HTTP/1.1\r\n\r\n")?;
+    let (status, body) = read_response(&mut stream)?;
+    assert_eq!(status, 404);
+    assert!(body.starts_with(r#"{"error":"#));
+    send(&mut stream, b"DELETE /api/save HTTP/1.1\r\n\r\n")?;
+    assert_eq!(read_response(&mut stream)?.0, 405);
+    let mut stream = connect()?;
+    send(&mut stream, b"garbage\r\n\r\n")?;
+    assert_eq!(read_response(&mut stream)?.0, 400);
     println!("...done");
     println!("terminate...");
-    let terminate_stream = b"GET /TERMINATE HTTP/1.1\r\n";
-    send_request(&terminate_stream.as_slice())?;
+    let mut stream = connect()?;
+    send(&mut stream, b"GET /TERMINATE HTTP/1.1\r\n\r\n")?;
+    assert_eq!(read_response(&mut stream)?.0, 200);
     thread::sleep(Duration::from_millis(500));
     assert!(handle.is_finished());
     println!("...done");
     Ok(())
 }
 #[test]
-fn test_increase_port() -> RvResult<()> {
-    assert_eq!(increase_port("address:1234")?, "address:1235");
+fn test_token() -> RvResult<()> {
+    let address = "127.0.0.1:7943";
+    let handle = launch_with_echo(address, Some("secret".to_string()))?;
+    let request = |req: &[u8]| {
+        let mut stream = TcpStream::connect(address).map_err(to_rv)?;
+        stream.write_all(req).map_err(to_rv)?;
+        read_response(&mut stream)
+    };
+    assert_eq!(request(b"GET /api/files HTTP/1.1\r\n\r\n")?.0, 401);
+    assert_eq!(
+        request(b"GET /api/files HTTP/1.1\r\nAuthorization: Bearer wrong\r\n\r\n")?.0,
+        401
+    );
+    assert_eq!(request(b"GET /TERMINATE HTTP/1.1\r\n\r\n")?.0, 401);
+    let (status, body) =
+        request(b"GET /api/files HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n")?;
+    assert_eq!(status, 200);
+    assert!(body.contains("ListFiles"));
+    request(b"GET /TERMINATE HTTP/1.1\r\nAuthorization: Bearer secret\r\n\r\n")?;
+    thread::sleep(Duration::from_millis(500));
+    assert!(handle.is_finished());
+    Ok(())
+}
+#[test]
+fn test_events() -> RvResult<()> {
+    let address = "127.0.0.1:7944";
+    let events = EventHub::default();
+    let (handle, _rx) = launch(address, None, events.clone())?;
+    let mut stream = TcpStream::connect(address).map_err(to_rv)?;
+    stream
+        .write_all(b"GET /api/events HTTP/1.1\r\n\r\n")
+        .map_err(to_rv)?;
+    let read_until = |stream: &mut TcpStream, end: &[u8]| -> RvResult<String> {
+        let mut read = vec![];
+        let mut byte = [0u8];
+        while !read.ends_with(end) {
+            if stream.read(&mut byte).map_err(to_rv)? == 0 {
+                return Err(rverr!("connection closed, got {:?}", read));
+            }
+            read.push(byte[0]);
+        }
+        Ok(String::from_utf8_lossy(&read).to_string())
+    };
+    let header = read_until(&mut stream, b"\r\n\r\n")?;
+    assert!(header.starts_with("HTTP/1.1 200 OK"));
+    assert!(header.contains("Content-Type: text/event-stream"));
+    while events.n_subscribers() == 0 {
+        thread::sleep(Duration::from_millis(10));
+    }
+    events.publish(ServerEvent::ImageSelected {
+        index: Some(2),
+        path: Some("a.png".to_string()),
+    });
+    events.publish(ServerEvent::ProjectSaved {
+        path: PathBuf::from("prj.rvi"),
+    });
+    assert_eq!(
+        read_until(&mut stream, b"\n\n")?,
+        "event: image_selected\ndata: {\"index\":2,\"path\":\"a.png\"}\n\n"
+    );
+    assert_eq!(
+        read_until(&mut stream, b"\n\n")?,
+        "event: project_saved\ndata: {\"path\":\"prj.rvi\"}\n\n"
+    );
+    // connections are unsubscribed as soon as writing to the closed connection fails
+    drop(stream);
+    for _ in 0..50 {
+        if events.n_subscribers() == 0 {
+            break;
+        }
+        events.publish(ServerEvent::AnnotationsChanged {
+            tool: "Bbox".to_string(),
+            path: None,
+        });
+        thread::sleep(Duration::from_millis(20));
+    }
+    assert_eq!(events.n_subscribers(), 0);
+    let mut stream = TcpStream::connect(address).map_err(to_rv)?;
+    stream
+        .write_all(b"GET /TERMINATE HTTP/1.1\r\n\r\n")
+        .map_err(to_rv)?;
+    thread::sleep(Duration::from_millis(500));
+    assert!(handle.is_finished());
     Ok(())
 }
+#[test]
+fn test_forward() {
+    // selecting a file is answered by the main loop that knows the files
+    let (tx, rx) = mpsc::channel::<ApiCall>();
+    let main_loop = thread::spawn(move || {
+        let call = rx.recv().unwrap();
+        assert_eq!(call.request, ApiRequest::SelectFile("x.png".to_string()));
+        call.respond(ApiResponse::error(404, "x.png is not in the opened folder"));
+    });
+    let response = forward(&tx, ApiRequest::SelectFile("x.png".to_string()));
+    assert_eq!(response.status, 404);
+    main_loop.join().unwrap();
+    drop(tx);
+    let (tx, rx) = mpsc::channel::<ApiCall>();
+    drop(rx);
+    assert_eq!(forward(&tx, ApiRequest::ListFiles).status, 503);
+}
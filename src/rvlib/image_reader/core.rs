@@ -8,9 +8,6 @@ use crate::types::AsyncResultImage;
 
 pub const SUPPORTED_EXTENSIONS: [&str; 4] = [".png", ".jpg", ".tif", ".tiff"];
 
-#[derive(Clone)]
-pub struct CloneDummy;
-
 /// All [`Loader`](Loader) structs with their different generic parameters implement this trait
 /// such that the loader can be created dynamically based on the config.
 pub trait LoadImageForGui {
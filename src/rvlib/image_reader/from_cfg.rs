@@ -12,7 +12,7 @@ use {
 };
 
 use super::{
-    core::{CloneDummy, LoadImageForGui, Loader},
+    core::{LoadImageForGui, Loader},
     local_reader::ReadImageFromPath,
     py_http_reader::ReadImageFromPyHttp,
     ssh_reader::ReadImageFromSsh,
@@ -34,6 +34,7 @@ impl ReaderFromCfg {
     pub fn from_cfg(cfg: Cfg) -> RvResult<Self> {
         let n_ssh_reconnections = cfg.ssh_cfg.n_reconnection_attempts();
         let tmpdir = format!("{}/{}", cfg.tmpdir()?, uuid::Uuid::new_v4());
+        let video_cfg = cfg.video_cfg.clone().unwrap_or_default();
         Ok(Self {
             reader: match (&cfg.connection, &cfg.cache) {
                 (Connection::Local, Cache::FileCache) => {
@@ -41,7 +42,7 @@ impl ReaderFromCfg {
                     Box::new(Loader::<FileCache<ReadImageFromPath, _>, _>::new(
                         FileCacheArgs {
                             cfg_args: args,
-                            reader_args: CloneDummy {},
+                            reader_args: video_cfg.clone(),
                             tmpdir,
                         },
                         0,
@@ -63,7 +64,7 @@ impl ReaderFromCfg {
                 }
                 (Connection::Local, Cache::NoCache) => {
                     Box::new(Loader::<NoCache<ReadImageFromPath, _>, _>::new(
-                        CloneDummy {},
+                        video_cfg.clone(),
                         0,
                     )?)
                 }
@@ -124,7 +125,7 @@ impl ReaderFromCfg {
                     ))?;
                     // return a dummy such that the compiler is happy
                     Box::new(Loader::<NoCache<ReadImageFromPath, _>, _>::new(
-                        CloneDummy {},
+                        video_cfg.clone(),
                         0,
                     )?)
                 }
@@ -2,12 +2,27 @@ use walkdir::WalkDir;
 
 use crate::{
     cache::ReadImageToCache,
+    cfg::VideoCfg,
     file_util, image_util,
     result::{to_rv, RvResult},
     types::ResultImage,
 };
 
-use super::core::{CloneDummy, SUPPORTED_EXTENSIONS};
+use super::core::SUPPORTED_EXTENSIONS;
+#[cfg(feature = "ffmpeg")]
+use super::video;
+
+fn is_supported(ext: &str) -> bool {
+    let is_image = SUPPORTED_EXTENSIONS
+        .iter()
+        .any(|sup_ext| &sup_ext[1..] == ext);
+    #[cfg(feature = "ffmpeg")]
+    let is_image = is_image
+        || video::VIDEO_EXTENSIONS
+            .iter()
+            .any(|vid_ext| vid_ext[1..].eq_ignore_ascii_case(ext));
+    is_image
+}
 
 fn read_image_paths(path: &str) -> RvResult<Vec<String>> {
     WalkDir::new(path)
@@ -16,9 +31,7 @@ fn read_image_paths(path: &str) -> RvResult<Vec<String>> {
         .filter(|p| match p {
             Err(_) => true,
             Ok(p_) => match p_.path().extension() {
-                Some(ext) => SUPPORTED_EXTENSIONS
-                    .iter()
-                    .any(|sup_ext| Some(&sup_ext[1..]) == ext.to_str()),
+                Some(ext) => ext.to_str().map(is_supported).unwrap_or(false),
                 None => false,
             },
         })
@@ -27,19 +40,46 @@ fn read_image_paths(path: &str) -> RvResult<Vec<String>> {
 }
 
 #[derive(Clone, Debug)]
-pub struct ReadImageFromPath;
-impl ReadImageToCache<CloneDummy> for ReadImageFromPath {
-    fn new(_: CloneDummy) -> RvResult<Self> {
-        Ok(Self {})
+pub struct ReadImageFromPath {
+    #[cfg_attr(not(feature = "ffmpeg"), allow(dead_code))]
+    video_cfg: VideoCfg,
+}
+impl ReadImageToCache<VideoCfg> for ReadImageFromPath {
+    fn new(video_cfg: VideoCfg) -> RvResult<Self> {
+        Ok(Self { video_cfg })
     }
     fn read(&self, path: &str) -> ResultImage {
+        #[cfg(feature = "ffmpeg")]
+        if let Some((video_path, frame_idx)) = video::parse_frame_path(path) {
+            return video::read_frame(video_path, frame_idx);
+        }
         image_util::read_image(path)
     }
     fn ls(&self, folder_path: &str) -> RvResult<Vec<String>> {
         let image_paths = read_image_paths(folder_path)?;
+        // frames of videos are listed like images in a folder named like the video
+        #[cfg(feature = "ffmpeg")]
+        let image_paths = image_paths
+            .into_iter()
+            .map(|p| {
+                if video::is_video(&p) {
+                    video::frame_paths(&p, self.video_cfg.frame_stride())
+                } else {
+                    Ok(vec![p])
+                }
+            })
+            .collect::<RvResult<Vec<_>>>()?
+            .concat();
         Ok(image_paths)
     }
     fn file_info(&self, path: &str) -> RvResult<String> {
+        #[cfg(feature = "ffmpeg")]
+        if let Some((video_path, frame_idx)) = video::parse_frame_path(path) {
+            return Ok(format!(
+                "frame {frame_idx} of {}",
+                file_util::local_file_info(video_path)
+            ));
+        }
         Ok(file_util::local_file_info(path))
     }
 }
@@ -6,5 +6,20 @@ mod ssh_reader;
 
 #[cfg(feature = "azure_blob")]
 mod azure_blob_reader;
+#[cfg(feature = "ffmpeg")]
+mod video;
 
 pub use self::{core::LoadImageForGui, from_cfg::ReaderFromCfg};
+
+/// Path of the video and index of the frame if the path is a virtual frame path of a video
+pub fn parse_frame_path(path: &str) -> Option<(&str, usize)> {
+    #[cfg(feature = "ffmpeg")]
+    {
+        video::parse_frame_path(path)
+    }
+    #[cfg(not(feature = "ffmpeg"))]
+    {
+        let _ = path;
+        None
+    }
+}
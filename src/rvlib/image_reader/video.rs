@@ -0,0 +1,235 @@
+//! Video files are treated as folders of frames. A frame is addressed by a virtual path like
+//! `clip.mp4/clip_000012.png` and decoded on demand with the command line tools of ffmpeg.
+use std::{
+    collections::HashMap,
+    path::Path,
+    process::Command,
+    sync::{Arc, Mutex},
+};
+
+use lazy_static::lazy_static;
+
+use crate::{
+    file_util,
+    result::{to_rv, RvResult},
+    rverr,
+    types::ResultImage,
+};
+
+pub const VIDEO_EXTENSIONS: [&str; 3] = [".mp4", ".avi", ".mkv"];
+
+lazy_static! {
+    /// Frame timestamps per video since probing them reads the whole container
+    static ref TIMESTAMPS: Mutex<HashMap<String, Arc<Vec<f64>>>> = Mutex::new(HashMap::new());
+}
+
+pub fn is_video(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| {
+            VIDEO_EXTENSIONS
+                .iter()
+                .any(|vid_ext| vid_ext[1..].eq_ignore_ascii_case(ext))
+        })
+        .unwrap_or(false)
+}
+
+/// Virtual path of a frame inside the video, the file name contains the video's name such that
+/// frames of different videos do not collide in the cache folder
+pub fn frame_path(video_path: &str, frame_idx: usize) -> RvResult<String> {
+    let stem = file_util::osstr_to_str(Path::new(video_path).file_stem()).map_err(to_rv)?;
+    let path = Path::new(video_path).join(format!("{stem}_{frame_idx:06}.png"));
+    Ok(file_util::path_to_str(&path)?.to_string())
+}
+
+/// Path of the video and index of the frame if the path is a virtual frame path
+pub fn parse_frame_path(path: &str) -> Option<(&str, usize)> {
+    let path = Path::new(path);
+    let video_path = path.parent()?.to_str()?;
+    if !is_video(video_path) {
+        return None;
+    }
+    let (_, frame_idx) = path.file_stem()?.to_str()?.rsplit_once('_')?;
+    Some((video_path, frame_idx.parse().ok()?))
+}
+
+fn run(cmd: &mut Command) -> RvResult<Vec<u8>> {
+    let output = cmd
+        .output()
+        .map_err(|e| rverr!("could not run {:?}, is ffmpeg installed? {}", cmd, e))?;
+    if output.status.success() {
+        Ok(output.stdout)
+    } else {
+        Err(rverr!(
+            "{:?} failed with {}",
+            cmd,
+            String::from_utf8_lossy(&output.stderr)
+        ))
+    }
+}
+
+/// Parses rates like `30000/1001`
+fn parse_frame_rate(s: &str) -> RvResult<f64> {
+    let parse = |x: &str| x.trim().parse::<f64>().map_err(to_rv);
+    let fps = match s.split_once('/') {
+        Some((num, denom)) => parse(num)? / parse(denom)?,
+        None => parse(s)?,
+    };
+    if fps.is_finite() && fps > 0.0 {
+        Ok(fps)
+    } else {
+        Err(rverr!("invalid frame rate {}", s))
+    }
+}
+
+fn probe(video_path: &str, entries: &str, count_packets: bool) -> RvResult<String> {
+    let mut cmd = Command::new("ffprobe");
+    cmd.args(["-v", "error", "-select_streams", "v:0"]);
+    if count_packets {
+        cmd.arg("-count_packets");
+    }
+    cmd.args(["-show_entries", entries, "-of", "csv=p=0", video_path]);
+    Ok(String::from_utf8_lossy(&run(&mut cmd)?).trim().to_string())
+}
+
+fn frame_rate(video_path: &str) -> RvResult<f64> {
+    parse_frame_rate(&probe(video_path, "stream=r_frame_rate", false)?)
+}
+
+/// The number of frames is taken from the header and only if missing, which is the case for some
+/// containers, we count the packets of the video stream.
+fn n_frames(video_path: &str) -> RvResult<usize> {
+    if let Ok(n) = probe(video_path, "stream=nb_frames", false)?.parse() {
+        return Ok(n);
+    }
+    let n = probe(video_path, "stream=nb_read_packets", true)?;
+    n.parse().map_err(|_| {
+        rverr!(
+            "could not read number of frames of {} from {}",
+            video_path,
+            n
+        )
+    })
+}
+
+/// Sorted presentation timestamps relative to the first frame, packets without timestamp are
+/// skipped
+fn parse_timestamps(s: &str) -> Vec<f64> {
+    let mut timestamps = s
+        .lines()
+        .filter_map(|line| line.trim().trim_end_matches(',').parse::<f64>().ok())
+        .collect::<Vec<_>>();
+    timestamps.sort_by(f64::total_cmp);
+    if let Some(&first) = timestamps.first() {
+        for ts in &mut timestamps {
+            *ts -= first;
+        }
+    }
+    timestamps
+}
+
+/// Timestamps of all frames which also works for variable frame rates. Without timestamps in the
+/// container, we fall back to the frame rate.
+fn timestamps(video_path: &str) -> RvResult<Arc<Vec<f64>>> {
+    if let Some(timestamps) = TIMESTAMPS.lock().map_err(to_rv)?.get(video_path) {
+        return Ok(timestamps.clone());
+    }
+    let mut timestamps = parse_timestamps(&probe(video_path, "packet=pts_time", false)?);
+    if timestamps.is_empty() {
+        let fps = frame_rate(video_path)?;
+        timestamps = (0..n_frames(video_path)?)
+            .map(|frame_idx| frame_idx as f64 / fps)
+            .collect();
+    }
+    let timestamps = Arc::new(timestamps);
+    TIMESTAMPS
+        .lock()
+        .map_err(to_rv)?
+        .insert(video_path.to_string(), timestamps.clone());
+    Ok(timestamps)
+}
+
+/// We seek halfway between the frame and its predecessor such that ffmpeg drops all frames before
+/// the requested one and rounding of timestamps does not matter.
+fn seek_secs(timestamps: &[f64], frame_idx: usize) -> Option<f64> {
+    let ts = *timestamps.get(frame_idx)?;
+    Some(match frame_idx.checked_sub(1) {
+        Some(prev_idx) => 0.5 * (timestamps[prev_idx] + ts),
+        None => 0.0,
+    })
+}
+
+/// Virtual paths of every `stride`-th frame of the video. The frames are counted by their
+/// timestamps such that every listed frame can be read by [`read_frame`].
+pub fn frame_paths(video_path: &str, stride: usize) -> RvResult<Vec<String>> {
+    (0..timestamps(video_path)?.len())
+        .step_by(stride.max(1))
+        .map(|frame_idx| frame_path(video_path, frame_idx))
+        .collect()
+}
+
+/// Decodes a single frame. ffmpeg decodes from the key frame preceding the seek position and drops
+/// all frames before the seek position.
+pub fn read_frame(video_path: &str, frame_idx: usize) -> ResultImage {
+    let seek_secs = seek_secs(&timestamps(video_path)?, frame_idx)
+        .ok_or_else(|| rverr!("frame {} not found in {}", frame_idx, video_path))?;
+    let png = run(Command::new("ffmpeg").args([
+        "-v",
+        "error",
+        "-ss",
+        &format!("{seek_secs:.6}"),
+        "-i",
+        video_path,
+        "-frames:v",
+        "1",
+        "-f",
+        "image2pipe",
+        "-vcodec",
+        "png",
+        "-",
+    ]))?;
+    if png.is_empty() {
+        return Err(rverr!("frame {} not found in {}", frame_idx, video_path));
+    }
+    image::load_from_memory(&png).map_err(to_rv)
+}
+
+#[test]
+fn test_frame_path() -> RvResult<()> {
+    assert!(is_video("a/b/clip.MP4"));
+    assert!(!is_video("a/b/clip.png"));
+    let path = frame_path("videos/clip.mkv", 12)?;
+    assert_eq!(
+        Path::new(&path),
+        Path::new("videos").join("clip.mkv").join("clip_000012.png")
+    );
+    assert_eq!(parse_frame_path(&path), Some(("videos/clip.mkv", 12)));
+    assert_eq!(parse_frame_path("videos/clip_000012.png"), None);
+    assert_eq!(parse_frame_path("videos/clip.mkv/clip.png"), None);
+    Ok(())
+}
+
+#[test]
+fn test_parse_frame_rate() -> RvResult<()> {
+    assert_eq!(parse_frame_rate("25/1")?, 25.0);
+    assert!((parse_frame_rate("30000/1001")? - 29.97).abs() < 1e-2);
+    assert_eq!(parse_frame_rate("24")?, 24.0);
+    assert!(parse_frame_rate("0/0").is_err());
+    assert!(parse_frame_rate("N/A").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_timestamps() {
+    // packets are listed in decoding order and might lack timestamps
+    let timestamps = parse_timestamps("1.5\n1.6,\nN/A\n1.55\n1.9\n");
+    let expected = [0.0, 0.05, 0.1, 0.4];
+    assert_eq!(timestamps.len(), expected.len());
+    for (ts, exp) in timestamps.iter().zip(expected) {
+        assert!((ts - exp).abs() < 1e-9);
+    }
+    assert!(seek_secs(&timestamps, 0) == Some(0.0));
+    assert!((seek_secs(&timestamps, 3).unwrap() - 0.25).abs() < 1e-9);
+    assert_eq!(seek_secs(&timestamps, 4), None);
+}
@@ -0,0 +1,275 @@
+//! Pre-annotation by a model that is served via http, e.g., by TorchServe or FastAPI.
+//!
+//! The image is posted as raw bytes to the configured url. The endpoint answers with
+//! ```json
+//! {"predictions": [
+//!     {"label": "car", "score": 0.93, "bbox": [10.0, 20.5, 100.0, 50.0]},
+//!     {"label": "person", "score": 0.71, "polygon": [[5, 5], [40, 8], [22, 60]]}
+//! ]}
+//! ```
+//! where boxes are given as `[x, y, w, h]` in pixels of the original image.
+use std::{
+    io::Cursor,
+    mem,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use image::{DynamicImage, ImageOutputFormat};
+use reqwest::blocking::Client;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    cfg::{Cfg, InferenceCfg},
+    domain::{PtI, Shape, BB},
+    image_reader::ReaderFromCfg,
+    result::{to_rv, RvResult},
+    rverr,
+    threadpool::ThreadPool,
+    tools_data::image_export,
+    types::ResultImage,
+    GeoFig, Polygon,
+};
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct RawPrediction {
+    pub label: String,
+    pub score: f32,
+    #[serde(default)]
+    pub bbox: Option<[f64; 4]>,
+    #[serde(default)]
+    pub polygon: Option<Vec<[f64; 2]>>,
+}
+impl RawPrediction {
+    /// Geometry clipped to the image, `None` if the prediction has no or a degenerated geometry.
+    pub fn to_geo(&self, shape: Shape) -> Option<GeoFig> {
+        let clip = |v: f64, max: u32| v.round().clamp(0.0, max as f64) as u32;
+        if let Some(polygon) = &self.polygon {
+            let points = polygon
+                .iter()
+                .map(|[x, y]| PtI {
+                    x: clip(*x, shape.w.saturating_sub(1)),
+                    y: clip(*y, shape.h.saturating_sub(1)),
+                })
+                .collect::<Vec<_>>();
+            if points.len() < 3 {
+                None
+            } else {
+                Polygon::from_vec(points, false).ok().map(GeoFig::Poly)
+            }
+        } else if let Some([x, y, w, h]) = self.bbox {
+            let (x_min, y_min) = (clip(x, shape.w), clip(y, shape.h));
+            let (x_max, y_max) = (clip(x + w, shape.w), clip(y + h, shape.h));
+            if x_max > x_min && y_max > y_min {
+                Some(GeoFig::BB(BB::from_arr(&[
+                    x_min,
+                    y_min,
+                    x_max - x_min,
+                    y_max - y_min,
+                ])))
+            } else {
+                None
+            }
+        } else {
+            None
+        }
+    }
+
+    /// Scores outside of `[0, 1]` are clamped.
+    pub fn score(&self) -> f32 {
+        if self.score.is_finite() {
+            self.score.clamp(0.0, 1.0)
+        } else {
+            0.0
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Response {
+    Wrapped { predictions: Vec<RawPrediction> },
+    Plain(Vec<RawPrediction>),
+}
+
+pub fn parse_response(s: &str) -> RvResult<Vec<RawPrediction>> {
+    let response: Response =
+        serde_json::from_str(s).map_err(|e| rverr!("invalid inference response, {:?}", e))?;
+    Ok(match response {
+        Response::Wrapped { predictions } => predictions,
+        Response::Plain(predictions) => predictions,
+    })
+}
+
+fn make_client(cfg: &InferenceCfg) -> RvResult<Client> {
+    Client::builder()
+        .timeout(Duration::from_secs(cfg.timeout_secs()))
+        .build()
+        .map_err(to_rv)
+}
+
+fn request(
+    client: &Client,
+    cfg: &InferenceCfg,
+    image_bytes: Vec<u8>,
+) -> RvResult<Vec<RawPrediction>> {
+    let resp = client
+        .post(&cfg.url)
+        .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+        .body(image_bytes)
+        .send()
+        .and_then(|resp| resp.error_for_status())
+        .and_then(|resp| resp.text())
+        .map_err(to_rv)?;
+    parse_response(&resp)
+}
+
+/// Reader of the configured connection that is shared by the requests of a batch. It is created
+/// by the first request that needs it to not block the main thread.
+#[derive(Clone)]
+pub struct SharedReader {
+    cfg: Arc<Cfg>,
+    reader: Arc<Mutex<Option<ReaderFromCfg>>>,
+}
+impl SharedReader {
+    pub fn new(cfg: Cfg) -> Self {
+        Self {
+            cfg: Arc::new(cfg),
+            reader: Arc::new(Mutex::new(None)),
+        }
+    }
+    fn read(&self, file_path: &str) -> ResultImage {
+        let mut reader = self
+            .reader
+            .lock()
+            .map_err(|e| rverr!("could not lock reader, {:?}", e))?;
+        let reader = match &mut *reader {
+            Some(reader) => reader,
+            slot @ None => slot.insert(ReaderFromCfg::from_cfg((*self.cfg).clone())?),
+        };
+        image_export::read_image(reader, file_path)
+    }
+}
+
+pub enum ImageSource {
+    /// The image that is already loaded, e.g., the currently shown one
+    Loaded(DynamicImage),
+    /// Image of the opened folder that is read via the configured connection
+    Read {
+        reader: SharedReader,
+        file_path: String,
+    },
+}
+impl ImageSource {
+    fn into_bytes(self) -> RvResult<(Vec<u8>, Shape)> {
+        match self {
+            Self::Loaded(im) => {
+                let shape = Shape::from_im(&im);
+                let mut bytes = vec![];
+                im.write_to(&mut Cursor::new(&mut bytes), ImageOutputFormat::Png)
+                    .map_err(to_rv)?;
+                Ok((bytes, shape))
+            }
+            Self::Read { reader, file_path } => Self::Loaded(reader.read(&file_path)?).into_bytes(),
+        }
+    }
+}
+
+pub struct PredictionResult {
+    pub file_path: String,
+    pub shape: Shape,
+    pub predictions: RvResult<Vec<RawPrediction>>,
+}
+
+/// Sends images to the inference endpoint in the background.
+#[derive(Default)]
+pub struct Predictor {
+    tp: Option<ThreadPool<RvResult<PredictionResult>>>,
+    job_ids: Vec<u128>,
+    /// shared by all requests, rebuilt if the configuration changes
+    client: Option<(InferenceCfg, Client)>,
+}
+impl Predictor {
+    pub fn predict(
+        &mut self,
+        cfg: &InferenceCfg,
+        file_path: String,
+        source: ImageSource,
+    ) -> RvResult<()> {
+        let client = match &self.client {
+            Some((client_cfg, client)) if client_cfg == cfg => client.clone(),
+            _ => {
+                let client = make_client(cfg)?;
+                self.client = Some((cfg.clone(), client.clone()));
+                client
+            }
+        };
+        let tp = self
+            .tp
+            .get_or_insert_with(|| ThreadPool::new(cfg.n_threads()));
+        let cfg = cfg.clone();
+        let job_id = tp.apply(Box::new(move || {
+            let (bytes, shape) = source.into_bytes()?;
+            Ok(PredictionResult {
+                predictions: request(&client, &cfg, bytes),
+                file_path,
+                shape,
+            })
+        }))?;
+        self.job_ids.push(job_id);
+        Ok(())
+    }
+
+    /// Results of finished jobs, each result is returned once.
+    pub fn results(&mut self) -> Vec<RvResult<PredictionResult>> {
+        let mut results = vec![];
+        if let Some(tp) = &mut self.tp {
+            for job_id in mem::take(&mut self.job_ids) {
+                match tp.result(job_id) {
+                    Some(res) => results.push(res),
+                    None => self.job_ids.push(job_id),
+                }
+            }
+        }
+        results
+    }
+
+    pub fn n_pending(&self) -> usize {
+        self.job_ids.len()
+    }
+}
+
+#[test]
+fn test_parse_response() -> RvResult<()> {
+    let predictions = parse_response(
+        r#"{"predictions": [
+            {"label": "car", "score": 0.93, "bbox": [10.4, 20.5, 100.0, 50.0]},
+            {"label": "person", "score": 1.5, "polygon": [[5, 5], [40, 8], [22, 60]]},
+            {"label": "tree", "score": 0.4, "bbox": [95, 95, 20, 20]},
+            {"label": "dot", "score": 0.4, "bbox": [120, 5, 3, 3]},
+            {"label": "nothing", "score": 0.2}
+        ]}"#,
+    )?;
+    let shape = Shape::new(100, 100);
+    assert_eq!(predictions.len(), 5);
+    assert_eq!(
+        predictions[0].to_geo(shape),
+        Some(GeoFig::BB(BB::from_arr(&[10, 21, 90, 50])))
+    );
+    assert!(matches!(
+        predictions[1].to_geo(shape),
+        Some(GeoFig::Poly(_))
+    ));
+    assert_eq!(predictions[1].score(), 1.0);
+    // clipped to the image
+    assert_eq!(
+        predictions[2].to_geo(shape),
+        Some(GeoFig::BB(BB::from_arr(&[95, 95, 5, 5])))
+    );
+    assert_eq!(predictions[3].to_geo(shape), None);
+    assert_eq!(predictions[4].to_geo(shape), None);
+    let plain = parse_response(r#"[{"label": "car", "score": 0.5, "bbox": [0, 0, 1, 1]}]"#)?;
+    assert_eq!(plain[0].label, "car");
+    assert!(parse_response(r#"{"boxes": []}"#).is_err());
+    Ok(())
+}
@@ -0,0 +1,541 @@
+use std::{collections::BTreeMap, fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    events::{Events, KeyCode},
+    result::{RvError, RvResult},
+    rverr,
+    tools::{BBOX_NAME, CLASSIFY_NAME, KEYPOINTS_NAME, ZOOM_NAME},
+    tools_data::image_review::ImageStatus,
+};
+
+/// A key together with the modifiers that need to be held, written as, e.g., `"Ctrl+Shift+Z"`.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyChord {
+    pub key: KeyCode,
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+impl KeyChord {
+    pub fn new(key: KeyCode) -> Self {
+        Self {
+            key,
+            ctrl: false,
+            alt: false,
+            shift: false,
+        }
+    }
+    pub fn ctrl(key: KeyCode) -> Self {
+        Self {
+            ctrl: true,
+            ..Self::new(key)
+        }
+    }
+    pub fn alt(key: KeyCode) -> Self {
+        Self {
+            alt: true,
+            ..Self::new(key)
+        }
+    }
+}
+impl Display for KeyChord {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        write!(f, "{}", self.key.name())
+    }
+}
+impl FromStr for KeyChord {
+    type Err = RvError;
+    fn from_str(s: &str) -> RvResult<Self> {
+        let mut parts = s.split('+').map(str::trim).collect::<Vec<_>>();
+        let key = parts
+            .pop()
+            .filter(|k| !k.is_empty())
+            .ok_or_else(|| rverr!("no key in key chord '{}'", s))?;
+        let mut chord = Self::new(key.parse()?);
+        for modifier in parts {
+            match modifier.to_lowercase().as_str() {
+                "ctrl" | "cmd" => chord.ctrl = true,
+                "alt" => chord.alt = true,
+                "shift" => chord.shift = true,
+                _ => return Err(rverr!("unknown modifier '{}' in '{}'", modifier, s)),
+            }
+        }
+        Ok(chord)
+    }
+}
+impl TryFrom<String> for KeyChord {
+    type Error = RvError;
+    fn try_from(s: String) -> RvResult<Self> {
+        s.parse()
+    }
+}
+impl From<KeyChord> for String {
+    fn from(chord: KeyChord) -> Self {
+        chord.to_string()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+impl Direction {
+    /// shift in x and y by one pixel
+    pub fn delta(&self) -> (i32, i32) {
+        match self {
+            Self::Up => (0, -1),
+            Self::Down => (0, 1),
+            Self::Left => (-1, 0),
+            Self::Right => (1, 0),
+        }
+    }
+}
+impl Display for Direction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Up => write!(f, "up"),
+            Self::Down => write!(f, "down"),
+            Self::Left => write!(f, "left"),
+            Self::Right => write!(f, "right"),
+        }
+    }
+}
+
+/// Key chords for each direction, e.g., of moving boxes
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq, Default)]
+#[serde(default)]
+pub struct DirectionChords {
+    pub up: Vec<KeyChord>,
+    pub down: Vec<KeyChord>,
+    pub left: Vec<KeyChord>,
+    pub right: Vec<KeyChord>,
+}
+impl DirectionChords {
+    /// arrow keys with the modifiers of `make_chord`
+    fn arrows(make_chord: fn(KeyCode) -> KeyChord) -> Self {
+        Self {
+            up: vec![make_chord(KeyCode::Up)],
+            down: vec![make_chord(KeyCode::Down)],
+            left: vec![make_chord(KeyCode::Left)],
+            right: vec![make_chord(KeyCode::Right)],
+        }
+    }
+    fn bindings(
+        &self,
+        action: fn(Direction) -> KeyAction,
+    ) -> impl Iterator<Item = (KeyAction, &[KeyChord])> {
+        [
+            (action(Direction::Up), self.up.as_slice()),
+            (action(Direction::Down), self.down.as_slice()),
+            (action(Direction::Left), self.left.as_slice()),
+            (action(Direction::Right), self.right.as_slice()),
+        ]
+        .into_iter()
+    }
+}
+
+/// Actions that can be bound to key chords in the `keymap` section of `rv_cfg.toml`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KeyAction {
+    NextImage,
+    PrevImage,
+    Undo,
+    Redo,
+    DeleteSelected,
+    /// index of the label in the label list
+    Label(usize),
+    LabelPalette,
+    DeactivateTools,
+    ToggleToolsMenu,
+    ToggleMainMenu,
+    Reload,
+    ResetZoom,
+    Rotate,
+    /// send the current image to the inference endpoint
+    Predict,
+    /// tighten the selected boxes to the objects inside
+    SnapToContent,
+    /// review status of the current image
+    SetImageStatus(ImageStatus),
+    /// jump to the next image with the status that is selected in the review window
+    NextImageWithStatus,
+    ToggleBoxesVisible,
+    ToggleLabelsVisible,
+    SelectAll,
+    DeselectAll,
+    CopyToClipboard,
+    PasteFromClipboard,
+    ToggleAutoPaste,
+    /// clone the selected boxes to the mouse position
+    CloneToMouse,
+    /// toggle the visibility of the selected keypoint between visible and occluded
+    ToggleKeypointVisibility,
+    ZoomIn,
+    ZoomOut,
+    /// move the selected boxes, repeated while the chord is held
+    MoveSelected(Direction),
+    /// move the bottom right corner of the selected boxes, repeated while the chord is held
+    MoveMaxCorner(Direction),
+    /// move the top left corner of the selected boxes, repeated while the chord is held
+    MoveMinCorner(Direction),
+}
+impl KeyAction {
+    /// Actions that are triggered as long as their chord is held
+    pub fn is_repeated(&self) -> bool {
+        matches!(
+            self,
+            Self::MoveSelected(_) | Self::MoveMaxCorner(_) | Self::MoveMinCorner(_)
+        )
+    }
+    /// Actions that are triggered when their key is released while the modifiers of the chord
+    /// are held, all others are triggered when their chord is pressed
+    pub fn is_on_release(&self) -> bool {
+        matches!(
+            self,
+            Self::DeleteSelected
+                | Self::Label(_)
+                | Self::LabelPalette
+                | Self::Reload
+                | Self::ToggleBoxesVisible
+                | Self::ToggleLabelsVisible
+                | Self::SelectAll
+                | Self::DeselectAll
+                | Self::CopyToClipboard
+                | Self::PasteFromClipboard
+                | Self::ToggleAutoPaste
+                | Self::CloneToMouse
+                | Self::ToggleKeypointVisibility
+                | Self::ZoomIn
+                | Self::ZoomOut
+        )
+    }
+}
+impl Display for KeyAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NextImage => write!(f, "next image"),
+            Self::PrevImage => write!(f, "previous image"),
+            Self::Undo => write!(f, "undo"),
+            Self::Redo => write!(f, "redo"),
+            Self::DeleteSelected => write!(f, "delete selected"),
+            Self::Label(idx) => write!(f, "label {}", idx + 1),
+            Self::LabelPalette => write!(f, "search labels"),
+            Self::DeactivateTools => write!(f, "deactivate tools"),
+            Self::ToggleToolsMenu => write!(f, "toggle tools menu"),
+            Self::ToggleMainMenu => write!(f, "toggle main menu"),
+            Self::Reload => write!(f, "reload folder"),
+            Self::ResetZoom => write!(f, "reset zoom"),
+            Self::Rotate => write!(f, "rotate by 90°"),
+            Self::Predict => write!(f, "predict"),
+            Self::SnapToContent => write!(f, "snap boxes to content"),
+            Self::SetImageStatus(status) => write!(f, "set image status {status}"),
+            Self::NextImageWithStatus => write!(f, "next image with review status"),
+            Self::ToggleBoxesVisible => write!(f, "show or hide boxes"),
+            Self::ToggleLabelsVisible => write!(f, "show or hide labels of boxes"),
+            Self::SelectAll => write!(f, "select all boxes"),
+            Self::DeselectAll => write!(f, "deselect all boxes"),
+            Self::CopyToClipboard => write!(f, "copy selected boxes"),
+            Self::PasteFromClipboard => write!(f, "paste boxes"),
+            Self::ToggleAutoPaste => write!(f, "toggle auto-paste"),
+            Self::CloneToMouse => write!(f, "clone selected boxes to mouse"),
+            Self::ToggleKeypointVisibility => write!(f, "toggle visibility of keypoint"),
+            Self::ZoomIn => write!(f, "zoom in"),
+            Self::ZoomOut => write!(f, "zoom out"),
+            Self::MoveSelected(dir) => write!(f, "move selected boxes {dir}"),
+            Self::MoveMaxCorner(dir) => write!(f, "move bottom right corners {dir}"),
+            Self::MoveMinCorner(dir) => write!(f, "move top left corners {dir}"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(default)]
+pub struct Keymap {
+    pub next_image: Vec<KeyChord>,
+    pub prev_image: Vec<KeyChord>,
+    pub undo: Vec<KeyChord>,
+    pub redo: Vec<KeyChord>,
+    pub delete_selected: Vec<KeyChord>,
+    /// the n-th chord selects the n-th label
+    pub labels: Vec<KeyChord>,
+    pub label_palette: Vec<KeyChord>,
+    pub deactivate_tools: Vec<KeyChord>,
+    pub toggle_tools_menu: Vec<KeyChord>,
+    pub toggle_main_menu: Vec<KeyChord>,
+    pub reload: Vec<KeyChord>,
+    pub reset_zoom: Vec<KeyChord>,
+    pub zoom_in: Vec<KeyChord>,
+    pub zoom_out: Vec<KeyChord>,
+    pub rotate: Vec<KeyChord>,
+    pub predict: Vec<KeyChord>,
+    pub snap_to_content: Vec<KeyChord>,
+    pub status_todo: Vec<KeyChord>,
+    pub status_in_progress: Vec<KeyChord>,
+    pub status_done: Vec<KeyChord>,
+    pub status_needs_fix: Vec<KeyChord>,
+    pub next_with_status: Vec<KeyChord>,
+    pub toggle_boxes_visible: Vec<KeyChord>,
+    pub toggle_labels_visible: Vec<KeyChord>,
+    pub select_all: Vec<KeyChord>,
+    pub deselect_all: Vec<KeyChord>,
+    pub copy: Vec<KeyChord>,
+    pub paste: Vec<KeyChord>,
+    pub toggle_auto_paste: Vec<KeyChord>,
+    pub clone_to_mouse: Vec<KeyChord>,
+    pub toggle_keypoint_visibility: Vec<KeyChord>,
+    pub move_selected: DirectionChords,
+    pub move_max_corner: DirectionChords,
+    pub move_min_corner: DirectionChords,
+    /// tool name -> chord that activates the tool
+    pub activate_tool: BTreeMap<String, KeyChord>,
+}
+impl Keymap {
+    pub fn bindings(&self) -> Vec<(KeyAction, &[KeyChord])> {
+        let mut bindings: Vec<(KeyAction, &[KeyChord])> = vec![
+            (KeyAction::NextImage, &self.next_image),
+            (KeyAction::PrevImage, &self.prev_image),
+            (KeyAction::Undo, &self.undo),
+            (KeyAction::Redo, &self.redo),
+            (KeyAction::DeleteSelected, &self.delete_selected),
+            (KeyAction::LabelPalette, &self.label_palette),
+            (KeyAction::DeactivateTools, &self.deactivate_tools),
+            (KeyAction::ToggleToolsMenu, &self.toggle_tools_menu),
+            (KeyAction::ToggleMainMenu, &self.toggle_main_menu),
+            (KeyAction::Reload, &self.reload),
+            (KeyAction::ResetZoom, &self.reset_zoom),
+            (KeyAction::ZoomIn, &self.zoom_in),
+            (KeyAction::ZoomOut, &self.zoom_out),
+            (KeyAction::Rotate, &self.rotate),
+            (KeyAction::Predict, &self.predict),
+            (KeyAction::SnapToContent, &self.snap_to_content),
+            (
+                KeyAction::SetImageStatus(ImageStatus::Todo),
+                &self.status_todo,
+            ),
+            (
+                KeyAction::SetImageStatus(ImageStatus::InProgress),
+                &self.status_in_progress,
+            ),
+            (
+                KeyAction::SetImageStatus(ImageStatus::Done),
+                &self.status_done,
+            ),
+            (
+                KeyAction::SetImageStatus(ImageStatus::NeedsFix),
+                &self.status_needs_fix,
+            ),
+            (KeyAction::NextImageWithStatus, &self.next_with_status),
+            (KeyAction::ToggleBoxesVisible, &self.toggle_boxes_visible),
+            (KeyAction::ToggleLabelsVisible, &self.toggle_labels_visible),
+            (KeyAction::SelectAll, &self.select_all),
+            (KeyAction::DeselectAll, &self.deselect_all),
+            (KeyAction::CopyToClipboard, &self.copy),
+            (KeyAction::PasteFromClipboard, &self.paste),
+            (KeyAction::ToggleAutoPaste, &self.toggle_auto_paste),
+            (KeyAction::CloneToMouse, &self.clone_to_mouse),
+            (
+                KeyAction::ToggleKeypointVisibility,
+                &self.toggle_keypoint_visibility,
+            ),
+        ];
+        bindings.extend(self.move_selected.bindings(KeyAction::MoveSelected));
+        bindings.extend(self.move_max_corner.bindings(KeyAction::MoveMaxCorner));
+        bindings.extend(self.move_min_corner.bindings(KeyAction::MoveMinCorner));
+        bindings.extend(
+            self.labels
+                .iter()
+                .enumerate()
+                .map(|(idx, chord)| (KeyAction::Label(idx), std::slice::from_ref(chord))),
+        );
+        bindings
+    }
+
+    /// Actions whose chords have been pressed or released, or are held for repeated actions
+    pub fn triggered_actions(&self, events: &Events) -> Vec<KeyAction> {
+        self.bindings()
+            .into_iter()
+            .filter(|(action, chords)| {
+                chords.iter().any(|c| {
+                    if action.is_repeated() {
+                        events.held_chord(c)
+                    } else if action.is_on_release() {
+                        events.released_chord(c)
+                    } else {
+                        events.pressed_chord(c)
+                    }
+                })
+            })
+            .map(|(action, _)| action)
+            .collect()
+    }
+
+    /// Repeated actions whose keys have been released independent of the modifiers
+    pub fn released_actions(&self, events: &Events) -> Vec<KeyAction> {
+        self.bindings()
+            .into_iter()
+            .filter(|(action, chords)| {
+                action.is_repeated() && chords.iter().any(|c| events.released(c.key))
+            })
+            .map(|(action, _)| action)
+            .collect()
+    }
+
+    /// name of the tool whose activation chord has been pressed
+    pub fn activated_tool(&self, events: &Events) -> Option<&str> {
+        self.activate_tool
+            .iter()
+            .find(|(_, chord)| events.pressed_chord(chord))
+            .map(|(name, _)| name.as_str())
+    }
+}
+impl Default for Keymap {
+    fn default() -> Self {
+        let labels = [
+            KeyCode::Key1,
+            KeyCode::Key2,
+            KeyCode::Key3,
+            KeyCode::Key4,
+            KeyCode::Key5,
+            KeyCode::Key6,
+            KeyCode::Key7,
+            KeyCode::Key8,
+            KeyCode::Key9,
+        ];
+        Self {
+            next_image: vec![KeyChord::new(KeyCode::PageDown)],
+            prev_image: vec![KeyChord::new(KeyCode::PageUp)],
+            undo: vec![KeyChord::ctrl(KeyCode::Z)],
+            redo: vec![KeyChord::ctrl(KeyCode::Y)],
+            delete_selected: vec![
+                KeyChord::new(KeyCode::Delete),
+                KeyChord::ctrl(KeyCode::Delete),
+                KeyChord::new(KeyCode::Back),
+            ],
+            labels: labels.into_iter().map(KeyChord::new).collect(),
+            label_palette: vec![KeyChord::ctrl(KeyCode::P)],
+            deactivate_tools: vec![KeyChord::alt(KeyCode::Q)],
+            toggle_tools_menu: vec![KeyChord::ctrl(KeyCode::T)],
+            toggle_main_menu: vec![KeyChord::ctrl(KeyCode::M)],
+            reload: vec![KeyChord::new(KeyCode::F5)],
+            reset_zoom: vec![
+                KeyChord::new(KeyCode::Escape),
+                KeyChord::ctrl(KeyCode::Key0),
+            ],
+            zoom_in: vec![KeyChord::ctrl(KeyCode::PlusEquals)],
+            zoom_out: vec![KeyChord::ctrl(KeyCode::Minus)],
+            rotate: vec![KeyChord::new(KeyCode::R)],
+            predict: vec![KeyChord::ctrl(KeyCode::I)],
+            snap_to_content: vec![KeyChord::alt(KeyCode::S)],
+            status_todo: vec![KeyChord::alt(KeyCode::Key1)],
+            status_in_progress: vec![KeyChord::alt(KeyCode::Key2)],
+            status_done: vec![KeyChord::alt(KeyCode::Key3)],
+            status_needs_fix: vec![KeyChord::alt(KeyCode::Key4)],
+            next_with_status: vec![KeyChord::alt(KeyCode::N)],
+            toggle_boxes_visible: vec![KeyChord::ctrl(KeyCode::H)],
+            toggle_labels_visible: vec![KeyChord::ctrl(KeyCode::L)],
+            select_all: vec![KeyChord::ctrl(KeyCode::A)],
+            deselect_all: vec![KeyChord::ctrl(KeyCode::D)],
+            copy: vec![KeyChord::ctrl(KeyCode::C)],
+            paste: vec![KeyChord::ctrl(KeyCode::V)],
+            toggle_auto_paste: vec![KeyChord::new(KeyCode::V)],
+            clone_to_mouse: vec![KeyChord::new(KeyCode::C)],
+            toggle_keypoint_visibility: vec![KeyChord::new(KeyCode::V)],
+            move_selected: DirectionChords::arrows(KeyChord::alt),
+            move_max_corner: DirectionChords::arrows(KeyChord::new),
+            move_min_corner: DirectionChords::arrows(KeyChord::ctrl),
+            activate_tool: BTreeMap::from([
+                (BBOX_NAME.to_string(), KeyChord::alt(KeyCode::B)),
+                (ZOOM_NAME.to_string(), KeyChord::alt(KeyCode::Z)),
+                (CLASSIFY_NAME.to_string(), KeyChord::alt(KeyCode::C)),
+                (KEYPOINTS_NAME.to_string(), KeyChord::alt(KeyCode::K)),
+            ]),
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::events::Event;
+
+#[test]
+fn test_chord() -> RvResult<()> {
+    let chord: KeyChord = "Ctrl+Shift+Z".parse()?;
+    assert_eq!(
+        chord,
+        KeyChord {
+            shift: true,
+            ..KeyChord::ctrl(KeyCode::Z)
+        }
+    );
+    assert_eq!(chord.to_string(), "Ctrl+Shift+Z");
+    assert_eq!("alt + F2".parse::<KeyChord>()?, KeyChord::alt(KeyCode::F2));
+    assert!("Ctrl+".parse::<KeyChord>().is_err());
+    assert!("Hyper+A".parse::<KeyChord>().is_err());
+    assert!("Ctrl+Ä".parse::<KeyChord>().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_keymap() -> RvResult<()> {
+    let keymap: Keymap = toml::from_str(
+        r#"
+        undo = ["Ctrl+U", "Back"]
+        labels = ["Q", "W", "E"]
+        [activate_tool]
+        BBox = "F1"
+        "#,
+    )
+    .map_err(|e| rverr!("{:?}", e))?;
+    // unspecified actions keep their defaults
+    assert_eq!(keymap.redo, vec![KeyChord::ctrl(KeyCode::Y)]);
+    let key_events = |event, modifiers: Vec<KeyCode>| {
+        let mut events = vec![event];
+        events.extend(modifiers.into_iter().map(Event::Held));
+        Events::default().events(events).actions(&keymap)
+    };
+    let events = |key, modifiers| key_events(Event::Pressed(key), modifiers);
+    let released = |key, modifiers| key_events(Event::Released(key), modifiers);
+    assert!(events(KeyCode::U, vec![KeyCode::Ctrl]).triggered(KeyAction::Undo));
+    assert!(!events(KeyCode::U, vec![]).triggered(KeyAction::Undo));
+    assert!(events(KeyCode::Back, vec![]).triggered(KeyAction::Undo));
+    // actions of the tools are triggered on release
+    assert!(released(KeyCode::W, vec![]).triggered(KeyAction::Label(1)));
+    assert!(!events(KeyCode::W, vec![]).triggered(KeyAction::Label(1)));
+    assert!(!released(KeyCode::Key2, vec![]).triggered(KeyAction::Label(1)));
+    assert!(released(KeyCode::A, vec![KeyCode::Ctrl]).triggered(KeyAction::SelectAll));
+    assert!(!released(KeyCode::A, vec![]).triggered(KeyAction::SelectAll));
+    assert!(released(KeyCode::Delete, vec![KeyCode::Ctrl]).triggered(KeyAction::DeleteSelected));
+    // repeated actions are triggered while held and released independent of the modifiers
+    let held = Events::default()
+        .events(vec![Event::Held(KeyCode::Up), Event::Held(KeyCode::Alt)])
+        .actions(&keymap);
+    assert!(held.triggered(KeyAction::MoveSelected(Direction::Up)));
+    assert!(!held.triggered(KeyAction::MoveMaxCorner(Direction::Up)));
+    let released = Events::default()
+        .events(vec![Event::Released(KeyCode::Up)])
+        .actions(&keymap);
+    assert!(released.released_action(KeyAction::MoveSelected(Direction::Up)));
+    assert!(released.released_action(KeyAction::MoveMaxCorner(Direction::Up)));
+    assert_eq!(
+        keymap.activated_tool(&events(KeyCode::F1, vec![])),
+        Some("BBox")
+    );
+    let toml_str = toml::to_string(&keymap).map_err(|e| rverr!("{:?}", e))?;
+    let keymap_back: Keymap = toml::from_str(&toml_str).map_err(|e| rverr!("{:?}", e))?;
+    assert_eq!(keymap, keymap_back);
+    Ok(())
+}
@@ -9,9 +9,13 @@ pub mod history;
 pub mod httpserver;
 mod image_reader;
 pub mod image_util;
+mod inference;
+pub mod keymap;
 pub mod main_loop;
 pub mod menu;
 mod paths_selector;
+#[cfg(feature = "python")]
+mod python;
 pub mod result;
 mod ssh;
 mod threadpool;
@@ -22,7 +26,10 @@ mod util;
 mod view;
 pub mod world;
 pub use domain::{GeoFig, Polygon, Shape, BB};
-pub use drawme::{Annotation, Stroke, UpdateAnnos, UpdateImage, UpdateView, UpdateZoomBox};
+pub use drawme::{
+    Annotation, BboxAnnotation, KeypointsAnnotation, Stroke, UpdateAnnos, UpdateImage, UpdateView,
+    UpdateZoomBox,
+};
 pub use events::{Event, Events, KeyCode};
 pub use main_loop::MainEventLoop;
 pub use tools_data::annotations;
@@ -5,25 +5,42 @@ use crate::cfg::{self, Cfg};
 use crate::control::{Control, Info};
 use crate::domain::PtI;
 use crate::drawme::ImageInfo;
-use crate::events::{Events, KeyCode};
+use crate::events::Events;
 use crate::file_util::make_prjcfg_filename;
-use crate::history::History;
+use crate::history::{History, Record};
+use crate::httpserver::{self, ApiCall, ApiRequest, ApiResponse, EventHub, ServerEvent};
+use crate::inference::{ImageSource, Predictor, SharedReader};
+use crate::keymap::KeyAction;
 use crate::menu::{are_tools_active, Menu, ToolSelectMenu};
-use crate::result::RvResult;
-use crate::tools::{make_tool_vec, Manipulate, ToolState, ToolWrapper, BBOX_NAME, ZOOM_NAME};
+use crate::result::{RvError, RvResult};
+use crate::tools::{
+    make_tool_vec, Manipulate, ToolState, ToolWrapper, BBOX_NAME, BRUSH_NAME, CLASSIFY_NAME,
+    KEYPOINTS_NAME,
+};
+use crate::tools_data::image_review::ImageStatus;
+use crate::tools_data::{
+    image_export::ImageExporter,
+    mask_io::{self, MaskStyle},
+    mot_io, patch_io,
+};
+use crate::tools_data::{BboxSpecificData, ToolSpecifics, ToolsData};
 use crate::world::World;
-use crate::{apply_tool_method_mut, httpserver, image_util, UpdateView};
+use crate::{apply_tool_method_mut, image_util, rverr, tools_data, Shape, UpdateView};
 use egui::Context;
 use image::{DynamicImage, GenericImageView};
 use image::{ImageBuffer, Rgb};
 use lazy_static::lazy_static;
+use serde_json::{json, Value};
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::mem;
 use std::sync::mpsc::Receiver;
+use std::time::Duration;
 
 const START_WIDTH: u32 = 640;
 const START_HEIGHT: u32 = 480;
+/// Changes of these tools are announced as annotation changes to the event stream
+const ANNOTATION_TOOLS: [&str; 4] = [BBOX_NAME, BRUSH_NAME, CLASSIFY_NAME, KEYPOINTS_NAME];
 
 fn cfg_static_ref() -> &'static Cfg {
     lazy_static! {
@@ -79,21 +96,6 @@ fn apply_tools(
     (world, history)
 }
 
-macro_rules! activate_tool_event {
-    ($key:ident, $name:expr, $input:expr, $rat:expr, $tools:expr) => {
-        if $input.held_alt() && $input.pressed(KeyCode::$key) {
-            $rat = Some(
-                $tools
-                    .iter()
-                    .enumerate()
-                    .find(|(_, t)| t.name == $name)
-                    .unwrap()
-                    .0,
-            );
-        }
-    };
-}
-
 fn empty_world() -> World {
     World::from_real_im(
         DynamicImage::ImageRgb8(ImageBuffer::<Rgb<u8>, _>::new(START_WIDTH, START_HEIGHT)),
@@ -109,9 +111,14 @@ pub struct MainEventLoop {
     history: History,
     tools: Vec<ToolState>,
     recently_activated_tool_idx: Option<usize>,
-    rx_from_http: Option<Receiver<RvResult<String>>>,
-    http_addr: String,
+    rx_from_http: Option<Receiver<ApiCall>>,
+    http_events: EventHub,
+    published_file_path: Option<String>,
     loop_counter: u128,
+    claim_checked_file: Option<String>,
+    claimed_by_other: Option<String>,
+    predictor: Predictor,
+    image_exporter: ImageExporter,
 }
 impl Default for MainEventLoop {
     fn default() -> Self {
@@ -134,29 +141,507 @@ impl Default for MainEventLoop {
             };
         }
         let tools = make_tool_vec();
-        let http_addr = http_address().to_string();
         // http server state
-        let rx_from_http = if let Ok((_, rx)) = httpserver::launch(http_addr.clone()) {
-            Some(rx)
-        } else {
-            None
+        let token = cfg_static_ref().http_token().map(|t| t.to_string());
+        let mut menu = Menu::default();
+        let http_events = EventHub::default();
+        let rx_from_http = match httpserver::launch(http_address(), token, http_events.clone()) {
+            Ok((_, rx)) => Some(rx),
+            Err(e) => {
+                menu.show_info(Info::Error(format!("{e:?}")));
+                None
+            }
         };
         Self {
             world,
             ctrl,
             tools,
-            http_addr,
             tools_select_menu: ToolSelectMenu::default(),
-            menu: Menu::default(),
+            menu,
             history: History::default(),
             recently_activated_tool_idx: None,
             rx_from_http,
+            http_events,
+            published_file_path: None,
             loop_counter: 0,
+            claim_checked_file: None,
+            claimed_by_other: None,
+            predictor: Predictor::default(),
+            image_exporter: ImageExporter::default(),
         }
     }
 }
 impl MainEventLoop {
+    /// Imported annotations of images that are not in the opened folder are moved to images with
+    /// the same file name, e.g., after the dataset has been copied to another machine.
+    fn match_imported_paths(&mut self) {
+        let is_path_matching_triggered = match self.world.data.tools_data_map.get_mut(BBOX_NAME) {
+            Some(bbox_data) => mem::take(
+                &mut bbox_data
+                    .specifics
+                    .bbox_mut()
+                    .options
+                    .is_path_matching_triggered,
+            ),
+            None => false,
+        };
+        if !is_path_matching_triggered {
+            return;
+        }
+        let file_paths = self
+            .ctrl
+            .paths_navigator
+            .paths_selector()
+            .as_ref()
+            .map(|ps| ps.file_paths())
+            .unwrap_or_default();
+        if let Some(bbox_data) = self.world.data.tools_data_map.get_mut(BBOX_NAME) {
+            let bbox_data = bbox_data.specifics.bbox_mut();
+            let n_moved = bbox_data.match_by_filename(&file_paths);
+            if n_moved > 0 {
+                println!("matched annotations of {n_moved} images by file name");
+                let are_boxes_visible = bbox_data.options.are_boxes_visible;
+                self.world
+                    .request_redraw_annotations(BBOX_NAME, are_boxes_visible);
+            }
+        }
+    }
+    /// Sends the current image or all images of the filtered folder to the inference endpoint.
+    fn predict_if_triggered(&mut self) -> RvResult<()> {
+        let (is_predict_triggered, is_batch_predict_triggered) =
+            match self.world.data.tools_data_map.get_mut(BBOX_NAME) {
+                Some(bbox_data) => {
+                    let options = &mut bbox_data.specifics.bbox_mut().options;
+                    (
+                        mem::take(&mut options.is_predict_triggered),
+                        mem::take(&mut options.is_batch_predict_triggered),
+                    )
+                }
+                None => (false, false),
+            };
+        if !is_predict_triggered && !is_batch_predict_triggered {
+            return Ok(());
+        }
+        let inference_cfg = self.ctrl.cfg.inference_cfg.as_ref().ok_or_else(|| {
+            RvError::new("no inference endpoint, add the section [inference_cfg] to rv_cfg.toml")
+        })?;
+        if is_batch_predict_triggered {
+            let file_paths = self
+                .ctrl
+                .paths_navigator
+                .paths_selector()
+                .as_ref()
+                .map(|ps| ps.filtered_file_paths())
+                .unwrap_or_default();
+            let reader = SharedReader::new(self.ctrl.cfg.clone());
+            for file_path in file_paths {
+                self.predictor.predict(
+                    inference_cfg,
+                    file_path.to_string(),
+                    ImageSource::Read {
+                        reader: reader.clone(),
+                        file_path: file_path.to_string(),
+                    },
+                )?;
+            }
+        } else if let Some(file_path) = &self.world.data.meta_data.file_path {
+            self.predictor.predict(
+                inference_cfg,
+                file_path.clone(),
+                ImageSource::Loaded(self.world.data.im_background().clone()),
+            )?;
+        }
+        Ok(())
+    }
+
+    fn selected_file_path(&self) -> Option<String> {
+        self.ctrl
+            .file_selected_idx
+            .and_then(|idx| self.ctrl.paths_navigator.file_path(idx))
+            .map(|fp| fp.to_string())
+    }
+
+    fn set_image_status(&mut self, status: ImageStatus) {
+        if let Some(file_path) = self.selected_file_path() {
+            self.world
+                .data
+                .tools_data_map
+                .entry(BBOX_NAME)
+                .or_insert_with(|| ToolsData::new(ToolSpecifics::Bbox(BboxSpecificData::new())))
+                .specifics
+                .bbox_mut()
+                .set_image_status(&file_path, status);
+        }
+    }
+
+    /// Cuts the annotated objects out of all annotated images in background threads.
+    fn export_patches_if_triggered(&mut self) -> RvResult<()> {
+        let bbox_data = match self.world.data.tools_data_map.get_mut(BBOX_NAME) {
+            Some(bbox_data) => bbox_data.specifics.bbox_mut(),
+            None => return Ok(()),
+        };
+        if !mem::take(&mut bbox_data.options.is_patch_export_triggered) {
+            return Ok(());
+        }
+        let jobs = patch_io::patch_jobs(bbox_data, bbox_data.options.patch_padding);
+        let folder = patch_io::patches_folder(&self.world.data.meta_data)?;
+        println!("exporting patches of {} images to {folder:?}", jobs.len());
+        let cfg = self.ctrl.cfg.clone();
+        let size = bbox_data.options.patch_size;
+        self.image_exporter.export(jobs, move |jobs| {
+            patch_io::export_patches(cfg.clone(), jobs, &folder, size)
+        })
+    }
+
+    /// Writes semantic and instance masks and optionally overlays of all annotated images in
+    /// background threads.
+    fn export_masks_if_triggered(&mut self) -> RvResult<()> {
+        let bbox_data = match self.world.data.tools_data_map.get_mut(BBOX_NAME) {
+            Some(bbox_data) => bbox_data.specifics.bbox_mut(),
+            None => return Ok(()),
+        };
+        if !mem::take(&mut bbox_data.options.is_mask_export_triggered) {
+            return Ok(());
+        }
+        let folder = mask_io::masks_folder(&self.world.data.meta_data, bbox_data.labels())?;
+        let jobs = mask_io::mask_jobs(bbox_data);
+        println!("exporting masks of {} images to {folder:?}", jobs.len());
+        let style = MaskStyle {
+            labels: bbox_data.labels().clone(),
+            colors: bbox_data.colors().clone(),
+            fill_alpha: bbox_data.options.fill_alpha,
+            with_overlays: bbox_data.options.export_overlays,
+        };
+        let cfg = self.ctrl.cfg.clone();
+        self.image_exporter.export(jobs, move |jobs| {
+            mask_io::export_masks(cfg.clone(), jobs, &folder, &style)
+        })
+    }
+
+    /// Writes the tracks as MOT files. Frames are numbered among all files of the opened folder
+    /// such that images without annotations do not shift the frame numbers.
+    fn export_mot_if_triggered(&mut self) -> RvResult<()> {
+        let bbox_data = match self.world.data.tools_data_map.get_mut(BBOX_NAME) {
+            Some(bbox_data) => bbox_data.specifics.bbox_mut(),
+            None => return Ok(()),
+        };
+        if !mem::take(&mut bbox_data.options.is_mot_export_triggered) {
+            return Ok(());
+        }
+        let file_paths = self
+            .ctrl
+            .paths_navigator
+            .paths_selector()
+            .as_ref()
+            .map(|ps| ps.file_paths())
+            .unwrap_or_default();
+        mot_io::write_mot(&self.world.data.meta_data, bbox_data, &file_paths)
+            .map(|_| ())
+            .map_err(|e| rverr!("could not export mot files due to {:?}", e))
+    }
+
+    fn check_image_export(&mut self) {
+        for res in self.image_exporter.results() {
+            match res {
+                Ok(n_written) => println!(
+                    "exported {n_written} files, {} job(s) pending",
+                    self.image_exporter.n_pending()
+                ),
+                Err(e) => self
+                    .menu
+                    .show_info(Info::Error(format!("export failed, {e:?}"))),
+            }
+        }
+    }
+
+    /// Claims the shown image or refreshes our claim on it, see [`Control::claim_image`].
+    fn refresh_claim(&mut self) {
+        self.claimed_by_other = None;
+        if let Some(fp) = &self.claim_checked_file {
+            match self.ctrl.claim_image(fp) {
+                Ok(claim) => self.claimed_by_other = claim.map(|c| c.user),
+                Err(e) => self.menu.show_info(Info::Error(format!("{e:?}"))),
+            }
+        }
+    }
+
+    /// Announces the changes in the history, attributed to their tools, for the shown image.
+    fn publish_changes(&mut self) {
+        let actors = self.history.take_change_actors();
+        if !actors.is_empty() {
+            // edits keep our claim on the shown image from going stale
+            self.refresh_claim();
+        }
+        let mut tools = actors
+            .into_iter()
+            .filter(|actor| ANNOTATION_TOOLS.contains(actor))
+            .collect::<Vec<_>>();
+        tools.sort_unstable();
+        tools.dedup();
+        for tool in tools {
+            self.http_events.publish(ServerEvent::AnnotationsChanged {
+                tool: tool.to_string(),
+                path: self.world.data.meta_data.file_path.clone(),
+            });
+        }
+    }
+
+    /// Announces bounding box changes of images that are not necessarily the shown one. The
+    /// record of the changes is pushed to the history without being announced again for the
+    /// shown image.
+    fn publish_annotation_changes(
+        &mut self,
+        file_paths: impl IntoIterator<Item = String>,
+        record: Option<Record>,
+    ) {
+        if let Some(record) = record {
+            self.publish_changes();
+            self.history.push(record);
+            self.history.take_change_actors();
+        }
+        for file_path in file_paths {
+            self.http_events.publish(ServerEvent::AnnotationsChanged {
+                tool: BBOX_NAME.to_string(),
+                path: Some(file_path),
+            });
+        }
+    }
+
+    /// Tells the clients of the event stream of the http server what has changed.
+    fn publish_events(&mut self) {
+        let file_path = self.selected_file_path();
+        if file_path != self.published_file_path {
+            self.http_events.publish(ServerEvent::ImageSelected {
+                index: self.ctrl.file_selected_idx,
+                path: file_path.clone(),
+            });
+            self.published_file_path = file_path;
+        }
+        self.publish_changes();
+        if let Some(path) = self.ctrl.saved_prj_path.take() {
+            self.http_events.publish(ServerEvent::ProjectSaved { path });
+        }
+    }
+
+    /// Inserts the predictions of finished requests as pre-annotations.
+    fn insert_predictions(&mut self) {
+        let results = self.predictor.results();
+        if let (false, Some(bbox_data)) = (
+            results.is_empty(),
+            self.world.data.tools_data_map.get_mut(BBOX_NAME),
+        ) {
+            let bbox_data = bbox_data.specifics.bbox_mut();
+            let mut errors = vec![];
+            let mut file_paths = vec![];
+            for res in results {
+                match res.and_then(|res| {
+                    res.predictions
+                        .map(|preds| (res.file_path, res.shape, preds))
+                }) {
+                    Ok((file_path, shape, predictions)) => {
+                        let skipped = bbox_data.set_predictions(&file_path, shape, &predictions);
+                        if !skipped.is_empty() {
+                            errors.push(format!(
+                                "skipped labels {skipped:?} of {file_path} since they are not among the locked labels"
+                            ));
+                        }
+                        file_paths.push(file_path);
+                    }
+                    Err(e) => errors.push(format!("{e:?}")),
+                }
+            }
+            bbox_data.options.is_redraw_annos_triggered = true;
+            // a batch is undone at once
+            let record = (self.predictor.n_pending() == 0)
+                .then(|| Record::new(self.world.data.clone(), BBOX_NAME));
+            self.publish_annotation_changes(file_paths, record);
+            if let Some(e) = errors.last() {
+                self.menu.show_info(Info::Warning(format!(
+                    "{} problem(s) with predictions, {} request(s) pending, last problem: {e}",
+                    errors.len(),
+                    self.predictor.n_pending()
+                )));
+            }
+        }
+    }
+
+    /// The passed path or the path of the selected image if it is in the opened folder
+    fn api_path(&self, path: &Option<String>) -> Result<String, ApiResponse> {
+        let path = path
+            .clone()
+            .or_else(|| self.selected_file_path())
+            .ok_or_else(|| ApiResponse::error(404, "no path passed and no image selected"))?;
+        let is_in_folder = self
+            .ctrl
+            .paths_navigator
+            .paths_selector()
+            .as_ref()
+            .map(|ps| ps.file_paths().contains(&path.as_str()))
+            .unwrap_or(false);
+        if is_in_folder {
+            Ok(path)
+        } else {
+            Err(ApiResponse::error(
+                404,
+                format!("{path} is not in the opened folder"),
+            ))
+        }
+    }
+
+    fn api_result(&mut self, request: &ApiRequest) -> Result<Value, ApiResponse> {
+        match request {
+            ApiRequest::SelectFile(file_label) => {
+                let is_known = self
+                    .ctrl
+                    .paths_navigator
+                    .paths_selector()
+                    .as_ref()
+                    .and_then(|ps| ps.idx_of_file_label(file_label))
+                    .is_some();
+                if !is_known {
+                    return Err(ApiResponse::error(
+                        404,
+                        format!("{file_label} is not in the opened folder"),
+                    ));
+                }
+                self.ctrl.paths_navigator.select_file_label(file_label);
+                self.ctrl
+                    .paths_navigator
+                    .activate_scroll_to_selected_label();
+                Ok(json!({}))
+            }
+            ApiRequest::ListFiles => {
+                let files = self
+                    .ctrl
+                    .paths_navigator
+                    .paths_selector()
+                    .as_ref()
+                    .map(|ps| ps.filtered_file_paths())
+                    .unwrap_or_default();
+                Ok(json!({ "files": files }))
+            }
+            ApiRequest::CurrentImage => Ok(json!({
+                "index": self.ctrl.file_selected_idx,
+                "path": self.selected_file_path()
+            })),
+            ApiRequest::GetAnnotations(path) => {
+                let path = self.api_path(path)?;
+                let annos = self
+                    .world
+                    .data
+                    .tools_data_map
+                    .get(BBOX_NAME)
+                    .map(|bbox_data| httpserver::get_annotations(bbox_data.specifics.bbox(), &path))
+                    .unwrap_or_default();
+                let mut value = serde_json::to_value(annos)
+                    .map_err(|e| ApiResponse::error(500, format!("{e:?}")))?;
+                value["path"] = json!(path);
+                Ok(value)
+            }
+            ApiRequest::SetAnnotations { path, annotations } => {
+                let path = self.api_path(path)?;
+                let claim = self
+                    .ctrl
+                    .claim_of_other(&path)
+                    .map_err(|e| ApiResponse::error(500, e))?;
+                if let Some(claim) = claim {
+                    return Err(ApiResponse::error(
+                        409,
+                        format!("{path} is claimed by {}", claim.user),
+                    ));
+                }
+                // the shape of the shown image is only known for the image itself
+                let shown_shape = if self.world.data.meta_data.file_path.as_ref() == Some(&path) {
+                    Some(self.world.data.shape())
+                } else {
+                    None
+                };
+                let bbox_data = self
+                    .world
+                    .data
+                    .tools_data_map
+                    .entry(BBOX_NAME)
+                    .or_insert_with(|| ToolsData::new(ToolSpecifics::Bbox(BboxSpecificData::new())))
+                    .specifics
+                    .bbox_mut();
+                let shape = match (annotations.width, annotations.height) {
+                    (Some(w), Some(h)) => Some(Shape::new(w, h)),
+                    _ => bbox_data
+                        .annotations_map()
+                        .get(&path)
+                        .map(|(_, shape)| *shape)
+                        .or(shown_shape),
+                }
+                .ok_or_else(|| {
+                    ApiResponse::error(400, "unknown image size, pass width and height")
+                })?;
+                let n_annotations =
+                    httpserver::set_annotations(bbox_data, &path, shape, &annotations.annotations)
+                        .map_err(|e| ApiResponse::error(400, e))?;
+                bbox_data.options.is_redraw_annos_triggered = true;
+                let record = Record::new(self.world.data.clone(), BBOX_NAME);
+                self.publish_annotation_changes([path.clone()], Some(record));
+                Ok(json!({ "path": path, "n_annotations": n_annotations }))
+            }
+            ApiRequest::SetLabel(label) => {
+                let bbox_data = self
+                    .world
+                    .data
+                    .tools_data_map
+                    .entry(BBOX_NAME)
+                    .or_insert_with(|| ToolsData::new(ToolSpecifics::Bbox(BboxSpecificData::new())))
+                    .specifics
+                    .bbox_mut();
+                let cat_idx = bbox_data
+                    .labels()
+                    .iter()
+                    .position(|l| l == label)
+                    .ok_or_else(|| ApiResponse::error(404, format!("unknown label {label}")))?;
+                bbox_data.set_cat_idx_current(cat_idx);
+                Ok(json!({ "label": label, "index": cat_idx }))
+            }
+            ApiRequest::Save => {
+                let path = self
+                    .ctrl
+                    .save(&mut self.world.data.tools_data_map)
+                    .map_err(|e| ApiResponse::error(500, e))?;
+                Ok(json!({ "path": path }))
+            }
+            ApiRequest::ExportCoco => {
+                let bbox_data = self
+                    .world
+                    .data
+                    .tools_data_map
+                    .get(BBOX_NAME)
+                    .ok_or_else(|| ApiResponse::error(404, "no annotations to export"))?
+                    .specifics
+                    .bbox()
+                    .clone();
+                let meta_data = self
+                    .ctrl
+                    .meta_data(self.ctrl.file_selected_idx, Some(false));
+                let path = tools_data::write_coco(&meta_data, bbox_data)
+                    .map_err(|e| ApiResponse::error(500, e))?;
+                Ok(json!({ "path": path }))
+            }
+        }
+    }
+
+    /// Answers a request of the remote control api of the http server.
+    fn handle_api_call(&mut self, call: ApiCall) {
+        let response = match self.api_result(&call.request) {
+            Ok(body) => ApiResponse::ok(body),
+            Err(response) => response,
+        };
+        call.respond(response);
+    }
+
     pub fn one_iteration(&mut self, e: &Events, ctx: &Context) -> RvResult<UpdateView> {
+        let e = &e.clone().actions(&self.ctrl.cfg.keymap);
+        if self.rx_from_http.is_some() {
+            // requests of the http server are answered also if the user does nothing
+            ctx.request_repaint_after(Duration::from_millis(100));
+        }
         self.menu
             .ui(ctx, &mut self.ctrl, &mut self.world.data.tools_data_map);
         egui::SidePanel::right("my_panel")
@@ -199,7 +684,7 @@ impl MainEventLoop {
             }
         }
 
-        if e.held_alt() && e.pressed(KeyCode::Q) {
+        if e.triggered(KeyAction::DeactivateTools) {
             println!("deactivate all tools");
             for t in self.tools.iter_mut() {
                 let meta_data = self.ctrl.meta_data(
@@ -211,66 +696,66 @@ impl MainEventLoop {
                     t.deactivate(mem::take(&mut self.world), mem::take(&mut self.history));
             }
         }
-        activate_tool_event!(
-            B,
-            BBOX_NAME,
-            e,
-            self.recently_activated_tool_idx,
-            self.tools
-        );
-        activate_tool_event!(
-            Z,
-            ZOOM_NAME,
-            e,
-            self.recently_activated_tool_idx,
-            self.tools
-        );
+        if let Some(name) = self.ctrl.cfg.keymap.activated_tool(e) {
+            match self.tools.iter().position(|t| t.name == name) {
+                Some(idx) => self.recently_activated_tool_idx = Some(idx),
+                None => println!("cannot activate unknown tool {name}"),
+            }
+        }
 
-        if e.held_ctrl() && e.pressed(KeyCode::T) {
+        if e.triggered(KeyAction::ToggleToolsMenu) {
             self.tools_select_menu.toggle();
         }
-        if e.held_ctrl() && e.pressed(KeyCode::M) {
+        if e.triggered(KeyAction::ToggleMainMenu) {
             self.menu.toggle();
         }
-        if e.released(KeyCode::F5) {
+        if e.triggered(KeyAction::Reload) {
             if let Err(e) = self.ctrl.reload(self.menu.sort_type()) {
                 self.menu.show_info(Info::Error(format!("{e:?}")));
             }
         }
-        if e.pressed(KeyCode::PageDown) {
+        if e.triggered(KeyAction::NextImage) {
             self.ctrl.paths_navigator.next();
         }
-        if e.pressed(KeyCode::PageUp) {
+        if e.triggered(KeyAction::PrevImage) {
             self.ctrl.paths_navigator.prev();
         }
-        if e.pressed(KeyCode::Escape) {
+        if are_tools_active(&self.menu, &self.tools_select_menu) {
+            for status in ImageStatus::ALL {
+                if e.triggered(KeyAction::SetImageStatus(status)) {
+                    self.set_image_status(status);
+                }
+            }
+        }
+        if e.triggered(KeyAction::NextImageWithStatus) {
+            let status = self.menu.review_jump_status();
+            if !self
+                .ctrl
+                .select_next_with_status(status, &self.world.data.tools_data_map)
+            {
+                self.menu.show_info(Info::Warning(format!(
+                    "no other image with status {status}"
+                )));
+            }
+        }
+        if e.triggered(KeyAction::ResetZoom) {
             self.world.set_zoom_box(None);
         }
 
-        // check for new image requests from http server
-        let rx_match = &self.rx_from_http.as_ref().map(|rx| rx.try_iter().last());
-        if let Some(Some(Ok(file_label))) = rx_match {
-            self.ctrl.paths_navigator.select_file_label(file_label);
-            self.ctrl
-                .paths_navigator
-                .activate_scroll_to_selected_label();
-        } else if let Some(Some(Err(e))) = rx_match {
-            // if the server thread sends an error we restart the server
-            println!("{e:?}");
-            (self.http_addr, self.rx_from_http) =
-                match httpserver::restart_with_increased_port(&self.http_addr) {
-                    Ok(x) => x,
-                    Err(e) => {
-                        println!("{e:?}");
-                        (self.http_addr.to_string(), None)
-                    }
-                };
+        // answer requests from the http server
+        let calls = self
+            .rx_from_http
+            .as_ref()
+            .map(|rx| rx.try_iter().collect::<Vec<_>>())
+            .unwrap_or_default();
+        for call in calls {
+            self.handle_api_call(call);
         }
 
         // load new image if requested by a menu click or by the http server
-        let ims_raw_idx_pair = if e.held_ctrl() && e.pressed(KeyCode::Z) {
+        let ims_raw_idx_pair = if e.triggered(KeyAction::Undo) {
             self.ctrl.undo(&mut self.history)
-        } else if e.held_ctrl() && e.pressed(KeyCode::Y) {
+        } else if e.triggered(KeyAction::Redo) {
             self.ctrl.redo(&mut self.history)
         } else {
             match self
@@ -298,7 +783,20 @@ impl MainEventLoop {
         }
         // Update the scale factor
 
-        if are_tools_active(&self.menu, &self.tools_select_menu) {
+        // images that are claimed by another annotator cannot be edited
+        if !self.ctrl.flags().is_loading_screen_active {
+            let file_path = self
+                .ctrl
+                .meta_data(self.ctrl.file_selected_idx, Some(false))
+                .file_path;
+            if file_path != self.claim_checked_file {
+                self.claim_checked_file = file_path;
+                self.refresh_claim();
+            }
+        }
+
+        if are_tools_active(&self.menu, &self.tools_select_menu) && self.claimed_by_other.is_none()
+        {
             let meta_data = self.ctrl.meta_data(
                 self.ctrl.file_selected_idx,
                 Some(self.ctrl.flags().is_loading_screen_active),
@@ -310,7 +808,34 @@ impl MainEventLoop {
                 mem::take(&mut self.history),
                 e,
             );
+            match self.world.take_info() {
+                Info::None => (),
+                info => self.menu.show_info(info),
+            }
+            if let Some(classify_data) = self.world.data.tools_data_map.get_mut(CLASSIFY_NAME) {
+                let options = &mut classify_data.specifics.classify_mut().options;
+                if options.is_next_image_triggered {
+                    options.is_next_image_triggered = false;
+                    self.ctrl.paths_navigator.next();
+                }
+            }
+            self.match_imported_paths();
+            if let Err(e) = self.predict_if_triggered() {
+                self.menu.show_info(Info::Error(format!("{e:?}")));
+            }
+            if let Err(e) = self.export_patches_if_triggered() {
+                self.menu.show_info(Info::Error(format!("{e:?}")));
+            }
+            if let Err(e) = self.export_masks_if_triggered() {
+                self.menu.show_info(Info::Error(format!("{e:?}")));
+            }
+            if let Err(e) = self.export_mot_if_triggered() {
+                self.menu.show_info(Info::Error(format!("{e:?}")));
+            }
         }
+        self.check_image_export();
+        self.insert_predictions();
+        self.publish_events();
 
         // show position and rgb value
         if let Some(idx) = self.ctrl.paths_navigator.file_label_selected_idx() {
@@ -319,7 +844,9 @@ impl MainEventLoop {
             let shape = self.world.shape_orig();
             let file_label = self.ctrl.file_label(idx);
             let active_tool = self.tools.iter().find(|t| t.is_active());
-            let tool_string = if let Some(t) = active_tool {
+            let tool_string = if let Some(user) = &self.claimed_by_other {
+                format!("image is claimed by {user}")
+            } else if let Some(t) = active_tool {
                 format!("{} tool is active", t.name)
             } else {
                 "".to_string()
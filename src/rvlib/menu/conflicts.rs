@@ -0,0 +1,47 @@
+use egui::Context;
+
+use crate::{control::collab::Conflict, tools::BBOX_NAME, world::ToolsDataMap};
+
+/// Lists images that have been changed by us and by another annotator since our last save and
+/// lets the user decide which version to keep.
+pub fn conflicts_window(
+    ctx: &Context,
+    conflicts: &mut Vec<Conflict>,
+    tools_data_map: &mut ToolsDataMap,
+) {
+    if conflicts.is_empty() {
+        return;
+    }
+    let mut resolved = None;
+    egui::Window::new("merge conflicts").show(ctx, |ui| {
+        ui.label("these images have been changed by you and by somebody else");
+        for (i, conflict) in conflicts.iter().enumerate() {
+            let n_ours = tools_data_map
+                .get(BBOX_NAME)
+                .and_then(|td| td.specifics.bbox().get_annos(&conflict.file_path))
+                .map(|annos| annos.geos().len())
+                .unwrap_or(0);
+            let n_theirs = conflict.theirs.geos().len();
+            ui.separator();
+            ui.label(&conflict.file_path);
+            ui.horizontal(|ui| {
+                if ui.button(format!("keep mine ({n_ours})")).clicked() {
+                    resolved = Some((i, false));
+                }
+                if ui.button(format!("take theirs ({n_theirs})")).clicked() {
+                    resolved = Some((i, true));
+                }
+            });
+        }
+    });
+    if let Some((i, take_theirs)) = resolved {
+        let conflict = conflicts.remove(i);
+        if take_theirs {
+            if let Some(td) = tools_data_map.get_mut(BBOX_NAME) {
+                let bbox_data = td.specifics.bbox_mut();
+                *bbox_data.get_annos_mut(&conflict.file_path, conflict.shape) = conflict.theirs;
+                bbox_data.options.is_redraw_annos_triggered = true;
+            }
+        }
+    }
+}
@@ -6,13 +6,19 @@ use crate::{
     paths_selector::PathsSelector,
     result::{to_rv, RvResult},
     tools::{ToolState, BBOX_NAME},
-    tools_data::ToolSpecifics,
+    tools_data::{image_review::ImageStatus, ToolSpecifics},
     world::ToolsDataMap,
 };
 use egui::{Area, Context, Frame, Id, Order, Response, Ui, Widget};
 use std::mem;
 
-use super::tools_menus::bbox_menu;
+use super::{
+    conflicts::conflicts_window,
+    keymap::KeymapMenu,
+    merge::MergeMenu,
+    review::ReviewMenu,
+    tools_menus::{bbox_menu, classify_menu, keypoint_menu},
+};
 
 fn show_popup(
     ui: &mut Ui,
@@ -101,9 +107,13 @@ impl ToolSelectMenu {
             let tmp = match &mut v.specifics {
                 ToolSpecifics::Bbox(x) => bbox_menu(ui, v.menu_active, mem::take(x)),
                 ToolSpecifics::Brush(_) => Ok(mem::take(v)),
+                ToolSpecifics::Classify(x) => classify_menu(ui, v.menu_active, mem::take(x)),
+                ToolSpecifics::Keypoint(x) => keypoint_menu(ui, v.menu_active, mem::take(x)),
             };
             *v = tmp?;
         }
+        // typing into text fields of tool menus such as the label search must not trigger tools
+        self.are_tools_active = !ui.ctx().wants_keyboard_input();
         Ok(())
     }
 
@@ -186,6 +196,9 @@ pub struct Menu {
     stats: Stats,
     filename_sort_type: SortType,
     show_about: bool,
+    merge_menu: MergeMenu,
+    keymap_menu: KeymapMenu,
+    review_menu: ReviewMenu,
 }
 
 impl Menu {
@@ -207,11 +220,18 @@ impl Menu {
             stats: Stats::default(),
             filename_sort_type: SortType::default(),
             show_about: false,
+            merge_menu: MergeMenu::default(),
+            keymap_menu: KeymapMenu::default(),
+            review_menu: ReviewMenu::default(),
         }
     }
     pub fn sort_type(&self) -> SortType {
         self.filename_sort_type
     }
+    /// Review status of the images that are visited when jumping to the next image with status
+    pub fn review_jump_status(&self) -> ImageStatus {
+        self.review_menu.jump_status
+    }
     pub fn popup(&mut self, info: Info) {
         self.info_message = info;
     }
@@ -253,6 +273,26 @@ impl Menu {
 
                 if ui.button("save project").clicked() {
                     handle_error!(ctrl.save(tools_data_map), self);
+                    if !ctrl.conflicts.is_empty() {
+                        self.info_message = Info::Warning(format!(
+                            "{} images have also been changed by somebody else",
+                            ctrl.conflicts.len()
+                        ));
+                    } else if !ctrl.kept_conflicts.is_empty() {
+                        self.info_message = Info::Warning(format!(
+                            "{} images have also been changed by somebody else, we kept ours",
+                            ctrl.kept_conflicts.len()
+                        ));
+                    }
+                }
+                if ui.button("merge project").clicked() {
+                    self.merge_menu.toggle();
+                }
+                if ui.button("key bindings").clicked() {
+                    self.keymap_menu.toggle();
+                }
+                if ui.button("review").clicked() {
+                    self.review_menu.toggle();
                 }
 
                 let cfg_gui = CfgMenu::new(popup_id, &mut ctrl.cfg, &mut self.editable_ssh_cfg_str);
@@ -262,6 +302,16 @@ impl Menu {
             });
         });
 
+        conflicts_window(ctx, &mut ctrl.conflicts, tools_data_map);
+        handle_error!(self.merge_menu.ui(ctx, ctrl, tools_data_map), self);
+        self.keymap_menu.ui(ctx, &ctrl.cfg.keymap);
+        if self.review_menu.ui(ctx, ctrl, tools_data_map) {
+            let status = self.review_menu.jump_status;
+            if !ctrl.select_next_with_status(status, tools_data_map) {
+                self.info_message = Info::Warning(format!("no other image with status {status}"));
+            }
+        }
+
         egui::SidePanel::left("left-main-menu").show(ctx, |ui| {
             if let Ok(folder) = ctrl.cfg.export_folder() {
                 if let Some(load_btn_resp) = &self.load_button_resp.resp {
@@ -367,6 +417,9 @@ impl Menu {
                     ctrl.file_info_selected.as_deref(),
                     scroll_to_selected,
                     self.scroll_offset,
+                    tools_data_map
+                        .get(BBOX_NAME)
+                        .map(|bbox_data| bbox_data.specifics.bbox().image_reviews()),
                 );
                 ctrl.paths_navigator.deactivate_scroll_to_selected_label();
                 if ctrl.paths_navigator.file_label_selected_idx() != filtered_label_selected_idx {
@@ -445,5 +498,5 @@ impl Default for Menu {
 }
 
 pub fn are_tools_active(menu: &Menu, tsm: &ToolSelectMenu) -> bool {
-    menu.are_tools_active && tsm.are_tools_active
+    menu.are_tools_active && !menu.review_menu.is_editing && tsm.are_tools_active
 }
@@ -0,0 +1,44 @@
+use egui::Context;
+
+use crate::keymap::Keymap;
+
+/// Lists the current key bindings, they are changed in the `keymap` section of `rv_cfg.toml`.
+#[derive(Default)]
+pub struct KeymapMenu {
+    pub open: bool,
+}
+
+impl KeymapMenu {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn ui(&mut self, ctx: &Context, keymap: &Keymap) {
+        egui::Window::new("key bindings")
+            .open(&mut self.open)
+            .show(ctx, |ui| {
+                egui::Grid::new("keymap-grid")
+                    .num_columns(2)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (action, chords) in keymap.bindings() {
+                            ui.label(action.to_string());
+                            let chords = chords
+                                .iter()
+                                .map(|c| c.to_string())
+                                .collect::<Vec<_>>()
+                                .join(", ");
+                            ui.label(chords);
+                            ui.end_row();
+                        }
+                        for (tool_name, chord) in &keymap.activate_tool {
+                            ui.label(format!("activate {tool_name}"));
+                            ui.label(chord.to_string());
+                            ui.end_row();
+                        }
+                    });
+                ui.separator();
+                ui.label("edit the [keymap] section of rv_cfg.toml to change the bindings");
+            });
+    }
+}
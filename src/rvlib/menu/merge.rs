@@ -0,0 +1,118 @@
+use egui::Context;
+
+use crate::{
+    control::Control,
+    file_util::{self, RVPRJ_PREFIX},
+    result::{to_rv, RvResult},
+    tools_data::merge::{ImageDiff, MergeMode},
+    world::ToolsDataMap,
+};
+
+/// Merges another project of the export folder into the current one or shows where both differ.
+pub struct MergeMenu {
+    pub open: bool,
+    file_name: Option<String>,
+    mode: MergeMode,
+    iou_threshold: f64,
+    report: Option<Vec<ImageDiff>>,
+}
+
+impl Default for MergeMenu {
+    fn default() -> Self {
+        Self {
+            open: false,
+            file_name: None,
+            mode: MergeMode::default(),
+            iou_threshold: 0.5,
+            report: None,
+        }
+    }
+}
+
+impl MergeMenu {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    pub fn ui(
+        &mut self,
+        ctx: &Context,
+        ctrl: &Control,
+        tools_data_map: &mut ToolsDataMap,
+    ) -> RvResult<()> {
+        let mut open = self.open;
+        let res =
+            egui::Window::new("merge project")
+                .open(&mut open)
+                .show(ctx, |ui| -> RvResult<()> {
+                    let folder = ctrl.cfg.export_folder()?;
+                    let files = file_util::files_in_folder(folder, RVPRJ_PREFIX, "json")
+                        .map_err(to_rv)?
+                        .filter_map(|p| p.file_name().and_then(|p| p.to_str()).map(String::from))
+                        .collect::<Vec<_>>();
+                    ui.label("project to compare with");
+                    for f in files {
+                        let is_selected = self.file_name.as_ref() == Some(&f);
+                        if ui.selectable_label(is_selected, &f).clicked() {
+                            self.file_name = Some(f);
+                        }
+                    }
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        ui.radio_value(&mut self.mode, MergeMode::Union, "union");
+                        ui.radio_value(&mut self.mode, MergeMode::Intersection, "intersection");
+                    });
+                    ui.add(
+                        egui::Slider::new(&mut self.iou_threshold, 0.0..=1.0).text("IoU threshold"),
+                    );
+                    if let Some(file_name) = &self.file_name {
+                        ui.horizontal(|ui| -> RvResult<()> {
+                            if ui.button("merge into current").clicked() {
+                                self.report = Some(ctrl.merge_project(
+                                    tools_data_map,
+                                    file_name,
+                                    self.mode,
+                                    self.iou_threshold,
+                                )?);
+                            }
+                            if ui.button("diff").clicked() {
+                                self.report = Some(ctrl.diff_project(
+                                    tools_data_map,
+                                    file_name,
+                                    self.iou_threshold,
+                                )?);
+                            }
+                            Ok(())
+                        })
+                        .inner?;
+                    }
+                    if let Some(report) = &self.report {
+                        ui.separator();
+                        ui.label(format!("{} images with disagreements", report.len()));
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            egui::Grid::new("merge-report")
+                                .striped(true)
+                                .show(ui, |ui| {
+                                    ui.label("image");
+                                    ui.label("matches");
+                                    ui.label("other label");
+                                    ui.label("only current");
+                                    ui.label("only other");
+                                    ui.end_row();
+                                    for diff in report {
+                                        ui.label(&diff.file_path);
+                                        ui.label(diff.n_matches.to_string());
+                                        ui.label(diff.n_label_mismatches.to_string());
+                                        ui.label(diff.n_only_first.to_string());
+                                        ui.label(diff.n_only_second.to_string());
+                                        ui.end_row();
+                                    }
+                                });
+                        });
+                    }
+                    Ok(())
+                });
+        self.open = open;
+        res.and_then(|r| r.inner).unwrap_or(Ok(()))
+    }
+}
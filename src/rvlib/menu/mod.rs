@@ -1,7 +1,11 @@
 mod cfg_menu;
+mod conflicts;
 mod core;
+mod keymap;
+mod merge;
 mod open_folder;
 mod picklist;
+mod review;
 mod scroll_area;
 mod tools_menus;
 pub use crate::menu::core::{are_tools_active, Menu, ToolSelectMenu};
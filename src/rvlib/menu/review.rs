@@ -0,0 +1,96 @@
+use egui::{Color32, Context};
+
+use crate::{
+    control::Control,
+    tools::BBOX_NAME,
+    tools_data::{image_review::ImageStatus, BboxSpecificData, ToolSpecifics, ToolsData},
+    world::ToolsDataMap,
+};
+
+pub fn status_color(status: ImageStatus) -> Color32 {
+    let [r, g, b] = status.color();
+    Color32::from_rgb(r, g, b)
+}
+
+/// Sets the review status and comment of the current image and jumps to images with a status.
+#[derive(Default)]
+pub struct ReviewMenu {
+    pub open: bool,
+    /// status of the images that are visited by the next-image-with-status action
+    pub jump_status: ImageStatus,
+    /// the comment field has the focus and key bindings should be ignored
+    pub is_editing: bool,
+}
+
+impl ReviewMenu {
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+    }
+
+    /// Returns true if the next image with the jump status was requested.
+    pub fn ui(&mut self, ctx: &Context, ctrl: &Control, tools_data_map: &mut ToolsDataMap) -> bool {
+        let mut is_jump_triggered = false;
+        let file_path = ctrl
+            .file_selected_idx
+            .and_then(|idx| ctrl.paths_navigator.file_path(idx))
+            .map(|fp| fp.to_string());
+        let mut open = self.open;
+        egui::Window::new("review").open(&mut open).show(ctx, |ui| {
+            if let Some(file_path) = &file_path {
+                let bbox_data = tools_data_map
+                    .entry(BBOX_NAME)
+                    .or_insert_with(|| ToolsData::new(ToolSpecifics::Bbox(BboxSpecificData::new())))
+                    .specifics
+                    .bbox_mut();
+                let mut review = bbox_data
+                    .image_review(file_path)
+                    .cloned()
+                    .unwrap_or_default();
+                let mut is_changed = false;
+                ui.horizontal(|ui| {
+                    for status in ImageStatus::ALL {
+                        let text =
+                            egui::RichText::new(status.to_string()).color(status_color(status));
+                        if ui.radio_value(&mut review.status, status, text).clicked() {
+                            is_changed = true;
+                        }
+                    }
+                });
+                ui.label("reviewer comment");
+                let comment_field = ui.text_edit_multiline(&mut review.comment);
+                if comment_field.gained_focus() {
+                    self.is_editing = true;
+                }
+                if comment_field.lost_focus() {
+                    self.is_editing = false;
+                }
+                if comment_field.changed() {
+                    is_changed = true;
+                }
+                if is_changed {
+                    bbox_data.set_image_review(file_path, review);
+                }
+            } else {
+                ui.label("no image selected");
+            }
+            ui.separator();
+            ui.horizontal(|ui| {
+                egui::ComboBox::from_id_source("review-jump-status")
+                    .selected_text(self.jump_status.to_string())
+                    .show_ui(ui, |ui| {
+                        for status in ImageStatus::ALL {
+                            ui.selectable_value(&mut self.jump_status, status, status.to_string());
+                        }
+                    });
+                if ui.button("next image with status").clicked() {
+                    is_jump_triggered = true;
+                }
+            });
+        });
+        self.open = open;
+        if !self.open {
+            self.is_editing = false;
+        }
+        is_jump_triggered
+    }
+}
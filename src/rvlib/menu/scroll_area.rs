@@ -1,6 +1,10 @@
-use egui::{Align, Pos2, Rect, Ui};
+use std::collections::HashMap;
 
-use crate::paths_selector::PathsSelector;
+use egui::{Align, Pos2, Rect, RichText, Ui};
+
+use crate::{paths_selector::PathsSelector, tools_data::image_review::ImageReview};
+
+use super::review::status_color;
 
 pub fn scroll_area(
     ui: &mut Ui,
@@ -9,6 +13,7 @@ pub fn scroll_area(
     file_info_selected: Option<&str>,
     scroll_to_selected_label: bool,
     scroll_offset: f32,
+    image_reviews: Option<&HashMap<String, ImageReview>>,
 ) -> f32 {
     let scroll_height = ui.available_height() - 120.0;
     let n_rows = paths_selector.len_filtered();
@@ -33,6 +38,18 @@ pub fn scroll_area(
         let file_label = paths_selector
             .filtered_idx_file_label_pairs(filtered_label_idx)
             .1;
+        // colored marker for reviewed images, the comment is shown on hover
+        let review = paths_selector
+            .file_selected_path(filtered_label_idx)
+            .and_then(|path| image_reviews.and_then(|reviews| reviews.get(path)));
+        if let Some(review) = review {
+            let marker = ui.label(RichText::new("●").color(status_color(review.status)));
+            if review.comment.is_empty() {
+                marker.on_hover_text(review.status.to_string());
+            } else {
+                marker.on_hover_text(format!("{}\n{}", review.status, review.comment));
+            }
+        }
         let sl = if *selected_filtered_label_idx == Some(filtered_label_idx) {
             let path = paths_selector.file_selected_path(filtered_label_idx);
             if let Some(path) = path {
@@ -71,7 +88,7 @@ pub fn scroll_area(
         .max_height(scroll_height)
         .show_rows(ui, row_height, n_rows, |ui, row_range| {
             for filtered_idx in row_range {
-                add_content(ui, filtered_idx);
+                ui.horizontal(|ui| add_content(ui, filtered_idx));
             }
         });
     scroll.state.offset.y
@@ -1,15 +1,406 @@
-use std::{path::PathBuf, str::FromStr};
+use std::{collections::HashMap, mem, path::PathBuf, str::FromStr};
 
 use egui::Ui;
 
 use crate::{
-    annotations::SplitMode,
+    annotations::{AttrVal, ReviewStatus, SplitMode},
     cfg::{self, get_cfg, CocoFileConnection},
     file_util::path_to_str,
     result::{to_rv, RvResult},
-    tools_data::{bbox_data::BboxSpecificData, ToolSpecifics, ToolsData},
+    tools_data::{
+        bbox_data::{BboxSpecificData, LabelEdit},
+        classify_data::TagsExportFormat,
+        ClassifyToolData, KeypointToolData, ToolSpecifics, ToolsData,
+    },
 };
 
+const SUB_PIXEL_HINT: &str =
+    "imported sub-pixel boxes are exported unchanged unless they are edited, \
+edited boxes are exported with pixel coordinates";
+
+fn attr_value_widget(ui: &mut Ui, value: &mut AttrVal) -> bool {
+    match value {
+        AttrVal::Bool(b) => ui.checkbox(b, "").changed(),
+        AttrVal::Int(i) => ui.add(egui::DragValue::new(i)).changed(),
+        AttrVal::Text(s) => ui.text_edit_singleline(s).changed(),
+    }
+}
+
+/// Definitions of the attributes of the current label and their values for the selected boxes
+fn attributes_menu(ui: &mut Ui, data: &mut BboxSpecificData) -> RvResult<()> {
+    let cat_idx = data.cat_idx_current;
+    let selected_attributes = data
+        .anno_iter()
+        .find_map(|(_, (annos, _))| annos.first_selected_attributes(cat_idx))
+        .cloned();
+    let mut def_to_be_removed = None;
+    let mut new_values = vec![];
+    for def in data.attribute_defs_of(cat_idx) {
+        ui.horizontal(|ui| {
+            if ui.button("x").clicked() {
+                def_to_be_removed = Some(def.name.clone());
+            }
+            ui.label(format!("{} ({})", def.name, def.default.type_name()));
+            if let Some(attributes) = &selected_attributes {
+                let mut value = attributes
+                    .get(&def.name)
+                    .cloned()
+                    .unwrap_or_else(|| def.default.clone());
+                if attr_value_widget(ui, &mut value) {
+                    new_values.push((def.name.clone(), value));
+                }
+            } else {
+                ui.label(format!("default {}", def.default));
+            }
+        });
+    }
+    for (name, value) in new_values {
+        for (_, (annos, _)) in data.anno_iter_mut() {
+            annos.set_attribute_selected(cat_idx, &name, &value);
+        }
+    }
+    if let Some(name) = def_to_be_removed {
+        data.remove_attribute_def(cat_idx, &name);
+    }
+    ui.separator();
+    ui.text_edit_singleline(&mut data.new_attribute.name);
+    let mut add_triggered = false;
+    ui.horizontal(|ui| {
+        let default = &mut data.new_attribute.default;
+        if ui
+            .radio(matches!(default, AttrVal::Bool(_)), "bool")
+            .clicked()
+        {
+            *default = AttrVal::Bool(false);
+        }
+        if ui
+            .radio(matches!(default, AttrVal::Int(_)), "int")
+            .clicked()
+        {
+            *default = AttrVal::Int(0);
+        }
+        if ui
+            .radio(matches!(default, AttrVal::Text(_)), "text")
+            .clicked()
+        {
+            *default = AttrVal::Text("".to_string());
+        }
+        attr_value_widget(ui, default);
+        add_triggered = ui.button("add").clicked();
+    });
+    if add_triggered {
+        let new_attribute = mem::take(&mut data.new_attribute);
+        data.add_attribute_def(cat_idx, new_attribute)?;
+    }
+    Ok(())
+}
+
+const MAX_PALETTE_MATCHES: usize = 20;
+
+fn label_button(ui: &mut Ui, data: &BboxSpecificData, label_idx: usize) -> bool {
+    let rgb = data.colors()[label_idx];
+    let checked = label_idx == data.cat_idx_current;
+    ui.horizontal_top(|ui| {
+        ui.label(
+            egui::RichText::new("■")
+                .strong()
+                .color(egui::Color32::from_rgb(rgb[0], rgb[1], rgb[2])),
+        );
+        ui.selectable_label(checked, &data.labels()[label_idx])
+            .clicked()
+    })
+    .inner
+}
+
+/// Window with a fuzzy search over all labels, returns the index of the picked label
+fn label_palette(ctx: &egui::Context, data: &mut BboxSpecificData) -> Option<usize> {
+    let mut picked = None;
+    let mut is_open = data.palette.is_open;
+    egui::Window::new("search labels")
+        .open(&mut is_open)
+        .collapsible(false)
+        .show(ctx, |ui| {
+            let query_field = ui.text_edit_singleline(&mut data.palette.query);
+            if data.palette.query.is_empty() && !query_field.has_focus() {
+                query_field.request_focus();
+            }
+            let matches = data.search_labels(&data.palette.query);
+            if query_field.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter)) {
+                picked = matches.first().copied();
+            }
+            if data.palette.query.is_empty() {
+                ui.label("recent");
+                for label_idx in data.recent_label_idxs() {
+                    if label_button(ui, data, label_idx) {
+                        picked = Some(label_idx);
+                    }
+                }
+                ui.separator();
+            }
+            egui::ScrollArea::vertical().show(ui, |ui| {
+                for label_idx in matches.into_iter().take(MAX_PALETTE_MATCHES) {
+                    if label_button(ui, data, label_idx) {
+                        picked = Some(label_idx);
+                    }
+                }
+            });
+        });
+    if picked.is_some() || ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+        is_open = false;
+    }
+    data.palette.is_open = is_open;
+    picked
+}
+
+/// Pins the current label to a quick-select slot, i.e., to a number key by default
+fn pinned_labels_menu(ui: &mut Ui, data: &mut BboxSpecificData) {
+    let mut to_be_unpinned = None;
+    for (slot, label) in data.pinned_labels() {
+        ui.horizontal(|ui| {
+            if ui.button("x").clicked() {
+                to_be_unpinned = Some(*slot);
+            }
+            ui.label(format!("label {} → {label}", slot + 1));
+        });
+    }
+    if let Some(slot) = to_be_unpinned {
+        data.unpin_label(slot);
+    }
+    ui.horizontal(|ui| {
+        let mut slot = data.palette.pin_slot + 1;
+        ui.add(egui::DragValue::new(&mut slot).clamp_range(1..=usize::MAX));
+        data.palette.pin_slot = slot - 1;
+        let label = &data.labels()[data.cat_idx_current];
+        if ui.button(format!("pin {label}")).clicked() {
+            data.pin_label(data.palette.pin_slot, data.cat_idx_current);
+        }
+    });
+}
+
+enum PredictionDecision {
+    Accept(usize),
+    Reject(usize),
+    AcceptAll,
+    RejectAll,
+}
+
+/// Requests predictions from the inference endpoint and lists the predictions of the current
+/// image above the score threshold to accept or reject them.
+fn predictions_menu(ui: &mut Ui, data: &mut BboxSpecificData) {
+    ui.horizontal(|ui| {
+        if ui.button("predict").clicked() {
+            data.options.is_predict_triggered = true;
+        }
+        if ui.button("predict filtered folder").clicked() {
+            data.options.is_batch_predict_triggered = true;
+        }
+    });
+    if ui
+        .add(egui::Slider::new(&mut data.options.score_threshold, 0..=100).text("min score %"))
+        .changed()
+    {
+        data.options.is_redraw_annos_triggered = true;
+    }
+    let threshold = data.score_threshold();
+    let mut decision = None;
+    if let Some(annos) = data
+        .current_file_path
+        .as_ref()
+        .and_then(|fp| data.get_annos(fp))
+    {
+        for (pred_idx, pred) in annos.predictions().iter().enumerate() {
+            if pred.score >= threshold {
+                ui.horizontal(|ui| {
+                    if ui.button("✔").clicked() {
+                        decision = Some(PredictionDecision::Accept(pred_idx));
+                    }
+                    if ui.button("✖").clicked() {
+                        decision = Some(PredictionDecision::Reject(pred_idx));
+                    }
+                    ui.label(format!("{} {:.2}", data.labels()[pred.cat_idx], pred.score));
+                });
+            }
+        }
+        ui.horizontal(|ui| {
+            if ui.button("accept all").clicked() {
+                decision = Some(PredictionDecision::AcceptAll);
+            }
+            if ui.button("reject all").clicked() {
+                decision = Some(PredictionDecision::RejectAll);
+            }
+        });
+    }
+    let current_file_path = data.current_file_path.clone();
+    let mut is_decided = false;
+    if let (Some(decision), Some(file_path)) = (decision, current_file_path) {
+        if let Some((_, (annos, _))) = data.anno_iter_mut().find(|(fp, _)| **fp == file_path) {
+            match decision {
+                PredictionDecision::Accept(pred_idx) => annos.accept_prediction(pred_idx),
+                PredictionDecision::Reject(pred_idx) => annos.reject_prediction(pred_idx),
+                PredictionDecision::AcceptAll => annos.accept_predictions(threshold),
+                PredictionDecision::RejectAll => annos.reject_predictions(threshold),
+            }
+            is_decided = true;
+        }
+    }
+    if is_decided {
+        data.options.is_history_update_triggered = true;
+    }
+}
+
+/// Shows the provenance of the first selected annotation and sets the review status of all
+/// selected annotations of the current image.
+fn review_menu(ui: &mut Ui, data: &mut BboxSpecificData) {
+    let current_file_path = data.current_file_path.clone();
+    let mut is_reviewed = false;
+    if let Some((_, (annos, _))) = data
+        .anno_iter_mut()
+        .find(|(fp, _)| Some(fp.as_str()) == current_file_path.as_deref())
+    {
+        match annos.first_selected_provenance() {
+            Some(prov) => {
+                let score = prov
+                    .score
+                    .map(|s| format!(", score {s:.2}"))
+                    .unwrap_or_default();
+                ui.label(format!("{}, {}{score}", prov.source, prov.review));
+            }
+            None => {
+                ui.label("no annotation selected");
+            }
+        }
+        ui.horizontal(|ui| {
+            for review in [
+                ReviewStatus::Accepted,
+                ReviewStatus::Rejected,
+                ReviewStatus::Unreviewed,
+            ] {
+                if ui.button(review.to_string()).clicked() {
+                    annos.set_review_selected(review);
+                    is_reviewed = true;
+                }
+            }
+        });
+    }
+    if is_reviewed {
+        data.options.is_history_update_triggered = true;
+    }
+}
+
+fn assists_menu(ui: &mut Ui, data: &mut BboxSpecificData) {
+    if ui.button("snap selected to content").clicked() {
+        data.options.is_snap_triggered = true;
+    }
+    ui.checkbox(&mut data.options.is_magic_wand_active, "magic wand");
+    ui.add(egui::Slider::new(&mut data.options.magic_wand_tolerance, 0..=255).text("tolerance"));
+}
+
+/// Applies all changes of the edit or none of them. Returns true if something has changed.
+fn apply_label_edit(
+    data: &mut BboxSpecificData,
+    cat_idx: usize,
+    edit: LabelEdit,
+) -> RvResult<bool> {
+    let is_renamed = edit.name != data.labels()[cat_idx];
+    let is_recolored = edit.color != data.colors()[cat_idx];
+    let is_cat_id_changed = edit.cat_id != data.cat_ids()[cat_idx];
+    if is_renamed {
+        data.check_rename_label(cat_idx, &edit.name)?;
+    }
+    if is_recolored {
+        data.check_color(cat_idx, edit.color)?;
+    }
+    if is_cat_id_changed {
+        data.check_cat_id(cat_idx, edit.cat_id)?;
+    }
+    if is_renamed {
+        data.rename_label(cat_idx, edit.name)?;
+    }
+    if is_recolored {
+        data.set_color(cat_idx, edit.color)?;
+    }
+    if is_cat_id_changed {
+        data.set_cat_id(cat_idx, edit.cat_id)?;
+    }
+    Ok(is_renamed || is_recolored || is_cat_id_changed)
+}
+
+/// Renames, recolors, merges, or changes the Coco category id of the label that is edited.
+/// Returns true if labels have been merged, which changes the label indices.
+fn label_edit_menu(ui: &mut Ui, data: &mut BboxSpecificData) -> bool {
+    let cat_idx = match data.label_edit.cat_idx {
+        Some(cat_idx) if cat_idx < data.len() => cat_idx,
+        _ => {
+            // the label might have been removed, e.g., by undo
+            data.label_edit.cat_idx = None;
+            return false;
+        }
+    };
+    let mut apply_triggered = false;
+    let mut merge_triggered = false;
+    ui.group(|ui| {
+        ui.label(format!("edit label {}", data.labels()[cat_idx]));
+        ui.horizontal(|ui| {
+            ui.label("name");
+            ui.text_edit_singleline(&mut data.label_edit.name);
+        });
+        ui.horizontal(|ui| {
+            ui.label("color");
+            ui.color_edit_button_srgb(&mut data.label_edit.color);
+            ui.label("coco category id");
+            ui.add(egui::DragValue::new(&mut data.label_edit.cat_id));
+        });
+        apply_triggered = ui.button("apply").clicked();
+        ui.horizontal(|ui| {
+            let mut merge_into = data.label_edit.merge_into.min(data.len() - 1);
+            egui::ComboBox::from_id_source("bbox-merge-label")
+                .selected_text(data.labels()[merge_into].as_str())
+                .show_ui(ui, |ui| {
+                    for (label_idx, label) in data.labels().iter().enumerate() {
+                        if label_idx != cat_idx {
+                            ui.selectable_value(&mut merge_into, label_idx, label);
+                        }
+                    }
+                });
+            data.label_edit.merge_into = merge_into;
+            merge_triggered = ui.button("merge into").clicked();
+        });
+        if let Some(error) = &data.label_edit.error {
+            ui.colored_label(egui::Color32::RED, error);
+        }
+    });
+    let edit = data.label_edit.clone();
+    if apply_triggered {
+        // errors are shown in the menu since they would discard the tool data when propagated
+        match apply_label_edit(data, cat_idx, edit) {
+            Ok(is_changed) => {
+                data.label_edit.error = None;
+                if is_changed {
+                    data.options.is_history_update_triggered = true;
+                }
+            }
+            Err(e) => {
+                data.label_edit.error = Some(e.to_string());
+            }
+        }
+        false
+    } else if merge_triggered {
+        match data.merge_labels(cat_idx, edit.merge_into) {
+            Ok(()) => {
+                data.label_edit = LabelEdit::default();
+                data.options.is_history_update_triggered = true;
+                true
+            }
+            Err(e) => {
+                data.label_edit.error = Some(e.to_string());
+                false
+            }
+        }
+    } else {
+        false
+    }
+}
+
 pub fn bbox_menu(
     ui: &mut Ui,
     mut window_open: bool,
@@ -17,7 +408,11 @@ pub fn bbox_menu(
 ) -> RvResult<ToolsData> {
     let mut new_idx = data.cat_idx_current;
     let mut new_label = None;
-    if ui.text_edit_singleline(&mut data.new_label).lost_focus() {
+    let new_label_field = ui.add_enabled(
+        !data.is_locked(),
+        egui::TextEdit::singleline(&mut data.new_label),
+    );
+    if new_label_field.lost_focus() {
         new_label = Some(data.new_label.clone());
     }
     let default_label = data.find_default();
@@ -26,14 +421,28 @@ pub fn bbox_menu(
     } else if let Some(new_label) = new_label {
         data.push(new_label, None, None)?;
         new_idx = data.len() - 1;
+        data.options.is_history_update_triggered = true;
     }
     let mut to_be_removed = None;
+    let mut to_be_edited = None;
+    let mut drag_started = None;
+    let mut drop_target = None;
+    let pointer_pos = ui.input(|i| i.pointer.interact_pos());
     for (label_idx, label) in data.labels().iter().enumerate() {
         let checked = label_idx == data.cat_idx_current;
-        ui.horizontal_top(|ui| {
+        let row = ui.horizontal_top(|ui| {
+            let handle = ui
+                .add(egui::Label::new("☰").sense(egui::Sense::drag()))
+                .on_hover_cursor(egui::CursorIcon::Grab);
+            if handle.drag_started() {
+                drag_started = Some(label_idx);
+            }
             if ui.button("x").clicked() {
                 to_be_removed = Some(label_idx);
             }
+            if ui.button("✏").clicked() {
+                to_be_edited = Some(label_idx);
+            }
             if ui.selectable_label(checked, label).clicked() {
                 new_idx = label_idx;
             }
@@ -45,15 +454,106 @@ pub fn bbox_menu(
                     .color(egui::Color32::from_rgb(rgb[0], rgb[1], rgb[2])),
             );
         });
+        if pointer_pos.map(|pos| row.response.rect.y_range().contains(pos.y)) == Some(true) {
+            drop_target = Some(label_idx);
+        }
+    }
+    if drag_started.is_some() {
+        data.label_edit.dragged = drag_started;
+    }
+    if let Some(dragged) = data.label_edit.dragged {
+        if ui.input(|i| i.pointer.any_released()) {
+            data.label_edit.dragged = None;
+            if let Some(target) = drop_target.filter(|target| *target != dragged) {
+                data.move_label(dragged, target)?;
+                new_idx = data.cat_idx_current;
+                data.options.is_history_update_triggered = true;
+            }
+        }
+    }
+    if let Some(label_idx) = to_be_edited {
+        data.label_edit = if data.label_edit.cat_idx == Some(label_idx) {
+            LabelEdit::default()
+        } else {
+            LabelEdit {
+                cat_idx: Some(label_idx),
+                name: data.labels()[label_idx].clone(),
+                color: data.colors()[label_idx],
+                cat_id: data.cat_ids()[label_idx],
+                merge_into: usize::from(label_idx == 0),
+                dragged: None,
+                error: None,
+            }
+        };
+    }
+    if label_edit_menu(ui, &mut data) {
+        new_idx = data.cat_idx_current;
+    }
+    if data.palette.is_open {
+        if let Some(picked) = label_palette(ui.ctx(), &mut data) {
+            new_idx = picked;
+        }
+    }
+    if data.recent_label_idxs().next().is_some() {
+        ui.horizontal_wrapped(|ui| {
+            ui.label("recent");
+            for label_idx in data.recent_label_idxs() {
+                if ui.small_button(&data.labels()[label_idx]).clicked() {
+                    new_idx = label_idx;
+                }
+            }
+        });
     }
     if new_idx != data.cat_idx_current {
         for (_, (anno, _)) in data.anno_iter_mut() {
             anno.label_selected(new_idx);
         }
-        data.cat_idx_current = new_idx;
+        data.set_cat_idx_current(new_idx);
     }
     if let Some(idx) = to_be_removed {
         data.remove_catidx(idx);
+        data.label_edit = LabelEdit::default();
+        data.options.is_history_update_triggered = true;
+    }
+    let label = data.labels()[data.cat_idx_current].clone();
+    egui::CollapsingHeader::new(format!("attributes of {label}"))
+        .id_source("bbox-attributes")
+        .show(ui, |ui| attributes_menu(ui, &mut data))
+        .body_returned
+        .unwrap_or(Ok(()))?;
+    egui::CollapsingHeader::new("label schema")
+        .id_source("bbox-label-schema")
+        .show(ui, |ui| {
+            ui.horizontal(|ui| {
+                ui.label("json, toml, or classes.txt");
+                ui.text_edit_singleline(&mut data.schema_path);
+            });
+            ui.horizontal(|ui| {
+                if ui.button("import schema").clicked() {
+                    data.options.is_schema_import_triggered = true;
+                }
+                let mut locked = data.is_locked();
+                if ui.checkbox(&mut locked, "lock labels").changed() {
+                    data.set_locked(locked);
+                    data.options.is_history_update_triggered = true;
+                }
+            });
+        });
+    egui::CollapsingHeader::new("pinned labels")
+        .id_source("bbox-pinned-labels")
+        .show(ui, |ui| pinned_labels_menu(ui, &mut data));
+    egui::CollapsingHeader::new("predictions")
+        .id_source("bbox-predictions")
+        .show(ui, |ui| predictions_menu(ui, &mut data));
+    egui::CollapsingHeader::new("review selected")
+        .id_source("bbox-review")
+        .show(ui, |ui| review_menu(ui, &mut data));
+    egui::CollapsingHeader::new("assists")
+        .id_source("bbox-assists")
+        .show(ui, |ui| assists_menu(ui, &mut data));
+    if ui.button("search labels").clicked() {
+        data.palette.is_open = true;
+        data.palette.query.clear();
     }
     let mut pathincfg_triggered = false;
     ui.separator();
@@ -65,6 +565,8 @@ pub fn bbox_menu(
     data.options.are_boxes_visible = !hide_boxes;
 
     ui.checkbox(&mut data.options.auto_paste, "auto paste");
+    ui.checkbox(&mut data.options.auto_track, "auto track")
+        .on_hover_text("move the boxes of the previous image onto the objects in a new image");
 
     let mut txt = path_to_str(&data.coco_file.path)?.to_string();
     egui::CollapsingHeader::new("advanced").show(ui, |ui| {
@@ -119,6 +621,61 @@ pub fn bbox_menu(
         if ui.button("clear out of folder annotations").clicked() {
             data.options.is_anno_rm_triggered = true;
         }
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("patches");
+            ui.add(egui::DragValue::new(&mut data.options.patch_padding).prefix("padding "));
+            ui.add(egui::DragValue::new(&mut data.options.patch_size).prefix("size "))
+                .on_hover_text("width and height of the patches, 0 keeps the size of the boxes");
+        });
+        ui.separator();
+        ui.horizontal(|ui| {
+            ui.label("splits");
+            for (ratio, name) in data
+                .split_cfg
+                .ratios
+                .iter_mut()
+                .zip(["train ", "val ", "test "])
+            {
+                ui.add(egui::DragValue::new(ratio).prefix(name));
+            }
+        })
+        .response
+        .on_hover_text("weights of the splits of the Coco split export");
+        ui.horizontal(|ui| {
+            ui.add(egui::DragValue::new(&mut data.split_cfg.seed).prefix("seed "));
+            ui.checkbox(&mut data.split_cfg.stratify, "stratify")
+                .on_hover_text("keep the label distribution in each split");
+        });
+        ui.horizontal(|ui| {
+            ui.label("group regex");
+            ui.text_edit_singleline(&mut data.split_cfg.group_regex)
+                .on_hover_text(
+                "images whose paths have the same match stay in the same split, e.g., ^(.*)/frame",
+            );
+        });
+        ui.horizontal(|ui| {
+            if ui.button("export coco splits").clicked() {
+                println!("export coco splits triggered");
+                data.options.is_split_export_triggered = true;
+            }
+            if ui
+                .button("reset splits")
+                .on_hover_text(format!(
+                    "forget the recorded splits of {} images, the next export assigns all images anew",
+                    data.splits().len()
+                ))
+                .clicked()
+            {
+                data.set_splits(HashMap::new());
+            }
+        });
+        ui.separator();
+        ui.checkbox(
+            &mut data.options.export_overlays,
+            "export overlays with masks",
+        )
+        .on_hover_text("images with the annotations and their labels burned in");
     });
     if path_to_str(&data.coco_file.path)? != txt {
         data.coco_file.path = PathBuf::from_str(&txt).map_err(to_rv)?;
@@ -131,12 +688,32 @@ pub fn bbox_menu(
     }
     ui.separator();
     ui.horizontal(|ui| {
-        if ui.button("export coco").clicked() {
+        if ui
+            .button("export coco")
+            .on_hover_text(SUB_PIXEL_HINT)
+            .clicked()
+        {
             println!("export coco triggered");
             data.options.is_export_triggered = true;
             pathincfg_triggered = true;
         }
-        if ui.button("import coco").clicked() {
+        if ui.button("export mot").clicked() {
+            println!("export mot triggered");
+            data.options.is_mot_export_triggered = true;
+        }
+        if ui.button("export patches").clicked() {
+            println!("export patches triggered");
+            data.options.is_patch_export_triggered = true;
+        }
+        if ui.button("export masks").clicked() {
+            println!("export masks triggered");
+            data.options.is_mask_export_triggered = true;
+        }
+        if ui
+            .button("import coco")
+            .on_hover_text(SUB_PIXEL_HINT)
+            .clicked()
+        {
             println!("import triggered");
             data.options.is_coco_import_triggered = true;
             pathincfg_triggered = true;
@@ -150,3 +727,186 @@ pub fn bbox_menu(
         menu_active: window_open,
     })
 }
+
+pub fn classify_menu(
+    ui: &mut Ui,
+    mut window_open: bool,
+    mut data: ClassifyToolData,
+) -> RvResult<ToolsData> {
+    if ui.text_edit_singleline(&mut data.new_tag).lost_focus() && !data.new_tag.is_empty() {
+        let new_tag = mem::take(&mut data.new_tag);
+        data.push(new_tag)?;
+    }
+    let mut to_be_removed = None;
+    let mut to_be_applied = None;
+    for (tag_idx, tag) in data.tags().iter().enumerate() {
+        let checked = data
+            .current_file_path
+            .as_ref()
+            .map(|fp| data.has_tag(fp, tag_idx))
+            .unwrap_or(false);
+        ui.horizontal_top(|ui| {
+            if ui.button("x").clicked() {
+                to_be_removed = Some(tag_idx);
+            }
+            let key = if tag_idx < 9 {
+                format!("{}: ", tag_idx + 1)
+            } else {
+                String::new()
+            };
+            if ui
+                .selectable_label(checked, format!("{key}{tag}"))
+                .clicked()
+            {
+                to_be_applied = Some(tag_idx);
+            }
+        });
+    }
+    if let (Some(tag_idx), Some(file_path)) = (to_be_applied, data.current_file_path.clone()) {
+        data.apply_tag(&file_path, tag_idx);
+    }
+    if let Some(tag_idx) = to_be_removed {
+        data.remove_tag(tag_idx);
+    }
+    ui.separator();
+    ui.checkbox(&mut data.options.is_multi_tag, "multiple tags per image");
+    ui.checkbox(
+        &mut data.options.is_auto_next,
+        "next image after number key",
+    );
+    ui.label(format!("{} tagged images", data.n_tagged_images()));
+    ui.separator();
+    ui.horizontal(|ui| {
+        ui.radio_value(
+            &mut data.options.export_format,
+            TagsExportFormat::Csv,
+            "csv",
+        );
+        ui.radio_value(
+            &mut data.options.export_format,
+            TagsExportFormat::Json,
+            "json",
+        );
+        if ui.button("export tags").clicked() {
+            data.options.is_export_triggered = true;
+        }
+        if ui.button("close").clicked() {
+            window_open = false;
+        }
+    });
+    Ok(ToolsData {
+        specifics: ToolSpecifics::Classify(data),
+        menu_active: window_open,
+    })
+}
+
+fn keypoint_combo(ui: &mut Ui, id: &str, names: &[String], selected: &mut usize) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(names.get(*selected).map(|s| s.as_str()).unwrap_or(""))
+        .show_ui(ui, |ui| {
+            for (kp_idx, name) in names.iter().enumerate() {
+                ui.selectable_value(selected, kp_idx, name);
+            }
+        });
+}
+
+pub fn keypoint_menu(
+    ui: &mut Ui,
+    mut window_open: bool,
+    mut data: KeypointToolData,
+) -> RvResult<ToolsData> {
+    ui.horizontal(|ui| {
+        ui.label("category");
+        ui.text_edit_singleline(&mut data.label);
+    });
+    ui.separator();
+    ui.label("keypoints");
+    if ui
+        .text_edit_singleline(&mut data.new_keypoint_name)
+        .lost_focus()
+        && !data.new_keypoint_name.is_empty()
+    {
+        let name = mem::take(&mut data.new_keypoint_name);
+        data.push_keypoint(name)?;
+        data.options.is_redraw_annos_triggered = true;
+    }
+    let names = data.skeleton().keypoint_names().clone();
+    let mut kp_to_be_removed = None;
+    for (kp_idx, name) in names.iter().enumerate() {
+        ui.horizontal_top(|ui| {
+            if ui.button("x").clicked() {
+                kp_to_be_removed = Some(kp_idx);
+            }
+            ui.label(format!("{}: {name}", kp_idx + 1));
+        });
+    }
+    if let Some(kp_idx) = kp_to_be_removed {
+        data.remove_keypoint(kp_idx);
+        data.options.is_redraw_annos_triggered = true;
+    }
+    ui.separator();
+    ui.label("skeleton");
+    let mut edge_to_be_removed = None;
+    for (edge_idx, (from, to)) in data.skeleton().edges().iter().enumerate() {
+        ui.horizontal_top(|ui| {
+            if ui.button("x").clicked() {
+                edge_to_be_removed = Some(edge_idx);
+            }
+            ui.label(format!("{} - {}", names[*from], names[*to]));
+        });
+    }
+    if let Some(edge_idx) = edge_to_be_removed {
+        data.remove_edge(edge_idx);
+        data.options.is_redraw_annos_triggered = true;
+    }
+    if names.len() > 1 {
+        let mut add_edge = false;
+        ui.horizontal(|ui| {
+            keypoint_combo(ui, "keypoint-edge-from", &names, &mut data.new_edge.0);
+            keypoint_combo(ui, "keypoint-edge-to", &names, &mut data.new_edge.1);
+            add_edge = ui.button("add edge").clicked();
+        });
+        if add_edge {
+            let (from, to) = data.new_edge;
+            data.push_edge(from, to)?;
+            data.options.is_redraw_annos_triggered = true;
+        }
+    }
+    ui.separator();
+    ui.label(format!("{} annotated instances", data.n_instances()));
+    ui.horizontal(|ui| {
+        if ui.button("export coco").clicked() {
+            data.options.is_export_triggered = true;
+        }
+        if ui.button("close").clicked() {
+            window_open = false;
+        }
+    });
+    Ok(ToolsData {
+        specifics: ToolSpecifics::Keypoint(data),
+        menu_active: window_open,
+    })
+}
+
+#[test]
+fn test_apply_label_edit() -> RvResult<()> {
+    let mut data = BboxSpecificData::new();
+    data.push("car".to_string(), None, None)?;
+    data.push("bus".to_string(), None, None)?;
+    let taken_cat_id = data.cat_ids()[0];
+    let edit = LabelEdit {
+        name: "lorry".to_string(),
+        color: data.colors()[2],
+        cat_id: taken_cat_id,
+        ..Default::default()
+    };
+    // the rename is not applied since the category id is taken
+    assert!(apply_label_edit(&mut data, 2, edit.clone()).is_err());
+    assert_eq!(data.labels()[2], "bus");
+    let edit = LabelEdit { cat_id: 42, ..edit };
+    assert!(apply_label_edit(&mut data, 2, edit.clone())?);
+    assert_eq!(data.labels()[2], "lorry");
+    assert_eq!(data.cat_ids()[2], 42);
+    assert!(!apply_label_edit(&mut data, 2, edit)?);
+    Ok(())
+}
@@ -108,6 +108,10 @@ impl PathsSelector {
             .map(|(idx, fl)| (*idx, fl.as_str()))
     }
 
+    pub fn file_paths(&self) -> Vec<&str> {
+        self.file_paths.iter().map(|fp| fp.as_str()).collect()
+    }
+
     pub fn filtered_file_paths(&self) -> Vec<&str> {
         self.filtered_file_labels
             .iter()
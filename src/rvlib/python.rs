@@ -0,0 +1,395 @@
+//! Python bindings of the annotation data model, enabled by the Cargo-feature `python`. Build the
+//! module with `maturin build --release`, see `pyproject.toml`.
+//! ```python
+//! import rvimage
+//! prj = rvimage.Project.load("my_prj.json")
+//! data = prj.bbox_data
+//! annos = data.annotations(data.file_paths()[0])
+//! boxes = annos.boxes()  # numpy array of shape (n, 4) with rows x, y, w, h
+//! ```
+use std::path::PathBuf;
+
+use numpy::{ndarray::Array2, IntoPyArray, PyArray2};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+use crate::{
+    cfg::{self, Cfg, CocoFile, CocoFileConnection},
+    control::detail,
+    domain::{Shape, BB},
+    file_util::MetaData,
+    result::{to_rv, RvError, RvResult},
+    tools::BBOX_NAME,
+    tools_data::{
+        annotations::BboxAnnotations, coco_io, mask_io, BboxSpecificData, ToolSpecifics, ToolsData,
+    },
+    world::ToolsDataMap,
+    GeoFig, Polygon,
+};
+
+impl From<RvError> for PyErr {
+    fn from(e: RvError) -> Self {
+        PyValueError::new_err(e.to_string())
+    }
+}
+
+fn to_array2<T: numpy::Element>(rows: usize, cols: usize, data: Vec<T>) -> RvResult<Array2<T>> {
+    Array2::from_shape_vec((rows, cols), data).map_err(to_rv)
+}
+
+/// Box or polygon in pixel coordinates
+#[pyclass(name = "GeoFig", module = "rvimage")]
+#[derive(Clone)]
+pub struct PyGeoFig {
+    geo: GeoFig,
+}
+#[pymethods]
+impl PyGeoFig {
+    #[staticmethod]
+    fn bbox(x: u32, y: u32, w: u32, h: u32) -> Self {
+        Self {
+            geo: GeoFig::BB(BB::from_arr(&[x, y, w, h])),
+        }
+    }
+    #[staticmethod]
+    fn polygon(points: Vec<(u32, u32)>) -> PyResult<Self> {
+        let points = points.into_iter().map(|p| p.into()).collect();
+        Ok(Self {
+            geo: GeoFig::Poly(Polygon::from_vec(points, false)?),
+        })
+    }
+    fn is_bbox(&self) -> bool {
+        matches!(self.geo, GeoFig::BB(_))
+    }
+    /// corners of the box or vertices of the polygon
+    fn points(&self) -> Vec<(u32, u32)> {
+        match &self.geo {
+            GeoFig::BB(bb) => bb.points_iter().map(|p| (p.x, p.y)).collect(),
+            GeoFig::Poly(poly) => poly.points_iter().map(|p| (p.x, p.y)).collect(),
+        }
+    }
+    /// `(x, y, w, h)`
+    fn enclosing_bb(&self) -> (u32, u32, u32, u32) {
+        let bb = self.geo.enclosing_bb();
+        (bb.x, bb.y, bb.w, bb.h)
+    }
+    /// points as array of shape `(n, 2)`
+    fn to_numpy<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<u32>>> {
+        let points = self.points();
+        let data = points.iter().flat_map(|(x, y)| [*x, *y]).collect();
+        Ok(to_array2(points.len(), 2, data)?.into_pyarray(py))
+    }
+    fn __repr__(&self) -> String {
+        format!("{:?}", self.geo)
+    }
+}
+
+/// Geometries and their label indices of one image
+#[pyclass(name = "BboxAnnotations", module = "rvimage")]
+#[derive(Clone, Default)]
+pub struct PyBboxAnnotations {
+    annos: BboxAnnotations,
+}
+#[pymethods]
+impl PyBboxAnnotations {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+    fn add(&mut self, geo: PyRef<'_, PyGeoFig>, cat_idx: usize) {
+        self.annos.add_geo(geo.geo.clone(), cat_idx);
+    }
+    fn geos(&self) -> Vec<PyGeoFig> {
+        self.annos
+            .geos()
+            .iter()
+            .map(|geo| PyGeoFig { geo: geo.clone() })
+            .collect()
+    }
+    fn cat_idxs(&self) -> Vec<usize> {
+        self.annos.cat_idxs().clone()
+    }
+    /// enclosing boxes as array of shape `(n, 4)` with rows `x, y, w, h`
+    fn boxes<'py>(&self, py: Python<'py>) -> PyResult<Bound<'py, PyArray2<u32>>> {
+        let geos = self.annos.geos();
+        let data = geos
+            .iter()
+            .map(|geo| geo.enclosing_bb())
+            .flat_map(|bb| [bb.x, bb.y, bb.w, bb.h])
+            .collect();
+        Ok(to_array2(geos.len(), 4, data)?.into_pyarray(py))
+    }
+    /// array of shape `(height, width)` with the label index plus 1 per pixel and 0 for the
+    /// background, raises a `ValueError` for label indices above 254
+    fn semantic_mask<'py>(
+        &self,
+        py: Python<'py>,
+        width: u32,
+        height: u32,
+    ) -> PyResult<Bound<'py, PyArray2<u8>>> {
+        let mask = mask_io::semantic_mask(&self.annos, Shape::new(width, height))?;
+        Ok(to_array2(height as usize, width as usize, mask.into_raw())?.into_pyarray(py))
+    }
+    /// array of shape `(height, width)` with the annotation index plus 1 per pixel and 0 for the
+    /// background
+    fn instance_mask<'py>(
+        &self,
+        py: Python<'py>,
+        width: u32,
+        height: u32,
+    ) -> PyResult<Bound<'py, PyArray2<u16>>> {
+        let mask = mask_io::instance_mask(&self.annos, Shape::new(width, height));
+        Ok(to_array2(height as usize, width as usize, mask.into_raw())?.into_pyarray(py))
+    }
+    fn __len__(&self) -> usize {
+        self.annos.geos().len()
+    }
+    fn __repr__(&self) -> String {
+        format!(
+            "BboxAnnotations(geos={:?}, cat_idxs={:?})",
+            self.annos.geos(),
+            self.annos.cat_idxs()
+        )
+    }
+}
+
+/// Labels and annotations of all images of the bounding box tool
+#[pyclass(name = "BboxData", module = "rvimage")]
+#[derive(Clone, Default)]
+pub struct PyBboxData {
+    data: BboxSpecificData,
+}
+#[pymethods]
+impl PyBboxData {
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+    #[getter]
+    fn labels(&self) -> Vec<String> {
+        self.data.labels().clone()
+    }
+    #[getter]
+    fn colors(&self) -> Vec<[u8; 3]> {
+        self.data.colors().clone()
+    }
+    #[getter]
+    fn cat_ids(&self) -> Vec<u32> {
+        self.data.cat_ids().clone()
+    }
+    fn add_label(&mut self, label: String) -> PyResult<()> {
+        Ok(self.data.push(label, None, None)?)
+    }
+    fn file_paths(&self) -> Vec<String> {
+        let mut file_paths = self
+            .data
+            .anno_iter()
+            .map(|(file_path, _)| file_path.clone())
+            .collect::<Vec<_>>();
+        file_paths.sort();
+        file_paths
+    }
+    fn annotations(&self, file_path: &str) -> Option<PyBboxAnnotations> {
+        self.data
+            .get_annos(file_path)
+            .map(|annos| PyBboxAnnotations {
+                annos: annos.clone(),
+            })
+    }
+    /// `(width, height)` of the image
+    fn shape(&self, file_path: &str) -> Option<(u32, u32)> {
+        self.data
+            .annotations_map()
+            .get(file_path)
+            .map(|(_, shape)| (shape.w, shape.h))
+    }
+    /// Replaces the annotations of the image, the label indices need to exist.
+    fn set_annotations(
+        &mut self,
+        file_path: String,
+        annotations: PyRef<'_, PyBboxAnnotations>,
+        width: u32,
+        height: u32,
+    ) -> PyResult<()> {
+        let mut annotations_map = self.data.annotations_map().clone();
+        annotations_map.insert(
+            file_path,
+            (annotations.annos.clone(), Shape::new(width, height)),
+        );
+        Ok(self.data.set_annotations_map(annotations_map)?)
+    }
+    fn remove_annotations(&mut self, file_path: &str) {
+        self.data.retain_fileannos(|fp| fp != file_path);
+    }
+    fn __repr__(&self) -> String {
+        format!(
+            "BboxData(labels={:?}, n_images={})",
+            self.data.labels(),
+            self.data.anno_iter().count()
+        )
+    }
+}
+
+fn coco_meta_data(opened_folder: Option<String>) -> MetaData {
+    MetaData {
+        opened_folder,
+        ..Default::default()
+    }
+}
+
+fn local_coco_file(path: PathBuf) -> CocoFile {
+    CocoFile {
+        path,
+        conn: CocoFileConnection::Local,
+        prefix_map: vec![],
+    }
+}
+
+/// Reads a Coco file, relative image paths are resolved against `opened_folder`.
+#[pyfunction]
+#[pyo3(signature = (path, opened_folder=None))]
+fn read_coco(path: PathBuf, opened_folder: Option<String>) -> PyResult<PyBboxData> {
+    let (data, _) = coco_io::read_coco(&coco_meta_data(opened_folder), &local_coco_file(path))?;
+    Ok(PyBboxData { data })
+}
+
+/// Writes a Coco file, image paths inside `opened_folder` are stored relative to it.
+#[pyfunction]
+#[pyo3(signature = (data, path, opened_folder=None))]
+fn write_coco(
+    data: PyRef<'_, PyBboxData>,
+    path: PathBuf,
+    opened_folder: Option<String>,
+) -> PyResult<PathBuf> {
+    let mut data = data.data.clone();
+    data.coco_file = local_coco_file(path);
+    Ok(coco_io::write_coco(&coco_meta_data(opened_folder), data)?)
+}
+
+/// Project file as saved by RV Image. Data of other tools is kept as is.
+#[pyclass(name = "Project", module = "rvimage")]
+#[derive(Clone)]
+pub struct PyProject {
+    tools_data_map: ToolsDataMap,
+    opened_folder: Option<String>,
+    cfg: Cfg,
+}
+#[pymethods]
+impl PyProject {
+    #[new]
+    #[pyo3(signature = (opened_folder=None))]
+    fn new(opened_folder: Option<String>) -> Self {
+        Self {
+            tools_data_map: ToolsDataMap::new(),
+            opened_folder,
+            cfg: cfg::get_default_cfg(),
+        }
+    }
+    #[staticmethod]
+    fn load(path: PathBuf) -> PyResult<Self> {
+        let (tools_data_map, opened_folder, cfg) = detail::read(&path)?;
+        Ok(Self {
+            tools_data_map,
+            opened_folder,
+            cfg,
+        })
+    }
+    fn save(&self, path: PathBuf) -> PyResult<()> {
+        Ok(detail::write(
+            &path,
+            self.opened_folder.as_ref(),
+            &self.tools_data_map,
+            &self.cfg,
+        )?)
+    }
+    #[getter]
+    fn opened_folder(&self) -> Option<String> {
+        self.opened_folder.clone()
+    }
+    #[setter]
+    fn set_opened_folder(&mut self, opened_folder: Option<String>) {
+        self.opened_folder = opened_folder;
+    }
+    #[getter]
+    fn bbox_data(&self) -> Option<PyBboxData> {
+        self.tools_data_map
+            .get(BBOX_NAME)
+            .map(|bbox_data| PyBboxData {
+                data: bbox_data.specifics.bbox().clone(),
+            })
+    }
+    #[setter]
+    fn set_bbox_data(&mut self, bbox_data: Option<PyRef<'_, PyBboxData>>) {
+        self.set_bbox_specifics(bbox_data.map(|bbox_data| bbox_data.data.clone()));
+    }
+}
+impl PyProject {
+    fn set_bbox_specifics(&mut self, bbox_data: Option<BboxSpecificData>) {
+        match bbox_data {
+            Some(bbox_data) => {
+                self.tools_data_map
+                    .insert(BBOX_NAME, ToolsData::new(ToolSpecifics::Bbox(bbox_data)));
+            }
+            None => {
+                self.tools_data_map.remove(BBOX_NAME);
+            }
+        }
+    }
+}
+
+#[pymodule]
+fn rvimage(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyGeoFig>()?;
+    m.add_class::<PyBboxAnnotations>()?;
+    m.add_class::<PyBboxData>()?;
+    m.add_class::<PyProject>()?;
+    m.add_function(wrap_pyfunction!(read_coco, m)?)?;
+    m.add_function(wrap_pyfunction!(write_coco, m)?)?;
+    Ok(())
+}
+
+#[cfg(all(test, feature = "python"))]
+use crate::{defer_file_removal, file_util::DEFAULT_TMPDIR};
+
+#[cfg(all(test, feature = "python"))]
+#[test]
+fn test_project_roundtrip() -> PyResult<()> {
+    let path = DEFAULT_TMPDIR.join("rvprj_test_python_roundtrip.json");
+    defer_file_removal!(&path);
+    let shape = Shape::new(40, 30);
+    let mut bbox_data = BboxSpecificData::new();
+    bbox_data.push("cat".to_string(), None, None)?;
+    bbox_data
+        .get_annos_mut("folder/a.png", shape)
+        .add_bb(BB::from_arr(&[1, 2, 3, 4]), 1);
+    let mut prj = PyProject::new(Some("folder".to_string()));
+    prj.set_bbox_specifics(Some(bbox_data));
+    prj.save(path.clone())?;
+
+    let loaded = PyProject::load(path.clone())?;
+    assert_eq!(loaded.opened_folder(), Some("folder".to_string()));
+    let loaded_data = loaded.bbox_data().unwrap();
+    assert_eq!(loaded_data.labels(), vec!["foreground", "cat"]);
+    assert_eq!(loaded_data.file_paths(), vec!["folder/a.png"]);
+    assert_eq!(loaded_data.shape("folder/a.png"), Some((40, 30)));
+    let annos = loaded_data.annotations("folder/a.png").unwrap();
+    assert_eq!(annos.cat_idxs(), vec![1]);
+    assert_eq!(annos.geos()[0].enclosing_bb(), (1, 2, 3, 4));
+    assert_eq!(loaded.tools_data_map, prj.tools_data_map);
+    Ok(())
+}
+
+#[cfg(all(test, feature = "python"))]
+#[test]
+fn test_to_array2() -> RvResult<()> {
+    let arr = to_array2(2, 3, vec![1u32, 2, 3, 4, 5, 6])?;
+    assert_eq!(arr.shape(), &[2, 3]);
+    assert_eq!(arr[[1, 0]], 4);
+    assert!(to_array2(2, 2, vec![1u32, 2, 3]).is_err());
+    let annos = BboxAnnotations::from_bbs(vec![BB::from_arr(&[1, 0, 2, 1])], 0);
+    let mask = mask_io::semantic_mask(&annos, Shape::new(4, 2))?;
+    let arr = to_array2(2, 4, mask.into_raw())?;
+    // rows are image rows
+    assert_eq!(arr.row(0).to_vec(), vec![0, 1, 1, 0]);
+    assert_eq!(arr.row(1).to_vec(), vec![0, 0, 0, 0]);
+    Ok(())
+}
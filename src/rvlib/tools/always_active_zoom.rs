@@ -4,6 +4,7 @@ use crate::{
     domain::zoom_box_mouse_wheel,
     events::{Events, KeyCode},
     history::History,
+    keymap::KeyAction,
     make_tool_transform,
     tools::core::Manipulate,
     world::World,
@@ -48,18 +49,14 @@ impl AlwaysActiveZoom {
         mut world: World,
         history: History,
     ) -> (World, History) {
-        if events.held_ctrl() {
-            let zb = if events.released(KeyCode::Key0) {
-                None
-            } else if events.released(KeyCode::PlusEquals) {
-                zoom_box_mouse_wheel(*world.zoom_box(), world.shape_orig(), 1.0)
-            } else if events.released(KeyCode::Minus) {
-                zoom_box_mouse_wheel(*world.zoom_box(), world.shape_orig(), -1.0)
-            } else {
-                *world.zoom_box()
-            };
-            world.set_zoom_box(zb);
-        }
+        let zb = if events.triggered(KeyAction::ZoomIn) {
+            zoom_box_mouse_wheel(*world.zoom_box(), world.shape_orig(), 1.0)
+        } else if events.triggered(KeyAction::ZoomOut) {
+            zoom_box_mouse_wheel(*world.zoom_box(), world.shape_orig(), -1.0)
+        } else {
+            *world.zoom_box()
+        };
+        world.set_zoom_box(zb);
         (world, history)
     }
 }
@@ -78,9 +75,8 @@ impl Manipulate for AlwaysActiveZoom {
             [
                 (pressed, KeyCode::MouseRight, mouse_pressed),
                 (held, KeyCode::MouseRight, mouse_held),
-                (released, KeyCode::Key0, key_released),
-                (released, KeyCode::PlusEquals, key_released), // Plus is equals
-                (released, KeyCode::Minus, key_released)
+                (triggered, KeyAction::ZoomIn, key_released),
+                (triggered, KeyAction::ZoomOut, key_released)
             ]
         )
     }
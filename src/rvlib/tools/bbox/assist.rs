@@ -0,0 +1,268 @@
+//! Classic image processing that helps drawing annotations, no model is involved.
+use std::collections::VecDeque;
+
+use image::{DynamicImage, GenericImageView, GrayImage, Luma};
+use imageproc::{
+    contours::{find_contours, BorderType},
+    contrast::otsu_level,
+    edges::canny,
+};
+
+use crate::{
+    domain::{PtI, Shape, BB},
+    Polygon,
+};
+
+const CANNY_LOW: f32 = 50.0;
+const CANNY_HIGH: f32 = 100.0;
+
+/// Box around all pixels of the mask that satisfy the predicate
+fn bb_of_pixels(mask: &GrayImage, pred: impl Fn(u8) -> bool) -> Option<BB> {
+    let mut min_max: Option<(u32, u32, u32, u32)> = None;
+    for (x, y, Luma([v])) in mask.enumerate_pixels() {
+        if pred(*v) {
+            min_max = Some(match min_max {
+                Some((x_min, y_min, x_max, y_max)) => {
+                    (x_min.min(x), y_min.min(y), x_max.max(x), y_max.max(y))
+                }
+                None => (x, y, x, y),
+            });
+        }
+    }
+    min_max.map(|(x_min, y_min, x_max, y_max)| {
+        BB::from_arr(&[x_min, y_min, x_max - x_min + 1, y_max - y_min + 1])
+    })
+}
+
+/// Tightens the box to the object inside. Pixels are split by Otsu's threshold and the class
+/// that dominates the border of the box is considered as background. If thresholding does not
+/// separate anything, the box is fitted to the Canny edges inside.
+pub fn snap_to_content(im: &DynamicImage, bb: BB) -> Option<BB> {
+    let bb = bb.intersect(BB::from_shape(Shape::from_im(im)));
+    if bb.w < 3 || bb.h < 3 {
+        return None;
+    }
+    let gray = im.crop_imm(bb.x, bb.y, bb.w, bb.h).to_luma8();
+    let level = otsu_level(&gray);
+    let is_border = |x: u32, y: u32| x == 0 || y == 0 || x == bb.w - 1 || y == bb.h - 1;
+    let (n_border, n_border_bright) = gray
+        .enumerate_pixels()
+        .filter(|(x, y, _)| is_border(*x, *y))
+        .fold((0, 0), |(n, n_bright), (_, _, Luma([v]))| {
+            (n + 1, n_bright + usize::from(*v > level))
+        });
+    let is_background_bright = 2 * n_border_bright > n_border;
+    let by_threshold = bb_of_pixels(&gray, |v| (v > level) != is_background_bright)
+        .filter(|inner| inner.w < bb.w || inner.h < bb.h);
+    let inner = by_threshold.or_else(|| {
+        let edges = canny(&gray, CANNY_LOW, CANNY_HIGH);
+        bb_of_pixels(&edges, |v| v > 0)
+    })?;
+    Some(BB::from_arr(&[
+        bb.x + inner.x,
+        bb.y + inner.y,
+        inner.w,
+        inner.h,
+    ]))
+}
+
+/// Mask of the pixels that are 4-connected to the seed and whose colors differ from the seed's
+/// color by at most `tolerance` in each channel
+pub fn magic_wand(im: &DynamicImage, seed: PtI, tolerance: u8) -> GrayImage {
+    let (w, h) = im.dimensions();
+    let mut mask = GrayImage::new(w, h);
+    if seed.x >= w || seed.y >= h {
+        return mask;
+    }
+    let seed_color = im.get_pixel(seed.x, seed.y).0;
+    let is_similar = |x: u32, y: u32| {
+        let color = im.get_pixel(x, y).0;
+        color
+            .iter()
+            .zip(seed_color.iter())
+            .all(|(c, s)| c.abs_diff(*s) <= tolerance)
+    };
+    let mut queue = VecDeque::from([(seed.x, seed.y)]);
+    mask.put_pixel(seed.x, seed.y, Luma([255]));
+    while let Some((x, y)) = queue.pop_front() {
+        let neighbors = [
+            (x.checked_sub(1), Some(y)),
+            ((x + 1 < w).then_some(x + 1), Some(y)),
+            (Some(x), y.checked_sub(1)),
+            (Some(x), (y + 1 < h).then_some(y + 1)),
+        ];
+        for (nx, ny) in neighbors {
+            if let (Some(nx), Some(ny)) = (nx, ny) {
+                if mask.get_pixel(nx, ny).0[0] == 0 && is_similar(nx, ny) {
+                    mask.put_pixel(nx, ny, Luma([255]));
+                    queue.push_back((nx, ny));
+                }
+            }
+        }
+    }
+    mask
+}
+
+fn dist_to_line(p: PtI, start: PtI, end: PtI) -> f64 {
+    let (px, py) = (p.x as f64, p.y as f64);
+    let (sx, sy) = (start.x as f64, start.y as f64);
+    let (ex, ey) = (end.x as f64, end.y as f64);
+    let len = ((ex - sx).powi(2) + (ey - sy).powi(2)).sqrt();
+    if len == 0.0 {
+        ((px - sx).powi(2) + (py - sy).powi(2)).sqrt()
+    } else {
+        ((ey - sy) * px - (ex - sx) * py + ex * sy - ey * sx).abs() / len
+    }
+}
+
+/// Ramer-Douglas-Peucker simplification of an open polyline, keeps both end points
+fn simplify(points: &[PtI], epsilon: f64) -> Vec<PtI> {
+    if points.len() < 3 {
+        return points.to_vec();
+    }
+    let (start, end) = (points[0], points[points.len() - 1]);
+    let (idx_max, dist_max) = points[1..points.len() - 1]
+        .iter()
+        .enumerate()
+        .map(|(i, p)| (i + 1, dist_to_line(*p, start, end)))
+        .fold(
+            (0, 0.0),
+            |(i_max, d_max), (i, d)| {
+                if d > d_max {
+                    (i, d)
+                } else {
+                    (i_max, d_max)
+                }
+            },
+        );
+    if dist_max > epsilon {
+        let mut left = simplify(&points[..=idx_max], epsilon);
+        let right = simplify(&points[idx_max..], epsilon);
+        left.pop();
+        left.extend(right);
+        left
+    } else {
+        vec![start, end]
+    }
+}
+
+/// Outline of the largest region of the mask, simplified such that no pixel of the outline is
+/// further than `epsilon` away from the polygon. The outline runs along the outer pixel corners
+/// such that the polygon covers the pixels of the region.
+pub fn mask_to_polygon(mask: &GrayImage, epsilon: f64) -> Option<Polygon> {
+    let contour = find_contours::<u32>(mask)
+        .into_iter()
+        .filter(|c| c.border_type == BorderType::Outer)
+        .max_by_key(|c| c.points.len())?;
+    let is_outside =
+        |x: u32, y: u32| x >= mask.width() || y >= mask.height() || mask.get_pixel(x, y).0[0] == 0;
+    // contours run through pixel centers, we move them to the corners on the outside
+    let points = contour
+        .points
+        .iter()
+        .map(|p| PtI {
+            x: if is_outside(p.x + 1, p.y) {
+                p.x + 1
+            } else {
+                p.x
+            },
+            y: if is_outside(p.x, p.y + 1) {
+                p.y + 1
+            } else {
+                p.y
+            },
+        })
+        .collect::<Vec<_>>();
+    if points.len() < 3 {
+        return None;
+    }
+    // the outline is closed, we split it at the point that is furthest from the first one
+    let first = points[0];
+    let idx_far = (1..points.len())
+        .max_by_key(|i| {
+            let p = points[*i];
+            p.x.abs_diff(first.x).pow(2) + p.y.abs_diff(first.y).pow(2)
+        })
+        .unwrap_or(0);
+    let mut closed = points.clone();
+    closed.push(first);
+    let mut simplified = simplify(&closed[..=idx_far], epsilon);
+    simplified.pop();
+    simplified.extend(simplify(&closed[idx_far..], epsilon));
+    // the last point equals the first one
+    simplified.pop();
+    if simplified.len() < 3 {
+        return None;
+    }
+    Polygon::from_vec(simplified, false).ok()
+}
+
+#[cfg(test)]
+use image::{Rgb, RgbImage};
+
+#[cfg(test)]
+fn make_test_im() -> DynamicImage {
+    // dark image with a bright square from (10, 20) to (29, 34)
+    DynamicImage::ImageRgb8(RgbImage::from_fn(50, 50, |x, y| {
+        if (10..30).contains(&x) && (20..35).contains(&y) {
+            Rgb([200, 210, 190])
+        } else {
+            Rgb([20, 25, 30])
+        }
+    }))
+}
+
+#[test]
+fn test_snap_to_content() {
+    let im = make_test_im();
+    assert_eq!(
+        snap_to_content(&im, BB::from_arr(&[5, 5, 40, 40])),
+        Some(BB::from_arr(&[10, 20, 20, 15]))
+    );
+    // boxes that leave the image are clipped first
+    assert_eq!(
+        snap_to_content(&im, BB::from_arr(&[5, 15, 100, 100])),
+        Some(BB::from_arr(&[10, 20, 20, 15]))
+    );
+    // a dark object on bright ground
+    let inverted = {
+        let mut im = im.clone();
+        im.invert();
+        im
+    };
+    assert_eq!(
+        snap_to_content(&inverted, BB::from_arr(&[5, 5, 40, 40])),
+        Some(BB::from_arr(&[10, 20, 20, 15]))
+    );
+    assert_eq!(snap_to_content(&im, BB::from_arr(&[0, 0, 2, 2])), None);
+}
+
+#[test]
+fn test_magic_wand() {
+    let im = make_test_im();
+    let mask = magic_wand(&im, PtI { x: 15, y: 25 }, 10);
+    assert_eq!(
+        bb_of_pixels(&mask, |v| v > 0),
+        Some(BB::from_arr(&[10, 20, 20, 15]))
+    );
+    assert_eq!(mask.pixels().filter(|p| p.0[0] > 0).count(), 20 * 15);
+    let poly = mask_to_polygon(&mask, 1.0).unwrap();
+    assert_eq!(poly.points().len(), 4);
+    assert_eq!(poly.enclosing_bb(), BB::from_arr(&[10, 20, 20, 15]));
+    // the tolerance is large enough to include the background
+    let mask = magic_wand(&im, PtI { x: 15, y: 25 }, 200);
+    assert_eq!(mask.pixels().filter(|p| p.0[0] > 0).count(), 50 * 50);
+    let mask = magic_wand(&im, PtI { x: 50, y: 25 }, 10);
+    assert!(mask_to_polygon(&mask, 1.0).is_none());
+}
+
+#[test]
+fn test_simplify() {
+    let line = (0..10).map(|x| PtI { x, y: 0 }).collect::<Vec<_>>();
+    assert_eq!(
+        simplify(&line, 0.5),
+        vec![PtI { x: 0, y: 0 }, PtI { x: 9, y: 0 }]
+    );
+    let corner = [PtI { x: 0, y: 0 }, PtI { x: 5, y: 0 }, PtI { x: 5, y: 5 }];
+    assert_eq!(simplify(&corner, 0.5).len(), 3);
+}
@@ -1,24 +1,35 @@
 use crate::{
     annotations::BboxAnnotations,
     annotations_accessor, annotations_accessor_mut,
+    control::Info,
     domain::{Shape, BB},
-    drawme::{Annotation, Stroke},
+    drawme::{Annotation, BboxAnnotation, Stroke},
     events::{Events, KeyCode},
     file_util,
     history::{History, Record},
+    keymap::KeyAction,
     make_tool_transform,
     tools::{core::Mover, Manipulate, BBOX_NAME},
-    tools_data::{bbox_data::Options, BboxSpecificData, ToolSpecifics, ToolsData},
+    tools_data::{
+        bbox_data::Options, data_split, label_schema::LabelSchema, mot_io, BboxSpecificData,
+        ToolSpecifics, ToolsData,
+    },
     tools_data_accessor, tools_data_accessor_mut, tools_data_initializer,
+    util::natural_cmp,
     world::World,
     GeoFig,
 };
-use std::mem;
-
-use super::on_events::{
-    export_if_triggered, import_coco_if_triggered, map_released_key, on_key_released,
-    on_mouse_held_right, on_mouse_released_left, on_mouse_released_right, KeyReleasedParams,
-    MouseHeldParams, MouseReleaseParams, PrevPos,
+use image::GrayImage;
+use std::{cmp::Ordering, mem};
+
+use super::{
+    assist,
+    on_events::{
+        export_if_triggered, import_coco_if_triggered, on_key_action, on_key_action_released,
+        on_magic_wand_click, on_mouse_held_right, on_mouse_released_left, on_mouse_released_right,
+        MouseHeldParams, MouseReleaseParams, PrevPos,
+    },
+    track,
 };
 pub const ACTOR_NAME: &str = "BBox";
 const MISSING_ANNO_MSG: &str = "bbox annotations have not yet been initialized";
@@ -48,11 +59,7 @@ pub(super) fn paste(mut world: World, mut history: History) -> (World, History)
         let cb_bbs = clipboard.geos();
         if !cb_bbs.is_empty() {
             let shape_orig = Shape::from_im(world.data.im_background());
-            get_annos_mut(&mut world).extend(
-                cb_bbs.iter().cloned(),
-                clipboard.cat_idxs().iter().copied(),
-                shape_orig,
-            );
+            get_annos_mut(&mut world).extend_with(clipboard.entries(), shape_orig);
             get_tools_data_mut(&mut world)
                 .specifics
                 .bbox_mut()
@@ -85,6 +92,7 @@ fn check_recolorboxes(mut world: World) -> World {
             data.new_random_colors();
             data.options.is_colorchange_triggered = false;
             data.options.are_boxes_visible = true;
+            data.options.is_history_update_triggered = true;
         }
         world.request_redraw_annotations(BBOX_NAME, are_boxes_visible);
     }
@@ -157,15 +165,47 @@ fn check_cocoexport(mut world: World) -> World {
 fn check_cocoimport(mut world: World) -> World {
     // import coco if demanded
     let flags = get_tools_data(&world).specifics.bbox().options;
-    if let Some(imported_data) = import_coco_if_triggered(
+    let imported = import_coco_if_triggered(
         &world.data.meta_data,
         flags.is_coco_import_triggered,
         &get_tools_data(&world).specifics.bbox().coco_file,
-    ) {
+    );
+    if let Some(Ok((mut imported_data, warnings))) = imported {
+        let current_data = get_tools_data(&world).specifics.bbox();
+        let outside = imported_data
+            .labels()
+            .iter()
+            .filter(|label| !current_data.labels().contains(label))
+            .cloned()
+            .collect::<Vec<_>>();
+        if current_data.is_locked() && !outside.is_empty() {
+            get_tools_data_mut(&mut world)
+                .specifics
+                .bbox_mut()
+                .options
+                .is_coco_import_triggered = false;
+            world.show_info(Info::Warning(format!(
+                "import refused, the coco file contains categories {outside:?} outside the locked labels"
+            )));
+            return world;
+        }
+        imported_data.set_locked(current_data.is_locked());
+        imported_data.set_schema(current_data.schema().cloned());
+        imported_data.options.is_path_matching_triggered = true;
         let are_boxes_visible = imported_data.options.are_boxes_visible;
         *get_tools_data_mut(&mut world).specifics.bbox_mut() = imported_data;
         world.request_redraw_annotations(BBOX_NAME, are_boxes_visible);
+        if !warnings.is_empty() {
+            let warnings = warnings.iter().map(|w| w.to_string()).collect::<Vec<_>>();
+            world.show_info(Info::Warning(format!(
+                "imported coco file with warnings, {}",
+                warnings.join("; ")
+            )));
+        }
     } else {
+        if let Some(Err(e)) = imported {
+            world.show_info(Info::Error(format!("could not import coco due to {e:?}")));
+        }
         get_tools_data_mut(&mut world)
             .specifics
             .bbox_mut()
@@ -175,6 +215,81 @@ fn check_cocoimport(mut world: World) -> World {
     world
 }
 
+fn check_splitexport(mut world: World) -> World {
+    let meta_data = world.data.meta_data.clone();
+    let data = get_tools_data_mut(&mut world).specifics.bbox_mut();
+    if data.options.is_split_export_triggered {
+        data.options.is_split_export_triggered = false;
+        if let Err(e) = data_split::write_coco_splits(&meta_data, data) {
+            world.show_info(Info::Error(format!("could not export splits due to {e:?}")));
+        }
+    }
+    world
+}
+
+fn check_schemaimport(mut world: World) -> World {
+    let data = get_tools_data_mut(&mut world).specifics.bbox_mut();
+    if data.options.is_schema_import_triggered {
+        data.options.is_schema_import_triggered = false;
+        match LabelSchema::read(&data.schema_path) {
+            Ok(schema) => {
+                let outside = data.import_label_schema(&schema);
+                data.options.is_history_update_triggered = true;
+                if !outside.is_empty() {
+                    world.show_info(Info::Warning(format!(
+                        "labels {outside:?} are not in the schema but in use"
+                    )));
+                }
+            }
+            Err(e) => world.show_info(Info::Error(format!(
+                "could not import label schema due to {e:?}"
+            ))),
+        }
+    }
+    world
+}
+
+fn check_snap(mut world: World) -> World {
+    let data = get_tools_data_mut(&mut world).specifics.bbox_mut();
+    if data.options.is_snap_triggered {
+        data.options.is_snap_triggered = false;
+        let im = world.data.im_background();
+        let snapped = world
+            .data
+            .meta_data
+            .file_path
+            .as_ref()
+            .and_then(|_| get_annos(&world))
+            .map(|annos| {
+                annos
+                    .geos()
+                    .iter()
+                    .zip(annos.selected_bbs().iter())
+                    .enumerate()
+                    .filter_map(|(idx, (geo, is_selected))| match geo {
+                        GeoFig::BB(bb) if *is_selected => {
+                            assist::snap_to_content(im, *bb).map(|snapped| (idx, snapped))
+                        }
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        if !snapped.is_empty() {
+            let annos = get_annos_mut(&mut world);
+            for (idx, bb) in snapped {
+                annos.set_geo(idx, GeoFig::BB(bb));
+            }
+            get_tools_data_mut(&mut world)
+                .specifics
+                .bbox_mut()
+                .options
+                .is_history_update_triggered = true;
+        }
+    }
+    world
+}
+
 fn check_labelchange(mut world: World, prev_label: usize, options: Options) -> World {
     let in_menu_selected_label = current_cat_idx(&world);
     if prev_label != in_menu_selected_label {
@@ -196,12 +311,87 @@ fn check_autopaste(
     (world, history)
 }
 
+/// Whether `file_path` is a later frame of the same sequence folder as `prev_path`
+fn is_forward_step(prev_path: &str, file_path: &str) -> bool {
+    mot_io::parent_folder(prev_path) == mot_io::parent_folder(file_path)
+        && natural_cmp(prev_path, file_path) == Ordering::Less
+}
+
+/// Tracks the annotations of the previously shown frame into the current frame when stepping
+/// forward through a sequence. Objects whose track ids are already on the current frame are
+/// skipped and so are frames with untracked annotations, e.g., pasted ones. Returns the current
+/// frame to be used as previous frame later on.
+fn check_autotrack(
+    mut world: World,
+    mut history: History,
+    auto_track: bool,
+    prev_frame: Option<(String, GrayImage)>,
+) -> (World, History, Option<(String, GrayImage)>) {
+    if !auto_track {
+        return (world, history, None);
+    }
+    let file_path = match &world.data.meta_data.file_path {
+        Some(fp) if world.data.meta_data.is_loading_screen_active == Some(false) => fp.clone(),
+        // the previous frame is kept while the next one is loading
+        _ => return (world, history, prev_frame),
+    };
+    if prev_frame.as_ref().map(|(fp, _)| fp) == Some(&file_path) {
+        return (world, history, prev_frame);
+    }
+    let cur = world.data.im_background().to_luma8();
+    let cur_track_ids = get_annos(&world)
+        .map(|annos| annos.track_ids().clone())
+        .unwrap_or_default();
+    let is_cur_untracked = cur_track_ids.iter().any(|t| t.is_none());
+    let prev_frame = prev_frame
+        .filter(|(prev_path, _)| !is_cur_untracked && is_forward_step(prev_path, &file_path));
+    if let Some((prev_path, prev)) = prev_frame {
+        let bbox_data = get_tools_data_mut(&mut world).specifics.bbox_mut();
+        let mut next_id = bbox_data.next_track_id();
+        let prev_shape = Shape::new(prev.width(), prev.height());
+        let tracked =
+            if prev.dimensions() == cur.dimensions() && bbox_data.get_annos(&prev_path).is_some() {
+                // objects keep their identity, hence untracked objects get a track id first
+                let prev_annos = bbox_data.get_annos_mut(&prev_path, prev_shape);
+                for idx in 0..prev_annos.track_ids().len() {
+                    if prev_annos.track_ids()[idx].is_none() {
+                        prev_annos.set_track_id(idx, Some(next_id));
+                        next_id += 1;
+                    }
+                }
+                prev_annos
+                    .geos()
+                    .iter()
+                    .zip(prev_annos.cat_idxs().iter())
+                    .zip(prev_annos.track_ids().iter().flatten())
+                    .filter(|(_, track_id)| !cur_track_ids.contains(&Some(**track_id)))
+                    .filter_map(|((geo, cat_idx), track_id)| {
+                        track::track_geo(&prev, &cur, geo).map(|geo| (geo, *cat_idx, *track_id))
+                    })
+                    .collect::<Vec<_>>()
+            } else {
+                vec![]
+            };
+        if !tracked.is_empty() {
+            let annos = get_annos_mut(&mut world);
+            for (geo, cat_idx, track_id) in tracked {
+                annos.add_tracked_geo(geo, cat_idx, track_id);
+            }
+            world.request_redraw_annotations(BBOX_NAME, are_boxes_visible(&world));
+            history.push(Record::new(world.data.clone(), ACTOR_NAME));
+        }
+    }
+    (world, history, Some((file_path, cur)))
+}
+
 #[derive(Clone, Debug)]
 pub struct BBox {
     prev_pos: PrevPos,
     mover: Mover,
     prev_label: usize,
     previous_file: Option<String>,
+    /// path and grayscale image of the last frame for tracking
+    prev_frame: Option<(String, GrayImage)>,
 }
 
 impl BBox {
@@ -229,7 +419,15 @@ impl BBox {
         mut history: History,
     ) -> (World, History) {
         let are_boxes_visible = are_boxes_visible(&world);
-        if event.released(KeyCode::MouseLeft) {
+        let is_magic_wand_active = get_tools_data(&world)
+            .specifics
+            .bbox()
+            .options
+            .is_magic_wand_active;
+        let is_modifier_held = event.held_alt() || event.held_shift() || event.held_ctrl();
+        if event.released(KeyCode::MouseLeft) && is_magic_wand_active && !is_modifier_held {
+            (world, history) = on_magic_wand_click(event.mouse_pos, world, history);
+        } else if event.released(KeyCode::MouseLeft) {
             let params = MouseReleaseParams {
                 prev_pos: self.prev_pos,
                 are_boxes_visible,
@@ -253,61 +451,18 @@ impl BBox {
         (world, history)
     }
 
-    fn key_held(
-        &mut self,
-        events: &Events,
-        mut world: World,
-        history: History,
-    ) -> (World, History) {
-        let are_boxes_visible = get_tools_data(&world)
-            .specifics
-            .bbox()
-            .options
-            .are_boxes_visible;
-        // up, down, left, right
-        let shape_orig = world.data.shape();
-        let split_mode = get_tools_data(&world).specifics.bbox().options.split_mode;
-        let annos = get_annos_mut(&mut world);
-        if events.held(KeyCode::Up) && events.held_ctrl() {
-            annos.shift_min_bbs(0, -1, shape_orig, split_mode);
-        } else if events.held(KeyCode::Down) && events.held_ctrl() {
-            annos.shift_min_bbs(0, 1, shape_orig, split_mode);
-        } else if events.held(KeyCode::Right) && events.held_ctrl() {
-            annos.shift_min_bbs(1, 0, shape_orig, split_mode);
-        } else if events.held(KeyCode::Left) && events.held_ctrl() {
-            annos.shift_min_bbs(-1, 0, shape_orig, split_mode);
-        } else if events.held(KeyCode::Up) && events.held_alt() {
-            annos.shift(0, -1, shape_orig, split_mode);
-        } else if events.held(KeyCode::Down) && events.held_alt() {
-            annos.shift(0, 1, shape_orig, split_mode);
-        } else if events.held(KeyCode::Right) && events.held_alt() {
-            annos.shift(1, 0, shape_orig, split_mode);
-        } else if events.held(KeyCode::Left) && events.held_alt() {
-            annos.shift(-1, 0, shape_orig, split_mode);
-        } else if events.held(KeyCode::Up) {
-            annos.shift_max_bbs(0, -1, shape_orig, split_mode);
-        } else if events.held(KeyCode::Down) {
-            annos.shift_max_bbs(0, 1, shape_orig, split_mode);
-        } else if events.held(KeyCode::Right) {
-            annos.shift_max_bbs(1, 0, shape_orig, split_mode);
-        } else if events.held(KeyCode::Left) {
-            annos.shift_max_bbs(-1, 0, shape_orig, split_mode);
-        }
-        world.request_redraw_annotations(BBOX_NAME, are_boxes_visible);
-        (world, history)
-    }
-
-    fn key_released(
+    fn key_actions(
         &mut self,
         events: &Events,
         mut world: World,
         mut history: History,
     ) -> (World, History) {
-        let params = KeyReleasedParams {
-            is_ctrl_held: events.held_ctrl(),
-            released_key: map_released_key(events),
-        };
-        (world, history) = on_key_released(world, history, events.mouse_pos, params);
+        for action in events.triggered_actions() {
+            (world, history) = on_key_action(world, history, events.mouse_pos, *action);
+        }
+        if !events.released_actions().is_empty() {
+            (world, history) = on_key_action_released(world, history);
+        }
         (world, history)
     }
 }
@@ -319,6 +474,7 @@ impl Manipulate for BBox {
             mover: Mover::new(),
             prev_label: 0,
             previous_file: None,
+            prev_frame: None,
         }
     }
 
@@ -358,14 +514,39 @@ impl Manipulate for BBox {
 
         world = check_cocoexport(world);
 
+        world = check_splitexport(world);
+
         world = check_cocoimport(world);
 
+        world = check_schemaimport(world);
+
+        {
+            let file_path = world.data.meta_data.file_path.clone();
+            let data = get_tools_data_mut(&mut world).specifics.bbox_mut();
+            data.current_file_path = file_path;
+            if events.triggered(KeyAction::Predict) {
+                data.options.is_predict_triggered = true;
+            }
+            if events.triggered(KeyAction::SnapToContent) {
+                data.options.is_snap_triggered = true;
+            }
+        }
+
+        world = check_snap(world);
+
         let options = get_tools_data(&world).specifics.bbox().options;
 
         world = check_labelchange(world, self.prev_label, options);
 
         (world, history) = check_autopaste(world, history, options.auto_paste, is_file_changed);
 
+        (world, history, self.prev_frame) = check_autotrack(
+            world,
+            history,
+            options.auto_track,
+            mem::take(&mut self.prev_frame),
+        );
+
         if options.is_redraw_annos_triggered {
             world.request_redraw_annotations(BBOX_NAME, are_boxes_visible(&world));
             get_tools_data_mut(&mut world)
@@ -375,13 +556,23 @@ impl Manipulate for BBox {
                 .is_redraw_annos_triggered = false;
         }
 
+        if options.is_history_update_triggered {
+            world.request_redraw_annotations(BBOX_NAME, are_boxes_visible(&world));
+            get_tools_data_mut(&mut world)
+                .specifics
+                .bbox_mut()
+                .options
+                .is_history_update_triggered = false;
+            history.push(Record::new(world.data.clone(), ACTOR_NAME));
+        }
+
         let in_menu_selected_label = current_cat_idx(&world);
         if let (Some(mp), Some(pp)) = (events.mouse_pos, self.prev_pos.prev_pos) {
             // animation
             let bb_data = get_tools_data(&world).specifics.bbox();
             let label = Some(bb_data.labels()[in_menu_selected_label].clone());
             let color = bb_data.colors()[in_menu_selected_label];
-            let anno = Annotation {
+            let anno = Annotation::Bbox(BboxAnnotation {
                 geofig: GeoFig::BB(BB::from_points(mp.into(), pp.into())),
                 label,
                 fill_color: Some(color),
@@ -389,48 +580,30 @@ impl Manipulate for BBox {
                 outline: Stroke::from_color(color),
                 outline_alpha: options.outline_alpha,
                 is_selected: None,
-            };
+            });
             let are_boxes_visible = are_boxes_visible(&world);
             world.request_redraw_annotations(BBOX_NAME, are_boxes_visible);
             world.request_redraw_tmp_anno(anno);
         }
-        (world, history) = make_tool_transform!(
-            self,
-            world,
-            history,
-            events,
-            [
-                (pressed, KeyCode::MouseRight, mouse_pressed),
-                (held, KeyCode::MouseRight, mouse_held),
-                (released, KeyCode::MouseLeft, mouse_released),
-                (released, KeyCode::MouseRight, mouse_released),
-                (released, KeyCode::Delete, key_released),
-                (released, KeyCode::Back, key_released),
-                (released, KeyCode::H, key_released),
-                (released, KeyCode::A, key_released),
-                (released, KeyCode::D, key_released),
-                (released, KeyCode::C, key_released),
-                (released, KeyCode::V, key_released),
-                (released, KeyCode::L, key_released),
-                (released, KeyCode::Down, key_released),
-                (released, KeyCode::Up, key_released),
-                (released, KeyCode::Left, key_released),
-                (released, KeyCode::Right, key_released),
-                (released, KeyCode::Key1, key_released),
-                (released, KeyCode::Key2, key_released),
-                (released, KeyCode::Key3, key_released),
-                (released, KeyCode::Key4, key_released),
-                (released, KeyCode::Key5, key_released),
-                (released, KeyCode::Key6, key_released),
-                (released, KeyCode::Key7, key_released),
-                (released, KeyCode::Key8, key_released),
-                (released, KeyCode::Key9, key_released),
-                (held, KeyCode::Down, key_held),
-                (held, KeyCode::Up, key_held),
-                (held, KeyCode::Left, key_held),
-                (held, KeyCode::Right, key_held)
-            ]
-        );
+        // actions from the keymap take precedence over the mouse
+        let is_action_triggered =
+            !events.triggered_actions().is_empty() || !events.released_actions().is_empty();
+        (world, history) = if is_action_triggered {
+            self.key_actions(events, world, history)
+        } else {
+            make_tool_transform!(
+                self,
+                world,
+                history,
+                events,
+                [
+                    (pressed, KeyCode::MouseRight, mouse_pressed),
+                    (held, KeyCode::MouseRight, mouse_held),
+                    (released, KeyCode::MouseLeft, mouse_released),
+                    (released, KeyCode::MouseRight, mouse_released)
+                ]
+            )
+        };
         self.prev_label = in_menu_selected_label;
         (world, history)
     }
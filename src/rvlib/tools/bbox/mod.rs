@@ -1,3 +1,5 @@
 pub use self::core::{BBox, ACTOR_NAME};
+mod assist;
 mod core;
 mod on_events;
+mod track;
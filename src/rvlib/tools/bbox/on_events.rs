@@ -4,22 +4,26 @@ use crate::{
     annotations::SplitMode,
     cfg::CocoFile,
     domain::{shape_unscaled, OutOfBoundsMode, Point, PtF, PtI, BB},
-    events::{Events, KeyCode},
     file_util::MetaData,
     history::Record,
+    keymap::KeyAction,
+    result::RvResult,
     tools::{core::Mover, BBOX_NAME},
-    tools_data::{self, bbox_data::ClipboardData, BboxSpecificData},
+    tools_data::{self, bbox_data::ClipboardData, coco_io::CocoIssue, BboxSpecificData},
     util::true_indices,
     GeoFig,
     {history::History, world::World},
 };
 
+use super::assist;
 use super::core::{
     current_cat_idx, get_annos, get_annos_mut, get_tools_data, get_tools_data_mut, paste,
     ACTOR_NAME,
 };
 
 const CORNER_TOL_DENOMINATOR: u32 = 5000;
+/// maximal distance in pixels between the outline of a magic wand region and its polygon
+const MAGIC_WAND_EPSILON: f64 = 1.0;
 
 fn find_closest_boundary_idx(pos: PtF, geos: &[GeoFig]) -> Option<usize> {
     geos.iter()
@@ -57,18 +61,8 @@ pub(super) fn import_coco_if_triggered(
     meta_data: &MetaData,
     is_coco_import_triggered: bool,
     coco_file: &CocoFile,
-) -> Option<BboxSpecificData> {
-    if is_coco_import_triggered {
-        match tools_data::coco_io::read_coco(meta_data, coco_file) {
-            Ok(bbox_data) => Some(bbox_data),
-            Err(e) => {
-                println!("could not import coco due to {e:?}");
-                None
-            }
-        }
-    } else {
-        None
-    }
+) -> Option<RvResult<(BboxSpecificData, Vec<CocoIssue>)>> {
+    is_coco_import_triggered.then(|| tools_data::coco_io::read_coco(meta_data, coco_file))
 }
 
 pub(super) fn export_if_triggered(meta_data: &MetaData, bbox_data: &BboxSpecificData) {
@@ -312,59 +306,46 @@ pub(super) fn on_mouse_released_left(
     (world, history, prev_pos)
 }
 
-macro_rules! released_key {
-    ($($key:ident),*) => {
-        #[derive(Debug, Clone, Copy)]
-        pub(super) enum ReleasedKey {
-            None,
-            $($key,)*
-        }
-        pub(super) fn map_released_key(event: &Events) -> ReleasedKey {
-            if false {
-                ReleasedKey::None
-            } $(else if event.released(KeyCode::$key) {
-                ReleasedKey::$key
-            })*
-            else {
-                ReleasedKey::None
-            }
-        }
-    };
-}
-
-released_key!(
-    A, D, H, C, V, L, Key0, Key1, Key2, Key3, Key4, Key5, Key6, Key7, Key8, Key9, Delete, Back,
-    Left, Right, Up, Down
-);
-
-macro_rules! set_cat_current {
-    ($num:expr, $world:expr) => {
-        let specifics = get_tools_data_mut(&mut $world).specifics.bbox_mut();
-        if $num < specifics.cat_ids().len() + 1 {
-            specifics.cat_idx_current = $num - 1;
-        }
-    };
-}
-
-pub(super) struct KeyReleasedParams {
-    pub is_ctrl_held: bool,
-    pub released_key: ReleasedKey,
+/// Adds the outline of the region around the click that has a similar color as polygon.
+pub(super) fn on_magic_wand_click(
+    mouse_pos: Option<PtF>,
+    mut world: World,
+    mut history: History,
+) -> (World, History) {
+    let options = get_tools_data(&world).specifics.bbox().options;
+    let polygon = mouse_pos.and_then(|mp| {
+        let mask = assist::magic_wand(
+            world.data.im_background(),
+            mp.into(),
+            options.magic_wand_tolerance,
+        );
+        assist::mask_to_polygon(&mask, MAGIC_WAND_EPSILON)
+    });
+    if let Some(polygon) = polygon {
+        let in_menu_selected_label = current_cat_idx(&world);
+        get_annos_mut(&mut world).add_geo(GeoFig::Poly(polygon), in_menu_selected_label);
+        history.push(Record::new(world.data.clone(), ACTOR_NAME));
+        world.request_redraw_annotations(BBOX_NAME, options.are_boxes_visible);
+    }
+    (world, history)
 }
 
-pub(super) fn on_key_released(
+/// Applies an action of the keymap. Repeated actions like moving boxes are recorded in the
+/// history when their key is released, see [`on_key_action_released`].
+pub(super) fn on_key_action(
     mut world: World,
     mut history: History,
     mouse_pos: Option<PtF>,
-    params: KeyReleasedParams,
+    action: KeyAction,
 ) -> (World, History) {
     let mut flags = get_tools_data_mut(&mut world).specifics.bbox_mut().options;
-    match params.released_key {
-        ReleasedKey::H if params.is_ctrl_held => {
+    match action {
+        KeyAction::ToggleBoxesVisible => {
             // Hide all boxes (selected or not)
             flags.are_boxes_visible = !flags.are_boxes_visible;
             world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
         }
-        ReleasedKey::Delete | ReleasedKey::Back => {
+        KeyAction::DeleteSelected => {
             // Remove selected
             let annos = get_annos_mut(&mut world);
             if !annos.selected_bbs().is_empty() {
@@ -373,17 +354,17 @@ pub(super) fn on_key_released(
                 history.push(Record::new(world.data.clone(), ACTOR_NAME));
             }
         }
-        ReleasedKey::A if params.is_ctrl_held => {
+        KeyAction::SelectAll => {
             // Select all
             get_annos_mut(&mut world).select_all();
             world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
         }
-        ReleasedKey::D if params.is_ctrl_held => {
+        KeyAction::DeselectAll => {
             // Deselect all
             get_annos_mut(&mut world).deselect_all();
             world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
         }
-        ReleasedKey::C if params.is_ctrl_held => {
+        KeyAction::CopyToClipboard => {
             // Copy to clipboard
             if let Some(annos) = get_annos(&world) {
                 get_tools_data_mut(&mut world)
@@ -393,13 +374,13 @@ pub(super) fn on_key_released(
                 world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
             }
         }
-        ReleasedKey::V if params.is_ctrl_held => {
+        KeyAction::PasteFromClipboard => {
             (world, history) = paste(world, history);
         }
-        ReleasedKey::V => {
+        KeyAction::ToggleAutoPaste => {
             flags.auto_paste = !flags.auto_paste;
         }
-        ReleasedKey::L if params.is_ctrl_held => {
+        KeyAction::ToggleLabelsVisible => {
             let show_label = if let Some(annos) = get_annos(&world) {
                 annos.show_labels
             } else {
@@ -408,7 +389,7 @@ pub(super) fn on_key_released(
             get_annos_mut(&mut world).show_labels = !show_label;
             world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
         }
-        ReleasedKey::C => {
+        KeyAction::CloneToMouse => {
             // Paste selection directly at current mouse position
             if let Some((x_shift, y_shift)) = mouse_pos.map(<PtF as Into<(i32, i32)>>::into) {
                 let shape_orig = world.shape_orig();
@@ -416,66 +397,64 @@ pub(super) fn on_key_released(
                 let selected_inds = true_indices(annos.selected_bbs());
                 let first_selected_idx = true_indices(annos.selected_bbs()).next();
                 if let Some(first_idx) = first_selected_idx {
-                    let translated = selected_inds.flat_map(|idx| {
-                        let geo = annos.geos()[idx].clone();
-                        let first = &annos.geos()[first_idx];
-                        geo.translate(
-                            Point {
-                                x: x_shift - first.enclosing_bb().min().x as i32,
-                                y: y_shift - first.enclosing_bb().min().y as i32,
-                            },
-                            shape_orig,
-                            OutOfBoundsMode::Deny,
-                        )
-                        .map(|bb| (bb, annos.cat_idxs()[idx]))
-                    });
-                    let translated_bbs = translated.clone().map(|(bb, _)| bb).collect::<Vec<_>>();
-                    let translated_cat_ids =
-                        translated.map(|(_, cat_id)| cat_id).collect::<Vec<_>>();
-
-                    if !translated_bbs.is_empty() {
-                        annos.extend(
-                            translated_bbs.iter().cloned(),
-                            translated_cat_ids.iter().copied(),
-                            shape_orig,
-                        );
+                    let translated = selected_inds
+                        .flat_map(|idx| {
+                            let first = &annos.geos()[first_idx];
+                            let mut entry = annos.entry(idx);
+                            entry.geo = entry.geo.translate(
+                                Point {
+                                    x: x_shift - first.enclosing_bb().min().x as i32,
+                                    y: y_shift - first.enclosing_bb().min().y as i32,
+                                },
+                                shape_orig,
+                                OutOfBoundsMode::Deny,
+                            )?;
+                            // the copy is another object at another position
+                            entry.track_id = None;
+                            entry.sub_pixel_bb = None;
+                            Some(entry)
+                        })
+                        .collect::<Vec<_>>();
+                    let n_translated = translated.len();
+
+                    if n_translated > 0 {
+                        annos.extend_with(translated.into_iter(), shape_orig);
                         annos.deselect_all();
-                        annos.select_last_n(translated_bbs.len());
+                        annos.select_last_n(n_translated);
                         world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
                         history.push(Record::new(world.data.clone(), ACTOR_NAME));
                     }
                 }
             }
         }
-        ReleasedKey::Up | ReleasedKey::Down | ReleasedKey::Left | ReleasedKey::Right => {
-            history.push(Record::new(world.data.clone(), ACTOR_NAME));
-        }
-        ReleasedKey::Key1 => {
-            set_cat_current!(1, world);
-        }
-        ReleasedKey::Key2 => {
-            set_cat_current!(2, world);
-        }
-        ReleasedKey::Key3 => {
-            set_cat_current!(3, world);
-        }
-        ReleasedKey::Key4 => {
-            set_cat_current!(4, world);
-        }
-        ReleasedKey::Key5 => {
-            set_cat_current!(5, world);
+        KeyAction::MoveSelected(dir) => {
+            let (x, y) = dir.delta();
+            let shape_orig = world.data.shape();
+            get_annos_mut(&mut world).shift(x, y, shape_orig, flags.split_mode);
+            world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
         }
-        ReleasedKey::Key6 => {
-            set_cat_current!(6, world);
+        KeyAction::MoveMaxCorner(dir) => {
+            let (x, y) = dir.delta();
+            let shape_orig = world.data.shape();
+            get_annos_mut(&mut world).shift_max_bbs(x, y, shape_orig, flags.split_mode);
+            world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
         }
-        ReleasedKey::Key7 => {
-            set_cat_current!(7, world);
+        KeyAction::MoveMinCorner(dir) => {
+            let (x, y) = dir.delta();
+            let shape_orig = world.data.shape();
+            get_annos_mut(&mut world).shift_min_bbs(x, y, shape_orig, flags.split_mode);
+            world.request_redraw_annotations(BBOX_NAME, flags.are_boxes_visible);
         }
-        ReleasedKey::Key8 => {
-            set_cat_current!(8, world);
+        KeyAction::Label(slot) => {
+            let specifics = get_tools_data_mut(&mut world).specifics.bbox_mut();
+            if let Some(label_idx) = specifics.quick_select_idx(slot) {
+                specifics.set_cat_idx_current(label_idx);
+            }
         }
-        ReleasedKey::Key9 => {
-            set_cat_current!(9, world);
+        KeyAction::LabelPalette => {
+            let palette = &mut get_tools_data_mut(&mut world).specifics.bbox_mut().palette;
+            palette.is_open = !palette.is_open;
+            palette.query.clear();
         }
         _ => (),
     }
@@ -483,13 +462,21 @@ pub(super) fn on_key_released(
     (world, history)
 }
 
+/// Records the boxes moved by a repeated action once its key is released.
+pub(super) fn on_key_action_released(world: World, mut history: History) -> (World, History) {
+    history.push(Record::new(world.data.clone(), ACTOR_NAME));
+    (world, history)
+}
+
 #[cfg(test)]
 use {
     super::core::initialize_tools_menu_data,
     crate::{
-        annotations::BboxAnnotations,
+        annotations::{AnnoSource, AttrVal, Attributes, BboxAnnotations, Provenance, ReviewStatus},
         domain::{make_test_bbs, make_test_geos, Shape},
-        point,
+        events::{Event, Events, KeyCode},
+        keymap::Keymap,
+        point, rverr,
         types::ViewImage,
     },
     image::DynamicImage,
@@ -513,6 +500,45 @@ fn test_data() -> (Option<PtF>, World, History) {
     (mouse_pos, world, history)
 }
 
+#[cfg(test)]
+fn on_key_event(
+    world: World,
+    history: History,
+    mouse_pos: Option<PtF>,
+    event: Event,
+    modifiers: &[KeyCode],
+    keymap: &Keymap,
+) -> (World, History) {
+    let mut events = vec![event];
+    events.extend(modifiers.iter().copied().map(Event::Held));
+    let events = Events::default().events(events).actions(keymap);
+    events
+        .triggered_actions()
+        .iter()
+        .fold((world, history), |(world, history), action| {
+            on_key_action(world, history, mouse_pos, *action)
+        })
+}
+
+#[cfg(test)]
+fn on_key_released(
+    world: World,
+    history: History,
+    mouse_pos: Option<PtF>,
+    key: KeyCode,
+    is_ctrl_held: bool,
+) -> (World, History) {
+    let modifiers: &[KeyCode] = if is_ctrl_held { &[KeyCode::Ctrl] } else { &[] };
+    on_key_event(
+        world,
+        history,
+        mouse_pos,
+        Event::Released(key),
+        modifiers,
+        &Keymap::default(),
+    )
+}
+
 #[cfg(test)]
 fn history_equal(hist1: &History, hist2: &History) -> bool {
     format!("{:?}", hist1) == format!("{:?}", hist2)
@@ -521,10 +547,6 @@ fn history_equal(hist1: &History, hist2: &History) -> bool {
 #[test]
 fn test_key_released() {
     let (_, mut world, history) = test_data();
-    let make_params = |released_key, is_ctrl_held| KeyReleasedParams {
-        is_ctrl_held,
-        released_key,
-    };
     let annos = get_annos_mut(&mut world);
     annos.add_bb(
         BB {
@@ -539,16 +561,13 @@ fn test_key_released() {
     let annos_orig = annos.clone();
 
     // select all boxes with ctrl+A
-    let params = make_params(ReleasedKey::A, false);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::A, false);
     assert!(!get_annos(&world).unwrap().selected_bbs()[0]);
-    let params = make_params(ReleasedKey::A, true);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::A, true);
     assert!(get_annos(&world).unwrap().selected_bbs()[0]);
 
     // copy and paste boxes to and from clipboard
-    let params = make_params(ReleasedKey::C, true);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::C, true);
     assert!(get_annos(&world).unwrap().selected_bbs()[0]);
     if let Some(clipboard) = get_tools_data(&world).specifics.bbox().clipboard.clone() {
         let mut annos = BboxAnnotations::new();
@@ -566,22 +585,18 @@ fn test_key_released() {
     } else {
         assert!(false);
     }
-    let params = make_params(ReleasedKey::V, true);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::V, true);
     assert!(get_tools_data(&world).specifics.bbox().clipboard.is_some());
     assert_eq!(get_annos(&world).unwrap().geos(), annos_orig.geos());
-    let params = make_params(ReleasedKey::C, true);
-    let (mut world, history) = on_key_released(world, history, None, params);
+    let (mut world, history) = on_key_released(world, history, None, KeyCode::C, true);
     get_annos_mut(&mut world).remove(0);
-    let params = make_params(ReleasedKey::V, true);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::V, true);
     assert_eq!(get_annos(&world).unwrap().geos(), annos_orig.geos());
 
     // clone box
-    let params = make_params(ReleasedKey::A, true);
-    let (world, history) = on_key_released(world, history, None, params);
-    let params = make_params(ReleasedKey::C, false);
-    let (world, history) = on_key_released(world, history, Some(point!(2.0, 2.0)), params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::A, true);
+    let (world, history) =
+        on_key_released(world, history, Some(point!(2.0, 2.0)), KeyCode::C, false);
     assert_eq!(get_annos(&world).unwrap().geos()[0], annos_orig.geos()[0]);
     assert_eq!(
         get_annos(&world).unwrap().geos()[1],
@@ -596,34 +611,132 @@ fn test_key_released() {
     assert_eq!(get_annos(&world).unwrap().geos().len(), 2);
 
     // deselect all boxes with ctrl+D
-    let params = make_params(ReleasedKey::A, true);
-    let (world, history) = on_key_released(world, history, None, params);
-    let params = make_params(ReleasedKey::D, false);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::A, true);
+    let (world, history) = on_key_released(world, history, None, KeyCode::D, false);
     assert!(get_annos(&world).unwrap().selected_bbs()[0]);
-    let params = make_params(ReleasedKey::D, true);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::D, true);
     let flags = get_tools_data(&world).specifics.bbox().options;
     assert!(flags.are_boxes_visible);
     assert!(!get_annos(&world).unwrap().selected_bbs()[0]);
 
     // hide all boxes with ctrl+H
-    let params = make_params(ReleasedKey::H, true);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::H, true);
     let flags = get_tools_data(&world).specifics.bbox().options;
     assert!(!flags.are_boxes_visible);
 
     // delete all selected boxes with ctrl+Delete
-    let params = make_params(ReleasedKey::Delete, true);
-    let (world, history) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::Delete, true);
     assert!(!get_annos(&world).unwrap().selected_bbs().is_empty());
-    let params = make_params(ReleasedKey::A, true);
-    let (world, history) = on_key_released(world, history, None, params);
-    let params = make_params(ReleasedKey::Delete, true);
-    let (world, _) = on_key_released(world, history, None, params);
+    let (world, history) = on_key_released(world, history, None, KeyCode::A, true);
+    let (world, _) = on_key_released(world, history, None, KeyCode::Delete, true);
     assert!(get_annos(&world).unwrap().selected_bbs().is_empty());
 }
 
+#[test]
+fn test_key_actions() -> RvResult<()> {
+    let (_, mut world, history) = test_data();
+    get_annos_mut(&mut world).add_bb(BB::from_arr(&[1, 1, 10, 10]), 0);
+    let keymap: Keymap = toml::from_str(
+        r#"
+        select_all = ["Alt+A"]
+        delete_selected = ["X"]
+        "#,
+    )
+    .map_err(|e| rverr!("{:?}", e))?;
+    let alt = [KeyCode::Alt];
+
+    // tool actions are triggered on release of their key
+    let (world, history) = on_key_event(
+        world,
+        history,
+        None,
+        Event::Pressed(KeyCode::A),
+        &alt,
+        &keymap,
+    );
+    assert!(!get_annos(&world).unwrap().selected_bbs()[0]);
+    // the default chord is replaced
+    let (world, history) = on_key_event(
+        world,
+        history,
+        None,
+        Event::Released(KeyCode::A),
+        &[KeyCode::Ctrl],
+        &keymap,
+    );
+    assert!(!get_annos(&world).unwrap().selected_bbs()[0]);
+    let (world, history) = on_key_event(
+        world,
+        history,
+        None,
+        Event::Released(KeyCode::A),
+        &alt,
+        &keymap,
+    );
+    assert!(get_annos(&world).unwrap().selected_bbs()[0]);
+
+    let (world, history) = on_key_event(
+        world,
+        history,
+        None,
+        Event::Released(KeyCode::Delete),
+        &[],
+        &keymap,
+    );
+    assert_eq!(get_annos(&world).unwrap().geos().len(), 1);
+    let (world, _) = on_key_event(
+        world,
+        history,
+        None,
+        Event::Released(KeyCode::X),
+        &[],
+        &keymap,
+    );
+    assert!(get_annos(&world).unwrap().geos().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_paste_keeps_attributes() -> RvResult<()> {
+    let (_, mut world, history) = test_data();
+    let annos = get_annos_mut(&mut world);
+    annos.add_tracked_geo(GeoFig::BB(BB::from_arr(&[1, 1, 10, 10])), 1, 7);
+    annos.set_attributes(vec![Attributes::from([(
+        "occluded".to_string(),
+        AttrVal::Bool(true),
+    )])])?;
+    annos.set_provenances(vec![Provenance {
+        score: Some(0.5),
+        source: AnnoSource::Predicted,
+        review: ReviewStatus::Accepted,
+    }])?;
+    annos.select_all();
+    let entry = annos.entry(0);
+
+    let (mut world, history) = on_key_action(world, history, None, KeyAction::CopyToClipboard);
+    // e.g., another image
+    get_annos_mut(&mut world).clear();
+    let (world, history) = on_key_action(world, history, None, KeyAction::PasteFromClipboard);
+    assert_eq!(get_annos(&world).unwrap().geos().len(), 1);
+    assert_eq!(get_annos(&world).unwrap().entry(0), entry);
+
+    // a copy at the mouse position is another object with the same attributes
+    let mut world = world;
+    get_annos_mut(&mut world).select_all();
+    let (world, _) = on_key_action(
+        world,
+        history,
+        Some(point!(20.0, 20.0)),
+        KeyAction::CloneToMouse,
+    );
+    let annos = get_annos(&world).unwrap();
+    assert_eq!(annos.geos().len(), 2);
+    assert_eq!(annos.attributes()[1], entry.attributes);
+    assert_eq!(annos.provenances()[1], entry.provenance);
+    assert_eq!(annos.track_ids()[1], None);
+    Ok(())
+}
+
 #[test]
 fn test_mouse_held() {
     let (mouse_pos, mut world, history) = test_data();
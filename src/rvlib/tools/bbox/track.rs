@@ -0,0 +1,136 @@
+//! Propagation of annotations from one video frame to the next by template matching.
+use image::{
+    imageops::{self, FilterType},
+    GrayImage,
+};
+use imageproc::template_matching::{find_extremes, match_template, MatchTemplateMethod};
+
+use crate::{
+    domain::{OutOfBoundsMode, Point, Shape, BB},
+    GeoFig,
+};
+
+/// Larger templates are matched on a downscaled image first
+const MAX_TEMPLATE_SIDE: u32 = 32;
+
+/// Box expanded by `margin` in each direction and clipped to the image
+fn expand(bb: BB, margin: u32, shape: Shape) -> BB {
+    let x = bb.x.saturating_sub(margin);
+    let y = bb.y.saturating_sub(margin);
+    let x_max = (bb.x + bb.w + margin).min(shape.w);
+    let y_max = (bb.y + bb.h + margin).min(shape.h);
+    BB::from_arr(&[x, y, x_max - x, y_max - y])
+}
+
+/// Position of the template's top left corner inside the window with the smallest normalized
+/// squared difference
+fn best_match(window: &GrayImage, template: &GrayImage) -> Option<(u32, u32)> {
+    if window.width() < template.width() || window.height() < template.height() {
+        return None;
+    }
+    let scores = match_template(
+        window,
+        template,
+        MatchTemplateMethod::SumOfSquaredErrorsNormalized,
+    );
+    Some(find_extremes(&scores).min_value_location)
+}
+
+/// Top left corner of the content of `bb` in `prev` within the search window of `cur`. Large
+/// boxes are matched coarsely on downscaled images and refined in full resolution.
+fn track_bb(prev: &GrayImage, cur: &GrayImage, bb: BB) -> Option<(u32, u32)> {
+    let template = imageops::crop_imm(prev, bb.x, bb.y, bb.w, bb.h).to_image();
+    let cur_shape = Shape::new(cur.width(), cur.height());
+    let window = expand(bb, bb.w.max(bb.h) / 2, cur_shape);
+    let window_im = imageops::crop_imm(cur, window.x, window.y, window.w, window.h).to_image();
+    let scale = (bb.w.max(bb.h) / MAX_TEMPLATE_SIDE).max(1);
+    if scale == 1 {
+        let (x, y) = best_match(&window_im, &template)?;
+        return Some((window.x + x, window.y + y));
+    }
+    let resize = |im: &GrayImage| {
+        imageops::resize(
+            im,
+            (im.width() / scale).max(1),
+            (im.height() / scale).max(1),
+            FilterType::Triangle,
+        )
+    };
+    let (x, y) = best_match(&resize(&window_im), &resize(&template))?;
+    let coarse = BB::from_arr(&[window.x + x * scale, window.y + y * scale, bb.w, bb.h]);
+    let refine_window = expand(coarse, scale, cur_shape);
+    let refine_im = imageops::crop_imm(
+        cur,
+        refine_window.x,
+        refine_window.y,
+        refine_window.w,
+        refine_window.h,
+    )
+    .to_image();
+    let (x, y) = best_match(&refine_im, &template)?;
+    Some((refine_window.x + x, refine_window.y + y))
+}
+
+/// Shifts the geometry by the displacement of its enclosing box from `prev` to `cur`. Both
+/// frames are expected to have the same size. Returns `None` if the box is not inside the
+/// previous frame.
+pub fn track_geo(prev: &GrayImage, cur: &GrayImage, geo: &GeoFig) -> Option<GeoFig> {
+    let bb = geo.enclosing_bb();
+    if bb.w == 0 || bb.h == 0 || !bb.is_contained_in_image(Shape::new(prev.width(), prev.height()))
+    {
+        return None;
+    }
+    let (x, y) = track_bb(prev, cur, bb)?;
+    let offset = Point {
+        x: x as i32 - bb.x as i32,
+        y: y as i32 - bb.y as i32,
+    };
+    geo.translate(
+        offset,
+        Shape::new(cur.width(), cur.height()),
+        OutOfBoundsMode::Resize(Shape::new(1, 1)),
+    )
+}
+
+#[cfg(test)]
+use {crate::Polygon, image::Luma};
+
+#[cfg(test)]
+fn make_frame(x_obj: u32, y_obj: u32, size: u32) -> GrayImage {
+    // textured object on a smooth background
+    GrayImage::from_fn(200, 150, |x, y| {
+        if (x_obj..x_obj + size).contains(&x) && (y_obj..y_obj + size).contains(&y) {
+            Luma([((x - x_obj) * 37 % 200 + (y - y_obj) * 11 % 50) as u8])
+        } else {
+            Luma([(x / 4 + y / 4) as u8])
+        }
+    })
+}
+
+#[test]
+fn test_track_geo() {
+    let prev = make_frame(40, 30, 20);
+    let cur = make_frame(47, 26, 20);
+    let geo = GeoFig::BB(BB::from_arr(&[40, 30, 20, 20]));
+    assert_eq!(
+        track_geo(&prev, &cur, &geo),
+        Some(GeoFig::BB(BB::from_arr(&[47, 26, 20, 20])))
+    );
+    let poly = Polygon::from_vec(
+        vec![(40, 30).into(), (59, 30).into(), (50, 49).into()],
+        false,
+    )
+    .unwrap();
+    let tracked = track_geo(&prev, &cur, &GeoFig::Poly(poly)).unwrap();
+    assert_eq!(tracked.enclosing_bb().min(), (47, 26).into());
+    // large objects are matched coarse to fine
+    let prev = make_frame(40, 30, 80);
+    let cur = make_frame(52, 21, 80);
+    let geo = GeoFig::BB(BB::from_arr(&[40, 30, 80, 80]));
+    assert_eq!(
+        track_geo(&prev, &cur, &geo),
+        Some(GeoFig::BB(BB::from_arr(&[52, 21, 80, 80])))
+    );
+    let outside = GeoFig::BB(BB::from_arr(&[190, 30, 20, 20]));
+    assert_eq!(track_geo(&prev, &cur, &outside), None);
+}
@@ -3,6 +3,7 @@ use crate::{
     annotations_accessor_mut,
     events::{Events, KeyCode},
     history::{History, Record},
+    keymap::KeyAction,
     make_tool_transform,
     tools_data::BrushToolData,
     tools_data::{ToolSpecifics, ToolsData},
@@ -94,7 +95,7 @@ impl Manipulate for Brush {
                 (pressed, KeyCode::MouseLeft, mouse_pressed),
                 (held, KeyCode::MouseLeft, mouse_held),
                 (released, KeyCode::MouseLeft, mouse_released),
-                (pressed, KeyCode::Back, key_pressed)
+                (triggered, KeyAction::DeleteSelected, key_pressed)
             ]
         )
     }
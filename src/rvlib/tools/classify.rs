@@ -0,0 +1,121 @@
+use std::path::{Path, PathBuf};
+
+use crate::{
+    events::Events,
+    file_util::{self, MetaData},
+    history::{History, Record},
+    result::{RvError, RvResult},
+    tools_data::{classify_data::TagsExportFormat, ClassifyToolData, ToolSpecifics, ToolsData},
+    tools_data_accessor, tools_data_accessor_mut, tools_data_initializer,
+    world::World,
+};
+
+use super::Manipulate;
+
+pub const ACTOR_NAME: &str = "Classify";
+const MISSING_TOOLSMENU_MSG: &str = "classify data has not yet been initialized";
+
+tools_data_initializer!(ACTOR_NAME, Classify, ClassifyToolData);
+tools_data_accessor!(ACTOR_NAME, MISSING_TOOLSMENU_MSG);
+tools_data_accessor_mut!(ACTOR_NAME, MISSING_TOOLSMENU_MSG);
+
+fn export_path(meta_data: &MetaData, format: TagsExportFormat) -> RvResult<PathBuf> {
+    let export_folder = meta_data
+        .export_folder
+        .as_ref()
+        .ok_or_else(|| RvError::new("no export folder given"))?;
+    let opened_folder = meta_data
+        .opened_folder
+        .as_deref()
+        .ok_or_else(|| RvError::new("no folder open"))?;
+    let opened_folder_name = Path::new(opened_folder)
+        .file_stem()
+        .and_then(|of| of.to_str())
+        .unwrap_or("default");
+    Ok(Path::new(export_folder).join(format!("{opened_folder_name}_tags.{}", format.extension())))
+}
+
+fn export(meta_data: &MetaData, data: &ClassifyToolData) -> RvResult<PathBuf> {
+    let path = export_path(meta_data, data.options.export_format)?;
+    let content = match data.options.export_format {
+        TagsExportFormat::Csv => data.to_csv(),
+        TagsExportFormat::Json => data.to_json()?,
+    };
+    file_util::write(&path, content)?;
+    println!("exported tags to {path:?}");
+    Ok(path)
+}
+
+fn check_export(mut world: World) -> World {
+    let data = get_tools_data(&world).specifics.classify();
+    if data.options.is_export_triggered {
+        if let Err(e) = export(&world.data.meta_data, data) {
+            println!("could not export tags due to {e:?}");
+        }
+        get_tools_data_mut(&mut world)
+            .specifics
+            .classify_mut()
+            .options
+            .is_export_triggered = false;
+    }
+    world
+}
+
+#[derive(Clone, Debug)]
+pub struct Classify {}
+
+impl Classify {
+    fn tag_key_released(
+        &mut self,
+        tag_idx: usize,
+        mut world: World,
+        mut history: History,
+    ) -> (World, History) {
+        if let Some(file_path) = world.data.meta_data.file_path.clone() {
+            let data = get_tools_data_mut(&mut world).specifics.classify_mut();
+            if tag_idx < data.tags().len() {
+                data.apply_tag(&file_path, tag_idx);
+                data.options.is_next_image_triggered = data.options.is_auto_next;
+                history.push(Record::new(world.data.clone(), ACTOR_NAME));
+            }
+        }
+        (world, history)
+    }
+}
+
+impl Manipulate for Classify {
+    fn new() -> Self {
+        Self {}
+    }
+
+    fn on_activate(&mut self, mut world: World, history: History) -> (World, History) {
+        world = initialize_tools_menu_data(world);
+        get_tools_data_mut(&mut world).menu_active = true;
+        (world, history)
+    }
+
+    fn on_deactivate(&mut self, mut world: World, history: History) -> (World, History) {
+        get_tools_data_mut(&mut world).menu_active = false;
+        (world, history)
+    }
+
+    fn events_tf(
+        &mut self,
+        mut world: World,
+        history: History,
+        events: &Events,
+    ) -> (World, History) {
+        world = initialize_tools_menu_data(world);
+        let file_path = world.data.meta_data.file_path.clone();
+        get_tools_data_mut(&mut world)
+            .specifics
+            .classify_mut()
+            .current_file_path = file_path;
+        world = check_export(world);
+        if let Some(tag_idx) = events.triggered_label() {
+            self.tag_key_released(tag_idx, world, history)
+        } else {
+            (world, history)
+        }
+    }
+}
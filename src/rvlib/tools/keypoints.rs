@@ -0,0 +1,287 @@
+use std::mem;
+
+use crate::{
+    annotations::{Keypoint, KeypointAnnotations, Visibility},
+    annotations_accessor, annotations_accessor_mut,
+    domain::{shape_unscaled, PtI},
+    events::{Events, KeyCode},
+    history::{History, Record},
+    keymap::KeyAction,
+    make_tool_transform,
+    tools::{Manipulate, KEYPOINTS_NAME},
+    tools_data::{coco_io::write_keypoints_coco, KeypointToolData, ToolSpecifics, ToolsData},
+    tools_data_accessor, tools_data_accessor_mut, tools_data_initializer,
+    world::World,
+};
+
+pub const ACTOR_NAME: &str = "Keypoints";
+const MISSING_ANNO_MSG: &str = "keypoint annotations have not yet been initialized";
+const MISSING_TOOLSMENU_MSG: &str = "keypoint tools menu has not yet been initialized";
+const KP_TOL_DENOMINATOR: u32 = 5000;
+tools_data_initializer!(ACTOR_NAME, Keypoint, KeypointToolData);
+tools_data_accessor!(ACTOR_NAME, MISSING_TOOLSMENU_MSG);
+tools_data_accessor_mut!(ACTOR_NAME, MISSING_TOOLSMENU_MSG);
+annotations_accessor_mut!(
+    ACTOR_NAME,
+    keypoint_mut,
+    MISSING_ANNO_MSG,
+    KeypointAnnotations
+);
+annotations_accessor!(ACTOR_NAME, keypoint, MISSING_ANNO_MSG, KeypointAnnotations);
+
+fn n_keypoints(world: &World) -> usize {
+    get_tools_data(world).specifics.keypoint().skeleton().len()
+}
+
+/// instance index and keypoint index of an existing keypoint slot
+fn is_valid(world: &World, (inst_idx, kp_idx): (usize, usize)) -> bool {
+    get_annos(world)
+        .map(|annos| inst_idx < annos.len() && kp_idx < annos.instances()[inst_idx].len())
+        .unwrap_or(false)
+}
+
+fn next_keypoint((inst_idx, kp_idx): (usize, usize), n_keypoints: usize) -> Option<(usize, usize)> {
+    if kp_idx + 1 < n_keypoints {
+        Some((inst_idx, kp_idx + 1))
+    } else {
+        None
+    }
+}
+
+fn check_export(mut world: World) -> World {
+    let data = get_tools_data(&world).specifics.keypoint();
+    if data.options.is_export_triggered {
+        if let Err(e) = write_keypoints_coco(&world.data.meta_data, data) {
+            println!("could not export keypoints due to {e:?}");
+        }
+        get_tools_data_mut(&mut world)
+            .specifics
+            .keypoint_mut()
+            .options
+            .is_export_triggered = false;
+    }
+    world
+}
+
+#[derive(Clone, Debug)]
+pub struct Keypoints {
+    /// instance and keypoint that are placed with the next left click
+    placing: Option<(usize, usize)>,
+    /// keypoint that is dragged while the left mouse button is held
+    moving: Option<(usize, usize)>,
+    /// position of the dragged keypoint when the left mouse button has been pressed
+    moving_start: Option<PtI>,
+    /// keypoint whose visibility is toggled
+    selected: Option<(usize, usize)>,
+    previous_file: Option<String>,
+}
+
+impl Keypoints {
+    fn reset(&mut self) {
+        self.placing = None;
+        self.moving = None;
+        self.moving_start = None;
+        self.selected = None;
+    }
+
+    fn mouse_pressed(
+        &mut self,
+        events: &Events,
+        mut world: World,
+        history: History,
+    ) -> (World, History) {
+        if let (None, Some(mp)) = (self.placing, events.mouse_pos) {
+            let unscaled = shape_unscaled(world.zoom_box(), world.data.shape());
+            let tolerance = (unscaled.w * unscaled.h / KP_TOL_DENOMINATOR).max(2);
+            let close_kp = get_annos(&world)
+                .and_then(|annos| annos.find_close_keypoint(mp.into(), tolerance as i64));
+            if let Some((inst_idx, kp_idx)) = close_kp {
+                self.moving = Some((inst_idx, kp_idx));
+                self.moving_start = get_annos(&world).map(|a| a.keypoint(inst_idx, kp_idx).pos);
+                self.selected = Some((inst_idx, kp_idx));
+                get_annos_mut(&mut world).select(inst_idx);
+                world.request_redraw_annotations(KEYPOINTS_NAME, true);
+            }
+        }
+        (world, history)
+    }
+
+    fn mouse_held(
+        &mut self,
+        events: &Events,
+        mut world: World,
+        history: History,
+    ) -> (World, History) {
+        if let (Some((inst_idx, kp_idx)), Some(mp)) = (self.moving, events.mouse_pos) {
+            let annos = get_annos_mut(&mut world);
+            let visibility = annos.keypoint(inst_idx, kp_idx).visibility;
+            let pos: PtI = mp.into();
+            annos.set_keypoint(inst_idx, kp_idx, Keypoint { pos, visibility });
+            world.request_redraw_annotations(KEYPOINTS_NAME, true);
+        }
+        (world, history)
+    }
+
+    fn mouse_released_left(
+        &mut self,
+        events: &Events,
+        mut world: World,
+        mut history: History,
+    ) -> (World, History) {
+        if let Some((inst_idx, kp_idx)) = self.moving.take() {
+            let start = self.moving_start.take();
+            let pos = get_annos(&world).map(|a| a.keypoint(inst_idx, kp_idx).pos);
+            if pos != start {
+                history.push(Record::new(world.data.clone(), ACTOR_NAME));
+            }
+            return (world, history);
+        }
+        let n_keypoints = n_keypoints(&world);
+        if let (true, Some(mp)) = (n_keypoints > 0, events.mouse_pos) {
+            let annos = get_annos_mut(&mut world);
+            let (inst_idx, kp_idx) = self
+                .placing
+                .unwrap_or_else(|| (annos.add_instance(n_keypoints), 0));
+            let visibility = if events.held_shift() {
+                Visibility::Occluded
+            } else {
+                Visibility::Visible
+            };
+            let pos: PtI = mp.into();
+            annos.set_keypoint(inst_idx, kp_idx, Keypoint { pos, visibility });
+            annos.select(inst_idx);
+            self.selected = Some((inst_idx, kp_idx));
+            self.placing = next_keypoint((inst_idx, kp_idx), n_keypoints);
+            world.request_redraw_annotations(KEYPOINTS_NAME, true);
+            history.push(Record::new(world.data.clone(), ACTOR_NAME));
+        }
+        (world, history)
+    }
+
+    /// skips the keypoint that would be placed next, it stays unlabeled
+    fn mouse_released_right(
+        &mut self,
+        _events: &Events,
+        mut world: World,
+        mut history: History,
+    ) -> (World, History) {
+        if let Some((inst_idx, kp_idx)) = self.placing {
+            self.placing = next_keypoint((inst_idx, kp_idx), n_keypoints(&world));
+            let annos = get_annos_mut(&mut world);
+            if self.placing.is_none()
+                && annos.instances()[inst_idx]
+                    .iter()
+                    .all(|kp| !kp.is_labeled())
+            {
+                annos.remove_instance(inst_idx);
+            }
+            world.request_redraw_annotations(KEYPOINTS_NAME, true);
+            history.push(Record::new(world.data.clone(), ACTOR_NAME));
+        }
+        (world, history)
+    }
+
+    fn key_released(
+        &mut self,
+        events: &Events,
+        mut world: World,
+        mut history: History,
+    ) -> (World, History) {
+        let mut is_changed = false;
+        if events.triggered(KeyAction::ToggleKeypointVisibility) {
+            if let Some((inst_idx, kp_idx)) = self.selected {
+                let annos = get_annos_mut(&mut world);
+                let kp = *annos.keypoint(inst_idx, kp_idx);
+                let visibility = kp.visibility.toggle();
+                annos.set_keypoint(inst_idx, kp_idx, Keypoint { visibility, ..kp });
+                is_changed = true;
+            }
+        } else if events.triggered(KeyAction::DeleteSelected) {
+            let annos = get_annos_mut(&mut world);
+            if let Some(inst_idx) = annos.selected() {
+                annos.remove_instance(inst_idx);
+                is_changed = true;
+            }
+            self.reset();
+        }
+        if is_changed {
+            world.request_redraw_annotations(KEYPOINTS_NAME, true);
+            history.push(Record::new(world.data.clone(), ACTOR_NAME));
+        }
+        (world, history)
+    }
+}
+
+impl Manipulate for Keypoints {
+    fn new() -> Self {
+        Self {
+            placing: None,
+            moving: None,
+            moving_start: None,
+            selected: None,
+            previous_file: None,
+        }
+    }
+
+    fn on_activate(&mut self, mut world: World, history: History) -> (World, History) {
+        self.reset();
+        world = initialize_tools_menu_data(world);
+        get_tools_data_mut(&mut world).menu_active = true;
+        world.request_redraw_annotations(KEYPOINTS_NAME, true);
+        (world, history)
+    }
+
+    fn on_deactivate(&mut self, mut world: World, history: History) -> (World, History) {
+        self.reset();
+        get_tools_data_mut(&mut world).menu_active = false;
+        world.request_redraw_annotations(KEYPOINTS_NAME, false);
+        (world, history)
+    }
+
+    fn events_tf(
+        &mut self,
+        mut world: World,
+        history: History,
+        events: &Events,
+    ) -> (World, History) {
+        // this is necessary in addition to the call in on_activate due to undo/redo
+        world = initialize_tools_menu_data(world);
+        world = check_export(world);
+        if world.data.meta_data.file_path.is_none() {
+            return (world, history);
+        }
+        if self.previous_file != world.data.meta_data.file_path {
+            self.reset();
+            get_annos_mut(&mut world).deselect();
+            world.request_redraw_annotations(KEYPOINTS_NAME, true);
+            self.previous_file = world.data.meta_data.file_path.clone();
+        }
+        let options = &mut get_tools_data_mut(&mut world)
+            .specifics
+            .keypoint_mut()
+            .options;
+        if mem::take(&mut options.is_redraw_annos_triggered) {
+            world.request_redraw_annotations(KEYPOINTS_NAME, true);
+        }
+        // undo, redo, or changes of the skeleton might invalidate the indices
+        for state in [&mut self.placing, &mut self.moving, &mut self.selected] {
+            if state.map(|s| !is_valid(&world, s)).unwrap_or(false) {
+                *state = None;
+            }
+        }
+        make_tool_transform!(
+            self,
+            world,
+            history,
+            events,
+            [
+                (pressed, KeyCode::MouseLeft, mouse_pressed),
+                (held, KeyCode::MouseLeft, mouse_held),
+                (released, KeyCode::MouseLeft, mouse_released_left),
+                (released, KeyCode::MouseRight, mouse_released_right),
+                (triggered, KeyAction::ToggleKeypointVisibility, key_released),
+                (triggered, KeyAction::DeleteSelected, key_released)
+            ]
+        )
+    }
+}
@@ -1,7 +1,9 @@
 mod always_active_zoom;
 mod bbox;
 mod brush;
+mod classify;
 mod core;
+mod keypoints;
 mod rot90;
 mod zoom;
 
@@ -11,12 +13,16 @@ pub use self::core::Manipulate;
 pub use always_active_zoom::AlwaysActiveZoom;
 pub use bbox::BBox;
 pub use brush::Brush;
+pub use classify::Classify;
+pub use keypoints::Keypoints;
 pub use rot90::Rot90;
 use std::fmt::Debug;
 pub use zoom::Zoom;
 
 pub const BBOX_NAME: &str = bbox::ACTOR_NAME;
 pub const BRUSH_NAME: &str = "Brush";
+pub const CLASSIFY_NAME: &str = classify::ACTOR_NAME;
+pub const KEYPOINTS_NAME: &str = keypoints::ACTOR_NAME;
 pub const ZOOM_NAME: &str = "Zoom";
 pub const ROT90_NAME: &str = "Rot90";
 pub const ALWAYS_ACTIVE_ZOOM: &str = "AlwaysActiveZoom";
@@ -43,6 +49,8 @@ make_tools!(
     (Rot90, "🔄", ROT90_NAME, false, false),
     (Brush, "✏", BRUSH_NAME, false, false),
     (BBox, "⬜", BBOX_NAME, false, false),
+    (Classify, "🏷", CLASSIFY_NAME, false, false),
+    (Keypoints, "☝", KEYPOINTS_NAME, false, false),
     (Zoom, "🔍", ZOOM_NAME, false, false),
     (AlwaysActiveZoom, "AA🔍", ALWAYS_ACTIVE_ZOOM, true, true)
 );
@@ -54,6 +62,8 @@ macro_rules! apply_tool_method_mut {
             ToolWrapper::Rot90(z) => z.$f($($args,)*),
             ToolWrapper::Brush(z) => z.$f($($args,)*),
             ToolWrapper::BBox(z) => z.$f($($args,)*),
+            ToolWrapper::Classify(z) => z.$f($($args,)*),
+            ToolWrapper::Keypoints(z) => z.$f($($args,)*),
             ToolWrapper::Zoom(z) => z.$f($($args,)*),
             ToolWrapper::AlwaysActiveZoom(z) => z.$f($($args,)*),
         }
@@ -1,6 +1,7 @@
 use crate::{
-    events::{Events, KeyCode},
+    events::Events,
     history::{History, Record},
+    keymap::KeyAction,
     make_tool_transform,
     world::{DataRaw, World},
 };
@@ -42,7 +43,7 @@ impl Manipulate for Rot90 {
             world,
             history,
             event,
-            [(pressed, KeyCode::R, key_pressed)]
+            [(triggered, KeyAction::Rotate, key_pressed)]
         )
     }
 }
@@ -2,9 +2,10 @@ use std::fmt::Debug;
 
 use crate::{
     domain::{OutOfBoundsMode, PtF, PtI, Shape, BB},
-    drawme::{Annotation, Stroke},
+    drawme::{Annotation, BboxAnnotation, Stroke},
     events::{Events, KeyCode},
     history::History,
+    keymap::KeyAction,
     make_tool_transform,
     tools::core::Manipulate,
     types::ViewImage,
@@ -140,7 +141,7 @@ impl Zoom {
                 // animation
                 let bb = BB::from_points(mps.into(), m.into());
                 let white = [255, 255, 255];
-                let anno = Annotation {
+                let anno = Annotation::Bbox(BboxAnnotation {
                     geofig: GeoFig::BB(bb),
                     fill_color: None,
                     fill_alpha: 0,
@@ -148,7 +149,7 @@ impl Zoom {
                     outline_alpha: 255,
                     label: None,
                     is_selected: None,
-                };
+                });
                 world.request_redraw_tmp_anno(anno);
             }
         }
@@ -185,7 +186,7 @@ impl Manipulate for Zoom {
                 (released, KeyCode::MouseLeft, mouse_released),
                 (held, KeyCode::MouseLeft, mouse_held),
                 (held, KeyCode::MouseRight, mouse_held),
-                (pressed, KeyCode::Back, key_pressed)
+                (triggered, KeyAction::DeleteSelected, key_pressed)
             ]
         )
     }
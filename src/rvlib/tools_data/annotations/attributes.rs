@@ -0,0 +1,87 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use serde::{Deserialize, Serialize};
+
+/// Value of an attribute of an annotation, e.g., whether the object is occluded.
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum AttrVal {
+    Bool(bool),
+    Int(i64),
+    Text(String),
+}
+impl AttrVal {
+    /// `false`, `0`, and the empty string are falsy
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Self::Bool(b) => *b,
+            Self::Int(i) => *i != 0,
+            Self::Text(s) => !s.is_empty(),
+        }
+    }
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Self::Bool(_) => "bool",
+            Self::Int(_) => "int",
+            Self::Text(_) => "text",
+        }
+    }
+}
+impl Default for AttrVal {
+    fn default() -> Self {
+        Self::Bool(false)
+    }
+}
+impl Display for AttrVal {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Int(i) => write!(f, "{i}"),
+            Self::Text(s) => write!(f, "{s}"),
+        }
+    }
+}
+
+/// An attribute all annotations of a label have. The type is given by the default value.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct AttrDef {
+    pub name: String,
+    pub default: AttrVal,
+}
+
+/// attribute name -> value of one annotation
+pub type Attributes = BTreeMap<String, AttrVal>;
+
+/// Defaults of the definitions overridden by the values stored for the annotation
+pub fn effective_attributes(defs: &[AttrDef], attributes: &Attributes) -> Attributes {
+    let mut effective = defs
+        .iter()
+        .map(|def| (def.name.clone(), def.default.clone()))
+        .collect::<Attributes>();
+    effective.extend(attributes.iter().map(|(k, v)| (k.clone(), v.clone())));
+    effective
+}
+
+#[test]
+fn test_effective() {
+    let defs = vec![
+        AttrDef {
+            name: "occluded".to_string(),
+            default: AttrVal::Bool(false),
+        },
+        AttrDef {
+            name: "track_id".to_string(),
+            default: AttrVal::Int(0),
+        },
+    ];
+    let attributes = Attributes::from([("track_id".to_string(), AttrVal::Int(3))]);
+    let effective = effective_attributes(&defs, &attributes);
+    assert_eq!(effective["occluded"], AttrVal::Bool(false));
+    assert_eq!(effective["track_id"], AttrVal::Int(3));
+    let json = serde_json::to_string(&effective).unwrap();
+    assert_eq!(json, r#"{"occluded":false,"track_id":3}"#);
+    assert_eq!(
+        serde_json::from_str::<Attributes>(&json).unwrap(),
+        effective
+    );
+}
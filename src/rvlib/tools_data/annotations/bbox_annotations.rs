@@ -1,19 +1,132 @@
 use crate::{
-    domain::{PtF, Shape, BB},
+    domain::{BbF, PtF, Shape, BB},
+    result::{RvError, RvResult},
+    rverr,
     util::true_indices,
     GeoFig,
 };
 use serde::{Deserialize, Serialize};
-use std::mem;
+use std::{fmt::Display, mem, str::FromStr};
 
-use super::bbox_splitmode::SplitMode;
+use super::{
+    attributes::{AttrVal, Attributes},
+    bbox_splitmode::SplitMode,
+};
+
+/// Annotation proposed by a model that is shown until the user accepts or rejects it
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq)]
+pub struct Prediction {
+    pub geo: GeoFig,
+    pub cat_idx: usize,
+    /// in `[0, 1]`, never `NaN`
+    pub score: f32,
+}
+impl Eq for Prediction {}
+
+/// Where an annotation comes from
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum AnnoSource {
+    #[default]
+    Manual,
+    Imported,
+    Predicted,
+}
+impl Display for AnnoSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Manual => write!(f, "manual"),
+            Self::Imported => write!(f, "imported"),
+            Self::Predicted => write!(f, "predicted"),
+        }
+    }
+}
+impl FromStr for AnnoSource {
+    type Err = RvError;
+    fn from_str(s: &str) -> RvResult<Self> {
+        match s.trim() {
+            "manual" => Ok(Self::Manual),
+            "imported" => Ok(Self::Imported),
+            "predicted" => Ok(Self::Predicted),
+            _ => Err(rverr!("unknown annotation source '{}'", s)),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ReviewStatus {
+    #[default]
+    Unreviewed,
+    Accepted,
+    Rejected,
+}
+impl Display for ReviewStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreviewed => write!(f, "unreviewed"),
+            Self::Accepted => write!(f, "accepted"),
+            Self::Rejected => write!(f, "rejected"),
+        }
+    }
+}
+impl FromStr for ReviewStatus {
+    type Err = RvError;
+    fn from_str(s: &str) -> RvResult<Self> {
+        match s.trim() {
+            "unreviewed" => Ok(Self::Unreviewed),
+            "accepted" => Ok(Self::Accepted),
+            "rejected" => Ok(Self::Rejected),
+            _ => Err(rverr!("unknown review status '{}'", s)),
+        }
+    }
+}
+
+/// Confidence, source, and review status of an annotation
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct Provenance {
+    /// confidence of a model in `[0, 1]`, never `NaN`
+    #[serde(default)]
+    pub score: Option<f32>,
+    #[serde(default)]
+    pub source: AnnoSource,
+    #[serde(default)]
+    pub review: ReviewStatus,
+}
+impl Eq for Provenance {}
+impl Provenance {
+    pub fn is_unreviewed_prediction(&self) -> bool {
+        self.source == AnnoSource::Predicted && self.review == ReviewStatus::Unreviewed
+    }
+}
+
+/// Geometry of an annotation with everything that belongs to it, e.g., to paste it elsewhere
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct AnnoEntry {
+    pub geo: GeoFig,
+    pub cat_idx: usize,
+    pub attributes: Attributes,
+    pub provenance: Provenance,
+    pub track_id: Option<u32>,
+    pub sub_pixel_bb: Option<BbF>,
+}
 
 #[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
 pub struct BboxAnnotations {
     geos: Vec<GeoFig>,
     cat_idxs: Vec<usize>,
     selected_bbs: Vec<bool>,
+    /// per geometry only the attribute values that differ from the defaults of the label
+    attributes: Vec<Attributes>,
+    provenances: Vec<Provenance>,
+    /// identity of the object across images, e.g., frames of a video
+    track_ids: Vec<Option<u32>>,
+    /// boxes of imported annotations with sub-pixel precision that are only valid as long as they
+    /// round to the box of the geometry
+    sub_pixel_bbs: Vec<Option<BbF>>,
     pub show_labels: bool,
+    #[serde(default)]
+    predictions: Vec<Prediction>,
 }
 
 impl BboxAnnotations {
@@ -22,7 +135,12 @@ impl BboxAnnotations {
             geos: vec![],
             cat_idxs: vec![],
             selected_bbs: vec![],
+            attributes: vec![],
+            provenances: vec![],
+            track_ids: vec![],
+            sub_pixel_bbs: vec![],
             show_labels: false,
+            predictions: vec![],
         }
     }
 
@@ -35,20 +153,61 @@ impl BboxAnnotations {
         IG: Iterator<Item = GeoFig>,
         IC: Iterator<Item = usize>,
     {
-        for (geo, cat_id) in geos.zip(cat_ids) {
-            if geo.is_contained_in_image(shape_image) && !self.geos().contains(&geo) {
-                self.add_geo(geo, cat_id)
+        self.extend_with(
+            geos.zip(cat_ids).map(|(geo, cat_idx)| AnnoEntry {
+                geo,
+                cat_idx,
+                attributes: Attributes::new(),
+                provenance: Provenance::default(),
+                track_id: None,
+                sub_pixel_bb: None,
+            }),
+            shape_image,
+        );
+    }
+
+    /// Adds the entries that are inside the image and whose geometries do not exist yet.
+    pub fn extend_with(&mut self, entries: impl Iterator<Item = AnnoEntry>, shape_image: Shape) {
+        for entry in entries {
+            if entry.geo.is_contained_in_image(shape_image) && !self.geos().contains(&entry.geo) {
+                self.add_entry(entry);
             }
         }
     }
 
+    pub fn entry(&self, box_idx: usize) -> AnnoEntry {
+        AnnoEntry {
+            geo: self.geos[box_idx].clone(),
+            cat_idx: self.cat_idxs[box_idx],
+            attributes: self.attributes[box_idx].clone(),
+            provenance: self.provenances[box_idx],
+            track_id: self.track_ids[box_idx],
+            sub_pixel_bb: self.sub_pixel_bbs[box_idx],
+        }
+    }
+
+    pub fn add_entry(&mut self, entry: AnnoEntry) {
+        self.cat_idxs.push(entry.cat_idx);
+        self.geos.push(entry.geo);
+        self.selected_bbs.push(false);
+        self.attributes.push(entry.attributes);
+        self.provenances.push(entry.provenance);
+        self.track_ids.push(entry.track_id);
+        self.sub_pixel_bbs.push(entry.sub_pixel_bb);
+    }
+
     pub fn from_bbs_cats(geos: Vec<GeoFig>, cat_ids: Vec<usize>) -> BboxAnnotations {
         let geos_len = geos.len();
         BboxAnnotations {
             geos,
             cat_idxs: cat_ids,
             selected_bbs: vec![false; geos_len],
+            attributes: vec![Attributes::new(); geos_len],
+            provenances: vec![Provenance::default(); geos_len],
+            track_ids: vec![None; geos_len],
+            sub_pixel_bbs: vec![None; geos_len],
             show_labels: false,
+            predictions: vec![],
         }
     }
 
@@ -59,7 +218,12 @@ impl BboxAnnotations {
             geos,
             cat_idxs: vec![cat_id; bbs_len],
             selected_bbs: vec![false; bbs_len],
+            attributes: vec![Attributes::new(); bbs_len],
+            provenances: vec![Provenance::default(); bbs_len],
+            track_ids: vec![None; bbs_len],
+            sub_pixel_bbs: vec![None; bbs_len],
             show_labels: false,
+            predictions: vec![],
         }
     }
 
@@ -70,12 +234,182 @@ impl BboxAnnotations {
                     *cid -= 1;
                 }
             }
+            for pred in self.predictions.iter_mut() {
+                if pred.cat_idx >= cat_idx {
+                    pred.cat_idx -= 1;
+                }
+            }
+        }
+    }
+
+    /// `idx_map[old_cat_idx]` is the new category index
+    pub fn remap_cat_idxs(&mut self, idx_map: &[usize]) {
+        for cid in self.cat_idxs.iter_mut() {
+            *cid = idx_map[*cid];
+        }
+        for pred in self.predictions.iter_mut() {
+            pred.cat_idx = idx_map[pred.cat_idx];
+        }
+    }
+
+    pub fn predictions(&self) -> &Vec<Prediction> {
+        &self.predictions
+    }
+
+    /// Predictions of a new model run replace the ones of the previous run.
+    pub fn set_predictions(&mut self, predictions: Vec<Prediction>) {
+        self.predictions = predictions;
+    }
+
+    fn add_accepted_prediction(&mut self, pred: Prediction) {
+        let provenance = Provenance {
+            score: Some(pred.score),
+            source: AnnoSource::Predicted,
+            review: ReviewStatus::Accepted,
+        };
+        self.add_geo_with_provenance(pred.geo, pred.cat_idx, provenance);
+    }
+
+    /// Turns the prediction into an annotation.
+    pub fn accept_prediction(&mut self, pred_idx: usize) {
+        let pred = self.predictions.remove(pred_idx);
+        self.add_accepted_prediction(pred);
+    }
+
+    pub fn reject_prediction(&mut self, pred_idx: usize) {
+        self.predictions.remove(pred_idx);
+    }
+
+    /// Accepts all predictions with a score of at least `threshold`.
+    pub fn accept_predictions(&mut self, threshold: f32) {
+        let (accepted, kept) = mem::take(&mut self.predictions)
+            .into_iter()
+            .partition::<Vec<_>, _>(|pred| pred.score >= threshold);
+        self.predictions = kept;
+        for pred in accepted {
+            self.add_accepted_prediction(pred);
+        }
+    }
+
+    /// Rejects all predictions with a score of at least `threshold`.
+    pub fn reject_predictions(&mut self, threshold: f32) {
+        self.predictions.retain(|pred| pred.score < threshold);
+    }
+
+    /// Compares geometries, categories, attributes, provenances, and track ids but ignores
+    /// selection and visibility of labels.
+    pub fn has_same_content(&self, other: &Self) -> bool {
+        self.geos == other.geos
+            && self.cat_idxs == other.cat_idxs
+            && self.attributes == other.attributes
+            && self.provenances == other.provenances
+            && self.track_ids == other.track_ids
+    }
+
+    pub fn track_ids(&self) -> &Vec<Option<u32>> {
+        &self.track_ids
+    }
+
+    pub fn set_track_ids(&mut self, track_ids: Vec<Option<u32>>) -> RvResult<()> {
+        if track_ids.len() != self.geos.len() {
+            return Err(rverr!(
+                "got track ids for {} geometries, but we have {}",
+                track_ids.len(),
+                self.geos.len()
+            ));
+        }
+        self.track_ids = track_ids;
+        Ok(())
+    }
+
+    pub fn set_track_id(&mut self, box_idx: usize, track_id: Option<u32>) {
+        self.track_ids[box_idx] = track_id;
+    }
+
+    pub fn sub_pixel_bbs(&self) -> &Vec<Option<BbF>> {
+        &self.sub_pixel_bbs
+    }
+
+    pub fn set_sub_pixel_bbs(&mut self, sub_pixel_bbs: Vec<Option<BbF>>) -> RvResult<()> {
+        if sub_pixel_bbs.len() != self.geos.len() {
+            return Err(rverr!(
+                "got sub-pixel boxes for {} geometries, but we have {}",
+                sub_pixel_bbs.len(),
+                self.geos.len()
+            ));
+        }
+        self.sub_pixel_bbs = sub_pixel_bbs;
+        Ok(())
+    }
+
+    pub fn provenances(&self) -> &Vec<Provenance> {
+        &self.provenances
+    }
+
+    pub fn set_provenances(&mut self, provenances: Vec<Provenance>) -> RvResult<()> {
+        if provenances.len() != self.geos.len() {
+            return Err(rverr!(
+                "got provenances for {} geometries, but we have {}",
+                provenances.len(),
+                self.geos.len()
+            ));
+        }
+        self.provenances = provenances;
+        Ok(())
+    }
+
+    pub fn set_review_selected(&mut self, review: ReviewStatus) {
+        for idx in true_indices(&self.selected_bbs) {
+            self.provenances[idx].review = review;
+        }
+    }
+
+    pub fn first_selected_provenance(&self) -> Option<&Provenance> {
+        true_indices(&self.selected_bbs)
+            .next()
+            .map(|idx| &self.provenances[idx])
+    }
+
+    pub fn attributes(&self) -> &Vec<Attributes> {
+        &self.attributes
+    }
+
+    pub fn set_attributes(&mut self, attributes: Vec<Attributes>) -> RvResult<()> {
+        if attributes.len() != self.geos.len() {
+            return Err(rverr!(
+                "got attributes for {} geometries, but we have {}",
+                attributes.len(),
+                self.geos.len()
+            ));
+        }
+        self.attributes = attributes;
+        Ok(())
+    }
+
+    /// Sets the attribute for all selected geometries with the given category.
+    pub fn set_attribute_selected(&mut self, cat_idx: usize, name: &str, value: &AttrVal) {
+        let selected_inds = true_indices(&self.selected_bbs);
+        for idx in selected_inds {
+            if self.cat_idxs[idx] == cat_idx {
+                self.attributes[idx].insert(name.to_string(), value.clone());
+            }
         }
     }
 
+    /// Attributes of the first selected geometry with the given category
+    pub fn first_selected_attributes(&self, cat_idx: usize) -> Option<&Attributes> {
+        true_indices(&self.selected_bbs)
+            .find(|idx| self.cat_idxs[*idx] == cat_idx)
+            .map(|idx| &self.attributes[idx])
+    }
+
     pub fn remove(&mut self, box_idx: usize) -> GeoFig {
         self.cat_idxs.remove(box_idx);
         self.selected_bbs.remove(box_idx);
+        self.attributes.remove(box_idx);
+        self.provenances.remove(box_idx);
+        self.track_ids.remove(box_idx);
+        self.sub_pixel_bbs.remove(box_idx);
         self.geos.remove(box_idx)
     }
 
@@ -85,7 +419,25 @@ impl BboxAnnotations {
             .clone()
             .map(|i| mem::take(&mut self.geos[i]))
             .collect::<Vec<_>>();
-        self.cat_idxs = keep_indices.map(|i| self.cat_idxs[i]).collect::<Vec<_>>();
+        self.cat_idxs = keep_indices
+            .clone()
+            .map(|i| self.cat_idxs[i])
+            .collect::<Vec<_>>();
+        self.attributes = keep_indices
+            .clone()
+            .map(|i| mem::take(&mut self.attributes[i]))
+            .collect::<Vec<_>>();
+        self.provenances = keep_indices
+            .clone()
+            .map(|i| self.provenances[i])
+            .collect::<Vec<_>>();
+        self.track_ids = keep_indices
+            .clone()
+            .map(|i| self.track_ids[i])
+            .collect::<Vec<_>>();
+        self.sub_pixel_bbs = keep_indices
+            .map(|i| self.sub_pixel_bbs[i])
+            .collect::<Vec<_>>();
         self.selected_bbs = vec![false; self.geos.len()];
     }
 
@@ -160,15 +512,29 @@ impl BboxAnnotations {
         }
     }
 
+    /// Replaces the geometry and keeps label, selection, attributes, and provenance.
+    pub fn set_geo(&mut self, box_idx: usize, geo: GeoFig) {
+        self.geos[box_idx] = geo;
+    }
+
     pub fn add_geo(&mut self, geo: GeoFig, cat_idx: usize) {
+        self.add_geo_with_provenance(geo, cat_idx, Provenance::default());
+    }
+    pub fn add_geo_with_provenance(&mut self, geo: GeoFig, cat_idx: usize, provenance: Provenance) {
         self.cat_idxs.push(cat_idx);
         self.geos.push(geo);
         self.selected_bbs.push(false);
+        self.attributes.push(Attributes::new());
+        self.provenances.push(provenance);
+        self.track_ids.push(None);
+        self.sub_pixel_bbs.push(None);
+    }
+    pub fn add_tracked_geo(&mut self, geo: GeoFig, cat_idx: usize, track_id: u32) {
+        self.add_geo(geo, cat_idx);
+        self.set_track_id(self.geos.len() - 1, Some(track_id));
     }
     pub fn add_bb(&mut self, bb: BB, cat_idx: usize) {
-        self.cat_idxs.push(cat_idx);
-        self.geos.push(GeoFig::BB(bb));
-        self.selected_bbs.push(false);
+        self.add_geo(GeoFig::BB(bb), cat_idx);
     }
 
     pub fn cat_idxs(&self) -> &Vec<usize> {
@@ -250,6 +616,10 @@ impl BboxAnnotations {
         self.geos.clear();
         self.selected_bbs.clear();
         self.cat_idxs.clear();
+        self.attributes.clear();
+        self.provenances.clear();
+        self.track_ids.clear();
+        self.sub_pixel_bbs.clear();
     }
 }
 #[cfg(test)]
@@ -315,6 +685,10 @@ fn test_annos() {
     fn len_check(annos: &BboxAnnotations) {
         assert_eq!(annos.selected_bbs.len(), annos.geos.len());
         assert_eq!(annos.cat_idxs.len(), annos.geos.len());
+        assert_eq!(annos.attributes.len(), annos.geos.len());
+        assert_eq!(annos.provenances.len(), annos.geos.len());
+        assert_eq!(annos.track_ids.len(), annos.geos.len());
+        assert_eq!(annos.sub_pixel_bbs.len(), annos.geos.len());
     }
     let mut annos = BboxAnnotations::from_bbs(make_test_bbs(), 0);
     len_check(&annos);
@@ -324,6 +698,13 @@ fn test_annos() {
     len_check(&annos);
     annos.label_selected(3);
     len_check(&annos);
+    let occluded = AttrVal::Bool(true);
+    annos.set_attribute_selected(3, "occluded", &occluded);
+    assert_eq!(
+        annos.first_selected_attributes(3).unwrap()["occluded"],
+        occluded
+    );
+    assert!(annos.attributes[0].is_empty());
     for i in 0..(annos.geos.len()) {
         if i == idx {
             assert_eq!(annos.cat_idxs[i], 3);
@@ -365,3 +746,77 @@ fn test_annos() {
     assert!(annos.cat_idxs.len() == 0);
     assert!(annos.cat_idxs.len() == 0);
 }
+#[test]
+fn test_predictions() {
+    let mut annos = BboxAnnotations::from_bbs(make_test_bbs(), 0);
+    let n_geos = annos.geos.len();
+    let pred = |score| Prediction {
+        geo: GeoFig::BB(BB::from_arr(&[1, 1, 5, 5])),
+        cat_idx: 1,
+        score,
+    };
+    annos.set_predictions(vec![pred(0.9), pred(0.3), pred(0.6)]);
+    annos.reject_prediction(2);
+    annos.accept_prediction(0);
+    assert_eq!(annos.selected_bbs.len(), annos.geos.len());
+    assert_eq!(annos.geos.len(), n_geos + 1);
+    assert_eq!(annos.cat_idxs[n_geos], 1);
+    assert_eq!(annos.provenances[n_geos].score, Some(0.9));
+    assert_eq!(annos.provenances[n_geos].review, ReviewStatus::Accepted);
+    assert_eq!(annos.predictions(), &vec![pred(0.3)]);
+    annos.set_predictions(vec![pred(0.9), pred(0.3), pred(0.6)]);
+    annos.accept_predictions(0.5);
+    assert_eq!(annos.selected_bbs.len(), annos.geos.len());
+    assert_eq!(annos.geos.len(), n_geos + 3);
+    annos.reject_predictions(0.2);
+    assert!(annos.predictions().is_empty());
+    annos.set_predictions(vec![pred(0.9)]);
+    annos.remap_cat_idxs(&[0, 0]);
+    assert_eq!(annos.predictions()[0].cat_idx, 0);
+}
+#[test]
+fn test_provenance() -> RvResult<()> {
+    let mut annos = BboxAnnotations::from_bbs(make_test_bbs(), 0);
+    let predicted = Provenance {
+        score: Some(0.4),
+        source: AnnoSource::Predicted,
+        review: ReviewStatus::Unreviewed,
+    };
+    annos.set_provenances(vec![Provenance::default(), predicted, predicted])?;
+    assert!(annos.set_provenances(vec![]).is_err());
+    assert!(annos.provenances[1].is_unreviewed_prediction());
+    annos.select(1);
+    annos.set_review_selected(ReviewStatus::Rejected);
+    assert_eq!(
+        annos.first_selected_provenance().map(|p| p.review),
+        Some(ReviewStatus::Rejected)
+    );
+    annos.remove(0);
+    assert_eq!(annos.provenances[0].review, ReviewStatus::Rejected);
+    assert!(annos.provenances[1].is_unreviewed_prediction());
+    annos.remove_multiple(&[0]);
+    assert_eq!(annos.provenances, vec![predicted]);
+    assert_eq!("predicted".parse::<AnnoSource>()?, AnnoSource::Predicted);
+    assert_eq!(ReviewStatus::Accepted.to_string(), "accepted");
+    assert!("maybe".parse::<ReviewStatus>().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_track_ids() -> RvResult<()> {
+    let mut annos = BboxAnnotations::from_bbs(make_test_bbs(), 0);
+    assert!(annos.track_ids().iter().all(|t| t.is_none()));
+    annos.add_tracked_geo(GeoFig::BB(BB::from_arr(&[1, 1, 2, 2])), 0, 7);
+    annos.set_track_id(0, Some(3));
+    annos.remove(1);
+    assert_eq!(annos.track_ids()[0], Some(3));
+    assert_eq!(annos.track_ids().last(), Some(&Some(7)));
+    annos.remove_multiple(&[0]);
+    assert_eq!(annos.track_ids().len(), annos.geos().len());
+    assert_eq!(annos.track_ids()[0], None);
+    assert!(annos.set_track_ids(vec![None]).is_err());
+    let other = annos.clone();
+    annos.set_track_id(0, Some(1));
+    assert!(!annos.has_same_content(&other));
+    Ok(())
+}
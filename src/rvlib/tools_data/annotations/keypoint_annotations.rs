@@ -0,0 +1,196 @@
+use serde::{Deserialize, Serialize};
+
+use crate::domain::{PtI, BB};
+
+/// Visibility flag of a keypoint as used by Coco, i.e., `v=0`, `v=1`, or `v=2`.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Visibility {
+    #[default]
+    NotLabeled,
+    Occluded,
+    Visible,
+}
+impl Visibility {
+    pub fn to_coco(self) -> u8 {
+        match self {
+            Self::NotLabeled => 0,
+            Self::Occluded => 1,
+            Self::Visible => 2,
+        }
+    }
+    /// labeled keypoints toggle between visible and occluded
+    pub fn toggle(self) -> Self {
+        match self {
+            Self::NotLabeled => Self::NotLabeled,
+            Self::Occluded => Self::Visible,
+            Self::Visible => Self::Occluded,
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Keypoint {
+    pub pos: PtI,
+    pub visibility: Visibility,
+}
+impl Keypoint {
+    pub fn is_labeled(&self) -> bool {
+        self.visibility != Visibility::NotLabeled
+    }
+}
+
+/// One keypoint per keypoint of the skeleton, unlabeled keypoints are contained as well.
+pub type KeypointInstance = Vec<Keypoint>;
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeypointAnnotations {
+    instances: Vec<KeypointInstance>,
+    #[serde(skip)]
+    selected: Option<usize>,
+}
+impl KeypointAnnotations {
+    pub fn instances(&self) -> &Vec<KeypointInstance> {
+        &self.instances
+    }
+
+    pub fn len(&self) -> usize {
+        self.instances.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instances.is_empty()
+    }
+
+    /// Adds an instance where no keypoint has been labeled yet and returns its index
+    pub fn add_instance(&mut self, n_keypoints: usize) -> usize {
+        self.instances.push(vec![Keypoint::default(); n_keypoints]);
+        self.instances.len() - 1
+    }
+
+    pub fn remove_instance(&mut self, instance_idx: usize) {
+        self.instances.remove(instance_idx);
+        self.selected = match self.selected {
+            Some(sel) if sel == instance_idx => None,
+            Some(sel) if sel > instance_idx => Some(sel - 1),
+            sel => sel,
+        };
+    }
+
+    pub fn select(&mut self, instance_idx: usize) {
+        self.selected = Some(instance_idx);
+    }
+
+    pub fn deselect(&mut self) {
+        self.selected = None;
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn set_keypoint(&mut self, instance_idx: usize, kp_idx: usize, keypoint: Keypoint) {
+        self.instances[instance_idx][kp_idx] = keypoint;
+    }
+
+    pub fn keypoint(&self, instance_idx: usize, kp_idx: usize) -> &Keypoint {
+        &self.instances[instance_idx][kp_idx]
+    }
+
+    /// Needs to be called when a keypoint has been appended to the skeleton
+    pub fn push_keypoint_slot(&mut self) {
+        for inst in &mut self.instances {
+            inst.push(Keypoint::default());
+        }
+    }
+
+    /// Needs to be called when a keypoint has been removed from the skeleton
+    pub fn remove_keypoint_slot(&mut self, kp_idx: usize) {
+        for inst in &mut self.instances {
+            inst.remove(kp_idx);
+        }
+    }
+
+    /// Returns the instance index and the keypoint index of the labeled keypoint that is closest to `pos`
+    /// if the squared distance is not larger than `tolerance`.
+    pub fn find_close_keypoint(&self, pos: PtI, tolerance: i64) -> Option<(usize, usize)> {
+        self.instances
+            .iter()
+            .enumerate()
+            .flat_map(|(inst_idx, inst)| {
+                inst.iter()
+                    .enumerate()
+                    .filter(|(_, kp)| kp.is_labeled())
+                    .map(move |(kp_idx, kp)| {
+                        let dx = kp.pos.x as i64 - pos.x as i64;
+                        let dy = kp.pos.y as i64 - pos.y as i64;
+                        (inst_idx, kp_idx, dx * dx + dy * dy)
+                    })
+            })
+            .filter(|(_, _, dist)| *dist <= tolerance)
+            .min_by_key(|(_, _, dist)| *dist)
+            .map(|(inst_idx, kp_idx, _)| (inst_idx, kp_idx))
+    }
+}
+
+pub fn n_labeled(instance: &KeypointInstance) -> usize {
+    instance.iter().filter(|kp| kp.is_labeled()).count()
+}
+
+/// Box around all labeled keypoints
+pub fn enclosing_bb(instance: &KeypointInstance) -> Option<BB> {
+    let mut labeled = instance.iter().filter(|kp| kp.is_labeled());
+    let first = labeled.next()?.pos;
+    let (min, max) = labeled.fold((first, first), |(min, max), kp| {
+        (
+            PtI {
+                x: min.x.min(kp.pos.x),
+                y: min.y.min(kp.pos.y),
+            },
+            PtI {
+                x: max.x.max(kp.pos.x),
+                y: max.y.max(kp.pos.y),
+            },
+        )
+    });
+    Some(BB::from_points(min, max))
+}
+
+#[test]
+fn test_keypoint_annos() {
+    let mut annos = KeypointAnnotations::default();
+    let inst_idx = annos.add_instance(3);
+    let kp = |x, y, visibility| Keypoint {
+        pos: PtI { x, y },
+        visibility,
+    };
+    annos.set_keypoint(inst_idx, 0, kp(10, 10, Visibility::Visible));
+    annos.set_keypoint(inst_idx, 2, kp(20, 30, Visibility::Occluded));
+    assert_eq!(n_labeled(&annos.instances()[0]), 2);
+    assert_eq!(
+        annos.find_close_keypoint(PtI { x: 19, y: 29 }, 4),
+        Some((0, 2))
+    );
+    assert_eq!(annos.find_close_keypoint(PtI { x: 0, y: 0 }, 4), None);
+    // unlabeled keypoints at the origin are not found
+    assert_eq!(annos.find_close_keypoint(PtI { x: 0, y: 0 }, 100), None);
+    assert_eq!(
+        enclosing_bb(&annos.instances()[0]),
+        Some(BB::from_points(PtI { x: 10, y: 10 }, PtI { x: 20, y: 30 }))
+    );
+    let inst_idx_2 = annos.add_instance(3);
+    annos.select(inst_idx_2);
+    annos.remove_instance(inst_idx);
+    assert_eq!(annos.selected(), Some(0));
+    annos.remove_instance(0);
+    assert_eq!(annos.selected(), None);
+    let inst_idx = annos.add_instance(3);
+    annos.set_keypoint(inst_idx, 2, kp(20, 30, Visibility::Occluded));
+    annos.remove_keypoint_slot(0);
+    annos.push_keypoint_slot();
+    assert_eq!(n_labeled(&annos.instances()[0]), 1);
+    assert_eq!(annos.instances()[0].len(), 3);
+    assert_eq!(
+        annos.keypoint(0, 1).visibility.toggle(),
+        Visibility::Visible
+    );
+}
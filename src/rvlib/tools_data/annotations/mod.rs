@@ -1,12 +1,20 @@
 //! Functionality to create and modify annotations.
 
-pub use self::bbox_annotations::BboxAnnotations;
+pub use self::attributes::{effective_attributes, AttrDef, AttrVal, Attributes};
+pub use self::bbox_annotations::{
+    AnnoEntry, AnnoSource, BboxAnnotations, Prediction, Provenance, ReviewStatus,
+};
 pub use self::bbox_splitmode::SplitMode;
 pub use self::brush_annotations::BrushAnnotations;
+pub use self::keypoint_annotations::{
+    enclosing_bb, n_labeled, Keypoint, KeypointAnnotations, KeypointInstance, Visibility,
+};
+mod attributes;
 mod bbox_annotations;
 mod bbox_splitmode;
 mod brush_annotations;
 mod core;
+mod keypoint_annotations;
 #[macro_export]
 macro_rules! implement_annotations_getters {
     ($tool_data_type:ident) => {
@@ -1,19 +1,31 @@
-use std::{collections::HashMap, mem};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    mem,
+};
 
 use serde::{Deserialize, Serialize};
 
-use super::annotations::BboxAnnotations;
+use super::{
+    annotations::BboxAnnotations,
+    data_split::{DataSplit, SplitCfg},
+    image_review::{ImageReview, ImageStatus},
+    label_schema::LabelSchema,
+};
 use crate::{
-    annotations::SplitMode,
+    annotations::{
+        effective_attributes, AnnoEntry, AttrDef, Attributes, Prediction, Provenance, SplitMode,
+    },
     cfg::{get_cfg, CocoFile},
-    domain::Shape,
+    domain::{BbF, Shape},
     file_util, implement_annotations_getters,
-    result::RvResult,
+    inference::RawPrediction,
+    result::{RvError, RvResult},
     rverr,
-    util::true_indices,
+    util::{fuzzy_score, true_indices},
     GeoFig,
 };
 const DEFAULT_LABEL: &str = "foreground";
+const MAX_RECENT_LABELS: usize = 8;
 
 /// filename -> (annotations per file, file dimensions)
 pub type AnnotationsMap = HashMap<String, (BboxAnnotations, Shape)>;
@@ -67,19 +79,28 @@ pub fn new_random_colors(n: usize) -> Vec<[u8; 3]> {
 pub struct ClipboardData {
     geos: Vec<GeoFig>,
     cat_idxs: Vec<usize>,
+    #[serde(default)]
+    attributes: Vec<Attributes>,
+    #[serde(default)]
+    provenances: Vec<Provenance>,
+    #[serde(default)]
+    track_ids: Vec<Option<u32>>,
+    #[serde(default)]
+    sub_pixel_bbs: Vec<Option<BbF>>,
 }
 
 impl ClipboardData {
     pub fn from_annotations(annos: &BboxAnnotations) -> Self {
-        let selected_inds = true_indices(annos.selected_bbs());
-        let bbs = selected_inds
-            .clone()
-            .map(|idx| annos.geos()[idx].clone())
-            .collect();
-        let cat_idxs = selected_inds.map(|idx| annos.cat_idxs()[idx]).collect();
+        let entries = true_indices(annos.selected_bbs())
+            .map(|idx| annos.entry(idx))
+            .collect::<Vec<_>>();
         ClipboardData {
-            geos: bbs,
-            cat_idxs,
+            geos: entries.iter().map(|e| e.geo.clone()).collect(),
+            cat_idxs: entries.iter().map(|e| e.cat_idx).collect(),
+            attributes: entries.iter().map(|e| e.attributes.clone()).collect(),
+            provenances: entries.iter().map(|e| e.provenance).collect(),
+            track_ids: entries.iter().map(|e| e.track_id).collect(),
+            sub_pixel_bbs: entries.iter().map(|e| e.sub_pixel_bb).collect(),
         }
     }
 
@@ -90,39 +111,123 @@ impl ClipboardData {
     pub fn cat_idxs(&self) -> &Vec<usize> {
         &self.cat_idxs
     }
+
+    /// Clipboards of older project files only contain geometries and labels.
+    pub fn entries(&self) -> impl Iterator<Item = AnnoEntry> + '_ {
+        (0..self.geos.len()).map(|idx| AnnoEntry {
+            geo: self.geos[idx].clone(),
+            cat_idx: self.cat_idxs[idx],
+            attributes: self.attributes.get(idx).cloned().unwrap_or_default(),
+            provenance: self.provenances.get(idx).copied().unwrap_or_default(),
+            track_id: self.track_ids.get(idx).copied().flatten(),
+            sub_pixel_bb: self.sub_pixel_bbs.get(idx).copied().flatten(),
+        })
+    }
 }
 
 #[derive(Clone, Copy, Deserialize, Serialize, Debug, PartialEq, Eq)]
 pub struct Options {
     pub are_boxes_visible: bool,
     pub auto_paste: bool,
+    /// annotations of the previous image are tracked into new images without annotations
+    pub auto_track: bool,
     pub is_anno_rm_triggered: bool,
     pub is_coco_import_triggered: bool,
+    /// imported annotations whose paths are not in the opened folder are matched by file name
+    pub is_path_matching_triggered: bool,
     pub is_export_triggered: bool,
+    pub is_mot_export_triggered: bool,
+    /// one Coco file per train, validation, and test split is exported
+    pub is_split_export_triggered: bool,
+    /// annotated objects are cut out of the images and exported into one folder per label
+    pub is_patch_export_triggered: bool,
+    /// pixels added to each side of the exported patches
+    pub patch_padding: u32,
+    /// width and height of the exported patches, 0 keeps the size of the annotation
+    pub patch_size: u32,
+    /// semantic and instance masks are exported per image
+    pub is_mask_export_triggered: bool,
+    /// images with burned in annotations are exported together with the masks
+    pub export_overlays: bool,
     pub is_colorchange_triggered: bool,
     pub is_redraw_annos_triggered: bool,
+    /// labels have been edited in the menu and the change needs to be recorded for undo
+    pub is_history_update_triggered: bool,
+    pub is_schema_import_triggered: bool,
+    /// the current image is sent to the inference endpoint
+    pub is_predict_triggered: bool,
+    /// all images of the filtered folder are sent to the inference endpoint
+    pub is_batch_predict_triggered: bool,
+    /// selected boxes are tightened to the objects inside
+    pub is_snap_triggered: bool,
+    /// left clicks fill regions of similar color instead of drawing boxes
+    pub is_magic_wand_active: bool,
+    /// maximal difference per color channel of pixels that are filled by the magic wand
+    pub magic_wand_tolerance: u8,
     pub split_mode: SplitMode,
     pub export_absolute: bool,
     pub fill_alpha: u8,
     pub outline_alpha: u8,
+    /// in percent, predictions with lower scores are hidden
+    pub score_threshold: u8,
 }
 impl Default for Options {
     fn default() -> Self {
         Self {
             are_boxes_visible: true,
             auto_paste: false,
+            auto_track: false,
             is_anno_rm_triggered: false,
             is_coco_import_triggered: false,
+            is_path_matching_triggered: false,
             is_export_triggered: false,
+            is_mot_export_triggered: false,
+            is_split_export_triggered: false,
+            is_patch_export_triggered: false,
+            patch_padding: 0,
+            patch_size: 0,
+            is_mask_export_triggered: false,
+            export_overlays: false,
             is_colorchange_triggered: false,
             is_redraw_annos_triggered: false,
+            is_history_update_triggered: false,
+            is_schema_import_triggered: false,
+            is_predict_triggered: false,
+            is_batch_predict_triggered: false,
+            is_snap_triggered: false,
+            is_magic_wand_active: false,
+            magic_wand_tolerance: 20,
             split_mode: SplitMode::default(),
             export_absolute: false,
             fill_alpha: 30,
             outline_alpha: 255,
+            score_threshold: 50,
         }
     }
 }
+/// State of the searchable label palette
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelPalette {
+    pub is_open: bool,
+    pub query: String,
+    /// quick-select slot the current label is pinned to from the menu
+    pub pin_slot: usize,
+}
+
+/// Label that is edited in the menu, the values are applied on request
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelEdit {
+    pub cat_idx: Option<usize>,
+    pub name: String,
+    pub color: [u8; 3],
+    pub cat_id: u32,
+    pub merge_into: usize,
+    /// label whose drag handle is held to reorder the labels
+    pub dragged: Option<usize>,
+    /// message of the last failed edit, shown in the menu
+    pub error: Option<String>,
+}
+
 #[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
 pub struct BboxSpecificData {
     pub new_label: String,
@@ -131,9 +236,38 @@ pub struct BboxSpecificData {
     cat_ids: Vec<u32>,
     pub cat_idx_current: usize,
     annotations_map: AnnotationsMap,
+    /// filename -> review status and reviewer comment, only for files that have been reviewed
+    image_reviews: HashMap<String, ImageReview>,
+    /// filename -> split of the last split export
+    splits: HashMap<String, DataSplit>,
+    pub split_cfg: SplitCfg,
     pub clipboard: Option<ClipboardData>,
     pub options: Options,
     pub coco_file: CocoFile,
+    /// label -> attributes of each annotation of the label
+    attribute_defs: HashMap<String, Vec<AttrDef>>,
+    /// attribute that is about to be added in the menu
+    pub new_attribute: AttrDef,
+    /// quick-select slot -> label, slots without pinned label select the label with the slot's index
+    pinned_labels: BTreeMap<usize, String>,
+    /// locked labels cannot be added or renamed ad hoc but only by importing a label schema
+    locked: bool,
+    /// most recently imported label schema
+    #[serde(default)]
+    schema: Option<LabelSchema>,
+    /// path of the label schema file that is about to be imported
+    #[serde(skip)]
+    pub schema_path: String,
+    /// most recently used labels, the most recent one first
+    #[serde(skip)]
+    recent_labels: Vec<String>,
+    #[serde(skip)]
+    pub palette: LabelPalette,
+    #[serde(skip)]
+    pub label_edit: LabelEdit,
+    /// image that is currently shown, needed by the menu
+    #[serde(skip)]
+    pub current_file_path: Option<String>,
 }
 
 impl BboxSpecificData {
@@ -160,12 +294,25 @@ impl BboxSpecificData {
             cat_ids: vec![],
             cat_idx_current: 0,
             annotations_map: HashMap::new(),
+            image_reviews: input_data.image_reviews,
+            splits: input_data.splits,
+            split_cfg: input_data.split_cfg,
             clipboard: None,
             options: Options {
                 are_boxes_visible: true,
                 ..Default::default()
             },
             coco_file: input_data.coco_file,
+            attribute_defs: input_data.attribute_defs,
+            new_attribute: AttrDef::default(),
+            pinned_labels: input_data.pinned_labels,
+            locked: false,
+            schema: input_data.schema,
+            schema_path: String::new(),
+            recent_labels: vec![],
+            palette: LabelPalette::default(),
+            label_edit: LabelEdit::default(),
+            current_file_path: None,
         };
         for ((lab, clr), cat_id) in input_data
             .labels
@@ -175,6 +322,7 @@ impl BboxSpecificData {
         {
             out_data.push(lab, Some(clr), Some(cat_id))?;
         }
+        out_data.locked = input_data.is_locked;
         out_data.set_annotations_map(
             input_data
                 .annotations
@@ -184,11 +332,40 @@ impl BboxSpecificData {
                 })
                 .collect(),
         )?;
+        for (file_path, attributes) in input_data.attributes {
+            if let Some((annos, _)) = out_data.annotations_map.get_mut(&file_path) {
+                annos.set_attributes(attributes)?;
+            }
+        }
+        for (file_path, provenances) in input_data.provenances {
+            if let Some((annos, _)) = out_data.annotations_map.get_mut(&file_path) {
+                annos.set_provenances(provenances)?;
+            }
+        }
+        for (file_path, track_ids) in input_data.track_ids {
+            if let Some((annos, _)) = out_data.annotations_map.get_mut(&file_path) {
+                annos.set_track_ids(track_ids)?;
+            }
+        }
+        for (file_path, sub_pixel_bbs) in input_data.sub_pixel_bbs {
+            if let Some((annos, _)) = out_data.annotations_map.get_mut(&file_path) {
+                annos.set_sub_pixel_bbs(sub_pixel_bbs)?;
+            }
+        }
+        for (file_path, predictions) in input_data.predictions {
+            if let Some((annos, _)) = out_data.annotations_map.get_mut(&file_path) {
+                annos.set_predictions(predictions);
+            }
+        }
         Ok(out_data)
     }
 
     pub fn remove_catidx(&mut self, cat_idx: usize) {
         if self.labels.len() > 1 {
+            let label = &self.labels[cat_idx];
+            self.pinned_labels.retain(|_, pinned| pinned != label);
+            self.recent_labels.retain(|recent| recent != label);
+            self.attribute_defs.remove(label);
             self.labels.remove(cat_idx);
             self.colors.remove(cat_idx);
             self.cat_ids.remove(cat_idx);
@@ -201,6 +378,155 @@ impl BboxSpecificData {
         }
     }
 
+    fn check_cat_idx(&self, cat_idx: usize) -> RvResult<()> {
+        if cat_idx < self.labels.len() {
+            Ok(())
+        } else {
+            Err(rverr!(
+                "label index {} out of bounds {}",
+                cat_idx,
+                self.labels.len()
+            ))
+        }
+    }
+
+    /// `idx_map[old_cat_idx]` is the new category index
+    fn remap_cat_idxs(&mut self, idx_map: &[usize]) {
+        for (anno, _) in self.annotations_map.values_mut() {
+            anno.remap_cat_idxs(idx_map);
+        }
+        self.cat_idx_current = idx_map[self.cat_idx_current];
+    }
+
+    fn replace_label_refs(&mut self, old: &str, new: &str) {
+        for pinned in self.pinned_labels.values_mut() {
+            if pinned == old {
+                *pinned = new.to_string();
+            }
+        }
+        self.recent_labels
+            .retain(|recent| recent != old && recent != new);
+    }
+
+    /// Checks whether the label can be renamed without changing anything.
+    pub fn check_rename_label(&self, cat_idx: usize, new_name: &str) -> RvResult<()> {
+        self.check_cat_idx(cat_idx)?;
+        if self.locked {
+            Err(rverr!(
+                "labels are locked, cannot rename '{}'",
+                self.labels[cat_idx]
+            ))
+        } else if new_name.is_empty() {
+            Err(RvError::new("label name must not be empty"))
+        } else if self
+            .labels
+            .iter()
+            .enumerate()
+            .any(|(idx, label)| idx != cat_idx && label == new_name)
+        {
+            Err(rverr!("label '{}' already exists", new_name))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Renames the label, annotations, attribute definitions, and pins are kept.
+    pub fn rename_label(&mut self, cat_idx: usize, new_name: String) -> RvResult<()> {
+        self.check_rename_label(cat_idx, &new_name)?;
+        let old_name = mem::replace(&mut self.labels[cat_idx], new_name.clone());
+        if let Some(defs) = self.attribute_defs.remove(&old_name) {
+            self.attribute_defs.insert(new_name.clone(), defs);
+        }
+        self.replace_label_refs(&old_name, &new_name);
+        Ok(())
+    }
+
+    /// All annotations of label `from_idx` get label `into_idx`, then label `from_idx` is removed.
+    pub fn merge_labels(&mut self, from_idx: usize, into_idx: usize) -> RvResult<()> {
+        self.check_cat_idx(from_idx)?;
+        self.check_cat_idx(into_idx)?;
+        if from_idx == into_idx {
+            return Err(rverr!(
+                "cannot merge label '{}' into itself",
+                self.labels[from_idx]
+            ));
+        }
+        let shift = |idx: usize| if idx > from_idx { idx - 1 } else { idx };
+        let idx_map = (0..self.labels.len())
+            .map(|idx| shift(if idx == from_idx { into_idx } else { idx }))
+            .collect::<Vec<_>>();
+        self.remap_cat_idxs(&idx_map);
+        let from_label = self.labels.remove(from_idx);
+        self.colors.remove(from_idx);
+        self.cat_ids.remove(from_idx);
+        self.attribute_defs.remove(&from_label);
+        let into_label = self.labels[shift(into_idx)].clone();
+        self.replace_label_refs(&from_label, &into_label);
+        Ok(())
+    }
+
+    /// Moves the label to another position in the label list.
+    pub fn move_label(&mut self, from_idx: usize, to_idx: usize) -> RvResult<()> {
+        self.check_cat_idx(from_idx)?;
+        self.check_cat_idx(to_idx)?;
+        // order[new_idx] = old_idx
+        let mut order = (0..self.labels.len()).collect::<Vec<_>>();
+        let moved = order.remove(from_idx);
+        order.insert(to_idx, moved);
+        let mut idx_map = vec![0; order.len()];
+        for (new_idx, old_idx) in order.iter().enumerate() {
+            idx_map[*old_idx] = new_idx;
+        }
+        self.labels = order.iter().map(|idx| self.labels[*idx].clone()).collect();
+        self.colors = order.iter().map(|idx| self.colors[*idx]).collect();
+        self.cat_ids = order.iter().map(|idx| self.cat_ids[*idx]).collect();
+        self.remap_cat_idxs(&idx_map);
+        Ok(())
+    }
+
+    /// Checks whether the color can be set without changing anything.
+    pub fn check_color(&self, cat_idx: usize, color: [u8; 3]) -> RvResult<()> {
+        self.check_cat_idx(cat_idx)?;
+        if self
+            .colors
+            .iter()
+            .enumerate()
+            .any(|(idx, clr)| idx != cat_idx && *clr == color)
+        {
+            Err(rverr!("color '{:?}' already exists", color))
+        } else {
+            Ok(())
+        }
+    }
+
+    pub fn set_color(&mut self, cat_idx: usize, color: [u8; 3]) -> RvResult<()> {
+        self.check_color(cat_idx, color)?;
+        self.colors[cat_idx] = color;
+        Ok(())
+    }
+
+    /// Checks whether the category id can be set without changing anything.
+    pub fn check_cat_id(&self, cat_idx: usize, cat_id: u32) -> RvResult<()> {
+        self.check_cat_idx(cat_idx)?;
+        if self
+            .cat_ids
+            .iter()
+            .enumerate()
+            .any(|(idx, cid)| idx != cat_idx && *cid == cat_id)
+        {
+            Err(rverr!("cat id '{:?}' already exists", cat_id))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Sets the category id that is used in the Coco export.
+    pub fn set_cat_id(&mut self, cat_idx: usize, cat_id: u32) -> RvResult<()> {
+        self.check_cat_id(cat_idx, cat_id)?;
+        self.cat_ids[cat_idx] = cat_id;
+        Ok(())
+    }
+
     pub fn is_empty(&self) -> bool {
         self.colors.len() == 0
     }
@@ -213,13 +539,166 @@ impl BboxSpecificData {
         self.labels.iter_mut().find(|lab| lab == &DEFAULT_LABEL)
     }
 
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    pub fn set_locked(&mut self, locked: bool) {
+        self.locked = locked;
+    }
+
+    pub fn schema(&self) -> Option<&LabelSchema> {
+        self.schema.as_ref()
+    }
+
+    pub fn set_schema(&mut self, schema: Option<LabelSchema>) {
+        self.schema = schema;
+    }
+
+    fn cat_idxs_in_use(&self) -> HashSet<usize> {
+        self.annotations_map
+            .values()
+            .flat_map(|(annos, _)| {
+                annos
+                    .cat_idxs()
+                    .iter()
+                    .copied()
+                    .chain(annos.predictions().iter().map(|pred| pred.cat_idx))
+            })
+            .collect()
+    }
+
+    pub fn score_threshold(&self) -> f32 {
+        self.options.score_threshold as f32 / 100.0
+    }
+
+    /// Replaces the predictions of the file by the ones of the model. Labels the model knows but
+    /// we do not are added unless the labels are locked. Returns the labels of predictions that
+    /// have been skipped since they are unknown and the labels are locked.
+    pub fn set_predictions(
+        &mut self,
+        file_path: &str,
+        shape: Shape,
+        raw_predictions: &[RawPrediction],
+    ) -> Vec<String> {
+        let mut skipped = vec![];
+        let mut predictions = vec![];
+        for raw in raw_predictions {
+            let cat_idx = match self.labels.iter().position(|l| *l == raw.label) {
+                Some(cat_idx) => Some(cat_idx),
+                None => match self.push(raw.label.clone(), None, None) {
+                    Ok(()) => Some(self.labels.len() - 1),
+                    Err(_) => {
+                        if !skipped.contains(&raw.label) {
+                            skipped.push(raw.label.clone());
+                        }
+                        None
+                    }
+                },
+            };
+            if let (Some(cat_idx), Some(geo)) = (cat_idx, raw.to_geo(shape)) {
+                predictions.push(Prediction {
+                    geo,
+                    cat_idx,
+                    score: raw.score(),
+                });
+            }
+        }
+        self.get_annos_mut(file_path, shape)
+            .set_predictions(predictions);
+        skipped
+    }
+
+    /// The labels of the schema become the first labels in the order of the schema. Labels outside
+    /// the schema are removed if no annotation uses them. Returns the labels outside the schema
+    /// that are still in use.
+    pub fn import_label_schema(&mut self, schema: &LabelSchema) -> Vec<String> {
+        self.schema = Some(schema.clone());
+        let in_use = self.cat_idxs_in_use();
+        let outside_in_use = (0..self.labels.len())
+            .filter(|idx| in_use.contains(idx) && !schema.contains(&self.labels[*idx]))
+            .collect::<Vec<_>>();
+        let old_idx = |name: &str| self.labels.iter().position(|l| l == name);
+
+        // removed labels are not used by any annotation, hence we can map them anywhere
+        let mut idx_map = vec![0; self.labels.len()];
+        let mut labels = vec![];
+        let mut requested = vec![];
+        for schema_label in &schema.labels {
+            let old = old_idx(&schema_label.name);
+            if let Some(old) = old {
+                idx_map[old] = labels.len();
+            }
+            labels.push(schema_label.name.clone());
+            requested.push((schema_label.id, schema_label.color, old));
+            if !schema_label.attributes.is_empty() {
+                self.attribute_defs
+                    .insert(schema_label.name.clone(), schema_label.attributes.clone());
+            }
+        }
+        for old in &outside_in_use {
+            idx_map[*old] = labels.len();
+            labels.push(self.labels[*old].clone());
+            requested.push((None, None, Some(*old)));
+        }
+
+        // ids and colors of the schema take precedence over existing ones
+        let mut cat_ids = requested.iter().map(|(id, _, _)| *id).collect::<Vec<_>>();
+        let mut colors = requested.iter().map(|(_, clr, _)| *clr).collect::<Vec<_>>();
+        for (idx, (_, _, old)) in requested.iter().enumerate() {
+            if let Some(old) = old {
+                if cat_ids[idx].is_none() && !cat_ids.contains(&Some(self.cat_ids[*old])) {
+                    cat_ids[idx] = Some(self.cat_ids[*old]);
+                }
+                if colors[idx].is_none() && !colors.contains(&Some(self.colors[*old])) {
+                    colors[idx] = Some(self.colors[*old]);
+                }
+            }
+        }
+        for idx in 0..labels.len() {
+            if cat_ids[idx].is_none() {
+                let max_id = cat_ids.iter().flatten().max().copied().unwrap_or(0);
+                cat_ids[idx] = Some(max_id + 1);
+            }
+            if colors[idx].is_none() {
+                let taken = colors.iter().flatten().copied().collect::<Vec<_>>();
+                colors[idx] = Some(new_color(&taken));
+            }
+        }
+
+        let current_label = &self.labels[self.cat_idx_current];
+        self.cat_idx_current = if labels.contains(current_label) {
+            idx_map[self.cat_idx_current]
+        } else {
+            0
+        };
+        for (annos, _) in self.annotations_map.values_mut() {
+            annos.remap_cat_idxs(&idx_map);
+        }
+        self.pinned_labels
+            .retain(|_, pinned| labels.contains(pinned));
+        self.recent_labels.retain(|recent| labels.contains(recent));
+        self.attribute_defs
+            .retain(|label, _| labels.contains(label));
+        self.cat_ids = cat_ids.into_iter().flatten().collect();
+        self.colors = colors.into_iter().flatten().collect();
+        let outside_in_use = outside_in_use
+            .iter()
+            .map(|old| self.labels[*old].clone())
+            .collect();
+        self.labels = labels;
+        outside_in_use
+    }
+
     pub fn push(
         &mut self,
         label: String,
         color: Option<[u8; 3]>,
         cat_id: Option<u32>,
     ) -> RvResult<()> {
-        if self.labels.contains(&label) {
+        if self.locked {
+            Err(rverr!("labels are locked, cannot add '{}'", label))
+        } else if self.labels.contains(&label) {
             Err(rverr!("label '{}' already exists", label))
         } else {
             self.labels.push(label);
@@ -246,9 +725,222 @@ impl BboxSpecificData {
         }
     }
 
+    /// Adds the labels that do not exist yet in `self` and returns for each passed label its
+    /// index in `self`.
+    pub fn unify_labels(
+        &mut self,
+        labels: &[String],
+        colors: &[[u8; 3]],
+        cat_ids: &[u32],
+    ) -> RvResult<Vec<usize>> {
+        labels
+            .iter()
+            .zip(colors.iter())
+            .zip(cat_ids.iter())
+            .map(|((label, color), cat_id)| {
+                if let Some(idx) = self.labels.iter().position(|l| l == label) {
+                    Ok(idx)
+                } else {
+                    let color = (!self.colors.contains(color)).then_some(*color);
+                    let cat_id = (!self.cat_ids.contains(cat_id)).then_some(*cat_id);
+                    self.push(label.clone(), color, cat_id)?;
+                    Ok(self.len() - 1)
+                }
+            })
+            .collect()
+    }
+
+    /// Adds attribute definitions of labels that do not have any definitions yet.
+    pub fn unify_attribute_defs(&mut self, attribute_defs: &HashMap<String, Vec<AttrDef>>) {
+        for (label, defs) in attribute_defs {
+            if !self.attribute_defs.contains_key(label) {
+                self.attribute_defs.insert(label.clone(), defs.clone());
+            }
+        }
+    }
+
+    pub fn attribute_defs(&self) -> &HashMap<String, Vec<AttrDef>> {
+        &self.attribute_defs
+    }
+
+    pub fn attribute_defs_of(&self, cat_idx: usize) -> &[AttrDef] {
+        self.attribute_defs
+            .get(&self.labels[cat_idx])
+            .map(|defs| defs.as_slice())
+            .unwrap_or(&[])
+    }
+
+    pub fn add_attribute_def(&mut self, cat_idx: usize, def: AttrDef) -> RvResult<()> {
+        if def.name.is_empty() {
+            return Err(rverr!(
+                "attribute of label {} needs a name",
+                self.labels[cat_idx]
+            ));
+        }
+        let defs = self
+            .attribute_defs
+            .entry(self.labels[cat_idx].clone())
+            .or_default();
+        if defs.iter().any(|d| d.name == def.name) {
+            Err(rverr!("attribute '{}' already exists", def.name))
+        } else {
+            defs.push(def);
+            Ok(())
+        }
+    }
+
+    pub fn remove_attribute_def(&mut self, cat_idx: usize, name: &str) {
+        if let Some(defs) = self.attribute_defs.get_mut(&self.labels[cat_idx]) {
+            defs.retain(|d| d.name != name);
+        }
+    }
+
+    /// Attribute values of an annotation including the defaults of its label
+    pub fn effective_attributes(&self, cat_idx: usize, attributes: &Attributes) -> Attributes {
+        effective_attributes(self.attribute_defs_of(cat_idx), attributes)
+    }
+
+    /// Label index that is selected by the quick-select slot, e.g., a number key
+    pub fn quick_select_idx(&self, slot: usize) -> Option<usize> {
+        match self.pinned_labels.get(&slot) {
+            Some(label) => self.labels.iter().position(|l| l == label),
+            None => (slot < self.labels.len()).then_some(slot),
+        }
+    }
+
+    pub fn pinned_labels(&self) -> &BTreeMap<usize, String> {
+        &self.pinned_labels
+    }
+
+    pub fn pin_label(&mut self, slot: usize, cat_idx: usize) {
+        self.pinned_labels
+            .insert(slot, self.labels[cat_idx].clone());
+    }
+
+    pub fn unpin_label(&mut self, slot: usize) {
+        self.pinned_labels.remove(&slot);
+    }
+
+    /// Indices of the most recently used labels, the most recent one first
+    pub fn recent_label_idxs(&self) -> impl Iterator<Item = usize> + '_ {
+        self.recent_labels
+            .iter()
+            .filter_map(|recent| self.labels.iter().position(|l| l == recent))
+    }
+
+    /// Makes the label the current one and puts it on top of the most recently used labels.
+    pub fn set_cat_idx_current(&mut self, cat_idx: usize) {
+        let label = &self.labels[cat_idx];
+        self.recent_labels.retain(|recent| recent != label);
+        self.recent_labels.insert(0, label.clone());
+        self.recent_labels.truncate(MAX_RECENT_LABELS);
+        self.cat_idx_current = cat_idx;
+    }
+
+    /// Label indices whose labels fuzzy-match the query, the best match first
+    pub fn search_labels(&self, query: &str) -> Vec<usize> {
+        let mut matches = self
+            .labels
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, label)| fuzzy_score(query, label).map(|score| (idx, score)))
+            .collect::<Vec<_>>();
+        matches.sort_by_key(|(idx, score)| (-score, *idx));
+        matches.into_iter().map(|(idx, _)| idx).collect()
+    }
+
+    pub fn annotations_map(&self) -> &AnnotationsMap {
+        &self.annotations_map
+    }
+
+    pub fn image_reviews(&self) -> &HashMap<String, ImageReview> {
+        &self.image_reviews
+    }
+
+    pub fn image_review(&self, file_path: &str) -> Option<&ImageReview> {
+        self.image_reviews.get(file_path)
+    }
+
+    /// Images without review are to do.
+    pub fn image_status(&self, file_path: &str) -> ImageStatus {
+        self.image_review(file_path)
+            .map(|review| review.status)
+            .unwrap_or_default()
+    }
+
+    /// Reviews that equal the default are removed to keep the project file small.
+    pub fn set_image_review(&mut self, file_path: &str, review: ImageReview) {
+        if review.is_default() {
+            self.image_reviews.remove(file_path);
+        } else {
+            self.image_reviews.insert(file_path.to_string(), review);
+        }
+    }
+
+    pub fn set_image_status(&mut self, file_path: &str, status: ImageStatus) {
+        let review = ImageReview {
+            status,
+            ..self.image_review(file_path).cloned().unwrap_or_default()
+        };
+        self.set_image_review(file_path, review);
+    }
+
+    /// Smallest track id that is larger than all track ids in use, track ids start at 1.
+    pub fn next_track_id(&self) -> u32 {
+        self.anno_iter()
+            .flat_map(|(_, (annos, _))| annos.track_ids().iter().flatten())
+            .max()
+            .map(|id| id + 1)
+            .unwrap_or(1)
+    }
+
+    /// Moves annotations of files that are not among `file_paths` to the file path with the same
+    /// file name. Ambiguous file names and targets that are already annotated are skipped.
+    /// Returns the number of moved files.
+    pub fn match_by_filename(&mut self, file_paths: &[&str]) -> usize {
+        let file_name = |p: &str| p.rsplit(['/', '\\']).next().unwrap_or(p).to_string();
+        let mut by_name: HashMap<String, Vec<&str>> = HashMap::new();
+        for fp in file_paths {
+            by_name.entry(file_name(fp)).or_default().push(fp);
+        }
+        let existing = file_paths.iter().copied().collect::<HashSet<_>>();
+        let unmatched = self
+            .annotations_map
+            .keys()
+            .filter(|k| !existing.contains(k.as_str()))
+            .cloned()
+            .collect::<Vec<_>>();
+        let mut n_moved = 0;
+        for key in unmatched {
+            if let Some([target]) = by_name.get(&file_name(&key)).map(|v| v.as_slice()) {
+                if !self.annotations_map.contains_key(*target) {
+                    if let Some(annos) = self.annotations_map.remove(&key) {
+                        self.annotations_map.insert(target.to_string(), annos);
+                    }
+                    if let Some(review) = self.image_reviews.remove(&key) {
+                        self.image_reviews.insert(target.to_string(), review);
+                    }
+                    n_moved += 1;
+                }
+            }
+        }
+        n_moved
+    }
+
     pub fn retain_fileannos_in_folder(&mut self, folder: &str) {
-        self.annotations_map
-            .retain(|f, _| file_util::url_encode(f).starts_with(folder));
+        self.retain_fileannos(|f| file_util::url_encode(f).starts_with(folder));
+    }
+
+    pub fn retain_fileannos(&mut self, mut f: impl FnMut(&str) -> bool) {
+        self.annotations_map.retain(|file_path, _| f(file_path));
+    }
+
+    pub fn splits(&self) -> &HashMap<String, DataSplit> {
+        &self.splits
+    }
+
+    pub fn set_splits(&mut self, splits: HashMap<String, DataSplit>) {
+        self.splits = splits;
     }
 
     pub fn colors(&self) -> &Vec<[u8; 3]> {
@@ -282,6 +974,9 @@ impl BboxSpecificData {
             cat_ids,
             cat_idx_current: 0,
             annotations_map: HashMap::new(),
+            image_reviews: HashMap::new(),
+            splits: HashMap::new(),
+            split_cfg: SplitCfg::default(),
             clipboard: None,
             options: Options {
                 are_boxes_visible: true,
@@ -292,6 +987,16 @@ impl BboxSpecificData {
             } else {
                 CocoFile::default()
             },
+            attribute_defs: HashMap::new(),
+            new_attribute: AttrDef::default(),
+            pinned_labels: BTreeMap::new(),
+            locked: false,
+            schema: None,
+            schema_path: String::new(),
+            recent_labels: vec![],
+            palette: LabelPalette::default(),
+            label_edit: LabelEdit::default(),
+            current_file_path: None,
         }
     }
 
@@ -328,13 +1033,87 @@ pub struct BboxExportData {
     pub annotations: HashMap<String, (Vec<GeoFig>, Vec<usize>, Shape)>,
     pub coco_file: CocoFile,
     pub is_export_absolute: bool,
+    #[serde(default)]
+    pub attribute_defs: HashMap<String, Vec<AttrDef>>,
+    /// filename -> attributes of each annotation, only for files with attributes
+    #[serde(default)]
+    pub attributes: HashMap<String, Vec<Attributes>>,
+    #[serde(default)]
+    pub pinned_labels: BTreeMap<usize, String>,
+    #[serde(default)]
+    pub is_locked: bool,
+    #[serde(default)]
+    pub schema: Option<LabelSchema>,
+    /// filename -> score, source, and review status of each annotation, only for files with
+    /// other than manual unreviewed annotations
+    #[serde(default)]
+    pub provenances: HashMap<String, Vec<Provenance>>,
+    /// filename -> predictions that have neither been accepted nor rejected
+    #[serde(default)]
+    pub predictions: HashMap<String, Vec<Prediction>>,
+    /// filename -> review status and reviewer comment of the image
+    #[serde(default)]
+    pub image_reviews: HashMap<String, ImageReview>,
+    /// filename -> track id of each annotation, only for files with tracked annotations
+    #[serde(default)]
+    pub track_ids: HashMap<String, Vec<Option<u32>>>,
+    /// filename -> sub-pixel box of each annotation, only for files with imported sub-pixel boxes
+    #[serde(default)]
+    pub sub_pixel_bbs: HashMap<String, Vec<Option<BbF>>>,
+    /// filename -> split of the last split export
+    #[serde(default)]
+    pub splits: HashMap<String, DataSplit>,
+    #[serde(default)]
+    pub split_cfg: SplitCfg,
 }
 
 impl BboxExportData {
     pub fn from_bbox_data(mut bbox_specifics: BboxSpecificData) -> Self {
         let coco_file = bbox_specifics.coco_file.clone();
         let is_export_absolute = bbox_specifics.options.export_absolute;
+        let attributes = bbox_specifics
+            .anno_iter()
+            .filter(|(_, (annos, _))| annos.attributes().iter().any(|a| !a.is_empty()))
+            .map(|(filename, (annos, _))| (filename.clone(), annos.attributes().clone()))
+            .collect();
+        let provenances = bbox_specifics
+            .anno_iter()
+            .filter(|(_, (annos, _))| {
+                annos
+                    .provenances()
+                    .iter()
+                    .any(|p| *p != Provenance::default())
+            })
+            .map(|(filename, (annos, _))| (filename.clone(), annos.provenances().clone()))
+            .collect();
+        let track_ids = bbox_specifics
+            .anno_iter()
+            .filter(|(_, (annos, _))| annos.track_ids().iter().any(|t| t.is_some()))
+            .map(|(filename, (annos, _))| (filename.clone(), annos.track_ids().clone()))
+            .collect();
+        let sub_pixel_bbs = bbox_specifics
+            .anno_iter()
+            .filter(|(_, (annos, _))| annos.sub_pixel_bbs().iter().any(|bb| bb.is_some()))
+            .map(|(filename, (annos, _))| (filename.clone(), annos.sub_pixel_bbs().clone()))
+            .collect();
+        let predictions = bbox_specifics
+            .anno_iter()
+            .filter(|(_, (annos, _))| !annos.predictions().is_empty())
+            .map(|(filename, (annos, _))| (filename.clone(), annos.predictions().clone()))
+            .collect();
         BboxExportData {
+            provenances,
+            predictions,
+            track_ids,
+            sub_pixel_bbs,
+            image_reviews: mem::take(&mut bbox_specifics.image_reviews),
+            splits: mem::take(&mut bbox_specifics.splits),
+            split_cfg: mem::take(&mut bbox_specifics.split_cfg),
+            attribute_defs: mem::take(&mut bbox_specifics.attribute_defs),
+            attributes,
+            pinned_labels: mem::take(&mut bbox_specifics.pinned_labels),
+            is_locked: bbox_specifics.locked,
+            schema: mem::take(&mut bbox_specifics.schema),
             labels: mem::take(&mut bbox_specifics.labels),
             colors: mem::take(&mut bbox_specifics.colors),
             cat_ids: mem::take(&mut bbox_specifics.cat_ids),
@@ -368,3 +1147,251 @@ fn test_argmax() {
     ];
     assert_eq!(argmax_clr_dist(&picklist, &legacylist), [0, 0, 1]);
 }
+
+#[test]
+fn test_quick_select() -> RvResult<()> {
+    let mut data = BboxSpecificData::new();
+    for label in ["car", "traffic light", "person", "bicycle"] {
+        data.push(label.to_string(), None, None)?;
+    }
+    assert_eq!(data.quick_select_idx(1), Some(1));
+    assert_eq!(data.quick_select_idx(5), None);
+    data.pin_label(1, 4);
+    data.pin_label(8, 2);
+    assert_eq!(data.quick_select_idx(1), Some(4));
+    assert_eq!(data.quick_select_idx(8), Some(2));
+    data.set_cat_idx_current(2);
+    data.set_cat_idx_current(4);
+    data.set_cat_idx_current(2);
+    assert_eq!(data.recent_label_idxs().collect::<Vec<_>>(), vec![2, 4]);
+    // pins and recent labels follow the labels when indices shift
+    data.remove_catidx(1);
+    assert_eq!(data.quick_select_idx(1), Some(3));
+    assert_eq!(data.recent_label_idxs().collect::<Vec<_>>(), vec![1, 3]);
+    data.remove_catidx(3);
+    assert_eq!(data.quick_select_idx(1), Some(1));
+    assert_eq!(data.recent_label_idxs().collect::<Vec<_>>(), vec![1]);
+    assert_eq!(data.search_labels("lig"), vec![1]);
+    assert_eq!(data.search_labels("per")[0], 2);
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::domain::BB;
+
+#[test]
+fn test_label_management() -> RvResult<()> {
+    let mut data = BboxSpecificData::new();
+    for label in ["car", "truck", "person"] {
+        data.push(label.to_string(), None, None)?;
+    }
+    let shape = Shape::new(100, 100);
+    let bb = |x| BB::from_arr(&[x, x, 5, 5]);
+    let annos = data.get_annos_mut("a.png", shape);
+    annos.add_bb(bb(0), 1);
+    annos.add_bb(bb(10), 2);
+    annos.add_bb(bb(20), 3);
+    data.cat_idx_current = 3;
+    data.pin_label(0, 2);
+
+    data.rename_label(2, "lorry".to_string())?;
+    assert!(data.rename_label(2, "car".to_string()).is_err());
+    assert!(data.rename_label(2, "".to_string()).is_err());
+    assert_eq!(data.quick_select_idx(0), Some(2));
+
+    data.move_label(3, 0)?;
+    assert_eq!(
+        data.labels(),
+        &vec!["person", "foreground", "car", "lorry"]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(data.get_annos("a.png").unwrap().cat_idxs(), &vec![2, 3, 0]);
+    assert_eq!(data.cat_idx_current, 0);
+    assert_eq!(data.quick_select_idx(0), Some(3));
+
+    // merge lorry into car
+    data.merge_labels(3, 2)?;
+    assert_eq!(data.labels().len(), 3);
+    assert_eq!(data.get_annos("a.png").unwrap().cat_idxs(), &vec![2, 2, 0]);
+    assert_eq!(data.quick_select_idx(0), Some(2));
+    assert!(data.merge_labels(1, 1).is_err());
+    data.merge_labels(0, 2)?;
+    assert_eq!(data.get_annos("a.png").unwrap().cat_idxs(), &vec![1, 1, 1]);
+    assert_eq!(data.cat_idx_current, 1);
+
+    let color = data.colors()[0];
+    assert!(data.set_color(1, color).is_err());
+    data.set_color(1, [1, 2, 3])?;
+    assert_eq!(data.colors()[1], [1, 2, 3]);
+    let cat_id = data.cat_ids()[0];
+    assert!(data.set_cat_id(1, cat_id).is_err());
+    data.set_cat_id(1, 42)?;
+    assert_eq!(data.cat_ids()[1], 42);
+    Ok(())
+}
+
+#[cfg(test)]
+use crate::tools_data::label_schema::SchemaLabel;
+
+#[test]
+fn test_import_schema() -> RvResult<()> {
+    let mut data = BboxSpecificData::new();
+    for label in ["car", "tree", "person"] {
+        data.push(label.to_string(), None, None)?;
+    }
+    let shape = Shape::new(100, 100);
+    let annos = data.get_annos_mut("a.png", shape);
+    annos.add_bb(BB::from_arr(&[0, 0, 5, 5]), 2);
+    annos.add_bb(BB::from_arr(&[10, 10, 5, 5]), 3);
+    data.cat_idx_current = 3;
+    let person_color = data.colors()[3];
+    let schema = LabelSchema {
+        labels: vec![
+            SchemaLabel {
+                name: "person".to_string(),
+                id: Some(1),
+                ..Default::default()
+            },
+            SchemaLabel {
+                name: "car".to_string(),
+                color: Some(person_color),
+                ..Default::default()
+            },
+            SchemaLabel {
+                name: "bicycle".to_string(),
+                ..Default::default()
+            },
+        ],
+    };
+    let outside = data.import_label_schema(&schema);
+    // foreground is unused and removed, tree is used and kept
+    assert_eq!(outside, vec!["tree".to_string()]);
+    assert_eq!(
+        data.labels(),
+        &vec!["person", "car", "bicycle", "tree"]
+            .into_iter()
+            .map(String::from)
+            .collect::<Vec<_>>()
+    );
+    assert_eq!(data.get_annos("a.png").unwrap().cat_idxs(), &vec![3, 0]);
+    assert_eq!(data.cat_idx_current, 0);
+    assert_eq!(data.cat_ids()[0], 1);
+    assert_eq!(data.colors()[1], person_color);
+    let unique = |v: Vec<String>| v.iter().collect::<HashSet<_>>().len() == v.len();
+    assert!(unique(
+        data.cat_ids().iter().map(|id| id.to_string()).collect()
+    ));
+    assert!(unique(
+        data.colors().iter().map(|c| format!("{c:?}")).collect()
+    ));
+
+    data.set_locked(true);
+    assert!(data.push("bus".to_string(), None, None).is_err());
+    assert!(data.rename_label(0, "human".to_string()).is_err());
+    let export = BboxExportData::from_bbox_data(data.clone());
+    let data_back = BboxSpecificData::from_bbox_export_data(export)?;
+    assert!(data_back.is_locked());
+    assert_eq!(data_back.schema(), Some(&schema));
+    Ok(())
+}
+
+#[test]
+fn test_set_predictions() -> RvResult<()> {
+    let mut data = BboxSpecificData::new();
+    data.push("car".to_string(), None, None)?;
+    let predictions = crate::inference::parse_response(
+        r#"[
+            {"label": "car", "score": 0.9, "bbox": [0, 0, 10, 10]},
+            {"label": "person", "score": 0.7, "bbox": [5, 5, 10, 10]},
+            {"label": "car", "score": 0.2}
+        ]"#,
+    )?;
+    let shape = Shape::new(100, 100);
+    let skipped = data.set_predictions("a.png", shape, &predictions);
+    assert!(skipped.is_empty());
+    let person_idx = data.labels().iter().position(|l| l == "person").unwrap();
+    let annos = data.get_annos("a.png").unwrap();
+    assert_eq!(annos.predictions().len(), 2);
+    assert_eq!(annos.predictions()[1].cat_idx, person_idx);
+    assert!(data.cat_idxs_in_use().contains(&person_idx));
+
+    // unknown labels are skipped if the labels are locked
+    data.remove_catidx(person_idx);
+    data.set_locked(true);
+    let skipped = data.set_predictions("a.png", shape, &predictions);
+    assert_eq!(skipped, vec!["person".to_string()]);
+    assert_eq!(data.get_annos("a.png").unwrap().predictions().len(), 1);
+    Ok(())
+}
+
+#[test]
+fn test_image_reviews() -> RvResult<()> {
+    let mut data = BboxSpecificData::new();
+    assert_eq!(data.image_status("a.png"), ImageStatus::Todo);
+    data.set_image_status("a.png", ImageStatus::NeedsFix);
+    data.set_image_review(
+        "b.png",
+        ImageReview {
+            status: ImageStatus::Done,
+            comment: "fine".to_string(),
+        },
+    );
+    data.set_image_status("b.png", ImageStatus::InProgress);
+    assert_eq!(data.image_status("a.png"), ImageStatus::NeedsFix);
+    assert_eq!(data.image_review("b.png").unwrap().comment, "fine");
+    assert_eq!(data.image_status("b.png"), ImageStatus::InProgress);
+    // reviews without information are not stored
+    data.set_image_status("a.png", ImageStatus::Todo);
+    assert!(data.image_review("a.png").is_none());
+    let data_back =
+        BboxSpecificData::from_bbox_export_data(BboxExportData::from_bbox_data(data.clone()))?;
+    assert_eq!(data_back.image_reviews(), data.image_reviews());
+    Ok(())
+}
+
+#[test]
+fn test_next_track_id() -> RvResult<()> {
+    let mut data = BboxSpecificData::new();
+    assert_eq!(data.next_track_id(), 1);
+    let shape = Shape::new(100, 100);
+    let annos = data.get_annos_mut("a.png", shape);
+    annos.add_tracked_geo(GeoFig::BB(BB::from_arr(&[0, 0, 10, 10])), 0, 3);
+    annos.add_bb(BB::from_arr(&[20, 20, 10, 10]), 0);
+    data.get_annos_mut("b.png", shape).add_tracked_geo(
+        GeoFig::BB(BB::from_arr(&[0, 0, 10, 10])),
+        0,
+        7,
+    );
+    assert_eq!(data.next_track_id(), 8);
+    let data_back =
+        BboxSpecificData::from_bbox_export_data(BboxExportData::from_bbox_data(data.clone()))?;
+    assert_eq!(
+        data_back.get_annos("a.png").unwrap().track_ids(),
+        &vec![Some(3), None]
+    );
+    assert_eq!(data_back.next_track_id(), 8);
+    Ok(())
+}
+
+#[test]
+fn test_match_by_filename() {
+    let mut data = BboxSpecificData::new();
+    let shape = Shape::new(100, 100);
+    let bb = BB::from_arr(&[0, 0, 10, 10]);
+    data.get_annos_mut("/old/a.png", shape).add_bb(bb, 0);
+    data.get_annos_mut("/old/b.png", shape).add_bb(bb, 0);
+    data.get_annos_mut("/old/c.png", shape).add_bb(bb, 0);
+    data.get_annos_mut("/new/c.png", shape).add_bb(bb, 0);
+    data.set_image_status("/old/a.png", ImageStatus::Done);
+    let n_moved =
+        data.match_by_filename(&["/new/a.png", "/new/b.png", "/new/sub/b.png", "/new/c.png"]);
+    assert_eq!(n_moved, 1);
+    assert!(data.get_annos("/new/a.png").is_some());
+    assert!(data.get_annos("/old/a.png").is_none());
+    assert!(data.image_review("/new/a.png").is_some());
+    // ambiguous or already annotated targets are not touched
+    assert!(data.get_annos("/old/b.png").is_some());
+    assert!(data.get_annos("/old/c.png").is_some());
+}
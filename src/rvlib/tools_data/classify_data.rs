@@ -0,0 +1,226 @@
+use std::{borrow::Cow, collections::HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    result::{to_rv, RvResult},
+    rverr,
+};
+
+/// filename -> tags of the image
+pub type TagsMap = HashMap<String, Vec<String>>;
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TagsExportFormat {
+    #[default]
+    Csv,
+    Json,
+}
+impl TagsExportFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Csv => "csv",
+            Self::Json => "json",
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    /// several tags per image, otherwise a tag replaces the previous one
+    pub is_multi_tag: bool,
+    /// move to the next image after a tag has been assigned via number key
+    pub is_auto_next: bool,
+    pub export_format: TagsExportFormat,
+    #[serde(skip)]
+    pub is_export_triggered: bool,
+    #[serde(skip)]
+    pub is_next_image_triggered: bool,
+}
+
+#[derive(Serialize)]
+struct TagsExport<'a> {
+    tags: &'a [String],
+    images: &'a TagsMap,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ClassifyToolData {
+    tags: Vec<String>,
+    tags_map: TagsMap,
+    pub options: Options,
+    #[serde(skip)]
+    pub new_tag: String,
+    /// image that is currently shown, needed by the menu
+    #[serde(skip)]
+    pub current_file_path: Option<String>,
+}
+
+impl ClassifyToolData {
+    pub fn tags(&self) -> &Vec<String> {
+        &self.tags
+    }
+
+    pub fn push(&mut self, tag: String) -> RvResult<()> {
+        if tag.is_empty() {
+            Err(rverr!("tag '{}' is empty", tag))
+        } else if self.tags.contains(&tag) {
+            Err(rverr!("tag '{}' already exists", tag))
+        } else {
+            self.tags.push(tag);
+            Ok(())
+        }
+    }
+
+    /// Appends the tags that we do not know yet.
+    pub fn unify_tags(&mut self, tags: &[String]) {
+        for tag in tags {
+            if !self.tags.contains(tag) {
+                self.tags.push(tag.clone());
+            }
+        }
+    }
+
+    pub fn tags_map(&self) -> &TagsMap {
+        &self.tags_map
+    }
+
+    /// The tags of the images need to be among [`Self::tags`].
+    pub(crate) fn tags_map_mut(&mut self) -> &mut TagsMap {
+        &mut self.tags_map
+    }
+
+    pub fn remove_tag(&mut self, tag_idx: usize) {
+        let tag = self.tags.remove(tag_idx);
+        for tags in self.tags_map.values_mut() {
+            tags.retain(|t| t != &tag);
+        }
+        self.tags_map.retain(|_, tags| !tags.is_empty());
+    }
+
+    pub fn get_tags(&self, file_path: &str) -> Option<&Vec<String>> {
+        self.tags_map.get(file_path)
+    }
+
+    pub fn has_tag(&self, file_path: &str, tag_idx: usize) -> bool {
+        self.get_tags(file_path)
+            .map(|tags| tags.contains(&self.tags[tag_idx]))
+            .unwrap_or(false)
+    }
+
+    /// In multi-tag mode the tag is toggled, else it replaces the tags of the image.
+    pub fn apply_tag(&mut self, file_path: &str, tag_idx: usize) {
+        let tag = &self.tags[tag_idx];
+        let tags = self.tags_map.entry(file_path.to_string()).or_default();
+        if !self.options.is_multi_tag {
+            *tags = vec![tag.clone()];
+        } else if let Some(pos) = tags.iter().position(|t| t == tag) {
+            tags.remove(pos);
+        } else {
+            tags.push(tag.clone());
+            // keep the order of the tag set
+            tags.sort_by_key(|t| self.tags.iter().position(|t_| t_ == t));
+        }
+        if tags.is_empty() {
+            self.tags_map.remove(file_path);
+        }
+    }
+
+    pub fn n_tagged_images(&self) -> usize {
+        self.tags_map.len()
+    }
+
+    /// One row per tagged image with one column per tag that is either 0 or 1. Fields are quoted
+    /// according to RFC 4180 if necessary.
+    pub fn to_csv(&self) -> String {
+        let header = std::iter::once("file_path")
+            .chain(self.tags.iter().map(|t| t.as_str()))
+            .map(csv_field)
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut file_paths = self.tags_map.keys().collect::<Vec<_>>();
+        file_paths.sort();
+        let rows = file_paths.into_iter().map(|file_path| {
+            let image_tags = &self.tags_map[file_path];
+            let cols = self
+                .tags
+                .iter()
+                .map(|t| if image_tags.contains(t) { "1" } else { "0" });
+            std::iter::once(csv_field(file_path))
+                .chain(cols.map(|c| c.into()))
+                .collect::<Vec<_>>()
+                .join(",")
+        });
+        std::iter::once(header)
+            .chain(rows)
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    pub fn to_json(&self) -> RvResult<String> {
+        let export = TagsExport {
+            tags: &self.tags,
+            images: &self.tags_map,
+        };
+        serde_json::to_string(&export).map_err(to_rv)
+    }
+}
+
+fn csv_field(field: &str) -> Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        Cow::Owned(format!("\"{}\"", field.replace('"', "\"\"")))
+    } else {
+        Cow::Borrowed(field)
+    }
+}
+
+#[test]
+fn test_csv() -> RvResult<()> {
+    let mut data = ClassifyToolData::default();
+    data.push("day, sunny".to_string())?;
+    data.push("night".to_string())?;
+    data.apply_tag("a,b/\"c\".png", 0);
+    assert_eq!(
+        data.to_csv(),
+        "file_path,\"day, sunny\",night\n\"a,b/\"\"c\"\".png\",1,0"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_tags() -> RvResult<()> {
+    let mut data = ClassifyToolData::default();
+    data.push("day".to_string())?;
+    data.push("night".to_string())?;
+    data.push("blurry".to_string())?;
+    assert!(data.push("day".to_string()).is_err());
+    data.apply_tag("a.png", 0);
+    data.apply_tag("a.png", 1);
+    assert_eq!(data.get_tags("a.png"), Some(&vec!["night".to_string()]));
+    data.options.is_multi_tag = true;
+    data.apply_tag("a.png", 2);
+    data.apply_tag("a.png", 0);
+    assert_eq!(
+        data.get_tags("a.png"),
+        Some(&vec![
+            "day".to_string(),
+            "night".to_string(),
+            "blurry".to_string()
+        ])
+    );
+    data.apply_tag("b.png", 2);
+    assert_eq!(
+        data.to_csv(),
+        "file_path,day,night,blurry\na.png,1,1,1\nb.png,0,0,1"
+    );
+    data.remove_tag(2);
+    assert!(data.get_tags("b.png").is_none());
+    data.apply_tag("a.png", 1);
+    assert!(data.has_tag("a.png", 0));
+    assert!(!data.has_tag("a.png", 1));
+    assert_eq!(
+        data.to_json()?,
+        r#"{"tags":["day","night"],"images":{"a.png":["day"]}}"#
+    );
+    Ok(())
+}
@@ -1,24 +1,42 @@
 use std::{
-    collections::HashMap,
-    fmt::Debug,
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::{Debug, Display},
     path::{Path, PathBuf},
 };
 
 use serde::{Deserialize, Serialize};
 
 use crate::{
-    cfg::{CocoFile, CocoFileConnection},
-    domain::{Shape, BB},
+    annotations::{
+        effective_attributes, enclosing_bb, n_labeled, AnnoSource, AttrDef, Attributes, Provenance,
+        ReviewStatus,
+    },
+    cfg::{CocoFile, CocoFileConnection, PrefixMapping},
+    domain::{BbF, PtI, Shape, BB},
     file_util::{self, path_to_str, MetaData},
     result::{to_rv, RvError, RvResult},
-    rverr, ssh, GeoFig,
+    rverr, ssh, GeoFig, Polygon,
+};
+
+use super::{
+    bbox_data::new_random_colors, data_split::SplitCfg, BboxExportData, BboxSpecificData,
+    KeypointToolData,
 };
 
-use super::{bbox_data::new_random_colors, BboxExportData, BboxSpecificData};
+/// Coordinates of boxes and segmentations are in pixels or relative to the image size.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum CoordinateMode {
+    Absolute,
+    Relative,
+}
 
 #[derive(Serialize, Deserialize, Debug)]
 struct CocoInfo {
     description: String,
+    /// not part of Coco, files without mode are relative if no coordinate is larger than 1
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    coordinate_mode: Option<CoordinateMode>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -33,6 +51,16 @@ struct CocoImage {
 struct CocoBboxCategory {
     id: u32,
     name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    attributes: Vec<AttrDef>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keypoints: Option<Vec<String>>,
+    /// 1-based indices of the keypoints
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    skeleton: Option<Vec<[usize; 2]>>,
+    /// not part of Coco, color of the label in RV Image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    color: Option<[u8; 3]>,
 }
 
 #[derive(Serialize, Deserialize, Debug)]
@@ -40,11 +68,31 @@ struct CocoAnnotation {
     id: u32,
     image_id: u32,
     category_id: u32,
-    bbox: [f32; 4],
-    segmentation: Option<Vec<Vec<f32>>>,
-    area: Option<f32>,
+    bbox: [f64; 4],
+    segmentation: Option<Vec<Vec<f64>>>,
+    area: Option<f64>,
+    #[serde(default, skip_serializing_if = "Attributes::is_empty")]
+    attributes: Attributes,
+    /// triplets of x, y, and visibility
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    keypoints: Option<Vec<f32>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    num_keypoints: Option<usize>,
+    /// confidence of a model as in Coco result files
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    score: Option<f32>,
+    /// not part of Coco, where the annotation comes from, missing in files from other tools
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    source: Option<AnnoSource>,
+    /// not part of Coco
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    review: Option<ReviewStatus>,
+    /// not part of Coco, identity of the object across frames as in the TAO and MOT datasets
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    track_id: Option<u32>,
 }
 
+#[cfg(test)]
 fn colors_to_string(colors: &[[u8; 3]]) -> Option<String> {
     colors
         .iter()
@@ -52,6 +100,7 @@ fn colors_to_string(colors: &[[u8; 3]]) -> Option<String> {
         .reduce(|s1, s2| format!("{s1}_{s2}"))
 }
 
+/// Older versions of RV Image stored the colors in the description of the info
 fn string_to_colors(s: &str) -> RvResult<Vec<[u8; 3]>> {
     let make_err = || rverr!("cannot convert str {} to rgb", s);
     s.trim()
@@ -69,6 +118,170 @@ fn string_to_colors(s: &str) -> RvResult<Vec<[u8; 3]>> {
         })
         .collect::<RvResult<Vec<[u8; 3]>>>()
 }
+fn is_separator(c: char) -> bool {
+    c == '/' || c == '\\'
+}
+
+/// Separator used by a folder, local Windows folders use backslashes, all others slashes
+fn separator_of(folder: &str) -> char {
+    if folder.contains('\\') && !folder.contains('/') {
+        '\\'
+    } else {
+        '/'
+    }
+}
+
+fn is_absolute_path(file_path: &str) -> bool {
+    let mut chars = file_path.chars();
+    file_path.starts_with(is_separator)
+        || file_path.contains("://")
+        || (chars.next().map(|c| c.is_ascii_alphabetic()) == Some(true)
+            && chars.next() == Some(':'))
+}
+
+/// Images inside the opened folder are referenced relative to the folder with `/` as separator
+/// such that the dataset can be moved, e.g., from a local drive to an SSH server.
+fn to_relative_path(file_path: &str, opened_folder: Option<&str>) -> String {
+    opened_folder
+        .and_then(|folder| file_path.strip_prefix(folder.trim_end_matches(is_separator)))
+        .and_then(|rest| rest.strip_prefix(is_separator))
+        .map(|rest| rest.replace('\\', "/"))
+        .unwrap_or_else(|| file_path.to_string())
+}
+
+/// Relative paths are interpreted relative to the opened folder, the prefixes of absolute paths
+/// are replaced according to the prefix map.
+fn to_image_path(
+    file_name: &str,
+    opened_folder: Option<&str>,
+    prefix_map: &[PrefixMapping],
+) -> String {
+    let join = |folder: &str, rest: &str| {
+        let sep = separator_of(folder);
+        let rest = rest
+            .trim_start_matches(is_separator)
+            .replace(is_separator, &sep.to_string());
+        format!("{}{sep}{rest}", folder.trim_end_matches(is_separator))
+    };
+    if is_absolute_path(file_name) {
+        prefix_map
+            .iter()
+            .find_map(|mapping| {
+                file_name
+                    .strip_prefix(mapping.from.as_str())
+                    .map(|rest| join(&mapping.to, rest))
+            })
+            .unwrap_or_else(|| file_name.to_string())
+    } else if let Some(folder) = opened_folder {
+        join(folder, file_name)
+    } else {
+        file_name.to_string()
+    }
+}
+
+/// The imported sub-pixel box if the geometry has not been changed since the import
+fn sub_pixel_bb(geo: &GeoFig, imported: Option<BbF>) -> BbF {
+    let bb = geo.enclosing_bb();
+    imported
+        .filter(|bb_f| bb_f.round() == bb)
+        .unwrap_or_else(|| BbF::from(bb))
+}
+
+/// Vertices as flat list `[x1, y1, x2, y2, ...]`, boxes are represented by the four corners of
+/// their sub-pixel box `bb_f`
+fn segmentation(geo: &GeoFig, bb_f: BbF, imw: f64, imh: f64) -> Vec<f64> {
+    match geo {
+        GeoFig::BB(_) => {
+            let BbF { x, y, w, h } = bb_f;
+            vec![x, y, x + w, y, x + w, y + h, x, y + h]
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| if i % 2 == 0 { v / imw } else { v / imh })
+                .collect()
+        }
+        GeoFig::Poly(poly) => poly
+            .points_iter()
+            .flat_map(|p| [p.x as f64 / imw, p.y as f64 / imh])
+            .collect(),
+    }
+}
+
+/// Area in pixels
+fn area(geo: &GeoFig, bb_f: BbF) -> f64 {
+    match geo {
+        GeoFig::BB(_) => bb_f.w * bb_f.h,
+        GeoFig::Poly(poly) => poly.area(),
+    }
+}
+
+/// Segmentations that are axis aligned rectangles are boxes. Otherwise, the part of the
+/// segmentation with the most vertices becomes a polygon if it fits the box of the annotation.
+fn segmentation_to_geo(
+    segmentation: Option<&Vec<Vec<f64>>>,
+    bb: BB,
+    w_factor: f64,
+    h_factor: f64,
+) -> GeoFig {
+    let part = segmentation
+        .and_then(|parts| parts.iter().max_by_key(|part| part.len()))
+        .filter(|part| part.len() >= 6);
+    let points = part.map(|part| {
+        part.chunks_exact(2)
+            .map(|xy| PtI {
+                x: (w_factor * xy[0]).round().max(0.0) as u32,
+                y: (h_factor * xy[1]).round().max(0.0) as u32,
+            })
+            .collect::<Vec<_>>()
+    });
+    let is_rectangle = |points: &[PtI]| {
+        let mut xs = points.iter().map(|p| p.x).collect::<Vec<_>>();
+        let mut ys = points.iter().map(|p| p.y).collect::<Vec<_>>();
+        xs.sort_unstable();
+        xs.dedup();
+        ys.sort_unstable();
+        ys.dedup();
+        points.len() == 4
+            && xs.len() == 2
+            && ys.len() == 2
+            && xs
+                .iter()
+                .all(|x| points.iter().filter(|p| p.x == *x).count() == 2)
+            && ys
+                .iter()
+                .all(|y| points.iter().filter(|p| p.y == *y).count() == 2)
+    };
+    let fits_bb = |poly: &Polygon| {
+        let enclosing = poly.enclosing_bb();
+        enclosing.x.abs_diff(bb.x) <= 1
+            && enclosing.y.abs_diff(bb.y) <= 1
+            && enclosing.w.abs_diff(bb.w) <= 1
+            && enclosing.h.abs_diff(bb.h) <= 1
+    };
+    match points {
+        Some(points) if !is_rectangle(&points) => Polygon::from_vec(points, false)
+            .ok()
+            .filter(fits_bb)
+            .map(GeoFig::Poly)
+            .unwrap_or(GeoFig::BB(bb)),
+        _ => GeoFig::BB(bb),
+    }
+}
+
+/// Problem of a Coco file at a location like `annotations[3].bbox`. Errors prevent the import.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct CocoIssue {
+    pub location: String,
+    pub message: String,
+    pub is_error: bool,
+}
+impl Display for CocoIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.location, self.message)
+    }
+}
+
+const INFO_DESCRIPTION: &str = "created with Rvimage, https://github.com/bertiqwerty/rvimage";
+
 #[derive(Serialize, Deserialize, Debug)]
 struct CocoExportData {
     info: CocoInfo,
@@ -77,18 +290,16 @@ struct CocoExportData {
     categories: Vec<CocoBboxCategory>,
 }
 impl CocoExportData {
-    fn from_coco(bbox_specifics: BboxSpecificData) -> RvResult<Self> {
-        let color_str = if let Some(s) = colors_to_string(bbox_specifics.colors()) {
-            format!(", {s}")
-        } else {
-            "".to_string()
-        };
-        let info_str =
-            format!("created with Rvimage, https://github.com/bertiqwerty/rvimage{color_str}");
+    fn from_coco(bbox_specifics: BboxSpecificData, opened_folder: Option<&str>) -> RvResult<Self> {
+        let export_data = BboxExportData::from_bbox_data(bbox_specifics);
         let info = CocoInfo {
-            description: info_str,
+            description: INFO_DESCRIPTION.to_string(),
+            coordinate_mode: Some(if export_data.is_export_absolute {
+                CoordinateMode::Absolute
+            } else {
+                CoordinateMode::Relative
+            }),
         };
-        let export_data = BboxExportData::from_bbox_data(bbox_specifics);
 
         type AnnotationMapValue<'a> = (&'a String, &'a (Vec<GeoFig>, Vec<usize>, Shape));
         let make_image_map = |(idx, (file_path, (_, _, shape))): (usize, AnnotationMapValue)| {
@@ -96,7 +307,7 @@ impl CocoExportData {
                 id: idx as u32,
                 width: shape.w,
                 height: shape.h,
-                file_name: file_path.clone(),
+                file_name: to_relative_path(file_path, opened_folder),
             })
         };
         let images = export_data
@@ -110,54 +321,80 @@ impl CocoExportData {
             .labels
             .iter()
             .zip(export_data.cat_ids.iter())
-            .map(|(label, cat_id)| CocoBboxCategory {
+            .zip(export_data.colors.iter())
+            .map(|((label, cat_id), color)| CocoBboxCategory {
                 id: *cat_id,
                 name: label.clone(),
+                attributes: export_data
+                    .attribute_defs
+                    .get(label)
+                    .cloned()
+                    .unwrap_or_default(),
+                keypoints: None,
+                skeleton: None,
+                color: Some(*color),
             })
             .collect::<Vec<_>>();
 
         let mut box_id = 0;
+        let no_attributes = Attributes::new();
         let make_anno_map =
-            |(image_idx, (bbs, cat_idxs, shape)): (usize, &(Vec<GeoFig>, Vec<usize>, Shape))| {
+            |(image_idx, (file_path, (bbs, cat_idxs, shape))): (usize, AnnotationMapValue)| {
+                let attributes = export_data.attributes.get(file_path);
+                let provenances = export_data.provenances.get(file_path);
+                let track_ids = export_data.track_ids.get(file_path);
+                let sub_pixel_bbs = export_data.sub_pixel_bbs.get(file_path);
                 bbs.iter()
                     .zip(cat_idxs.iter())
-                    .map(|(geo, cat_idx): (&GeoFig, &usize)| {
-                        let bb = geo.enclosing_bb();
+                    .enumerate()
+                    .map(|(geo_idx, (geo, cat_idx)): (usize, (&GeoFig, &usize))| {
                         let (imw, imh) = if export_data.is_export_absolute {
                             (1.0, 1.0)
                         } else {
-                            (shape.w as f32, shape.h as f32)
+                            (shape.w as f64, shape.h as f64)
                         };
-                        let bb_f = [
-                            bb.x as f32 / imw,
-                            bb.y as f32 / imh,
-                            bb.w as f32 / imw,
-                            bb.h as f32 / imh,
-                        ];
+                        let bb_f = sub_pixel_bb(
+                            geo,
+                            sub_pixel_bbs
+                                .and_then(|bbs| bbs.get(geo_idx))
+                                .copied()
+                                .flatten(),
+                        );
                         box_id += 1;
+                        let provenance = provenances
+                            .and_then(|p| p.get(geo_idx))
+                            .copied()
+                            .unwrap_or_default();
                         CocoAnnotation {
                             id: box_id - 1,
                             image_id: image_idx as u32,
                             category_id: export_data.cat_ids[*cat_idx],
-                            bbox: bb_f,
-                            segmentation: Some(vec![vec![
-                                bb_f[0] / imw,
-                                bb_f[1] / imh,
-                                (bb_f[0] + bb_f[2]) / imw,
-                                bb_f[1] / imh,
-                                (bb_f[0] + bb_f[2]) / imw,
-                                (bb_f[1] + bb_f[3]) / imh,
-                                bb_f[0] / imw,
-                                (bb_f[1] + bb_f[3]) / imh,
-                            ]]),
-                            area: Some((bb.h * bb.w) as f32),
+                            bbox: bb_f.scale(1.0 / imw, 1.0 / imh).to_arr(),
+                            segmentation: Some(vec![segmentation(geo, bb_f, imw, imh)]),
+                            area: Some(area(geo, bb_f)),
+                            attributes: effective_attributes(
+                                export_data
+                                    .attribute_defs
+                                    .get(&export_data.labels[*cat_idx])
+                                    .map(|defs| defs.as_slice())
+                                    .unwrap_or(&[]),
+                                attributes
+                                    .and_then(|a| a.get(geo_idx))
+                                    .unwrap_or(&no_attributes),
+                            ),
+                            keypoints: None,
+                            num_keypoints: None,
+                            score: provenance.score,
+                            source: Some(provenance.source),
+                            review: Some(provenance.review),
+                            track_id: track_ids.and_then(|t| t.get(geo_idx)).copied().flatten(),
                         }
                     })
                     .collect::<Vec<_>>()
             };
         let annotations = export_data
             .annotations
-            .values()
+            .iter()
             .enumerate()
             .flat_map(make_anno_map)
             .collect::<Vec<_>>();
@@ -170,52 +407,272 @@ impl CocoExportData {
         })
     }
 
-    fn convert_to_bboxdata(self, coco_file: CocoFile) -> RvResult<BboxSpecificData> {
+    fn from_keypoint_data(keypoint_data: &KeypointToolData, opened_folder: Option<&str>) -> Self {
+        let info = CocoInfo {
+            description: INFO_DESCRIPTION.to_string(),
+            coordinate_mode: Some(CoordinateMode::Absolute),
+        };
+        let mut annos = keypoint_data.anno_iter().collect::<Vec<_>>();
+        annos.sort_by_key(|(file_path, _)| *file_path);
+        let images = annos
+            .iter()
+            .enumerate()
+            .map(|(idx, (file_path, (_, shape)))| CocoImage {
+                id: idx as u32,
+                width: shape.w,
+                height: shape.h,
+                file_name: to_relative_path(file_path, opened_folder),
+            })
+            .collect::<Vec<_>>();
+        let skeleton = keypoint_data.skeleton();
+        let categories = vec![CocoBboxCategory {
+            id: 1,
+            name: keypoint_data.label.clone(),
+            attributes: vec![],
+            keypoints: Some(skeleton.keypoint_names().clone()),
+            skeleton: Some(
+                skeleton
+                    .edges()
+                    .iter()
+                    .map(|(from, to)| [from + 1, to + 1])
+                    .collect(),
+            ),
+            color: Some(keypoint_data.color),
+        }];
+        let annotations = annos
+            .iter()
+            .enumerate()
+            .flat_map(|(image_idx, (_, (annos, _)))| {
+                annos.instances().iter().filter_map(move |instance| {
+                    enclosing_bb(instance).map(|bb| (image_idx, instance, bb))
+                })
+            })
+            .enumerate()
+            .map(|(anno_id, (image_idx, instance, bb))| CocoAnnotation {
+                id: anno_id as u32,
+                image_id: image_idx as u32,
+                category_id: 1,
+                bbox: BbF::from(bb).to_arr(),
+                segmentation: None,
+                area: Some((bb.w * bb.h) as f64),
+                attributes: Attributes::new(),
+                keypoints: Some(
+                    instance
+                        .iter()
+                        .flat_map(|kp| {
+                            if kp.is_labeled() {
+                                [
+                                    kp.pos.x as f32,
+                                    kp.pos.y as f32,
+                                    kp.visibility.to_coco() as f32,
+                                ]
+                            } else {
+                                [0.0; 3]
+                            }
+                        })
+                        .collect(),
+                ),
+                num_keypoints: Some(n_labeled(instance)),
+                score: None,
+                source: None,
+                review: None,
+                track_id: None,
+            })
+            .collect::<Vec<_>>();
+        CocoExportData {
+            info,
+            images,
+            annotations,
+            categories,
+        }
+    }
+
+    /// Coordinates are considered relative if the file says so or, for files without mode, if
+    /// all coordinates are in `[0, 1]`.
+    fn coordinate_mode(&self) -> CoordinateMode {
+        self.info.coordinate_mode.unwrap_or_else(|| {
+            let mut coords = self.annotations.iter().flat_map(|anno| {
+                anno.bbox
+                    .iter()
+                    .chain(anno.segmentation.iter().flatten().flatten())
+            });
+            if coords.all(|c| (0.0..=1.0).contains(c)) {
+                CoordinateMode::Relative
+            } else {
+                CoordinateMode::Absolute
+            }
+        })
+    }
+
+    /// Checks references between images, annotations, and categories as well as the geometry
+    /// of the annotations.
+    fn validate(&self) -> Vec<CocoIssue> {
+        let mut issues = vec![];
+        let mut error = |location: String, message: String| {
+            issues.push(CocoIssue {
+                location,
+                message,
+                is_error: true,
+            })
+        };
+        let mut image_shapes = HashMap::new();
+        for (i, image) in self.images.iter().enumerate() {
+            if image_shapes
+                .insert(image.id, (image.width, image.height))
+                .is_some()
+            {
+                error(
+                    format!("images[{i}].id"),
+                    format!("duplicate id {}", image.id),
+                );
+            }
+            if image.width == 0 || image.height == 0 {
+                error(
+                    format!("images[{i}]"),
+                    format!("invalid size {}x{}", image.width, image.height),
+                );
+            }
+        }
+        let mut cat_ids = HashSet::new();
+        let mut cat_names = HashSet::new();
+        for (i, cat) in self.categories.iter().enumerate() {
+            if !cat_ids.insert(cat.id) {
+                error(
+                    format!("categories[{i}].id"),
+                    format!("duplicate id {}", cat.id),
+                );
+            }
+            if !cat_names.insert(cat.name.as_str()) {
+                error(
+                    format!("categories[{i}].name"),
+                    format!("duplicate name '{}'", cat.name),
+                );
+            }
+        }
+        let mode = self.coordinate_mode();
+        let mut warnings = vec![];
+        for (i, anno) in self.annotations.iter().enumerate() {
+            let shape = image_shapes.get(&anno.image_id);
+            if shape.is_none() {
+                error(
+                    format!("annotations[{i}].image_id"),
+                    format!("unknown image id {}", anno.image_id),
+                );
+            }
+            if !cat_ids.contains(&anno.category_id) {
+                error(
+                    format!("annotations[{i}].category_id"),
+                    format!("unknown category id {}", anno.category_id),
+                );
+            }
+            if anno.bbox.iter().any(|c| !c.is_finite() || *c < 0.0) {
+                error(
+                    format!("annotations[{i}].bbox"),
+                    format!(
+                        "coordinates need to be finite and positive, got {:?}",
+                        anno.bbox
+                    ),
+                );
+            } else if let Some((w, h)) = shape {
+                let (w_max, h_max) = match mode {
+                    CoordinateMode::Absolute => (*w as f64, *h as f64),
+                    CoordinateMode::Relative => (1.0, 1.0),
+                };
+                let [x, y, bb_w, bb_h] = anno.bbox;
+                if x + bb_w > w_max || y + bb_h > h_max {
+                    warnings.push(CocoIssue {
+                        location: format!("annotations[{i}].bbox"),
+                        message: format!("{:?} exceeds the image", anno.bbox),
+                        is_error: false,
+                    });
+                }
+            }
+            for (j, part) in anno.segmentation.iter().flatten().enumerate() {
+                if part.len() % 2 == 1 || part.iter().any(|c| !c.is_finite()) {
+                    error(
+                        format!("annotations[{i}].segmentation[{j}]"),
+                        "expected pairs of finite coordinates".to_string(),
+                    );
+                }
+            }
+            if let Some(kps) = &anno.keypoints {
+                if kps.len() % 3 != 0 {
+                    error(
+                        format!("annotations[{i}].keypoints"),
+                        "expected triplets of x, y, and visibility".to_string(),
+                    );
+                }
+            }
+        }
+        issues.extend(warnings);
+        issues
+    }
+
+    fn convert_to_bboxdata(
+        self,
+        coco_file: CocoFile,
+        opened_folder: Option<&str>,
+    ) -> RvResult<BboxSpecificData> {
+        let coordinate_mode = self.coordinate_mode();
         let cat_ids: Vec<u32> = self.categories.iter().map(|coco_cat| coco_cat.id).collect();
+        let cat_colors = self
+            .categories
+            .iter()
+            .map(|coco_cat| coco_cat.color)
+            .collect::<Option<Vec<_>>>();
+        let mut attribute_defs = HashMap::new();
         let labels: Vec<String> = self
             .categories
             .into_iter()
-            .map(|coco_cat| coco_cat.name)
+            .map(|coco_cat| {
+                if !coco_cat.attributes.is_empty() {
+                    attribute_defs.insert(coco_cat.name.clone(), coco_cat.attributes);
+                }
+                coco_cat.name
+            })
             .collect();
         let color_str = self.info.description.split(',').last();
-        let colors: Vec<[u8; 3]> = if let Some(s) = color_str {
-            string_to_colors(s).unwrap_or_else(|_| new_random_colors(labels.len()))
-        } else {
-            new_random_colors(labels.len())
+        let colors: Vec<[u8; 3]> = match (cat_colors, color_str) {
+            (Some(colors), _) => colors,
+            (None, Some(s)) => {
+                string_to_colors(s).unwrap_or_else(|_| new_random_colors(labels.len()))
+            }
+            (None, None) => new_random_colors(labels.len()),
         };
         let id_image_map = self
             .images
             .iter()
             .map(|coco_image: &CocoImage| {
+                let file_path =
+                    to_image_path(&coco_image.file_name, opened_folder, &coco_file.prefix_map);
                 Ok((
                     coco_image.id,
-                    (
-                        coco_image.file_name.as_str(),
-                        coco_image.width,
-                        coco_image.height,
-                    ),
+                    (file_path, coco_image.width, coco_image.height),
                 ))
             })
-            .collect::<RvResult<HashMap<u32, (&str, u32, u32)>>>()?;
+            .collect::<RvResult<HashMap<u32, (String, u32, u32)>>>()?;
 
         let mut annotations: HashMap<String, (Vec<GeoFig>, Vec<usize>, Shape)> = HashMap::new();
+        let mut attributes: HashMap<String, Vec<Attributes>> = HashMap::new();
+        let mut provenances: HashMap<String, Vec<Provenance>> = HashMap::new();
+        let mut track_ids: HashMap<String, Vec<Option<u32>>> = HashMap::new();
+        let mut sub_pixel_bbs: HashMap<String, Vec<Option<BbF>>> = HashMap::new();
         for coco_anno in self.annotations {
-            let (file_name, w, h) = id_image_map[&coco_anno.image_id];
+            let (file_name, w, h) = &id_image_map[&coco_anno.image_id];
+            let (w, h) = (*w, *h);
 
-            let coords_absolute = coco_anno.bbox.iter().any(|x| *x > 1.0);
-            let (w_factor, h_factor) = if coords_absolute {
-                (1.0, 1.0)
-            } else {
-                (w as f32, h as f32)
+            let (w_factor, h_factor) = match coordinate_mode {
+                CoordinateMode::Absolute => (1.0, 1.0),
+                CoordinateMode::Relative => (w as f64, h as f64),
             };
-            let bbox = [
-                (w_factor * coco_anno.bbox[0]).round() as u32,
-                (h_factor * coco_anno.bbox[1]).round() as u32,
-                (w_factor * coco_anno.bbox[2]).round() as u32,
-                (h_factor * coco_anno.bbox[3]).round() as u32,
-            ];
-
-            let bb = GeoFig::BB(BB::from_arr(&bbox));
+            let bb_f = BbF::from_arr(&coco_anno.bbox).scale(w_factor, h_factor);
+
+            let bb = segmentation_to_geo(
+                coco_anno.segmentation.as_ref(),
+                bb_f.round(),
+                w_factor,
+                h_factor,
+            );
             let cat_idx = cat_ids
                 .iter()
                 .position(|cat_id| *cat_id == coco_anno.category_id)
@@ -231,6 +688,34 @@ impl CocoExportData {
             } else {
                 file_name.to_string()
             };
+            attributes
+                .entry(k.clone())
+                .or_default()
+                .push(coco_anno.attributes);
+            // annotations with scores but without source come from a model, e.g., Coco results
+            let default_source = if coco_anno.score.is_some() {
+                AnnoSource::Predicted
+            } else {
+                AnnoSource::Imported
+            };
+            provenances.entry(k.clone()).or_default().push(Provenance {
+                score: coco_anno
+                    .score
+                    .filter(|s| s.is_finite())
+                    .map(|s| s.clamp(0.0, 1.0)),
+                source: coco_anno.source.unwrap_or(default_source),
+                review: coco_anno.review.unwrap_or_default(),
+            });
+            track_ids
+                .entry(k.clone())
+                .or_default()
+                .push(coco_anno.track_id);
+            // only boxes that are not on the pixel grid need to be kept
+            let is_on_grid = BbF::from(bb_f.round()) == bb_f;
+            sub_pixel_bbs
+                .entry(k.clone())
+                .or_default()
+                .push((!is_on_grid).then_some(bb_f));
             if let Some(annos_of_image) = annotations.get_mut(&k) {
                 annos_of_image.0.push(bb);
                 annos_of_image.1.push(cat_idx);
@@ -244,12 +729,42 @@ impl CocoExportData {
             cat_ids,
             annotations,
             coco_file,
-            is_export_absolute: false,
+            is_export_absolute: coordinate_mode == CoordinateMode::Absolute,
+            attribute_defs,
+            attributes,
+            pinned_labels: BTreeMap::new(),
+            is_locked: false,
+            schema: None,
+            provenances,
+            predictions: HashMap::new(),
+            image_reviews: HashMap::new(),
+            track_ids,
+            sub_pixel_bbs,
+            splits: HashMap::new(),
+            split_cfg: SplitCfg::default(),
         })
     }
 }
 
-fn meta_data_to_coco_path(meta_data: &MetaData) -> RvResult<PathBuf> {
+fn meta_data_to_coco_path(meta_data: &MetaData, suffix: &str) -> RvResult<PathBuf> {
+    meta_data_to_export_path(meta_data, suffix, "json")
+}
+
+/// File in the export folder named after the opened folder
+pub(super) fn meta_data_to_export_path(
+    meta_data: &MetaData,
+    suffix: &str,
+    extension: &str,
+) -> RvResult<PathBuf> {
+    let path = meta_data_to_export_folder(meta_data, suffix)?;
+    Ok(PathBuf::from(format!(
+        "{}.{extension}",
+        path_to_str(&path)?
+    )))
+}
+
+/// Folder in the export folder named after the opened folder
+pub(super) fn meta_data_to_export_folder(meta_data: &MetaData, suffix: &str) -> RvResult<PathBuf> {
     let export_folder = Path::new(
         meta_data
             .export_folder
@@ -270,15 +785,15 @@ fn meta_data_to_coco_path(meta_data: &MetaData) -> RvResult<PathBuf> {
         .and_then(|of| of.to_str())
         .ok_or_else(|| rverr!("cannot find folder name  of {}", opened_folder))?;
     let file_name = if let Some(p) = parent {
-        format!("{p}_{opened_folder_name}_coco.json")
+        format!("{p}_{opened_folder_name}_{suffix}")
     } else {
-        format!("{opened_folder_name}_coco.json")
+        format!("{opened_folder_name}_{suffix}")
     };
     Ok(export_folder.join(file_name))
 }
-fn get_cocofilepath(meta_data: &MetaData, coco_file: &CocoFile) -> RvResult<PathBuf> {
+pub(super) fn get_cocofilepath(meta_data: &MetaData, coco_file: &CocoFile) -> RvResult<PathBuf> {
     if path_to_str(&coco_file.path)?.is_empty() {
-        meta_data_to_coco_path(meta_data)
+        meta_data_to_coco_path(meta_data, "coco")
     } else {
         Ok(coco_file.path.clone())
     }
@@ -286,7 +801,7 @@ fn get_cocofilepath(meta_data: &MetaData, coco_file: &CocoFile) -> RvResult<Path
 pub fn write_coco(meta_data: &MetaData, bbox_specifics: BboxSpecificData) -> RvResult<PathBuf> {
     let coco_out_path = get_cocofilepath(meta_data, &bbox_specifics.coco_file)?;
     let conn = bbox_specifics.coco_file.conn.clone();
-    let coco_data = CocoExportData::from_coco(bbox_specifics)?;
+    let coco_data = CocoExportData::from_coco(bbox_specifics, meta_data.opened_folder.as_deref())?;
     let data_str = serde_json::to_string(&coco_data).map_err(to_rv)?;
     match conn {
         CocoFileConnection::Ssh => {
@@ -303,14 +818,51 @@ pub fn write_coco(meta_data: &MetaData, bbox_specifics: BboxSpecificData) -> RvR
     Ok(coco_out_path)
 }
 
-pub fn read_coco(meta_data: &MetaData, coco_file: &CocoFile) -> RvResult<BboxSpecificData> {
+/// Keypoints are always exported locally into the export folder.
+pub fn write_keypoints_coco(
+    meta_data: &MetaData,
+    keypoint_data: &KeypointToolData,
+) -> RvResult<PathBuf> {
+    let coco_out_path = meta_data_to_coco_path(meta_data, "keypoints_coco")?;
+    let coco_data =
+        CocoExportData::from_keypoint_data(keypoint_data, meta_data.opened_folder.as_deref());
+    let data_str = serde_json::to_string(&coco_data).map_err(to_rv)?;
+    file_util::write(&coco_out_path, data_str)?;
+    println!("exported coco keypoints to {coco_out_path:?}");
+    Ok(coco_out_path)
+}
+
+/// Warnings are returned alongside the converted data, errors prevent the conversion.
+fn validate_and_convert(
+    read: CocoExportData,
+    coco_file: &CocoFile,
+    opened_folder: Option<&str>,
+) -> RvResult<(BboxSpecificData, Vec<CocoIssue>)> {
+    let (errors, warnings): (Vec<_>, Vec<_>) = read
+        .validate()
+        .into_iter()
+        .partition(|issue| issue.is_error);
+    if errors.is_empty() {
+        let data = read.convert_to_bboxdata(coco_file.clone(), opened_folder)?;
+        Ok((data, warnings))
+    } else {
+        let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+        Err(rverr!("invalid coco file, {}", errors.join("; ")))
+    }
+}
+
+/// Imported data and warnings about the Coco file that do not prevent the import
+pub fn read_coco(
+    meta_data: &MetaData,
+    coco_file: &CocoFile,
+) -> RvResult<(BboxSpecificData, Vec<CocoIssue>)> {
     let coco_inpath = get_cocofilepath(meta_data, coco_file)?;
     match &coco_file.conn {
         CocoFileConnection::Local => {
             let s = file_util::read_to_string(&coco_inpath)?;
             let read: CocoExportData = serde_json::from_str(s.as_str()).map_err(to_rv)?;
             println!("imported coco file from {coco_inpath:?}");
-            read.convert_to_bboxdata(coco_file.clone())
+            validate_and_convert(read, coco_file, meta_data.opened_folder.as_deref())
         }
         CocoFileConnection::Ssh => {
             if let Some(ssh_cfg) = &meta_data.ssh_cfg {
@@ -320,7 +872,7 @@ pub fn read_coco(meta_data: &MetaData, coco_file: &CocoFile) -> RvResult<BboxSpe
 
                 let read: CocoExportData = serde_json::from_str(s.as_str()).map_err(to_rv)?;
                 println!("imported coco file from {coco_inpath:?}");
-                read.convert_to_bboxdata(coco_file.clone())
+                validate_and_convert(read, coco_file, meta_data.opened_folder.as_deref())
             } else {
                 Err(rverr!("cannot read coco from ssh, ssh-cfg missing.",))
             }
@@ -399,11 +951,12 @@ fn test_coco_export() -> RvResult<()> {
         let (bbox_data, meta, _) = make_data("json", &file_path, opened_folder);
         let coco_file = write_coco(&meta, bbox_data.clone())?;
         defer_file_removal!(&coco_file);
-        let read = read_coco(
+        let (read, _) = read_coco(
             &meta,
             &CocoFile {
                 path: coco_file.clone(),
                 conn: CocoFileConnection::Local,
+                prefix_map: vec![],
             },
         )?;
         assert_eq!(bbox_data.cat_ids(), read.cat_ids());
@@ -426,6 +979,282 @@ fn test_coco_export() -> RvResult<()> {
     Ok(())
 }
 
+#[test]
+fn test_coco_attributes() -> RvResult<()> {
+    use crate::annotations::AttrVal;
+    let file_path = DEFAULT_TMPDIR.join("test_attributes.png");
+    let (mut bbox_data, meta, _) = make_data("json", &file_path, Some(Path::new("attrs")));
+    let def = AttrDef {
+        name: "difficult".to_string(),
+        default: AttrVal::Bool(false),
+    };
+    bbox_data.add_attribute_def(0, def)?;
+    let annos = bbox_data.get_annos_mut(file_path.to_str().unwrap(), Shape::new(10, 10));
+    annos.select(1);
+    annos.set_attribute_selected(0, "difficult", &AttrVal::Bool(true));
+    let coco_file = write_coco(&meta, bbox_data.clone())?;
+    defer_file_removal!(&coco_file);
+    let (read, _) = read_coco(
+        &meta,
+        &CocoFile {
+            path: coco_file.clone(),
+            conn: CocoFileConnection::Local,
+            prefix_map: vec![],
+        },
+    )?;
+    assert_eq!(read.attribute_defs(), bbox_data.attribute_defs());
+    let (_, (annos, _)) = bbox_data.anno_iter().next().unwrap();
+    let (_, (read_annos, _)) = read.anno_iter().next().unwrap();
+    for ((cat_idx, attrs), read_attrs) in annos
+        .cat_idxs()
+        .iter()
+        .zip(annos.attributes())
+        .zip(read_annos.attributes())
+    {
+        assert_eq!(&bbox_data.effective_attributes(*cat_idx, attrs), read_attrs);
+    }
+    assert_eq!(read_annos.attributes()[1]["difficult"], AttrVal::Bool(true));
+    Ok(())
+}
+
+#[test]
+fn test_coco_provenance() -> RvResult<()> {
+    let file_path = DEFAULT_TMPDIR.join("test_provenance.png");
+    let (mut bbox_data, meta, _) = make_data("json", &file_path, Some(Path::new("provs")));
+    let annos = bbox_data.get_annos_mut(file_path.to_str().unwrap(), Shape::new(10, 10));
+    let mut provenances = annos.provenances().clone();
+    provenances[1] = Provenance {
+        score: Some(0.75),
+        source: AnnoSource::Predicted,
+        review: ReviewStatus::Unreviewed,
+    };
+    annos.set_provenances(provenances)?;
+    annos.set_track_id(1, Some(4));
+    let coco_file = write_coco(&meta, bbox_data.clone())?;
+    defer_file_removal!(&coco_file);
+    let (read, _) = read_coco(
+        &meta,
+        &CocoFile {
+            path: coco_file.clone(),
+            conn: CocoFileConnection::Local,
+            prefix_map: vec![],
+        },
+    )?;
+    let (_, (read_annos, _)) = read.anno_iter().next().unwrap();
+    assert_eq!(read_annos.provenances()[0], Provenance::default());
+    assert!(read_annos.provenances()[1].is_unreviewed_prediction());
+    assert_eq!(read_annos.provenances()[1].score, Some(0.75));
+    assert_eq!(read_annos.track_ids()[..2], [None, Some(4)]);
+
+    // files of other tools have neither source nor review
+    let anno: CocoAnnotation = serde_json::from_str(
+        r#"{"id": 0, "image_id": 0, "category_id": 1, "bbox": [0, 0, 1, 1],
+            "segmentation": null, "area": null, "score": 0.3}"#,
+    )
+    .map_err(to_rv)?;
+    assert_eq!(anno.source, None);
+    assert_eq!(anno.score, Some(0.3));
+    Ok(())
+}
+
+#[test]
+fn test_coco_polygon() -> RvResult<()> {
+    let file_path = DEFAULT_TMPDIR.join("test_polygon.png");
+    let (mut bbox_data, meta, _) = make_data("json", &file_path, Some(Path::new("polys")));
+    let poly = Polygon::from_vec(
+        vec![PtI { x: 1, y: 1 }, PtI { x: 7, y: 1 }, PtI { x: 4, y: 9 }],
+        false,
+    )?;
+    let file_path_str = file_path.to_str().unwrap();
+    let annos = bbox_data.get_annos_mut(file_path_str, Shape::new(10, 10));
+    annos.add_geo(GeoFig::Poly(poly.clone()), 0);
+    let n_geos = annos.geos().len();
+    let coco_data = CocoExportData::from_coco(bbox_data.clone(), None)?;
+    let coco_anno = coco_data.annotations.last().unwrap();
+    assert_eq!(coco_anno.area, Some(24.0));
+    assert_eq!(
+        coco_anno.segmentation,
+        Some(vec![vec![0.1, 0.1, 0.7, 0.1, 0.4, 0.9]])
+    );
+    assert_eq!(
+        coco_data.annotations[0].segmentation.as_ref().unwrap()[0].len(),
+        8
+    );
+    let coco_file = write_coco(&meta, bbox_data.clone())?;
+    defer_file_removal!(&coco_file);
+    let (read, _) = read_coco(
+        &meta,
+        &CocoFile {
+            path: coco_file.clone(),
+            conn: CocoFileConnection::Local,
+            prefix_map: vec![],
+        },
+    )?;
+    let read_annos = read.get_annos(file_path_str).unwrap();
+    assert_eq!(read_annos.geos().len(), n_geos);
+    assert_eq!(read_annos.geos()[n_geos - 1], GeoFig::Poly(poly));
+    assert_eq!(
+        read_annos.geos()[..n_geos - 1],
+        bbox_data.get_annos(file_path_str).unwrap().geos()[..n_geos - 1]
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+fn coco_from_str(annotations: &str, info: &str) -> CocoExportData {
+    let s = format!(
+        r#"{{"info": {info},
+            "images": [{{"id": 0, "width": 100, "height": 50, "file_name": "a.png"}}],
+            "categories": [{{"id": 1, "name": "x", "color": [1, 2, 3]}}],
+            "annotations": {annotations}}}"#
+    );
+    serde_json::from_str(&s).unwrap()
+}
+
+#[test]
+fn test_coco_coordinate_mode() -> RvResult<()> {
+    let tiny = r#"[{"id": 0, "image_id": 0, "category_id": 1, "bbox": [0, 0, 1, 1],
+        "segmentation": null, "area": null}]"#;
+    // without mode, small coordinates are considered relative
+    let coco = coco_from_str(tiny, r#"{"description": ""}"#);
+    assert_eq!(coco.coordinate_mode(), CoordinateMode::Relative);
+    let coco = coco_from_str(
+        tiny,
+        r#"{"description": "", "coordinate_mode": "absolute"}"#,
+    );
+    assert_eq!(coco.coordinate_mode(), CoordinateMode::Absolute);
+    let read = coco.convert_to_bboxdata(CocoFile::default(), None)?;
+    assert_eq!(read.colors(), &vec![[1, 2, 3]]);
+    assert_eq!(
+        read.get_annos("a.png").unwrap().geos(),
+        &vec![GeoFig::BB(BB::from_arr(&[0, 0, 1, 1]))]
+    );
+    // sub-pixel boxes are rounded at their corners
+    let subpixel = r#"[{"id": 0, "image_id": 0, "category_id": 1, "bbox": [0.004, 0.032, 0.022, 0.044],
+        "segmentation": null, "area": null}]"#;
+    let coco = coco_from_str(
+        subpixel,
+        r#"{"description": "", "coordinate_mode": "relative"}"#,
+    );
+    let read = coco.convert_to_bboxdata(CocoFile::default(), None)?;
+    assert_eq!(
+        read.get_annos("a.png").unwrap().geos(),
+        &vec![GeoFig::BB(BB::from_arr(&[0, 2, 3, 2]))]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_coco_subpixel_roundtrip() -> RvResult<()> {
+    let subpixel = r#"[{"id": 0, "image_id": 0, "category_id": 1, "bbox": [10.25, 5.5, 20.75, 10.125],
+        "segmentation": null, "area": null}]"#;
+    let coco = coco_from_str(
+        subpixel,
+        r#"{"description": "", "coordinate_mode": "absolute"}"#,
+    );
+    let mut read = coco.convert_to_bboxdata(CocoFile::default(), None)?;
+    read.options.export_absolute = true;
+    assert_eq!(
+        read.get_annos("a.png").unwrap().geos(),
+        &vec![GeoFig::BB(BB::from_arr(&[10, 6, 21, 10]))]
+    );
+    let exported = CocoExportData::from_coco(read.clone(), None)?;
+    let anno = &exported.annotations[0];
+    assert_eq!(anno.bbox, [10.25, 5.5, 20.75, 10.125]);
+    assert_eq!(anno.area, Some(20.75 * 10.125));
+    assert_eq!(
+        anno.segmentation,
+        Some(vec![vec![
+            10.25, 5.5, 31.0, 5.5, 31.0, 15.625, 10.25, 15.625
+        ]])
+    );
+    // the sub-pixel box is dropped as soon as the box is changed
+    read.get_annos_mut("a.png", Shape::new(100, 50))
+        .set_geo(0, GeoFig::BB(BB::from_arr(&[11, 6, 21, 10])));
+    let exported = CocoExportData::from_coco(read, None)?;
+    assert_eq!(exported.annotations[0].bbox, [11.0, 6.0, 21.0, 10.0]);
+    Ok(())
+}
+
+#[test]
+fn test_coco_validate() {
+    let annos = r#"[
+        {"id": 0, "image_id": 0, "category_id": 1, "bbox": [90, 0, 20, 10], "segmentation": null, "area": null},
+        {"id": 1, "image_id": 3, "category_id": 1, "bbox": [0, 0, 1, 1], "segmentation": null, "area": null},
+        {"id": 2, "image_id": 0, "category_id": 7, "bbox": [0, -1, 1, 1], "segmentation": [[1, 2, 3]], "area": null}
+    ]"#;
+    let coco = coco_from_str(annos, r#"{"description": ""}"#);
+    let issues = coco.validate();
+    let locations = |is_error: bool| {
+        issues
+            .iter()
+            .filter(|issue| issue.is_error == is_error)
+            .map(|issue| issue.location.as_str())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(
+        locations(true),
+        vec![
+            "annotations[1].image_id",
+            "annotations[2].category_id",
+            "annotations[2].bbox",
+            "annotations[2].segmentation[0]"
+        ]
+    );
+    assert_eq!(locations(false), vec!["annotations[0].bbox"]);
+    assert!(validate_and_convert(coco, &CocoFile::default(), None).is_err());
+}
+
+#[test]
+fn test_coco_keypoints() -> RvResult<()> {
+    use crate::{
+        annotations::{Keypoint, Visibility},
+        domain::PtI,
+    };
+    let mut data = KeypointToolData::default();
+    data.push_keypoint("nose".to_string())?;
+    data.push_keypoint("left_eye".to_string())?;
+    data.push_keypoint("right_eye".to_string())?;
+    data.push_edge(0, 1)?;
+    data.push_edge(0, 2)?;
+    let annos = data.get_annos_mut("a.png", Shape::new(100, 50));
+    let inst_idx = annos.add_instance(3);
+    let kp = |x, y, visibility| Keypoint {
+        pos: PtI { x, y },
+        visibility,
+    };
+    annos.set_keypoint(inst_idx, 0, kp(10, 20, Visibility::Visible));
+    annos.set_keypoint(inst_idx, 2, kp(30, 5, Visibility::Occluded));
+    // instances without labeled keypoints are not exported
+    annos.add_instance(3);
+    let (_, meta, _) = make_data("json", Path::new("kp.png"), Some(Path::new("kps")));
+    let coco_file = write_keypoints_coco(&meta, &data)?;
+    defer_file_removal!(&coco_file);
+    assert!(coco_file.ends_with("kps_keypoints_coco.json"));
+    let read: CocoExportData =
+        serde_json::from_str(&file_util::read_to_string(&coco_file)?).map_err(to_rv)?;
+    assert_eq!(read.images.len(), 1);
+    assert_eq!((read.images[0].width, read.images[0].height), (100, 50));
+    assert_eq!(
+        read.categories[0].keypoints,
+        Some(vec![
+            "nose".to_string(),
+            "left_eye".to_string(),
+            "right_eye".to_string()
+        ])
+    );
+    assert_eq!(read.categories[0].skeleton, Some(vec![[1, 2], [1, 3]]));
+    assert_eq!(read.annotations.len(), 1);
+    let anno = &read.annotations[0];
+    assert_eq!(
+        anno.keypoints,
+        Some(vec![10.0, 20.0, 2.0, 0.0, 0.0, 0.0, 30.0, 5.0, 1.0])
+    );
+    assert_eq!(anno.num_keypoints, Some(2));
+    assert_eq!(anno.bbox, [10.0, 5.0, 20.0, 15.0]);
+    Ok(())
+}
+
 #[cfg(test)]
 const TEST_DATA_FOLDER: &str = "resources/test_data/";
 
@@ -440,7 +1269,7 @@ fn test_coco_import() -> RvResult<()> {
             export_folder: Some(TEST_DATA_FOLDER.to_string()),
             is_loading_screen_active: None,
         };
-        let read = read_coco(&meta, &CocoFile::default()).unwrap();
+        let (read, _) = read_coco(&meta, &CocoFile::default()).unwrap();
         assert_eq!(read.cat_ids(), &cat_ids);
         assert_eq!(read.labels(), &vec!["first label", "second label"]);
         for (bb, file_path) in reference_bbs {
@@ -466,15 +1295,25 @@ fn test_coco_import() -> RvResult<()> {
             "http://localhost:5000/%2Bnowhere2.png",
         ),
     ];
+    // relative file names are interpreted relative to the opened folder
     let bb_im_ref_abs2 = [
-        (BB::from_arr(&[1, 1, 5, 5]), "nowhere.png"),
-        (BB::from_arr(&[11, 11, 4, 7]), "nowhere.png"),
-        (BB::from_arr(&[1, 1, 5, 5]), "nowhere2.png"),
+        (BB::from_arr(&[1, 1, 5, 5]), "catids_01/nowhere.png"),
+        (BB::from_arr(&[11, 11, 4, 7]), "catids_01/nowhere.png"),
+        (BB::from_arr(&[1, 1, 5, 5]), "catids_01/nowhere2.png"),
     ];
     let bb_im_ref_relative = [
-        (BB::from_arr(&[10, 100, 50, 500]), "nowhere.png"),
-        (BB::from_arr(&[91, 870, 15, 150]), "nowhere.png"),
-        (BB::from_arr(&[10, 1, 50, 5]), "nowhere2.png"),
+        (
+            BB::from_arr(&[10, 100, 50, 500]),
+            "catids_12_relative/nowhere.png",
+        ),
+        (
+            BB::from_arr(&[91, 870, 15, 150]),
+            "catids_12_relative/nowhere.png",
+        ),
+        (
+            BB::from_arr(&[10, 1, 50, 5]),
+            "catids_12_relative/nowhere2.png",
+        ),
     ];
     test("catids_12", vec![1, 2], &bb_im_ref_abs1);
     test("catids_01", vec![0, 1], &bb_im_ref_abs2);
@@ -489,3 +1328,58 @@ fn color_vs_str() {
     let colors_back = string_to_colors(&s.unwrap()).unwrap();
     assert_eq!(colors, colors_back);
 }
+
+#[test]
+fn test_coco_paths() {
+    let mapping = |from: &str, to: &str| PrefixMapping {
+        from: from.to_string(),
+        to: to.to_string(),
+    };
+    assert_eq!(
+        to_relative_path("/data/set/sub/a.png", Some("/data/set")),
+        "sub/a.png"
+    );
+    assert_eq!(
+        to_relative_path(r"C:\data\set\sub\a.png", Some(r"C:\data\set\")),
+        "sub/a.png"
+    );
+    assert_eq!(
+        to_relative_path("/data/set2/a.png", Some("/data/set")),
+        "/data/set2/a.png"
+    );
+    assert_eq!(to_relative_path("/data/a.png", None), "/data/a.png");
+    assert!(is_absolute_path("/a.png"));
+    assert!(is_absolute_path(r"D:\a.png"));
+    assert!(is_absolute_path("https://host/a.png"));
+    assert!(!is_absolute_path("sub/a.png"));
+    assert_eq!(
+        to_image_path("sub/a.png", Some("/remote/set"), &[]),
+        "/remote/set/sub/a.png"
+    );
+    assert_eq!(
+        to_image_path("sub/a.png", Some(r"C:\set"), &[]),
+        r"C:\set\sub\a.png"
+    );
+    let prefix_map = [
+        mapping("/local/set", "/mnt/remote/set"),
+        mapping("/other", r"E:\other"),
+    ];
+    assert_eq!(
+        to_image_path("/local/set/sub/a.png", None, &prefix_map),
+        "/mnt/remote/set/sub/a.png"
+    );
+    assert_eq!(
+        to_image_path("/other/a.png", None, &prefix_map),
+        r"E:\other\a.png"
+    );
+    assert_eq!(
+        to_image_path("/unmapped/a.png", Some("/set"), &prefix_map),
+        "/unmapped/a.png"
+    );
+    // exported relative paths are re-rooted when the folder is opened elsewhere
+    let exported = to_relative_path("/local/set/sub/a.png", Some("/local/set"));
+    assert_eq!(
+        to_image_path(&exported, Some("/mnt/remote/set"), &[]),
+        "/mnt/remote/set/sub/a.png"
+    );
+}
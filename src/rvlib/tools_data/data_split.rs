@@ -0,0 +1,357 @@
+//! Assignment of images to train, validation, and test splits. Images whose paths share the
+//! match of a regex form a group, e.g., frames of a sequence, and are put into the same split.
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::Display,
+    path::{Path, PathBuf},
+};
+
+use rand::{rngs::StdRng, seq::SliceRandom, SeedableRng};
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    file_util::{self, MetaData},
+    result::{to_rv, RvError, RvResult},
+};
+
+use super::{coco_io, BboxSpecificData};
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum DataSplit {
+    Train,
+    Val,
+    Test,
+}
+impl DataSplit {
+    pub const ALL: [DataSplit; 3] = [DataSplit::Train, DataSplit::Val, DataSplit::Test];
+}
+impl Display for DataSplit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Train => write!(f, "train"),
+            Self::Val => write!(f, "val"),
+            Self::Test => write!(f, "test"),
+        }
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct SplitCfg {
+    /// weights of train, validation, and test split
+    pub ratios: [u32; 3],
+    pub seed: u64,
+    /// the label distribution of each split follows the label distribution of the dataset
+    pub stratify: bool,
+    /// images whose paths have the same match are in the same split, the first capture group is
+    /// used if there is one
+    pub group_regex: String,
+}
+impl Default for SplitCfg {
+    fn default() -> Self {
+        Self {
+            ratios: [70, 20, 10],
+            seed: 42,
+            stratify: false,
+            group_regex: String::new(),
+        }
+    }
+}
+
+fn group_key(file_path: &str, group_re: Option<&Regex>) -> String {
+    group_re
+        .and_then(|re| re.captures(file_path))
+        .and_then(|caps| caps.get(1).or_else(|| caps.get(0)))
+        .map(|m| m.as_str().to_string())
+        .unwrap_or_else(|| file_path.to_string())
+}
+
+/// Images and annotations per label of each split during the assignment
+struct SplitTally {
+    n_images: [usize; 3],
+    n_labels: [Vec<usize>; 3],
+    splits: HashMap<String, DataSplit>,
+}
+impl SplitTally {
+    fn new(n_labels: usize) -> Self {
+        Self {
+            n_images: [0; 3],
+            n_labels: [vec![0; n_labels], vec![0; n_labels], vec![0; n_labels]],
+            splits: HashMap::new(),
+        }
+    }
+    fn add(&mut self, file_paths: Vec<String>, counts: &[usize], split_idx: usize) {
+        self.n_images[split_idx] += file_paths.len();
+        for (n, count) in self.n_labels[split_idx].iter_mut().zip(counts.iter()) {
+            *n += count;
+        }
+        for file_path in file_paths {
+            self.splits.insert(file_path, DataSplit::ALL[split_idx]);
+        }
+    }
+}
+
+/// Groups are shuffled and put one by one into the split that lacks most images. With
+/// stratification, groups with rare labels are distributed first into the split that lacks most
+/// annotations of the group's rarest label. Groups with an image in `recorded` keep the recorded
+/// split such that images do not move between splits when images are added.
+pub fn assign_splits(
+    bbox_data: &BboxSpecificData,
+    cfg: &SplitCfg,
+    recorded: &HashMap<String, DataSplit>,
+) -> RvResult<HashMap<String, DataSplit>> {
+    let weights_sum = cfg.ratios.iter().sum::<u32>();
+    if weights_sum == 0 {
+        return Err(RvError::new(
+            "at least one split ratio needs to be positive",
+        ));
+    }
+    let ratios = cfg.ratios.map(|r| r as f64 / weights_sum as f64);
+    let group_re = if cfg.group_regex.is_empty() {
+        None
+    } else {
+        Some(Regex::new(&cfg.group_regex).map_err(to_rv)?)
+    };
+
+    // group -> file paths and number of annotations per label
+    let n_labels = bbox_data.labels().len();
+    let mut groups: BTreeMap<String, (Vec<String>, Vec<usize>)> = BTreeMap::new();
+    for (file_path, (annos, _)) in bbox_data.anno_iter() {
+        let (file_paths, counts) = groups
+            .entry(group_key(file_path, group_re.as_ref()))
+            .or_insert_with(|| (vec![], vec![0; n_labels]));
+        file_paths.push(file_path.clone());
+        for cat_idx in annos.cat_idxs() {
+            counts[*cat_idx] += 1;
+        }
+    }
+    let mut groups = groups.into_values().collect::<Vec<_>>();
+    for (file_paths, _) in groups.iter_mut() {
+        file_paths.sort();
+    }
+
+    let n_images = groups.iter().map(|(fps, _)| fps.len()).sum::<usize>() as f64;
+    let label_totals = groups
+        .iter()
+        .fold(vec![0; n_labels], |mut totals, (_, counts)| {
+            for (total, count) in totals.iter_mut().zip(counts.iter()) {
+                *total += count;
+            }
+            totals
+        });
+
+    let mut tally = SplitTally::new(n_labels);
+    let mut new_groups = vec![];
+    for (file_paths, counts) in groups {
+        let recorded_split = file_paths
+            .iter()
+            .find_map(|fp| recorded.get(fp))
+            .and_then(|split| DataSplit::ALL.iter().position(|s| s == split));
+        match recorded_split {
+            Some(split_idx) => tally.add(file_paths, &counts, split_idx),
+            None => new_groups.push((file_paths, counts)),
+        }
+    }
+    let mut groups = new_groups;
+    groups.shuffle(&mut StdRng::seed_from_u64(cfg.seed));
+    let rarest_label = |counts: &[usize]| {
+        counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .min_by_key(|(cat_idx, _)| label_totals[*cat_idx])
+            .map(|(cat_idx, _)| cat_idx)
+    };
+    if cfg.stratify {
+        // stable sort keeps the shuffled order among groups with equally rare labels
+        groups.sort_by_key(|(_, counts)| {
+            rarest_label(counts)
+                .map(|cat_idx| label_totals[cat_idx])
+                .unwrap_or(usize::MAX)
+        });
+    }
+
+    for (file_paths, counts) in groups {
+        let rarest = if cfg.stratify {
+            rarest_label(&counts)
+        } else {
+            None
+        };
+        let deficits = |split_idx: usize| {
+            let label_deficit = rarest
+                .map(|cat_idx| {
+                    ratios[split_idx] * label_totals[cat_idx] as f64
+                        - tally.n_labels[split_idx][cat_idx] as f64
+                })
+                .unwrap_or(0.0);
+            let image_deficit = ratios[split_idx] * n_images - tally.n_images[split_idx] as f64;
+            (label_deficit, image_deficit)
+        };
+        // reversed such that earlier splits win ties
+        let split_idx = (0..3)
+            .rev()
+            .filter(|split_idx| ratios[*split_idx] > 0.0)
+            .max_by(|s1, s2| {
+                let (d1, d2) = (deficits(*s1), deficits(*s2));
+                d1.0.total_cmp(&d2.0).then(d1.1.total_cmp(&d2.1))
+            })
+            .unwrap_or(0);
+        tally.add(file_paths, &counts, split_idx);
+    }
+    Ok(tally.splits)
+}
+
+/// `folder/name_coco.json` becomes `folder/name_coco_train.json`
+fn split_path(path: &Path, split: DataSplit) -> RvResult<PathBuf> {
+    let stem = file_util::osstr_to_str(path.file_stem()).map_err(to_rv)?;
+    let file_name = match path.extension().and_then(|ext| ext.to_str()) {
+        Some(ext) => format!("{stem}_{split}.{ext}"),
+        None => format!("{stem}_{split}"),
+    };
+    Ok(path.with_file_name(file_name))
+}
+
+/// Assigns the images that do not have a recorded split yet to splits, records the assignment,
+/// and writes one Coco file per non-empty split next to the Coco file of the whole dataset.
+pub fn write_coco_splits(
+    meta_data: &MetaData,
+    bbox_data: &mut BboxSpecificData,
+) -> RvResult<Vec<PathBuf>> {
+    let splits = assign_splits(bbox_data, &bbox_data.split_cfg, bbox_data.splits())?;
+    let coco_path = coco_io::get_cocofilepath(meta_data, &bbox_data.coco_file)?;
+    let mut paths = vec![];
+    for split in DataSplit::ALL {
+        let mut split_data = bbox_data.clone();
+        split_data.retain_fileannos(|file_path| splits.get(file_path) == Some(&split));
+        if split_data.anno_iter().next().is_some() {
+            split_data.coco_file.path = split_path(&coco_path, split)?;
+            paths.push(coco_io::write_coco(meta_data, split_data)?);
+        }
+    }
+    bbox_data.set_splits(splits);
+    Ok(paths)
+}
+
+#[cfg(test)]
+use {
+    super::BboxExportData,
+    crate::{domain::Shape, BB},
+};
+
+#[cfg(test)]
+fn make_data(file_paths_labels: &[(&str, usize)]) -> BboxSpecificData {
+    let mut data = BboxSpecificData::new();
+    data.push("rare".to_string(), None, None).unwrap();
+    for (file_path, cat_idx) in file_paths_labels {
+        data.get_annos_mut(file_path, Shape::new(10, 10))
+            .add_bb(BB::from_arr(&[0, 0, 5, 5]), *cat_idx);
+    }
+    data
+}
+
+#[cfg(test)]
+fn count(splits: &HashMap<String, DataSplit>, split: DataSplit) -> usize {
+    splits.values().filter(|s| **s == split).count()
+}
+
+#[test]
+fn test_assign_splits() -> RvResult<()> {
+    let file_paths = (0..20).map(|i| format!("im{i}.png")).collect::<Vec<_>>();
+    let data = make_data(
+        &file_paths
+            .iter()
+            .map(|fp| (fp.as_str(), 0))
+            .collect::<Vec<_>>(),
+    );
+    let cfg = SplitCfg {
+        ratios: [70, 20, 10],
+        ..Default::default()
+    };
+    let splits = assign_splits(&data, &cfg, &HashMap::new())?;
+    assert_eq!(splits.len(), 20);
+    assert_eq!(count(&splits, DataSplit::Train), 14);
+    assert_eq!(count(&splits, DataSplit::Val), 4);
+    assert_eq!(count(&splits, DataSplit::Test), 2);
+    // same seed, same splits
+    assert_eq!(assign_splits(&data, &cfg, &HashMap::new())?, splits);
+    let other_seed = SplitCfg { seed: 7, ..cfg };
+    assert_ne!(assign_splits(&data, &other_seed, &HashMap::new())?, splits);
+    let zero = SplitCfg {
+        ratios: [0, 0, 0],
+        ..Default::default()
+    };
+    assert!(assign_splits(&data, &zero, &HashMap::new()).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_assign_splits_grouped_stratified() -> RvResult<()> {
+    let mut file_paths_labels = vec![];
+    for seq in 0..5 {
+        for frame in 0..4 {
+            file_paths_labels.push((format!("seq{seq}/frame{frame}.png"), 0));
+        }
+    }
+    file_paths_labels.push(("single0.png".to_string(), 1));
+    file_paths_labels.push(("single1.png".to_string(), 1));
+    let data = make_data(
+        &file_paths_labels
+            .iter()
+            .map(|(fp, cat_idx)| (fp.as_str(), *cat_idx))
+            .collect::<Vec<_>>(),
+    );
+    let cfg = SplitCfg {
+        ratios: [1, 1, 0],
+        stratify: true,
+        group_regex: "^(seq[0-9]+)/".to_string(),
+        ..Default::default()
+    };
+    let splits = assign_splits(&data, &cfg, &HashMap::new())?;
+    for seq in 0..5 {
+        let seq_split = splits[&format!("seq{seq}/frame0.png")];
+        for frame in 1..4 {
+            assert_eq!(splits[&format!("seq{seq}/frame{frame}.png")], seq_split);
+        }
+    }
+    // the rare label is in both splits
+    assert_ne!(splits["single0.png"], splits["single1.png"]);
+    assert_eq!(count(&splits, DataSplit::Test), 0);
+    // the assignment is part of the project
+    let mut data = data;
+    data.set_splits(splits.clone());
+    let data_back = BboxSpecificData::from_bbox_export_data(BboxExportData::from_bbox_data(data))?;
+    assert_eq!(data_back.splits(), &splits);
+    assert_eq!(
+        split_path(Path::new("export/a_coco.json"), DataSplit::Val)?,
+        Path::new("export/a_coco_val.json")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_assign_splits_recorded() -> RvResult<()> {
+    let file_paths = (0..20).map(|i| format!("im{i}.png")).collect::<Vec<_>>();
+    let mut file_paths_labels = file_paths
+        .iter()
+        .map(|fp| (fp.as_str(), 0))
+        .collect::<Vec<_>>();
+    let cfg = SplitCfg::default();
+    let recorded = assign_splits(&make_data(&file_paths_labels), &cfg, &HashMap::new())?;
+    // added images do not move the recorded ones
+    let added = (20..30).map(|i| format!("im{i}.png")).collect::<Vec<_>>();
+    file_paths_labels.extend(added.iter().map(|fp| (fp.as_str(), 0)));
+    let data = make_data(&file_paths_labels);
+    let splits = assign_splits(&data, &cfg, &recorded)?;
+    assert_eq!(splits.len(), 30);
+    for (file_path, split) in &recorded {
+        assert_eq!(splits[file_path], *split);
+    }
+    assert_eq!(count(&splits, DataSplit::Train), 21);
+    assert_eq!(count(&splits, DataSplit::Val), 6);
+    assert_eq!(count(&splits, DataSplit::Test), 3);
+    // recorded images that are not annotated anymore are dropped
+    let data = make_data(&file_paths_labels[1..]);
+    assert_eq!(assign_splits(&data, &cfg, &recorded)?.len(), 29);
+    Ok(())
+}
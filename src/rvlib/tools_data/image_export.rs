@@ -0,0 +1,74 @@
+//! Exports that write image files in background threads, e.g., patches or masks of the
+//! annotated objects.
+use std::{
+    mem, thread,
+    time::{Duration, Instant},
+};
+
+use crate::{
+    image_reader::{LoadImageForGui, ReaderFromCfg},
+    result::RvResult,
+    rverr,
+    threadpool::ThreadPool,
+    types::ResultImage,
+};
+
+const N_THREADS: usize = 2;
+const READ_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// The reader might load images asynchronously, so we wait for the image
+pub fn read_image(reader: &mut ReaderFromCfg, file_path: &str) -> ResultImage {
+    let start = Instant::now();
+    loop {
+        if let Some(loaded) = reader.read_image(0, &[file_path], false)? {
+            return Ok(loaded.im);
+        }
+        if start.elapsed() > READ_TIMEOUT {
+            return Err(rverr!("timeout when reading {}", file_path));
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[derive(Default)]
+pub struct ImageExporter {
+    tp: Option<ThreadPool<RvResult<usize>>>,
+    job_ids: Vec<u128>,
+}
+impl ImageExporter {
+    /// Distributes the jobs among the threads of the pool. `export` is called once per thread
+    /// with its share of the jobs and returns the number of written files.
+    pub fn export<J, F>(&mut self, jobs: Vec<J>, export: F) -> RvResult<()>
+    where
+        J: Clone + Send + 'static,
+        F: Fn(&[J]) -> RvResult<usize> + Clone + Send + 'static,
+    {
+        let tp = self.tp.get_or_insert_with(|| ThreadPool::new(N_THREADS));
+        let chunk_size = jobs.len().div_ceil(N_THREADS).max(1);
+        for chunk in jobs.chunks(chunk_size) {
+            let chunk = chunk.to_vec();
+            let export = export.clone();
+            let job_id = tp.apply(Box::new(move || export(&chunk)))?;
+            self.job_ids.push(job_id);
+        }
+        Ok(())
+    }
+
+    /// Numbers of written files of finished jobs
+    pub fn results(&mut self) -> Vec<RvResult<usize>> {
+        let mut results = vec![];
+        if let Some(tp) = &mut self.tp {
+            for job_id in mem::take(&mut self.job_ids) {
+                match tp.result(job_id) {
+                    Some(res) => results.push(res),
+                    None => self.job_ids.push(job_id),
+                }
+            }
+        }
+        results
+    }
+
+    pub fn n_pending(&self) -> usize {
+        self.job_ids.len()
+    }
+}
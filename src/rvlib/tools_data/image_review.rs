@@ -0,0 +1,87 @@
+use std::{fmt::Display, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    result::{RvError, RvResult},
+    rverr,
+};
+
+/// Review state of a whole image
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum ImageStatus {
+    #[default]
+    Todo,
+    InProgress,
+    Done,
+    NeedsFix,
+}
+impl ImageStatus {
+    pub const ALL: [ImageStatus; 4] = [
+        ImageStatus::Todo,
+        ImageStatus::InProgress,
+        ImageStatus::Done,
+        ImageStatus::NeedsFix,
+    ];
+    /// Color of the marker in the list of images
+    pub fn color(&self) -> [u8; 3] {
+        match self {
+            Self::Todo => [160, 160, 160],
+            Self::InProgress => [230, 180, 40],
+            Self::Done => [60, 190, 90],
+            Self::NeedsFix => [220, 60, 60],
+        }
+    }
+}
+impl Display for ImageStatus {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Todo => write!(f, "todo"),
+            Self::InProgress => write!(f, "in_progress"),
+            Self::Done => write!(f, "done"),
+            Self::NeedsFix => write!(f, "needs_fix"),
+        }
+    }
+}
+impl FromStr for ImageStatus {
+    type Err = RvError;
+    fn from_str(s: &str) -> RvResult<Self> {
+        Self::ALL
+            .into_iter()
+            .find(|status| status.to_string() == s.trim())
+            .ok_or_else(|| {
+                rverr!(
+                    "unknown image status '{}', use todo, in_progress, done, or needs_fix",
+                    s
+                )
+            })
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct ImageReview {
+    pub status: ImageStatus,
+    #[serde(default)]
+    pub comment: String,
+}
+impl ImageReview {
+    pub fn is_default(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+#[test]
+fn test_image_status() -> RvResult<()> {
+    for status in ImageStatus::ALL {
+        assert_eq!(status.to_string().parse::<ImageStatus>()?, status);
+    }
+    assert_eq!(" done ".parse::<ImageStatus>()?, ImageStatus::Done);
+    assert!("finished".parse::<ImageStatus>().is_err());
+    let review: ImageReview =
+        serde_json::from_str(r#"{"status": "needs_fix"}"#).map_err(crate::result::to_rv)?;
+    assert_eq!(review.status, ImageStatus::NeedsFix);
+    assert!(review.comment.is_empty());
+    assert!(ImageReview::default().is_default());
+    Ok(())
+}
@@ -0,0 +1,195 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use super::{annotations::KeypointAnnotations, bbox_data::random_clr};
+use crate::{domain::Shape, implement_annotations_getters, result::RvResult, rverr};
+
+const DEFAULT_LABEL: &str = "person";
+
+/// file path -> keypoint annotations and shape of the image
+pub type KeypointAnnotationsMap = HashMap<String, (KeypointAnnotations, Shape)>;
+
+/// Named keypoints and the edges between them, edges refer to the indices of the keypoints.
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Skeleton {
+    keypoint_names: Vec<String>,
+    edges: Vec<(usize, usize)>,
+}
+impl Skeleton {
+    pub fn keypoint_names(&self) -> &Vec<String> {
+        &self.keypoint_names
+    }
+    pub fn edges(&self) -> &Vec<(usize, usize)> {
+        &self.edges
+    }
+    pub fn len(&self) -> usize {
+        self.keypoint_names.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.keypoint_names.is_empty()
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Options {
+    pub is_export_triggered: bool,
+    pub is_redraw_annos_triggered: bool,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug, PartialEq, Eq)]
+pub struct KeypointToolData {
+    /// name of the Coco category
+    pub label: String,
+    pub color: [u8; 3],
+    skeleton: Skeleton,
+    annotations_map: KeypointAnnotationsMap,
+    #[serde(skip)]
+    pub options: Options,
+    #[serde(skip)]
+    pub new_keypoint_name: String,
+    #[serde(skip)]
+    pub new_edge: (usize, usize),
+}
+
+impl KeypointToolData {
+    implement_annotations_getters!(KeypointAnnotations);
+
+    pub fn skeleton(&self) -> &Skeleton {
+        &self.skeleton
+    }
+
+    pub fn annotations_map(&self) -> &KeypointAnnotationsMap {
+        &self.annotations_map
+    }
+
+    /// The instances of the annotations need one slot per keypoint of the skeleton.
+    pub(crate) fn annotations_map_mut(&mut self) -> &mut KeypointAnnotationsMap {
+        &mut self.annotations_map
+    }
+
+    pub fn push_keypoint(&mut self, name: String) -> RvResult<()> {
+        if name.is_empty() {
+            Err(rverr!("keypoint name '{}' is empty", name))
+        } else if self.skeleton.keypoint_names.contains(&name) {
+            Err(rverr!("keypoint '{}' already exists", name))
+        } else {
+            self.skeleton.keypoint_names.push(name);
+            for (annos, _) in self.annotations_map.values_mut() {
+                annos.push_keypoint_slot();
+            }
+            Ok(())
+        }
+    }
+
+    /// Removes the keypoint from the skeleton, from all edges, and from all annotations.
+    pub fn remove_keypoint(&mut self, kp_idx: usize) {
+        self.skeleton.keypoint_names.remove(kp_idx);
+        self.skeleton.edges = self
+            .skeleton
+            .edges
+            .iter()
+            .filter(|(from, to)| *from != kp_idx && *to != kp_idx)
+            .map(|(from, to)| {
+                let shift = |i: usize| if i > kp_idx { i - 1 } else { i };
+                (shift(*from), shift(*to))
+            })
+            .collect();
+        for (annos, _) in self.annotations_map.values_mut() {
+            annos.remove_keypoint_slot(kp_idx);
+        }
+        self.new_edge = (0, 0);
+    }
+
+    pub fn push_edge(&mut self, from: usize, to: usize) -> RvResult<()> {
+        let n_kps = self.skeleton.len();
+        if from >= n_kps || to >= n_kps {
+            Err(rverr!(
+                "edge ({}, {}) refers to unknown keypoints",
+                from,
+                to
+            ))
+        } else if from == to {
+            Err(rverr!(
+                "edge ({}, {}) needs two different keypoints",
+                from,
+                to
+            ))
+        } else if self
+            .skeleton
+            .edges
+            .iter()
+            .any(|e| *e == (from, to) || *e == (to, from))
+        {
+            Err(rverr!("edge ({}, {}) already exists", from, to))
+        } else {
+            self.skeleton.edges.push((from, to));
+            Ok(())
+        }
+    }
+
+    pub fn remove_edge(&mut self, edge_idx: usize) {
+        self.skeleton.edges.remove(edge_idx);
+    }
+
+    pub fn n_instances(&self) -> usize {
+        self.annotations_map
+            .values()
+            .map(|(annos, _)| annos.len())
+            .sum()
+    }
+}
+
+impl Default for KeypointToolData {
+    fn default() -> Self {
+        Self {
+            label: DEFAULT_LABEL.to_string(),
+            color: random_clr(),
+            skeleton: Skeleton::default(),
+            annotations_map: HashMap::new(),
+            options: Options::default(),
+            new_keypoint_name: String::new(),
+            new_edge: (0, 0),
+        }
+    }
+}
+
+#[cfg(test)]
+use crate::{
+    annotations::{Keypoint, Visibility},
+    domain::PtI,
+};
+
+#[test]
+fn test_skeleton() -> RvResult<()> {
+    let mut data = KeypointToolData::default();
+    data.push_keypoint("nose".to_string())?;
+    data.push_keypoint("left_eye".to_string())?;
+    data.push_keypoint("right_eye".to_string())?;
+    assert!(data.push_keypoint("nose".to_string()).is_err());
+    data.push_edge(0, 1)?;
+    data.push_edge(0, 2)?;
+    assert!(data.push_edge(1, 0).is_err());
+    assert!(data.push_edge(1, 1).is_err());
+    assert!(data.push_edge(1, 3).is_err());
+    let annos = data.get_annos_mut("a.png", Shape::new(10, 10));
+    let inst_idx = annos.add_instance(3);
+    let kp = Keypoint {
+        pos: PtI { x: 5, y: 5 },
+        visibility: Visibility::Visible,
+    };
+    annos.set_keypoint(inst_idx, 2, kp);
+    data.remove_keypoint(1);
+    assert_eq!(data.skeleton().edges(), &vec![(0, 1)]);
+    assert_eq!(
+        data.skeleton().keypoint_names(),
+        &vec!["nose".to_string(), "right_eye".to_string()]
+    );
+    let annos = data.get_annos("a.png").unwrap();
+    assert_eq!(annos.instances()[0], vec![Keypoint::default(), kp]);
+    data.push_keypoint("left_eye".to_string())?;
+    let annos = data.get_annos("a.png").unwrap();
+    assert_eq!(annos.instances()[0].len(), 3);
+    assert_eq!(data.n_instances(), 1);
+    Ok(())
+}
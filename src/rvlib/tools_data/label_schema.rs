@@ -0,0 +1,147 @@
+use std::{collections::HashSet, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations::AttrDef,
+    file_util,
+    result::{to_rv, RvError, RvResult},
+    rverr,
+};
+
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct SchemaLabel {
+    pub name: String,
+    /// Coco category id
+    #[serde(default)]
+    pub id: Option<u32>,
+    #[serde(default)]
+    pub color: Option<[u8; 3]>,
+    #[serde(default)]
+    pub attributes: Vec<AttrDef>,
+}
+
+/// Fixed list of labels a team agrees on, e.g.,
+/// ```toml
+/// [[labels]]
+/// name = "car"
+/// id = 3
+/// color = [255, 0, 0]
+/// ```
+#[derive(Deserialize, Serialize, Clone, Debug, Default, PartialEq, Eq)]
+pub struct LabelSchema {
+    pub labels: Vec<SchemaLabel>,
+}
+
+impl LabelSchema {
+    pub fn from_json_str(s: &str) -> RvResult<Self> {
+        let schema: Self = serde_json::from_str(s).map_err(to_rv)?;
+        schema.validate()
+    }
+
+    pub fn from_toml_str(s: &str) -> RvResult<Self> {
+        let schema: Self = toml::from_str(s).map_err(to_rv)?;
+        schema.validate()
+    }
+
+    /// One label name per line as in `classes.txt` of Yolo datasets, lines starting with `#` are
+    /// ignored.
+    pub fn from_classes_txt(s: &str) -> RvResult<Self> {
+        let labels = s
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(|name| SchemaLabel {
+                name: name.to_string(),
+                ..Default::default()
+            })
+            .collect();
+        Self { labels }.validate()
+    }
+
+    /// Reads the schema depending on the file extension, `json`, `toml`, or `txt`.
+    pub fn read<P: AsRef<Path>>(path: P) -> RvResult<Self> {
+        let path = path.as_ref();
+        let content = file_util::read_to_string(path)?;
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Self::from_json_str(&content),
+            Some("toml") => Self::from_toml_str(&content),
+            Some("txt") => Self::from_classes_txt(&content),
+            _ => Err(rverr!(
+                "label schema {:?} needs to be a json, toml, or txt file",
+                path
+            )),
+        }
+    }
+
+    fn validate(self) -> RvResult<Self> {
+        if self.labels.is_empty() {
+            return Err(RvError::new("label schema does not contain any labels"));
+        }
+        let mut names = HashSet::new();
+        let mut ids = HashSet::new();
+        let mut colors = HashSet::new();
+        for label in &self.labels {
+            if label.name.is_empty() {
+                return Err(RvError::new("label schema contains an empty label name"));
+            }
+            if !names.insert(&label.name) {
+                return Err(rverr!("label '{}' is not unique in schema", label.name));
+            }
+            if let Some(id) = label.id {
+                if !ids.insert(id) {
+                    return Err(rverr!("id {} is not unique in schema", id));
+                }
+            }
+            if let Some(color) = label.color {
+                if !colors.insert(color) {
+                    return Err(rverr!("color {:?} is not unique in schema", color));
+                }
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn contains(&self, label: &str) -> bool {
+        self.labels.iter().any(|l| l.name == label)
+    }
+}
+
+#[test]
+fn test_schema() -> RvResult<()> {
+    let from_toml = LabelSchema::from_toml_str(
+        r#"
+        [[labels]]
+        name = "car"
+        id = 3
+        color = [255, 0, 0]
+        [[labels]]
+        name = "person"
+        [[labels.attributes]]
+        name = "occluded"
+        default = false
+        "#,
+    )?;
+    let from_json = LabelSchema::from_json_str(
+        r#"{"labels": [
+            {"name": "car", "id": 3, "color": [255, 0, 0]},
+            {"name": "person", "attributes": [{"name": "occluded", "default": false}]}
+        ]}"#,
+    )?;
+    assert_eq!(from_toml, from_json);
+    assert_eq!(from_toml.labels[1].attributes[0].name, "occluded");
+    let from_txt = LabelSchema::from_classes_txt("# classes\ncar\n\nperson\n")?;
+    assert_eq!(
+        from_txt.labels.iter().map(|l| &l.name).collect::<Vec<_>>(),
+        vec!["car", "person"]
+    );
+    assert!(from_txt.contains("car"));
+    assert!(!from_txt.contains("bicycle"));
+    assert!(LabelSchema::from_classes_txt("car\ncar").is_err());
+    assert!(LabelSchema::from_classes_txt("").is_err());
+    assert!(LabelSchema::from_json_str(
+        r#"{"labels": [{"name": "a", "id": 1}, {"name": "b", "id": 1}]}"#
+    )
+    .is_err());
+    Ok(())
+}
@@ -0,0 +1,324 @@
+//! Export of the annotations as masks for segmentation trainers and as overlays for reports. Per
+//! image, we write
+//! * a semantic mask with the label index plus 1 per pixel and 0 for the background,
+//! * an instance mask with the annotation index plus 1 per pixel as 16-bit png, and
+//! * optionally the image with the annotations and their labels burned in.
+use std::path::{Path, PathBuf};
+
+use image::{DynamicImage, GrayImage, ImageBuffer, Luma, Rgb, RgbImage};
+use imageproc::{
+    drawing::{draw_filled_rect_mut, draw_text_mut, text_size},
+    pixelops::interpolate,
+    rect::Rect,
+};
+use rusttype::{Font, Scale};
+
+use crate::{
+    cfg::Cfg,
+    domain::{Shape, BB},
+    file_util::{self, MetaData},
+    image_reader::ReaderFromCfg,
+    image_util::draw_on_image,
+    result::{to_rv, RvError, RvResult},
+    rverr, GeoFig,
+};
+
+use super::{
+    annotations::BboxAnnotations, coco_io::meta_data_to_export_folder, image_export::read_image,
+    BboxSpecificData,
+};
+
+pub type InstanceMask = ImageBuffer<Luma<u16>, Vec<u16>>;
+
+const FONT_BYTES: &[u8] = include_bytes!("../../../resources/Roboto/Roboto-Bold.ttf");
+
+/// Annotations of one image with the labels and colors of their categories
+#[derive(Clone, Debug)]
+pub struct MaskJob {
+    pub file_path: String,
+    /// name of the image in the export that is unique among all jobs
+    pub name: String,
+    pub annos: BboxAnnotations,
+    pub shape: Shape,
+}
+
+/// Labels and colors are indexed by the category index of the annotations
+#[derive(Clone, Debug, Default)]
+pub struct MaskStyle {
+    pub labels: Vec<String>,
+    pub colors: Vec<[u8; 3]>,
+    pub fill_alpha: u8,
+    pub with_overlays: bool,
+}
+
+/// Pixels whose center is covered by the geometry
+fn pixels_inside(geo: &GeoFig, shape: Shape) -> impl Iterator<Item = (u32, u32)> + '_ {
+    let bb = geo.enclosing_bb().intersect(BB::from_shape(shape));
+    bb.y_range()
+        .flat_map(move |y| bb.x_range().map(move |x| (x, y)))
+        .filter(|(x, y)| geo.contains((*x as f32 + 0.5, *y as f32 + 0.5)))
+}
+
+/// Corners of the box or vertices of the polygon, the first point is repeated at the end
+fn boundary(geo: &GeoFig) -> Vec<(u32, u32)> {
+    let mut points = match geo {
+        GeoFig::BB(bb) => bb.points_iter().map(|p| (p.x, p.y)).collect::<Vec<_>>(),
+        GeoFig::Poly(poly) => poly.points_iter().map(|p| (p.x, p.y)).collect::<Vec<_>>(),
+    };
+    if let Some(first) = points.first() {
+        points.push(*first);
+    }
+    points
+}
+
+/// Later annotations cover earlier ones. Fails for label indices that do not fit into a mask
+/// value.
+pub fn semantic_mask(annos: &BboxAnnotations, shape: Shape) -> RvResult<GrayImage> {
+    let mut mask = GrayImage::new(shape.w, shape.h);
+    for (geo, cat_idx) in annos.geos().iter().zip(annos.cat_idxs().iter()) {
+        let value = u8::try_from(*cat_idx + 1).map_err(|_| {
+            rverr!(
+                "semantic masks support at most {} labels, found label index {}",
+                u8::MAX,
+                cat_idx
+            )
+        })?;
+        for (x, y) in pixels_inside(geo, shape) {
+            mask.put_pixel(x, y, Luma([value]));
+        }
+    }
+    Ok(mask)
+}
+
+/// Later annotations cover earlier ones
+pub fn instance_mask(annos: &BboxAnnotations, shape: Shape) -> InstanceMask {
+    let mut mask = InstanceMask::new(shape.w, shape.h);
+    for (idx, geo) in annos.geos().iter().enumerate() {
+        for (x, y) in pixels_inside(geo, shape) {
+            mask.put_pixel(x, y, Luma([(idx + 1) as u16]));
+        }
+    }
+    mask
+}
+
+/// Draws the annotations with their labels above them onto the image
+pub fn overlay(
+    im: &DynamicImage,
+    annos: &BboxAnnotations,
+    style: &MaskStyle,
+) -> RvResult<RgbImage> {
+    let font =
+        Font::try_from_bytes(FONT_BYTES).ok_or_else(|| RvError::new("could not load font"))?;
+    let shape = Shape::from_im(im);
+    let scale = Scale::uniform((shape.h as f32 / 40.0).max(12.0));
+    let alpha = style.fill_alpha as f32 / 255.0;
+    let mut im = im.to_rgb8();
+    for (geo, cat_idx) in annos.geos().iter().zip(annos.cat_idxs().iter()) {
+        let color = Rgb(style.colors[*cat_idx]);
+        im = draw_on_image(
+            im,
+            boundary(geo).into_iter(),
+            pixels_inside(geo, shape),
+            &color,
+            |rgb| interpolate(color, *rgb, alpha),
+        );
+        let label = &style.labels[*cat_idx];
+        let (w, h) = text_size(scale, &font, label);
+        let bb = geo.enclosing_bb();
+        // labels are put above the annotation if there is space
+        let y = if bb.y as i32 >= h {
+            bb.y as i32 - h
+        } else {
+            bb.y as i32
+        };
+        draw_filled_rect_mut(
+            &mut im,
+            Rect::at(bb.x as i32, y).of_size(w.max(1) as u32, h.max(1) as u32),
+            color,
+        );
+        let [r, g, b] = style.colors[*cat_idx];
+        let is_bright = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32 > 127.0;
+        let text_color = if is_bright {
+            Rgb([0, 0, 0])
+        } else {
+            Rgb([255, 255, 255])
+        };
+        draw_text_mut(&mut im, text_color, bb.x as i32, y, scale, &font, label);
+    }
+    Ok(im)
+}
+
+fn mask_path(folder: &Path, kind: &str, name: &str) -> PathBuf {
+    folder.join(kind).join(format!("{name}.png"))
+}
+
+fn save(im: &DynamicImage, path: &Path) -> RvResult<()> {
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent).map_err(to_rv)?;
+    }
+    im.save(path).map_err(to_rv)
+}
+
+/// One job per annotated image
+pub fn mask_jobs(bbox_data: &BboxSpecificData) -> Vec<MaskJob> {
+    let mut jobs = bbox_data
+        .anno_iter()
+        .filter(|(_, (annos, _))| !annos.geos().is_empty())
+        .map(|(file_path, (annos, shape))| MaskJob {
+            file_path: file_path.clone(),
+            name: String::new(),
+            annos: annos.clone(),
+            shape: *shape,
+        })
+        .collect::<Vec<_>>();
+    jobs.sort_by(|j1, j2| j1.file_path.cmp(&j2.file_path));
+    let file_paths = jobs
+        .iter()
+        .map(|j| j.file_path.as_str())
+        .collect::<Vec<_>>();
+    let names = file_util::flat_names(&file_paths);
+    for (job, name) in jobs.iter_mut().zip(names) {
+        job.name = name;
+    }
+    jobs
+}
+
+/// Writes the masks into `folder/semantic` and `folder/instances` and the overlays into
+/// `folder/overlays`. Images are only read for overlays. Returns the number of written files.
+pub fn export_masks(
+    cfg: Cfg,
+    jobs: &[MaskJob],
+    folder: &Path,
+    style: &MaskStyle,
+) -> RvResult<usize> {
+    let mut reader = if style.with_overlays {
+        Some(ReaderFromCfg::from_cfg(cfg)?)
+    } else {
+        None
+    };
+    let mut n_written = 0;
+    for job in jobs {
+        let semantic = semantic_mask(&job.annos, job.shape)?;
+        save(
+            &DynamicImage::ImageLuma8(semantic),
+            &mask_path(folder, "semantic", &job.name),
+        )?;
+        let instances = instance_mask(&job.annos, job.shape);
+        save(
+            &DynamicImage::ImageLuma16(instances),
+            &mask_path(folder, "instances", &job.name),
+        )?;
+        n_written += 2;
+        if let Some(reader) = &mut reader {
+            let im = read_image(reader, &job.file_path)?;
+            let im = overlay(&im, &job.annos, style)?;
+            save(
+                &DynamicImage::ImageRgb8(im),
+                &mask_path(folder, "overlays", &job.name),
+            )?;
+            n_written += 1;
+        }
+    }
+    Ok(n_written)
+}
+
+/// Folder in the export folder with the masks and a `classes.txt` that contains the label of
+/// mask value `i` in line `i`, starting with the background
+pub fn masks_folder(meta_data: &MetaData, labels: &[String]) -> RvResult<PathBuf> {
+    if labels.len() > u8::MAX as usize {
+        return Err(rverr!(
+            "semantic masks support at most {} labels, found {}",
+            u8::MAX,
+            labels.len()
+        ));
+    }
+    let folder = meta_data_to_export_folder(meta_data, "masks")?;
+    std::fs::create_dir_all(&folder).map_err(to_rv)?;
+    let classes = ["background".to_string()]
+        .iter()
+        .chain(labels.iter())
+        .cloned()
+        .collect::<Vec<_>>()
+        .join("\n");
+    file_util::write(folder.join("classes.txt"), classes)?;
+    Ok(folder)
+}
+
+#[cfg(test)]
+use crate::Polygon;
+
+#[cfg(test)]
+fn make_test_annos() -> BboxAnnotations {
+    let mut annos = BboxAnnotations::default();
+    annos.add_bb(BB::from_arr(&[1, 1, 4, 3]), 1);
+    let poly = Polygon::from_vec(vec![(4, 0).into(), (8, 0).into(), (8, 4).into()], false).unwrap();
+    annos.add_geo(GeoFig::Poly(poly), 0);
+    annos
+}
+
+#[test]
+fn test_masks() -> RvResult<()> {
+    let annos = make_test_annos();
+    let shape = Shape::new(10, 6);
+    let semantic = semantic_mask(&annos, shape)?;
+    assert_eq!(semantic.get_pixel(0, 0)[0], 0);
+    assert_eq!(semantic.get_pixel(1, 1)[0], 2);
+    assert_eq!(semantic.get_pixel(4, 3)[0], 2);
+    assert_eq!(semantic.get_pixel(5, 3)[0], 0);
+    // the polygon covers the box and the upper right triangle of its enclosing box
+    assert_eq!(semantic.get_pixel(4, 1)[0], 2);
+    assert_eq!(semantic.get_pixel(7, 1)[0], 1);
+    assert_eq!(semantic.get_pixel(7, 2)[0], 1);
+    assert_eq!(semantic.get_pixel(6, 3)[0], 0);
+    let instances = instance_mask(&annos, shape);
+    assert_eq!(instances.get_pixel(2, 2)[0], 1);
+    assert_eq!(instances.get_pixel(7, 1)[0], 2);
+    assert_eq!(instances.get_pixel(9, 5)[0], 0);
+    // mask value 256 does not exist
+    let mut annos = BboxAnnotations::default();
+    annos.add_bb(BB::from_arr(&[1, 1, 4, 3]), 254);
+    assert_eq!(semantic_mask(&annos, shape)?.get_pixel(1, 1)[0], 255);
+    annos.add_bb(BB::from_arr(&[1, 1, 4, 3]), 255);
+    assert!(semantic_mask(&annos, shape).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_overlay() -> RvResult<()> {
+    let mut annos = BboxAnnotations::default();
+    annos.add_bb(BB::from_arr(&[5, 20, 20, 8]), 1);
+    let style = MaskStyle {
+        labels: vec!["a".to_string(), "b".to_string()],
+        colors: vec![[255, 0, 0], [0, 0, 255]],
+        fill_alpha: 255,
+        with_overlays: true,
+    };
+    let im = DynamicImage::new_rgb8(40, 30);
+    let overlaid = overlay(&im, &annos, &style)?;
+    assert_eq!((overlaid.width(), overlaid.height()), (40, 30));
+    assert_eq!(overlaid.get_pixel(20, 25), &Rgb([0, 0, 255]));
+    assert_eq!(overlaid.get_pixel(35, 25), &Rgb([0, 0, 0]));
+    // the label is drawn above the box
+    assert_ne!(overlaid.get_pixel(5, 19), &Rgb([0, 0, 0]));
+    assert_eq!(
+        mask_path(Path::new("masks"), "semantic", "im_jpg"),
+        Path::new("masks").join("semantic").join("im_jpg.png")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_mask_names() {
+    let mut bbox_data = BboxSpecificData::new();
+    let shape = Shape::new(10, 6);
+    for file_path in ["x/a/im.png", "x/b/im.png", "x/b/im.jpg"] {
+        bbox_data
+            .get_annos_mut(file_path, shape)
+            .add_bb(BB::from_arr(&[1, 1, 4, 3]), 0);
+    }
+    let names = mask_jobs(&bbox_data)
+        .into_iter()
+        .map(|job| job.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["a_im_png", "b_im_jpg", "b_im_png"]);
+}
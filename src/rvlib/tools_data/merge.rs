@@ -0,0 +1,328 @@
+//! Merging of two labeled projects, e.g., from different annotators, and a report on the images
+//! where the annotators disagree.
+use std::{
+    collections::{HashMap, HashSet},
+    mem,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    annotations::{Attributes, BboxAnnotations, Provenance},
+    domain::{GeoFig, Shape},
+    result::{RvError, RvResult},
+};
+
+use super::{BboxExportData, BboxSpecificData};
+
+#[derive(Deserialize, Serialize, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MergeMode {
+    /// geometries of both projects, matching geometries are kept once
+    #[default]
+    Union,
+    /// only geometries that match and have the same label
+    Intersection,
+}
+
+#[derive(Deserialize, Serialize, Default, Clone, Debug, PartialEq, Eq)]
+pub struct ImageDiff {
+    pub file_path: String,
+    /// matching geometries with the same label
+    pub n_matches: usize,
+    /// matching geometries with different labels
+    pub n_label_mismatches: usize,
+    pub n_only_first: usize,
+    pub n_only_second: usize,
+}
+impl ImageDiff {
+    pub fn is_disagreement(&self) -> bool {
+        self.n_label_mismatches + self.n_only_first + self.n_only_second > 0
+    }
+}
+
+/// Greedily matches geometries by the IoU of their enclosing boxes starting with the highest IoU.
+/// Returns for each geometry of `geos1` the index of its match in `geos2`.
+fn match_geos(geos1: &[GeoFig], geos2: &[GeoFig], iou_threshold: f64) -> Vec<Option<usize>> {
+    let mut pairs = geos1
+        .iter()
+        .enumerate()
+        .flat_map(|(i1, g1)| {
+            geos2
+                .iter()
+                .enumerate()
+                .map(move |(i2, g2)| (i1, i2, g1.enclosing_bb().iou(&g2.enclosing_bb())))
+        })
+        .filter(|(_, _, iou)| *iou > 0.0 && *iou >= iou_threshold)
+        .collect::<Vec<_>>();
+    pairs.sort_by(|(_, _, iou1), (_, _, iou2)| iou2.total_cmp(iou1));
+    let mut matches = vec![None; geos1.len()];
+    let mut is_matched2 = vec![false; geos2.len()];
+    for (i1, i2, _) in pairs {
+        if matches[i1].is_none() && !is_matched2[i2] {
+            matches[i1] = Some(i2);
+            is_matched2[i2] = true;
+        }
+    }
+    matches
+}
+
+fn take_attributes(
+    attributes: &mut HashMap<String, Vec<Attributes>>,
+    file_path: &str,
+    n_geos: usize,
+) -> Vec<Attributes> {
+    attributes
+        .remove(file_path)
+        .unwrap_or_else(|| vec![Attributes::new(); n_geos])
+}
+
+fn take_provenances(
+    provenances: &mut HashMap<String, Vec<Provenance>>,
+    file_path: &str,
+    n_geos: usize,
+) -> Vec<Provenance> {
+    provenances
+        .remove(file_path)
+        .unwrap_or_else(|| vec![Provenance::default(); n_geos])
+}
+
+fn take_track_ids(
+    track_ids: &mut HashMap<String, Vec<Option<u32>>>,
+    file_path: &str,
+    n_geos: usize,
+) -> Vec<Option<u32>> {
+    track_ids
+        .remove(file_path)
+        .unwrap_or_else(|| vec![None; n_geos])
+}
+
+type ImageAnnos = (
+    Vec<GeoFig>,
+    Vec<usize>,
+    Vec<Attributes>,
+    Vec<Provenance>,
+    Vec<Option<u32>>,
+);
+
+fn merge_image(
+    (geos1, cats1, attrs1, provs1, tracks1): ImageAnnos,
+    (geos2, cats2, attrs2, provs2, tracks2): ImageAnnos,
+    mode: MergeMode,
+    iou_threshold: f64,
+) -> RvResult<(BboxAnnotations, ImageDiff)> {
+    let matches = match_geos(&geos1, &geos2, iou_threshold);
+    let mut diff = ImageDiff::default();
+    let mut geos = vec![];
+    let mut cats = vec![];
+    let mut attrs = vec![];
+    let mut provs = vec![];
+    let mut tracks = vec![];
+    for (((((geo1, cat1), attr1), prov1), track1), m) in geos1
+        .into_iter()
+        .zip(cats1)
+        .zip(attrs1)
+        .zip(provs1)
+        .zip(tracks1)
+        .zip(matches.iter())
+    {
+        let is_kept = match m {
+            Some(i2) if cats2[*i2] == cat1 => {
+                diff.n_matches += 1;
+                true
+            }
+            Some(_) => {
+                diff.n_label_mismatches += 1;
+                mode == MergeMode::Union
+            }
+            None => {
+                diff.n_only_first += 1;
+                mode == MergeMode::Union
+            }
+        };
+        if is_kept {
+            geos.push(geo1);
+            cats.push(cat1);
+            attrs.push(attr1);
+            provs.push(prov1);
+            tracks.push(track1);
+        }
+    }
+    let matched2 = matches.iter().flatten().copied().collect::<HashSet<_>>();
+    for (i2, ((((geo2, cat2), attr2), prov2), track2)) in geos2
+        .into_iter()
+        .zip(cats2)
+        .zip(attrs2)
+        .zip(provs2)
+        .zip(tracks2)
+        .enumerate()
+    {
+        if !matched2.contains(&i2) {
+            diff.n_only_second += 1;
+            if mode == MergeMode::Union {
+                geos.push(geo2);
+                cats.push(cat2);
+                attrs.push(attr2);
+                provs.push(prov2);
+                tracks.push(track2);
+            }
+        }
+    }
+    let mut annos = BboxAnnotations::from_bbs_cats(geos, cats);
+    annos.set_attributes(attrs)?;
+    annos.set_provenances(provs)?;
+    annos.set_track_ids(tracks)?;
+    Ok((annos, diff))
+}
+
+/// Merges the annotations of `data2` into `data1`. Labels are unified by name. Geometries of
+/// both projects match if the IoU of their enclosing boxes is at least `iou_threshold`. Matches
+/// are kept once with the label of `data1`. Returns the merged project and the images where both
+/// projects disagree.
+pub fn merge(
+    mut data1: BboxExportData,
+    data2: BboxExportData,
+    mode: MergeMode,
+    iou_threshold: f64,
+) -> RvResult<(BboxExportData, Vec<ImageDiff>)> {
+    let is_export_absolute = data1.is_export_absolute;
+    let annotations1 = mem::take(&mut data1.annotations);
+    let mut attributes1 = mem::take(&mut data1.attributes);
+    let mut provenances1 = mem::take(&mut data1.provenances);
+    let mut track_ids1 = mem::take(&mut data1.track_ids);
+    let mut predictions1 = mem::take(&mut data1.predictions);
+    let mut merged = BboxSpecificData::from_bbox_export_data(data1)?;
+    merged.options.export_absolute = is_export_absolute;
+    let idx_map = merged.unify_labels(&data2.labels, &data2.colors, &data2.cat_ids)?;
+    merged.unify_attribute_defs(&data2.attribute_defs);
+    // reviews of images that have not been reviewed in the first project
+    for (file_path, review) in data2.image_reviews {
+        if merged.image_review(&file_path).is_none() {
+            merged.set_image_review(&file_path, review);
+        }
+    }
+    let remap = |cats: Vec<usize>| cats.iter().map(|c| idx_map[*c]).collect::<Vec<_>>();
+    let mut annotations2 = data2.annotations;
+    let mut attributes2 = data2.attributes;
+    let mut provenances2 = data2.provenances;
+    let mut track_ids2 = data2.track_ids;
+
+    let mut diffs = vec![];
+    let mut add_merged_image = |file_path: String, annos1, annos2, shape: Shape| {
+        let (mut annos, mut diff) = merge_image(annos1, annos2, mode, iou_threshold)?;
+        // pending predictions are kept from the first project
+        if let Some(predictions) = predictions1.remove(&file_path) {
+            annos.set_predictions(predictions);
+        }
+        *merged.get_annos_mut(&file_path, shape) = annos;
+        if diff.is_disagreement() {
+            diff.file_path = file_path;
+            diffs.push(diff);
+        }
+        Ok::<(), RvError>(())
+    };
+    for (file_path, (geos1, cats1, shape)) in annotations1 {
+        let attrs1 = take_attributes(&mut attributes1, &file_path, geos1.len());
+        let provs1 = take_provenances(&mut provenances1, &file_path, geos1.len());
+        let tracks1 = take_track_ids(&mut track_ids1, &file_path, geos1.len());
+        let annos2 = annotations2
+            .remove(&file_path)
+            .map(|(geos, cats, _)| {
+                let attrs = take_attributes(&mut attributes2, &file_path, geos.len());
+                let provs = take_provenances(&mut provenances2, &file_path, geos.len());
+                let tracks = take_track_ids(&mut track_ids2, &file_path, geos.len());
+                (geos, remap(cats), attrs, provs, tracks)
+            })
+            .unwrap_or_default();
+        add_merged_image(
+            file_path,
+            (geos1, cats1, attrs1, provs1, tracks1),
+            annos2,
+            shape,
+        )?;
+    }
+    for (file_path, (geos2, cats2, shape)) in annotations2 {
+        let attrs2 = take_attributes(&mut attributes2, &file_path, geos2.len());
+        let provs2 = take_provenances(&mut provenances2, &file_path, geos2.len());
+        let tracks2 = take_track_ids(&mut track_ids2, &file_path, geos2.len());
+        add_merged_image(
+            file_path,
+            (vec![], vec![], vec![], vec![], vec![]),
+            (geos2, remap(cats2), attrs2, provs2, tracks2),
+            shape,
+        )?;
+    }
+    diffs.sort_by(|d1, d2| d1.file_path.cmp(&d2.file_path));
+    Ok((BboxExportData::from_bbox_data(merged), diffs))
+}
+
+/// Images where `data1` and `data2` disagree, see [`merge`].
+pub fn diff(
+    data1: BboxExportData,
+    data2: BboxExportData,
+    iou_threshold: f64,
+) -> RvResult<Vec<ImageDiff>> {
+    merge(data1, data2, MergeMode::Union, iou_threshold).map(|(_, diffs)| diffs)
+}
+
+#[cfg(test)]
+use crate::domain::BB;
+
+#[test]
+fn test_merge() -> RvResult<()> {
+    let shape = Shape::new(100, 100);
+    let bb = |x| GeoFig::BB(BB::from_arr(&[x, x, 10, 10]));
+    let bb_shifted = |x| GeoFig::BB(BB::from_arr(&[x + 1, x, 10, 10]));
+    let mut data1 = BboxExportData::from_bbox_data(BboxSpecificData::new());
+    data1.labels = vec!["cat".to_string(), "dog".to_string()];
+    data1.colors = vec![[255, 0, 0], [0, 255, 0]];
+    data1.cat_ids = vec![1, 2];
+    let mut data2 = data1.clone();
+    data2.labels = vec!["dog".to_string(), "mouse".to_string()];
+    data1
+        .annotations
+        .insert("agree.png".to_string(), (vec![bb(50)], vec![1], shape));
+    // cats of data2 refer to dog and mouse
+    data2.annotations.insert(
+        "agree.png".to_string(),
+        (vec![bb_shifted(50)], vec![0], shape),
+    );
+    data1
+        .annotations
+        .insert("disagree.png".to_string(), (vec![bb(0)], vec![1], shape));
+    data2.annotations.insert(
+        "disagree.png".to_string(),
+        (vec![bb(0), bb(50)], vec![1, 1], shape),
+    );
+    data2
+        .annotations
+        .insert("only2.png".to_string(), (vec![bb(0)], vec![1], shape));
+
+    let (union, diffs) = merge(data1.clone(), data2.clone(), MergeMode::Union, 0.5)?;
+    assert_eq!(union.labels, vec!["cat", "dog", "mouse"]);
+    let (geos, cats, _) = &union.annotations["agree.png"];
+    assert_eq!(geos, &vec![bb(50)]);
+    assert_eq!(cats, &vec![1]);
+    let (geos, cats, _) = &union.annotations["disagree.png"];
+    assert_eq!(geos, &vec![bb(0), bb(50)]);
+    assert_eq!(cats, &vec![1, 2]);
+    assert_eq!(union.annotations["only2.png"].1, vec![2]);
+    assert_eq!(diffs.len(), 2);
+    assert_eq!(
+        diffs[0],
+        ImageDiff {
+            file_path: "disagree.png".to_string(),
+            n_matches: 0,
+            n_label_mismatches: 1,
+            n_only_first: 0,
+            n_only_second: 1
+        }
+    );
+    assert_eq!(diffs[1].n_only_second, 1);
+
+    let (intersection, _) = merge(data1.clone(), data2.clone(), MergeMode::Intersection, 0.5)?;
+    assert_eq!(intersection.annotations["agree.png"].0, vec![bb(50)]);
+    assert!(intersection.annotations["disagree.png"].0.is_empty());
+    assert!(intersection.annotations["only2.png"].0.is_empty());
+    assert_eq!(diff(data1, data2, 0.99)?.len(), 3);
+    Ok(())
+}
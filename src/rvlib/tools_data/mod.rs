@@ -1,16 +1,26 @@
 use crate::{
-    drawme::{Annotation, Stroke},
+    drawme::{Annotation, BboxAnnotation, KeypointsAnnotation, Stroke},
     UpdateAnnos,
 };
 
 pub use self::{
     bbox_data::BboxExportData, bbox_data::BboxSpecificData, brush_data::BrushToolData,
-    coco_io::write_coco,
+    classify_data::ClassifyToolData, coco_io::write_coco, keypoint_data::KeypointToolData,
 };
 pub mod annotations;
 pub mod bbox_data;
 pub mod brush_data;
+pub mod classify_data;
 pub mod coco_io;
+pub mod data_split;
+pub mod image_export;
+pub mod image_review;
+pub mod keypoint_data;
+pub mod label_schema;
+pub mod mask_io;
+pub mod merge;
+pub mod mot_io;
+pub mod patch_io;
 
 macro_rules! variant_access {
     ($variant:ident, $func_name:ident, $self:ty, $return_type:ty) => {
@@ -28,12 +38,18 @@ macro_rules! variant_access {
 pub enum ToolSpecifics {
     Bbox(BboxSpecificData),
     Brush(BrushToolData),
+    Classify(ClassifyToolData),
+    Keypoint(KeypointToolData),
 }
 impl ToolSpecifics {
     variant_access!(Bbox, bbox, &Self, &BboxSpecificData);
     variant_access!(Brush, brush, &Self, &BrushToolData);
     variant_access!(Bbox, bbox_mut, &mut Self, &mut BboxSpecificData);
     variant_access!(Brush, brush_mut, &mut Self, &mut BrushToolData);
+    variant_access!(Classify, classify, &Self, &ClassifyToolData);
+    variant_access!(Classify, classify_mut, &mut Self, &mut ClassifyToolData);
+    variant_access!(Keypoint, keypoint, &Self, &KeypointToolData);
+    variant_access!(Keypoint, keypoint_mut, &mut Self, &mut KeypointToolData);
 
     pub fn to_annotations_view(&self, file_path: &str) -> UpdateAnnos {
         match &self {
@@ -49,19 +65,44 @@ impl ToolSpecifics {
                         .iter()
                         .zip(cats.iter())
                         .zip(selected_bbs.iter())
-                        .map(|((bb, cat_idx), is_selected)| Annotation {
-                            geofig: bb.clone(),
-                            fill_color: Some(colors[*cat_idx]),
-                            fill_alpha: bb_data.options.fill_alpha,
-                            label: Some(labels[*cat_idx].clone()),
-                            outline: Stroke {
-                                thickness: 1.0,
-                                color: colors[*cat_idx],
-                            },
-                            outline_alpha: bb_data.options.outline_alpha,
-                            is_selected: Some(*is_selected),
+                        .zip(annos.provenances().iter())
+                        .map(|(((bb, cat_idx), is_selected), provenance)| {
+                            Annotation::Bbox(BboxAnnotation {
+                                geofig: bb.clone(),
+                                fill_color: Some(colors[*cat_idx]),
+                                fill_alpha: bb_data.options.fill_alpha,
+                                label: Some(labels[*cat_idx].clone()),
+                                outline: Stroke {
+                                    thickness: 1.0,
+                                    color: colors[*cat_idx],
+                                    is_dashed: provenance.is_unreviewed_prediction(),
+                                },
+                                outline_alpha: bb_data.options.outline_alpha,
+                                is_selected: Some(*is_selected),
+                            })
                         })
                         .collect::<Vec<Annotation>>();
+                    let threshold = bb_data.score_threshold();
+                    let preds = annos
+                        .predictions()
+                        .iter()
+                        .filter(|pred| pred.score >= threshold)
+                        .map(|pred| {
+                            Annotation::Bbox(BboxAnnotation {
+                                geofig: pred.geo.clone(),
+                                fill_color: None,
+                                fill_alpha: 0,
+                                label: Some(format!("{} {:.2}", labels[pred.cat_idx], pred.score)),
+                                outline: Stroke {
+                                    thickness: 1.0,
+                                    color: colors[pred.cat_idx],
+                                    is_dashed: true,
+                                },
+                                outline_alpha: bb_data.options.outline_alpha / 2,
+                                is_selected: None,
+                            })
+                        });
+                    let bbs_colored = bbs_colored.into_iter().chain(preds).collect();
                     UpdateAnnos::Yes((bbs_colored, None))
                 } else {
                     UpdateAnnos::clear()
@@ -71,6 +112,28 @@ impl ToolSpecifics {
                 // TODO: draw polygon
                 UpdateAnnos::default()
             }
+            ToolSpecifics::Classify(_) => UpdateAnnos::default(),
+            ToolSpecifics::Keypoint(kp_data) => {
+                if let Some(annos) = kp_data.get_annos(file_path) {
+                    let edges = kp_data.skeleton().edges();
+                    let kp_annos = annos
+                        .instances()
+                        .iter()
+                        .enumerate()
+                        .map(|(inst_idx, instance)| {
+                            Annotation::Keypoints(KeypointsAnnotation {
+                                keypoints: instance.clone(),
+                                edges: edges.clone(),
+                                color: kp_data.color,
+                                is_selected: annos.selected() == Some(inst_idx),
+                            })
+                        })
+                        .collect::<Vec<Annotation>>();
+                    UpdateAnnos::Yes((kp_annos, None))
+                } else {
+                    UpdateAnnos::clear()
+                }
+            }
         }
     }
 }
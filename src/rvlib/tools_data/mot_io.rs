@@ -0,0 +1,206 @@
+//! Export of tracked boxes in the format of the MOT challenge, i.e., one line
+//! `frame,id,bb_left,bb_top,bb_width,bb_height,conf,x,y,z` per box.
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::{
+    file_util::{self, MetaData},
+    image_reader,
+    result::RvResult,
+    util::natural_cmp,
+};
+
+use super::{coco_io::meta_data_to_export_folder, BboxSpecificData};
+
+/// Folder part of the path, empty for paths without folder
+pub fn parent_folder(file_path: &str) -> &str {
+    file_path
+        .rfind(['/', '\\'])
+        .map(|idx| &file_path[..idx])
+        .unwrap_or("")
+}
+
+/// Files are grouped into sequences by their folders, frames of videos by their videos. Frames are
+/// numbered from 1. Frames of videos are numbered by their index in the video, other files by their
+/// position in natural order among all given files of their folder. Hence, all files of the folder
+/// need to be passed, not only the annotated ones.
+fn sequences<'a>(file_paths: &[&'a str]) -> BTreeMap<&'a str, Vec<(&'a str, u32)>> {
+    let mut sequences: BTreeMap<&str, Vec<&str>> = BTreeMap::new();
+    for fp in file_paths {
+        sequences.entry(parent_folder(fp)).or_default().push(fp);
+    }
+    sequences
+        .into_iter()
+        .map(|(folder, mut file_paths)| {
+            file_paths.sort_by(|fp1, fp2| natural_cmp(fp1, fp2));
+            file_paths.dedup();
+            let frames = file_paths
+                .into_iter()
+                .enumerate()
+                .map(|(idx, fp)| {
+                    let frame_idx = image_reader::parse_frame_path(fp)
+                        .map(|(_, frame_idx)| frame_idx)
+                        .unwrap_or(idx);
+                    (fp, frame_idx as u32 + 1)
+                })
+                .collect();
+            (folder, frames)
+        })
+        .collect()
+}
+
+fn sequence_to_mot(bbox_data: &BboxSpecificData, frames: &[(&str, u32)]) -> String {
+    let mut lines = vec![];
+    for (file_path, frame) in frames {
+        if let Some(annos) = bbox_data.get_annos(file_path) {
+            for ((geo, track_id), provenance) in annos
+                .geos()
+                .iter()
+                .zip(annos.track_ids().iter())
+                .zip(annos.provenances().iter())
+            {
+                let bb = geo.enclosing_bb();
+                let track_id = track_id.map(|id| id as i64).unwrap_or(-1);
+                let conf = provenance.score.unwrap_or(1.0);
+                lines.push(format!(
+                    "{frame},{track_id},{},{},{},{},{conf},-1,-1,-1",
+                    bb.x, bb.y, bb.w, bb.h
+                ));
+            }
+        }
+    }
+    lines.join("\n")
+}
+
+/// One MOT file content per sequence folder with annotations, named after the folder. The frame
+/// numbers are derived from `file_paths`, i.e., all files of the opened folder, see [`sequences`].
+/// Annotations without track id get the id `-1`. The confidence is the score of the annotation or 1
+/// for annotations without score.
+pub fn to_mot(bbox_data: &BboxSpecificData, file_paths: &[&str]) -> Vec<(String, String)> {
+    // annotated files that are not listed anymore are kept
+    let file_paths = file_paths
+        .iter()
+        .copied()
+        .chain(bbox_data.anno_iter().map(|(fp, _)| fp.as_str()))
+        .collect::<Vec<_>>();
+    let mut sequences = sequences(&file_paths);
+    sequences.retain(|_, frames| {
+        frames
+            .iter()
+            .any(|(fp, _)| bbox_data.get_annos(fp).is_some())
+    });
+    let folders = sequences.keys().copied().collect::<Vec<_>>();
+    file_util::flat_names(&folders)
+        .into_iter()
+        .zip(sequences.values())
+        .map(|(name, frames)| {
+            let name = if name.is_empty() {
+                "sequence".to_string()
+            } else {
+                name
+            };
+            (name, sequence_to_mot(bbox_data, frames))
+        })
+        .collect()
+}
+
+/// Writes one MOT file per sequence into a folder in the export folder.
+pub fn write_mot(
+    meta_data: &MetaData,
+    bbox_data: &BboxSpecificData,
+    file_paths: &[&str],
+) -> RvResult<PathBuf> {
+    let folder = meta_data_to_export_folder(meta_data, "mot")?;
+    for (name, mot) in to_mot(bbox_data, file_paths) {
+        file_util::write(folder.join(format!("{name}.txt")), mot)?;
+    }
+    println!("exported mot tracks to {folder:?}");
+    Ok(folder)
+}
+
+#[cfg(test)]
+use crate::{domain::Shape, GeoFig, BB};
+
+#[test]
+fn test_sequences() {
+    assert_eq!(
+        sequences(&["s2/f10.png", "s1/b.png", "s2/f2.png", "s1/a.png"]),
+        BTreeMap::from([
+            ("s1", vec![("s1/a.png", 1), ("s1/b.png", 2)]),
+            ("s2", vec![("s2/f2.png", 1), ("s2/f10.png", 2)])
+        ])
+    );
+    assert_eq!(
+        sequences(&["f.png"]),
+        BTreeMap::from([("", vec![("f.png", 1)])])
+    );
+}
+
+#[test]
+fn test_to_mot() {
+    let mut bbox_data = BboxSpecificData::new();
+    let shape = Shape::new(100, 100);
+    let annos = bbox_data.get_annos_mut("x/seq1/frame_2.png", shape);
+    annos.add_tracked_geo(GeoFig::BB(BB::from_arr(&[10, 20, 30, 40])), 0, 5);
+    annos.add_bb(BB::from_arr(&[0, 0, 5, 5]), 0);
+    bbox_data
+        .get_annos_mut("x/seq1/frame_1.png", shape)
+        .add_tracked_geo(GeoFig::BB(BB::from_arr(&[8, 20, 30, 40])), 0, 5);
+    bbox_data
+        .get_annos_mut("x/seq2/frame_1.png", shape)
+        .add_tracked_geo(GeoFig::BB(BB::from_arr(&[1, 2, 3, 4])), 0, 6);
+    assert_eq!(
+        to_mot(&bbox_data, &[]),
+        vec![
+            (
+                "seq1".to_string(),
+                "1,5,8,20,30,40,1,-1,-1,-1\n2,5,10,20,30,40,1,-1,-1,-1\n2,-1,0,0,5,5,1,-1,-1,-1"
+                    .to_string()
+            ),
+            ("seq2".to_string(), "1,6,1,2,3,4,1,-1,-1,-1".to_string())
+        ]
+    );
+}
+
+#[test]
+fn test_to_mot_gaps() {
+    let mut bbox_data = BboxSpecificData::new();
+    let shape = Shape::new(100, 100);
+    for fp in ["seq/frame_1.png", "seq/frame_4.png"] {
+        bbox_data.get_annos_mut(fp, shape).add_tracked_geo(
+            GeoFig::BB(BB::from_arr(&[1, 2, 3, 4])),
+            0,
+            5,
+        );
+    }
+    // frames 2 and 3 have not been annotated and the folder other has no annotations at all
+    let file_paths = [
+        "seq/frame_1.png",
+        "seq/frame_2.png",
+        "seq/frame_3.png",
+        "seq/frame_4.png",
+        "other/frame_1.png",
+    ];
+    assert_eq!(
+        to_mot(&bbox_data, &file_paths),
+        vec![(
+            "seq".to_string(),
+            "1,5,1,2,3,4,1,-1,-1,-1\n4,5,1,2,3,4,1,-1,-1,-1".to_string()
+        )]
+    );
+}
+
+#[cfg(feature = "ffmpeg")]
+#[test]
+fn test_sequences_video() {
+    // frames of videos are numbered by their index in the video, e.g., with a stride of 10
+    assert_eq!(
+        sequences(&["v/clip.mp4/clip_000000.png", "v/clip.mp4/clip_000010.png"]),
+        BTreeMap::from([(
+            "v/clip.mp4",
+            vec![
+                ("v/clip.mp4/clip_000000.png", 1),
+                ("v/clip.mp4/clip_000010.png", 11)
+            ]
+        )])
+    );
+}
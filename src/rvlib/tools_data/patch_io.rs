@@ -0,0 +1,198 @@
+//! Export of annotated objects as image patches in the ImageFolder layout, i.e., one folder per
+//! label that contains the patches `<image>_<idx>.png` of the label. The image part of the name
+//! contains the folders and the extension of the image such that names do not collide.
+use std::path::{Path, PathBuf};
+
+use image::{imageops::FilterType, DynamicImage};
+
+use crate::{
+    cfg::Cfg,
+    domain::{Shape, BB},
+    file_util::{self, MetaData},
+    image_reader::ReaderFromCfg,
+    result::{to_rv, RvResult},
+};
+
+use super::{coco_io::meta_data_to_export_folder, image_export::read_image, BboxSpecificData};
+
+/// Patches of one image, i.e., the boxes to cut out and their labels
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct PatchJob {
+    pub file_path: String,
+    /// name of the image in the export that is unique among all jobs
+    pub name: String,
+    pub patches: Vec<(BB, String)>,
+}
+
+/// Enclosing box of the annotation expanded by `padding` in each direction and clipped to the
+/// image
+fn padded_bb(bb: BB, padding: u32, shape: Shape) -> BB {
+    let padding = padding as i32;
+    BB::new_fit_to_image(
+        bb.x as i32 - padding,
+        bb.y as i32 - padding,
+        bb.w as i32 + 2 * padding,
+        bb.h as i32 + 2 * padding,
+        shape,
+    )
+}
+
+/// One job per annotated image, the boxes of polygons are their enclosing boxes
+pub fn patch_jobs(bbox_data: &BboxSpecificData, padding: u32) -> Vec<PatchJob> {
+    let mut jobs = bbox_data
+        .anno_iter()
+        .filter(|(_, (annos, _))| !annos.geos().is_empty())
+        .map(|(file_path, (annos, shape))| PatchJob {
+            file_path: file_path.clone(),
+            name: String::new(),
+            patches: annos
+                .geos()
+                .iter()
+                .zip(annos.cat_idxs().iter())
+                .map(|(geo, cat_idx)| {
+                    (
+                        padded_bb(geo.enclosing_bb(), padding, *shape),
+                        bbox_data.labels()[*cat_idx].clone(),
+                    )
+                })
+                .collect(),
+        })
+        .collect::<Vec<_>>();
+    jobs.sort_by(|j1, j2| j1.file_path.cmp(&j2.file_path));
+    let file_paths = jobs
+        .iter()
+        .map(|j| j.file_path.as_str())
+        .collect::<Vec<_>>();
+    let names = file_util::flat_names(&file_paths);
+    for (job, name) in jobs.iter_mut().zip(names) {
+        job.name = name;
+    }
+    jobs
+}
+
+/// Labels are folder names, hence, we replace characters that are not allowed in paths
+fn label_to_folder_name(label: &str) -> String {
+    label.replace(['/', '\\', ':', '*', '?', '"', '<', '>', '|'], "_")
+}
+
+fn patch_path(folder: &Path, label: &str, name: &str, idx: usize) -> PathBuf {
+    folder
+        .join(label_to_folder_name(label))
+        .join(format!("{name}_{idx}.png"))
+}
+
+/// Cuts the box out of the image, a `size` larger than 0 resizes the patch to `size`x`size`
+fn crop_patch(im: &DynamicImage, bb: BB, size: u32) -> Option<DynamicImage> {
+    let bb = BB::new_fit_to_image(
+        bb.x as i32,
+        bb.y as i32,
+        bb.w as i32,
+        bb.h as i32,
+        Shape::new(im.width(), im.height()),
+    );
+    if bb.w == 0 || bb.h == 0 {
+        return None;
+    }
+    let patch = im.crop_imm(bb.x, bb.y, bb.w, bb.h);
+    Some(if size > 0 {
+        patch.resize_exact(size, size, FilterType::Triangle)
+    } else {
+        patch
+    })
+}
+
+/// Reads the images with the reader of the configuration and writes their patches into `folder`.
+/// Returns the number of written patches.
+pub fn export_patches(cfg: Cfg, jobs: &[PatchJob], folder: &Path, size: u32) -> RvResult<usize> {
+    let mut reader = ReaderFromCfg::from_cfg(cfg)?;
+    let mut n_written = 0;
+    for job in jobs {
+        let im = read_image(&mut reader, &job.file_path)?;
+        for (idx, (bb, label)) in job.patches.iter().enumerate() {
+            if let Some(patch) = crop_patch(&im, *bb, size) {
+                let path = patch_path(folder, label, &job.name, idx);
+                if let Some(parent) = path.parent() {
+                    std::fs::create_dir_all(parent).map_err(to_rv)?;
+                }
+                patch.save(&path).map_err(to_rv)?;
+                n_written += 1;
+            }
+        }
+    }
+    Ok(n_written)
+}
+
+/// Folder in the export folder that contains one folder per label
+pub fn patches_folder(meta_data: &MetaData) -> RvResult<PathBuf> {
+    meta_data_to_export_folder(meta_data, "patches")
+}
+
+#[cfg(test)]
+use crate::{GeoFig, Polygon};
+
+#[test]
+fn test_patch_jobs() {
+    let mut bbox_data = BboxSpecificData::new();
+    bbox_data.push("car".to_string(), None, None).unwrap();
+    let shape = Shape::new(100, 50);
+    let annos = bbox_data.get_annos_mut("b.png", shape);
+    annos.add_bb(BB::from_arr(&[10, 10, 20, 20]), 1);
+    let poly =
+        Polygon::from_vec(vec![(0, 40).into(), (20, 49).into(), (5, 45).into()], false).unwrap();
+    annos.add_geo(GeoFig::Poly(poly), 0);
+    bbox_data
+        .get_annos_mut("a.png", shape)
+        .add_bb(BB::from_arr(&[95, 0, 5, 5]), 1);
+    bbox_data.get_annos_mut("empty.png", shape);
+    let jobs = patch_jobs(&bbox_data, 2);
+    let label0 = bbox_data.labels()[0].clone();
+    assert_eq!(
+        jobs,
+        vec![
+            PatchJob {
+                file_path: "a.png".to_string(),
+                name: "a_png".to_string(),
+                patches: vec![(BB::from_arr(&[93, 0, 7, 7]), "car".to_string())]
+            },
+            PatchJob {
+                file_path: "b.png".to_string(),
+                name: "b_png".to_string(),
+                patches: vec![
+                    (BB::from_arr(&[8, 8, 24, 24]), "car".to_string()),
+                    (BB::from_arr(&[0, 38, 22, 12]), label0)
+                ]
+            }
+        ]
+    );
+}
+
+#[test]
+fn test_crop_patch() -> RvResult<()> {
+    let im = DynamicImage::new_rgb8(40, 30);
+    let patch = crop_patch(&im, BB::from_arr(&[30, 20, 20, 20]), 0).unwrap();
+    assert_eq!((patch.width(), patch.height()), (10, 10));
+    let patch = crop_patch(&im, BB::from_arr(&[0, 0, 20, 10]), 16).unwrap();
+    assert_eq!((patch.width(), patch.height()), (16, 16));
+    assert!(crop_patch(&im, BB::from_arr(&[40, 0, 5, 5]), 0).is_none());
+    assert_eq!(
+        patch_path(Path::new("export"), "a/b", "im_png", 3),
+        Path::new("export").join("a_b").join("im_png_3.png")
+    );
+    Ok(())
+}
+
+#[test]
+fn test_patch_names() {
+    let mut bbox_data = BboxSpecificData::new();
+    let shape = Shape::new(100, 50);
+    for file_path in ["x/a/im.png", "x/b/im.png", "x/b/im.jpg"] {
+        bbox_data
+            .get_annos_mut(file_path, shape)
+            .add_bb(BB::from_arr(&[0, 0, 5, 5]), 0);
+    }
+    let names = patch_jobs(&bbox_data, 0)
+        .into_iter()
+        .map(|job| job.name)
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec!["a_im_png", "b_im_jpg", "b_im_png"]);
+}
@@ -49,6 +49,28 @@ pub fn natural_cmp(s1: &str, s2: &str) -> Ordering {
     s1.len().cmp(&s2.len())
 }
 
+/// Case-insensitive fuzzy match of `query` against `candidate`. Returns `None` if the characters
+/// of the query do not appear in order in the candidate. Otherwise, higher scores mean better
+/// matches, consecutive characters and matches at the start are preferred.
+pub fn fuzzy_score(query: &str, candidate: &str) -> Option<i64> {
+    let candidate = candidate.to_lowercase().chars().collect::<Vec<_>>();
+    let mut score = 0;
+    let mut cand_idx = 0;
+    let mut prev_match: Option<usize> = None;
+    for q in query.to_lowercase().chars().filter(|c| !c.is_whitespace()) {
+        let offset = candidate[cand_idx..].iter().position(|c| *c == q)?;
+        let match_idx = cand_idx + offset;
+        score += match prev_match {
+            Some(prev) if prev + 1 == match_idx => 10,
+            None if match_idx == 0 => 10,
+            _ => 1 - offset as i64,
+        };
+        prev_match = Some(match_idx);
+        cand_idx = match_idx + 1;
+    }
+    Some(score)
+}
+
 #[test]
 fn test_natural_sort() {
     assert_eq!(natural_cmp("s10", "s2"), Ordering::Greater);
@@ -65,3 +87,15 @@ fn test_natural_sort() {
         Ordering::Greater
     );
 }
+
+#[test]
+fn test_fuzzy_score() {
+    assert_eq!(fuzzy_score("", "car"), Some(0));
+    assert!(fuzzy_score("cr", "car").is_some());
+    assert!(fuzzy_score("rc", "car").is_none());
+    assert!(fuzzy_score("CAR", "race car").is_some());
+    let score = |q| fuzzy_score(q, "traffic light").unwrap();
+    assert!(score("traf") > score("tfc"));
+    assert!(score("light") > score("ight"));
+    assert!(fuzzy_score("tl", "traffic light") > fuzzy_score("tl", "bottle"));
+}
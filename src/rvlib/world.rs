@@ -1,3 +1,4 @@
+use crate::control::Info;
 use crate::domain::{Shape, BB};
 use crate::drawme::{Annotation, ImageInfo, UpdateImage};
 use crate::file_util::MetaData;
@@ -137,6 +138,8 @@ pub struct World {
     pub data: DataRaw,
     // transforms coordinates from view to raw image
     zoom_box: Option<BB>,
+    /// message of a tool that is shown by the main loop
+    info: Info,
 }
 
 impl World {
@@ -151,6 +154,7 @@ impl World {
                 zoom_box: UpdateZoomBox::Yes(zoom_box),
                 image_info: ImageInfo::default(),
             },
+            info: Info::None,
         }
     }
 
@@ -219,6 +223,15 @@ impl World {
     pub fn zoom_box(&self) -> &Option<BB> {
         &self.zoom_box
     }
+
+    /// Tools cannot access the menu, hence their errors and warnings are passed on by the world.
+    pub fn show_info(&mut self, info: Info) {
+        self.info = info;
+    }
+
+    pub fn take_info(&mut self) -> Info {
+        mem::take(&mut self.info)
+    }
 }
 impl Debug for World {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {